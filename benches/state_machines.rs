@@ -0,0 +1,29 @@
+//! Throughput of the discovery state machines, run with `cargo bench --features bench`.
+//!
+//! The number of iterations can be passed as `cargo bench --features bench -- <iterations>`.
+
+use pppoe::bench;
+use pppoe::{AllocationStrategy, DiscoveryOptions};
+
+fn main() {
+    let iterations = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(100_000);
+
+    let options = DiscoveryOptions {
+        host_uniq: Some(b"bench".to_vec()),
+        ..DiscoveryOptions::default()
+    };
+    let handshakes = bench::discovery_handshakes(iterations, &options).unwrap();
+    println!("discovery handshakes: {}", handshakes);
+
+    for &strategy in &[
+        AllocationStrategy::Sequential,
+        AllocationStrategy::Random,
+        AllocationStrategy::HashedFromMac,
+    ] {
+        let setups = bench::session_setups(iterations, strategy).unwrap();
+        println!("session setups ({:?}): {}", strategy, setups);
+    }
+}
@@ -0,0 +1,65 @@
+//! Numbers and sizes defined by RFC 2516, RFC 4638 and RFC 5578
+
+// Ethertypes
+pub const PPPOE_DISCOVERY: u16 = 0x8863;
+pub const PPPOE_SESSION: u16 = 0x8864;
+/// Ethertype of an IEEE 802.1Q VLAN tag
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+
+// PPPoE codes
+pub const PADI: u8 = 0x09;
+pub const PADO: u8 = 0x07;
+pub const PADR: u8 = 0x19;
+pub const PADS: u8 = 0x65;
+pub const PADT: u8 = 0xa7;
+// RFC 5578
+pub const PADG: u8 = 0x0a;
+pub const PADC: u8 = 0x0b;
+pub const PADQ: u8 = 0x0c;
+/// The PPPoE code of every session packet.
+pub const SESSION_DATA_CODE: u8 = 0x00;
+
+// Tags, RFC 2516
+pub const TAG_END_OF_LIST: u16 = 0x0000;
+pub const TAG_SERVICE_NAME: u16 = 0x0101;
+pub const TAG_AC_NAME: u16 = 0x0102;
+pub const TAG_HOST_UNIQ: u16 = 0x0103;
+pub const TAG_AC_COOKIE: u16 = 0x0104;
+pub const TAG_VENDOR_SPECIFIC: u16 = 0x0105;
+pub const TAG_RELAY_SESSION_ID: u16 = 0x0110;
+pub const TAG_SERVICE_NAME_ERROR: u16 = 0x0201;
+pub const TAG_AC_SYSTEM_ERROR: u16 = 0x0202;
+pub const TAG_GENERIC_ERROR: u16 = 0x0203;
+
+// RFC 4638
+pub const TAG_PPP_MAX_PAYLOAD: u16 = 0x0120;
+
+// RFC 5578
+pub const TAG_CREDITS: u16 = 0x0106;
+pub const TAG_METRICS: u16 = 0x0107;
+pub const TAG_SEQUENCE_NUMBER: u16 = 0x0108;
+pub const TAG_CREDIT_SCALE_FACTOR: u16 = 0x0109;
+
+/// The PPPoE version and type (both 1) as encoded in the first byte of the PPPoE header
+pub const VERSION_TYPE: u8 = 0x11;
+
+/// IANA enterprise number of the Broadband Forum, used in TR-101 vendor specific tags
+pub const BROADBAND_FORUM_VENDOR_ID: u32 = 0x0DE9;
+
+/// Length of an (untagged) Ethernet header
+pub const ETHERNET_HEADER_LEN: usize = 14;
+/// Length of the PPPoE header
+pub const PPPOE_HEADER_LEN: usize = 6;
+/// Minimal length of a PPPoE frame: Ethernet header + PPPoE header
+pub const MIN_FRAME_LEN: usize = ETHERNET_HEADER_LEN + PPPOE_HEADER_LEN;
+
+/// Length of a tag header (type + length)
+pub const TAG_HEADER_LEN: usize = 4;
+
+/// Maximal PPP MRU without RFC 4638 on a 1500 byte Ethernet MTU (RFC 2516 section 7)
+pub const MAX_MRU: u16 = 1492;
+/// Maximal length of the PPPoE payload on a 1500 byte Ethernet MTU
+pub const MAX_PAYLOAD_LEN: usize = 1500 - PPPOE_HEADER_LEN;
+/// Largest frame a PPPoE header can describe, as the payload length field is 16 bits wide.  The
+/// actual limit is the interface MTU, e.g. 9000 bytes for jumbo frames with RFC 4638.
+pub const MAX_FRAME_LEN: usize = ETHERNET_HEADER_LEN + PPPOE_HEADER_LEN + u16::MAX as usize;
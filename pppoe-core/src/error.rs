@@ -0,0 +1,114 @@
+//! Errors of the parsers
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ParseError {
+    BufferTooSmall(usize),
+
+    BufferTooSmallForTag {
+        available: u16,
+        requested: usize,
+    },
+
+    InvalidPppoeVersion(u8),
+    InvalidPppoeType(u8),
+    InvalidPppoeCode(u8),
+
+    UnexpectedCode(u8),
+    UnexpectedEtherType(u16),
+    InvalidEthernetHeaderLength(usize),
+
+    PayloadLengthOutOfBound {
+        actual_packet_length: u16,
+        payload_length: u16,
+    },
+    PayloadLengthOverflow(usize),
+    TagLengthOutOfBound {
+        expected_tag_length: u16,
+        remaining_payload_length: u16,
+    },
+
+    IncompleteTagAtPacketEnd {
+        total_packet_length: u16,
+        left_over_bytes: u16,
+    },
+
+    TagLengthOverflow {
+        tag_type: u16,
+        length: usize,
+    },
+
+    DataBehindEolTag,
+    TooManyTags(usize),
+    TrailingBytes(usize),
+    IncompleteTag(u8),
+    TagWithInvalidLength {
+        tag_type: u16,
+        length: u16,
+    },
+    InvalidMetricsPercentage(u8),
+    InvalidVendorId(u32),
+    VendorSubTlvTooLong {
+        sub_type: u8,
+        length: usize,
+    },
+
+    #[cfg(feature = "tr101")]
+    InvalidTr101TagLength {
+        tag_type: u8,
+        expected_min_length: u16,
+        expected_max_length: u16,
+        actual_length: u16,
+    },
+    #[cfg(feature = "tr101")]
+    Tr101LengthOutOfBound {
+        remaining_packet_length: u16,
+        requested_tag_length: u16,
+    },
+    #[cfg(feature = "tr101")]
+    InvalidTr101Id(u32),
+    #[cfg(feature = "tr101")]
+    TagIsNotVendorSpecific,
+    #[cfg(feature = "tr101")]
+    InvalidTr101VendorId(u32),
+
+    DuplicateTag(u16),
+    InvalidHexDump {
+        line: usize,
+    },
+    InvalidPcapMagic(u32),
+    UnsupportedLinkType(u32),
+    TruncatedPcapRecord {
+        offset: usize,
+    },
+    ReservedTagType(u16),
+    TagIndexOutOfBound {
+        index: usize,
+        tag_count: usize,
+    },
+
+    InvalidPppLength {
+        length: u16,
+        available: usize,
+    },
+    InvalidPppOptionLength {
+        option_type: u8,
+        length: u8,
+    },
+
+    MissingServiceName,
+    MissingAcName,
+
+    InvalidAcNameTemplate(usize),
+    UnknownPlaceholder {
+        offset: usize,
+        length: usize,
+    },
+    MissingPlaceholderValue(&'static str),
+    AcNameTooLong {
+        length: usize,
+        max_length: usize,
+    },
+
+    ServiceNameMismatch,
+    AcNameMismatch,
+}
@@ -0,0 +1,478 @@
+//! The PPPoE header of discovery packets and its parser
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use core::convert::TryFrom;
+use core::fmt;
+
+use crate::consts::{PPPOE_HEADER_LEN, VERSION_TYPE};
+use crate::error::ParseError;
+use crate::metrics::Metrics;
+use crate::tag::{self, Tag, TagIterator};
+
+pub use crate::consts::{PADC, PADG, PADI, PADO, PADQ, PADR, PADS, PADT};
+
+#[repr(u8)]
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum Code {
+    Padi = PADI,
+    Pado = PADO,
+    Padr = PADR,
+    Pads = PADS,
+    Padt = PADT,
+    Padg = PADG,
+    Padc = PADC,
+    Padq = PADQ,
+}
+
+impl Code {
+    fn try_from(code: u8) -> Result<Self, ParseError> {
+        Ok(match code {
+            PADI => Code::Padi,
+            PADO => Code::Pado,
+            PADR => Code::Padr,
+            PADS => Code::Pads,
+            PADT => Code::Padt,
+            PADG => Code::Padg,
+            PADC => Code::Padc,
+            PADQ => Code::Padq,
+            _ => return Err(ParseError::InvalidPppoeCode(code)),
+        })
+    }
+}
+
+/// Get the name of a code, e.g. `"PADI"`
+pub fn code_name(code: u8) -> Option<&'static str> {
+    Some(match code {
+        PADI => "PADI",
+        PADO => "PADO",
+        PADR => "PADR",
+        PADS => "PADS",
+        PADT => "PADT",
+        PADG => "PADG",
+        PADC => "PADC",
+        PADQ => "PADQ",
+        _ => return None,
+    })
+}
+
+fn ensure_minimal_buffer_length(buffer: &[u8]) -> Result<(), ParseError> {
+    if buffer.len() < PPPOE_HEADER_LEN {
+        return Err(ParseError::BufferTooSmall(buffer.len()));
+    }
+    Ok(())
+}
+
+/// Limits and tolerances of the header parser
+///
+/// The default is what `Header::with_buffer` accepts.  A config is meant to be created once,
+/// e.g. from the configuration of a daemon, and reused for all received packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserConfig {
+    /// Reject duplicates of tags which must only occur once and data behind End-Of-List
+    pub strict: bool,
+    /// Maximum number of tags, End-Of-List not counted
+    pub max_tags: Option<usize>,
+    /// Accept codes which are not defined by RFC 2516 or RFC 5578
+    pub allow_unknown_codes: bool,
+    /// Require a Service-Name tag, except in the PADG, PADC and PADQ packets of RFC 5578
+    pub require_service_name: bool,
+    /// Accept bytes behind the declared payload length, e.g. Ethernet padding
+    pub tolerate_padding: bool,
+    /// Accept PADIs with more than one Service-Name tag, which some clients send to probe
+    /// multiple services at once.  Other duplicate tags are still rejected when `strict`.
+    pub allow_multiple_service_names: bool,
+    /// Accept version and type fields other than 1, e.g. to inspect the packets of negative
+    /// tests.  No other version or type is defined, so they are parsed like version 1 type 1.
+    pub allow_any_version_type: bool,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            strict: true,
+            max_tags: None,
+            allow_unknown_codes: false,
+            require_service_name: true,
+            tolerate_padding: true,
+            allow_multiple_service_names: false,
+            allow_any_version_type: false,
+        }
+    }
+}
+
+impl ParserConfig {
+    /// Accept everything whose tags can still be iterated, e.g. for capture analysis.  Nothing
+    /// behind the End-Of-List tag is validated, `Header::tags` stops at it.
+    pub fn lenient() -> Self {
+        Self {
+            strict: false,
+            max_tags: None,
+            allow_unknown_codes: true,
+            require_service_name: false,
+            tolerate_padding: true,
+            allow_multiple_service_names: true,
+            allow_any_version_type: true,
+        }
+    }
+
+    pub fn parse<'a>(&self, buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
+        self.parse_with_code(buffer, None)
+    }
+
+    /// Parse a header and check that it has the expected code.
+    pub fn parse_with_code<'a>(
+        &self,
+        buffer: &'a [u8],
+        expected_code: Option<Code>,
+    ) -> Result<Header<'a>, ParseError> {
+        ensure_minimal_buffer_length(buffer)?;
+        if buffer[0] != VERSION_TYPE && !self.allow_any_version_type {
+            let version = buffer[0] >> 4;
+            let r#type = buffer[0] & 0x0f;
+            return if buffer[0] >> 4 != 1 {
+                Err(ParseError::InvalidPppoeVersion(version))
+            } else {
+                Err(ParseError::InvalidPppoeType(r#type))
+            };
+        }
+
+        let code = match Code::try_from(buffer[1]) {
+            Ok(code) => Some(code),
+            Err(_) if self.allow_unknown_codes => None,
+            Err(err) => return Err(err),
+        };
+        if let Some(expected_code) = expected_code {
+            if code != Some(expected_code) {
+                return Err(ParseError::UnexpectedCode(buffer[1]));
+            }
+        }
+
+        let length = usize::from(NE::read_u16(&buffer[4..]));
+        if length + 6 > buffer.len() {
+            return Err(ParseError::PayloadLengthOutOfBound {
+                actual_packet_length: u16::try_from(buffer.len()).unwrap_or(u16::MAX),
+                payload_length: length as u16,
+            });
+        }
+        if !self.tolerate_padding && length + 6 < buffer.len() {
+            return Err(ParseError::TrailingBytes(buffer.len() - length - 6));
+        }
+
+        let payload = &buffer[6..6 + length];
+        // RFC 5578 packets carry credits or quality metrics instead of a Service-Name
+        if matches!(code, Some(Code::Padg) | Some(Code::Padc) | Some(Code::Padq)) {
+            let rfc5578_config = ParserConfig {
+                require_service_name: false,
+                ..*self
+            };
+            Header::validate_tags(payload, code, &rfc5578_config)?;
+            if self.strict && code == Some(Code::Padq) {
+                Metrics::from_tags(TagIterator::new_unchecked(payload))?;
+            }
+        } else {
+            if length == 0 && self.require_service_name {
+                return Err(ParseError::MissingServiceName);
+            }
+            Header::validate_tags(payload, code, self)?;
+        }
+
+        Ok(Header(buffer))
+    }
+}
+
+#[derive(Debug)]
+pub struct Header<'a>(&'a [u8]);
+
+impl<'a> Header<'a> {
+    pub fn with_buffer(buffer: &'a [u8]) -> Result<Self, ParseError> {
+        Self::with_buffer_and_code(buffer, None)
+    }
+
+    pub fn with_buffer_and_code(
+        buffer: &'a [u8],
+        expected_code: Option<Code>,
+    ) -> Result<Header<'a>, ParseError> {
+        ParserConfig::default().parse_with_code(buffer, expected_code)
+    }
+
+    pub fn padi_with_buffer(buffer: &'a [u8]) -> Result<Self, ParseError> {
+        Self::with_buffer_and_code(buffer, Some(Code::Padi))
+    }
+
+    pub fn pado_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
+        Self::with_buffer_and_code(buffer, Some(Code::Pado))
+    }
+
+    pub fn padr_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
+        Self::with_buffer_and_code(buffer, Some(Code::Padr))
+    }
+
+    pub fn pads_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
+        Self::with_buffer_and_code(buffer, Some(Code::Pads))
+    }
+
+    pub fn padt_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
+        Self::with_buffer_and_code(buffer, Some(Code::Padt))
+    }
+
+    pub fn padg_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
+        Self::with_buffer_and_code(buffer, Some(Code::Padg))
+    }
+
+    pub fn padc_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
+        Self::with_buffer_and_code(buffer, Some(Code::Padc))
+    }
+
+    pub fn padq_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
+        Self::with_buffer_and_code(buffer, Some(Code::Padq))
+    }
+
+    /// Wrap a buffer which was already validated, e.g. by `ParserConfig::parse`.
+    pub fn new_unchecked(buffer: &'a [u8]) -> Self {
+        Header(buffer)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0
+    }
+
+    pub fn get_ref(&self) -> &[u8] {
+        self.0
+    }
+
+    fn check_duplicate(tag: u16, exists: &mut bool) -> Result<(), ParseError> {
+        if *exists {
+            return Err(ParseError::DuplicateTag(tag));
+        }
+
+        *exists = true;
+        Ok(())
+    }
+
+    fn validate_tags(
+        mut payload: &[u8],
+        code: Option<Code>,
+        config: &ParserConfig,
+    ) -> Result<(), ParseError> {
+        let multiple_service_names =
+            config.allow_multiple_service_names && code == Some(Code::Padi);
+        let mut tag;
+        let mut length;
+        let total_packet_length = payload.len() as u16;
+        let mut tag_count = 0;
+
+        // these tags must only exists once
+        let mut service_name = false;
+        let mut host_uniq = false;
+        let mut ac_name = false;
+        let mut relay_session_id = false;
+        let mut ppp_max_payload = false;
+        let mut ac_cookie = false;
+
+        loop {
+            match payload.len() {
+                0 => {
+                    return Ok(());
+                }
+                // tags must be at least 4 bytes
+                x if x < 4 => {
+                    return Err(ParseError::IncompleteTagAtPacketEnd {
+                        total_packet_length,
+                        left_over_bytes: x as u16,
+                    });
+                }
+
+                total_length => {
+                    tag = NE::read_u16(payload);
+
+                    // check for duplicates
+                    match tag {
+                        tag::TAG_SERVICE_NAME if !config.strict || multiple_service_names => {
+                            service_name = true
+                        }
+                        _ if !config.strict => (),
+                        tag::TAG_SERVICE_NAME => Self::check_duplicate(tag, &mut service_name)?,
+                        tag::TAG_AC_NAME => Self::check_duplicate(tag, &mut ac_name)?,
+                        tag::TAG_AC_COOKIE => Self::check_duplicate(tag, &mut ac_cookie)?,
+                        tag::TAG_HOST_UNIQ => Self::check_duplicate(tag, &mut host_uniq)?,
+                        tag::TAG_RELAY_SESSION_ID => {
+                            Self::check_duplicate(tag, &mut relay_session_id)?
+                        }
+                        tag::TAG_PPP_MAX_PAYLOAD => {
+                            Self::check_duplicate(tag, &mut ppp_max_payload)?
+                        }
+                        _ => (),
+                    }
+
+                    length = usize::from(NE::read_u16(&payload[2..]));
+                    if tag == tag::TAG_END_OF_LIST {
+                        break;
+                    }
+
+                    tag_count += 1;
+                    if config.max_tags.is_some_and(|max_tags| tag_count > max_tags) {
+                        return Err(ParseError::TooManyTags(tag_count));
+                    }
+
+                    if length + 4 > total_length {
+                        return Err(ParseError::TagLengthOutOfBound {
+                            expected_tag_length: length as u16,
+                            remaining_payload_length: total_length as u16,
+                        });
+                    };
+                    payload = &payload[4 + length..]
+                }
+            }
+        }
+
+        // Found End-of-List tag, nothing should be behind this
+        if config.strict && (length != 0 || payload.len() != 4) {
+            return Err(ParseError::DataBehindEolTag);
+        }
+
+        if config.require_service_name && !service_name {
+            return Err(ParseError::MissingServiceName);
+        }
+
+        Ok(())
+    }
+
+    /// Get the version field, always 1 unless parsed with
+    /// `ParserConfig::allow_any_version_type`.
+    pub fn version(&self) -> u8 {
+        self.0[0] >> 4
+    }
+
+    /// Get the type field, always 1 unless parsed with `ParserConfig::allow_any_version_type`.
+    pub fn type_(&self) -> u8 {
+        self.0[0] & 0x0f
+    }
+
+    pub fn code(&self) -> u8 {
+        self.0[1]
+    }
+
+    pub fn session_id(&self) -> u16 {
+        NE::read_u16(&self.0[2..])
+    }
+
+    /// Get the length of the header and the payload as declared in the header.  The buffer may
+    /// be longer, see `trailing_bytes`.
+    pub fn len(&self) -> usize {
+        6 + self.declared_payload_len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 6
+    }
+
+    /// Get the payload length field of the header
+    pub fn declared_payload_len(&self) -> usize {
+        usize::from(NE::read_u16(&self.0[4..]))
+    }
+
+    /// Get the payload, limited to the declared payload length.
+    pub fn payload(&self) -> &[u8] {
+        &self.0[6..self.len()]
+    }
+
+    /// Get the bytes of the buffer behind the payload, e.g. Ethernet padding.
+    pub fn trailing_bytes(&self) -> &[u8] {
+        &self.0[self.len()..]
+    }
+
+    pub fn tags(&self) -> TagIterator<'_> {
+        TagIterator::new_unchecked(&self.0[6..self.len()])
+    }
+
+    /// Iterate over the values of all Service-Name tags.  There is only one, except in PADIs
+    /// parsed with `ParserConfig::allow_multiple_service_names`.
+    pub fn service_names(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.tags().filter_map(|tag| match tag {
+            Tag::ServiceName(service_name) => Some(service_name),
+            _ => None,
+        })
+    }
+
+    /// Get the value of the first tag of type `tag_type`.
+    ///
+    /// Walks the tags once without decoding them, so it is cheaper than searching `tags`.
+    pub fn tag_value(&self, tag_type: u16) -> Option<&'a [u8]> {
+        let mut payload = &self.0[6..self.len()];
+        while payload.len() >= 4 {
+            let length = usize::from(NE::read_u16(&payload[2..]));
+            let value = payload.get(4..4 + length)?;
+            match NE::read_u16(payload) {
+                // the parser doesn't validate anything behind the end of the list
+                tag::TAG_END_OF_LIST => return None,
+                found if found == tag_type => return Some(value),
+                _ => (),
+            }
+            payload = &payload[4 + length..];
+        }
+        None
+    }
+
+    pub fn service_name(&self) -> Option<&'a [u8]> {
+        self.tag_value(tag::TAG_SERVICE_NAME)
+    }
+
+    pub fn ac_name(&self) -> Option<&'a [u8]> {
+        self.tag_value(tag::TAG_AC_NAME)
+    }
+
+    pub fn ac_cookie(&self) -> Option<&'a [u8]> {
+        self.tag_value(tag::TAG_AC_COOKIE)
+    }
+
+    pub fn host_uniq(&self) -> Option<&'a [u8]> {
+        self.tag_value(tag::TAG_HOST_UNIQ)
+    }
+
+    pub fn relay_session_id(&self) -> Option<&'a [u8]> {
+        self.tag_value(tag::TAG_RELAY_SESSION_ID)
+    }
+}
+
+impl<'a> Header<'a> {
+    fn write_line<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result {
+        match code_name(self.code()) {
+            Some(name) => w.write_str(name)?,
+            None => write!(w, "code {:#04x}", self.code())?,
+        }
+        write!(
+            w,
+            " session-id {:#06x} length {}",
+            self.session_id(),
+            self.declared_payload_len()
+        )
+    }
+
+    /// Write the summary line of `Display` followed by the summaries of all tags, e.g.
+    /// `PADI session-id 0x0000 length 4: Service-Name: ""`.
+    ///
+    /// Doesn't allocate, see `Tag::write_summary`.
+    pub fn write_summary(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        self.write_line(w)?;
+        for (i, tag) in self.tags().enumerate() {
+            w.write_str(if i == 0 { ": " } else { "; " })?;
+            tag.write_summary(w)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints a summary line like `PADO session-id 0x0000 length 42`.  The alternate form (`{:#}`)
+/// adds one indented line per tag.
+impl<'a> fmt::Display for Header<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_line(f)?;
+        if f.alternate() {
+            for tag in self.tags() {
+                write!(f, "\n  {}", tag)?;
+            }
+        }
+        Ok(())
+    }
+}
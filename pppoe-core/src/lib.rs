@@ -0,0 +1,22 @@
+//! The `no_std` part of `pppoe`
+//!
+//! This crate holds the protocol definitions and the parser of discovery packets, which don't
+//! need `std`, for bare-metal users.  `pppoe` re-exports everything in here at the paths it had
+//! before the split, so users of `pppoe` don't have to depend on this crate.
+//!
+//! The `alloc` feature adds the few methods which return allocated values, e.g.
+//! `Tag::text_lossy`.
+//!
+//! The `std` feature implements `poll::Instant` for `std::time::Instant`.
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod consts;
+pub mod error;
+pub mod header;
+pub mod metrics;
+pub mod poll;
+pub mod ppp;
+pub mod tag;
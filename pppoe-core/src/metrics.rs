@@ -0,0 +1,118 @@
+//! The Metrics tag of RFC 5578
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use crate::error::ParseError;
+use crate::tag::{Tag, TagIterator, TAG_METRICS};
+
+/// Length of the value of a Metrics tag
+pub const METRICS_LEN: usize = 10;
+
+// RLQ and resources are percentages
+const MAX_PERCENTAGE: u8 = 100;
+
+/// Unit of the data rates in a Metrics tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataRateScalar {
+    Kbps = 0,
+    Mbps = 1,
+    Gbps = 2,
+    Tbps = 3,
+}
+
+impl DataRateScalar {
+    fn from_bits(bits: u16) -> Self {
+        match bits & 0x3 {
+            0 => DataRateScalar::Kbps,
+            1 => DataRateScalar::Mbps,
+            2 => DataRateScalar::Gbps,
+            _ => DataRateScalar::Tbps,
+        }
+    }
+
+    /// Convert a data rate in this unit to kbps
+    pub fn to_kbps(self, rate: u16) -> u64 {
+        u64::from(rate) * 1000u64.pow(self as u32)
+    }
+}
+
+/// Link quality metrics as defined in RFC 5578, carried in the Metrics tag of PADQ packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// The link can only receive
+    pub receive_only: bool,
+    /// Relative link quality in percent
+    pub rlq: u8,
+    /// Remaining resources (e.g. battery) in percent
+    pub resources: u8,
+    /// Latency in milliseconds
+    pub latency: u16,
+    pub current_data_rate: u16,
+    pub cdr_scalar: DataRateScalar,
+    pub max_data_rate: u16,
+    pub mdr_scalar: DataRateScalar,
+}
+
+impl Metrics {
+    /// Parse the value of a Metrics tag
+    ///
+    /// The value must be exactly `METRICS_LEN` bytes and RLQ and resources must not exceed 100
+    /// percent.  The reserved bits are ignored.
+    pub fn from_bytes(buffer: &[u8]) -> Result<Self, ParseError> {
+        if buffer.len() != METRICS_LEN {
+            return Err(ParseError::TagWithInvalidLength {
+                tag_type: TAG_METRICS,
+                length: buffer.len() as u16 + 4,
+            });
+        }
+
+        for &percentage in &buffer[2..4] {
+            if percentage > MAX_PERCENTAGE {
+                return Err(ParseError::InvalidMetricsPercentage(percentage));
+            }
+        }
+
+        let flags = NE::read_u16(buffer);
+        Ok(Self {
+            receive_only: flags & 0x1 != 0,
+            rlq: buffer[2],
+            resources: buffer[3],
+            latency: NE::read_u16(&buffer[4..]),
+            current_data_rate: NE::read_u16(&buffer[6..]),
+            cdr_scalar: DataRateScalar::from_bits(flags >> 1),
+            max_data_rate: NE::read_u16(&buffer[8..]),
+            mdr_scalar: DataRateScalar::from_bits(flags >> 3),
+        })
+    }
+
+    /// Find and parse the Metrics tag of a packet
+    pub fn from_tags(mut tags: TagIterator) -> Result<Option<Self>, ParseError> {
+        match tags.find(|tag| matches!(tag, Tag::Metrics(_))) {
+            Some(Tag::Metrics(buffer)) => Self::from_bytes(buffer).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Encode the value of a Metrics tag, use it with `Tag::Metrics`
+    pub fn to_bytes(&self) -> [u8; METRICS_LEN] {
+        let mut buffer = [0u8; METRICS_LEN];
+        let flags = (self.mdr_scalar as u16) << 3
+            | (self.cdr_scalar as u16) << 1
+            | u16::from(self.receive_only);
+        NE::write_u16(&mut buffer, flags);
+        buffer[2] = self.rlq;
+        buffer[3] = self.resources;
+        NE::write_u16(&mut buffer[4..], self.latency);
+        NE::write_u16(&mut buffer[6..], self.current_data_rate);
+        NE::write_u16(&mut buffer[8..], self.max_data_rate);
+        buffer
+    }
+
+    pub fn current_data_rate_kbps(&self) -> u64 {
+        self.cdr_scalar.to_kbps(self.current_data_rate)
+    }
+
+    pub fn max_data_rate_kbps(&self) -> u64 {
+        self.mdr_scalar.to_kbps(self.max_data_rate)
+    }
+}
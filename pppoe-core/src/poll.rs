@@ -0,0 +1,197 @@
+//! Driving the state machines from a superloop
+//!
+//! Firmware without threads or async usually runs one loop which polls every component with
+//! the current tick count.  `Poller` fits the state machines into such a loop: every call to
+//! `Poller::poll` takes the milliseconds of a monotonic tick counter and at most one received
+//! frame, and returns whether a frame has to be sent or when to poll again.  Ticks are mapped
+//! onto the instants of the machine relative to the epoch given to `Poller::new`, tick 0 being
+//! that point in time.
+
+/// Milliseconds of a monotonic tick counter
+pub type Millis = u64;
+
+/// A point in time as used by a `Machine`
+///
+/// Implemented for `Millis`, so a machine can run on the tick counter directly, and with the
+/// `std` feature for `std::time::Instant`.
+pub trait Instant: Copy + Ord {
+    /// The instant `millis` milliseconds after `self`
+    fn add_millis(self, millis: Millis) -> Self;
+    /// The milliseconds from `earlier` to `self`, rounded up.  0 if `earlier` is later.
+    fn millis_since(self, earlier: Self) -> Millis;
+}
+
+impl Instant for Millis {
+    fn add_millis(self, millis: Millis) -> Self {
+        self.saturating_add(millis)
+    }
+
+    fn millis_since(self, earlier: Self) -> Millis {
+        self.saturating_sub(earlier)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Instant for std::time::Instant {
+    fn add_millis(self, millis: Millis) -> Self {
+        self + std::time::Duration::from_millis(millis)
+    }
+
+    // rounded up, so the machine is not polled before its timeout
+    fn millis_since(self, earlier: Self) -> Millis {
+        let elapsed = self.saturating_duration_since(earlier);
+        let millis =
+            elapsed.as_millis() + u128::from(!elapsed.subsec_nanos().is_multiple_of(1_000_000));
+        millis as Millis
+    }
+}
+
+/// A state machine which can be driven by a `Poller`
+pub trait Machine {
+    type Instant: Instant;
+    type Error;
+
+    /// Process a received frame (or packet, depending on the machine).
+    fn handle(&mut self, now: Self::Instant, rx: &[u8]) -> Result<(), Self::Error>;
+    /// Write the next frame to send into `tx` and return its length.
+    fn poll_transmit(
+        &mut self,
+        now: Self::Instant,
+        tx: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error>;
+    fn poll_timeout(&self) -> Option<Self::Instant>;
+    fn is_finished(&self) -> bool;
+}
+
+/// What the superloop has to do after a `Poller::poll`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollResult {
+    /// Send the first bytes of `tx`, then poll again without waiting
+    Transmit(usize),
+    /// Poll again at this tick or once a frame was received.  `None` means only on reception.
+    Wait(Option<Millis>),
+    /// The machine is done, e.g. the session was established
+    Finished,
+}
+
+/// Drives a `Machine` with millisecond ticks
+#[derive(Debug)]
+pub struct Poller<M: Machine> {
+    machine: M,
+    epoch: M::Instant,
+}
+
+impl<M: Machine> Poller<M> {
+    /// Drive `machine`, tick 0 being `epoch`, e.g. the current time of the machine's clock.
+    pub fn new(machine: M, epoch: M::Instant) -> Self {
+        Self { machine, epoch }
+    }
+
+    /// Hand `rx` to the machine, then let it transmit.
+    ///
+    /// Errors of the machine are passed on, e.g. `ProtocolError::NoOffer` of `pppoe::Discovery`
+    /// once all PADIs timed out.
+    pub fn poll(
+        &mut self,
+        now: Millis,
+        rx: Option<&[u8]>,
+        tx: &mut [u8],
+    ) -> Result<PollResult, M::Error> {
+        let now = self.epoch.add_millis(now);
+        if let Some(rx) = rx {
+            self.machine.handle(now, rx)?;
+        }
+        if self.machine.is_finished() {
+            return Ok(PollResult::Finished);
+        }
+        if let Some(len) = self.machine.poll_transmit(now, tx)? {
+            return Ok(PollResult::Transmit(len));
+        }
+        Ok(PollResult::Wait(
+            self.machine
+                .poll_timeout()
+                .map(|timeout| timeout.millis_since(self.epoch)),
+        ))
+    }
+
+    pub fn machine(&self) -> &M {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut M {
+        &mut self.machine
+    }
+
+    pub fn into_inner(self) -> M {
+        self.machine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends a frame every 100 ms until it received 2 frames
+    struct Beacon {
+        next: Millis,
+        received: u8,
+    }
+
+    impl Machine for Beacon {
+        type Instant = Millis;
+        type Error = ();
+
+        fn handle(&mut self, _now: Millis, rx: &[u8]) -> Result<(), ()> {
+            if rx.is_empty() {
+                return Err(());
+            }
+            self.received += 1;
+            Ok(())
+        }
+
+        fn poll_transmit(&mut self, now: Millis, tx: &mut [u8]) -> Result<Option<usize>, ()> {
+            if now < self.next {
+                return Ok(None);
+            }
+            self.next = now + 100;
+            tx[0] = 0x42;
+            Ok(Some(1))
+        }
+
+        fn poll_timeout(&self) -> Option<Millis> {
+            Some(self.next)
+        }
+
+        fn is_finished(&self) -> bool {
+            self.received == 2
+        }
+    }
+
+    #[test]
+    fn ticks() {
+        let mut tx = [0u8; 4];
+        let mut poller = Poller::new(
+            Beacon {
+                next: 1000,
+                received: 0,
+            },
+            1000,
+        );
+        assert_eq!(poller.poll(0, None, &mut tx), Ok(PollResult::Transmit(1)));
+        assert_eq!(
+            poller.poll(30, None, &mut tx),
+            Ok(PollResult::Wait(Some(100)))
+        );
+        assert_eq!(poller.poll(40, Some(&[]), &mut tx), Err(()));
+        assert_eq!(
+            poller.poll(50, Some(&[1]), &mut tx),
+            Ok(PollResult::Wait(Some(100)))
+        );
+        assert_eq!(poller.poll(100, None, &mut tx), Ok(PollResult::Transmit(1)));
+        assert_eq!(
+            poller.poll(110, Some(&[1]), &mut tx),
+            Ok(PollResult::Finished)
+        );
+        assert_eq!(poller.into_inner().received, 2);
+    }
+}
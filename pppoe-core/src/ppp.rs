@@ -0,0 +1,125 @@
+//! PPP protocol numbers and the codes shared by all LCP-like control protocols (RFC 1661)
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use core::fmt;
+
+pub const PROTOCOL_IPV4: u16 = 0x0021;
+pub const PROTOCOL_IPV6: u16 = 0x0057;
+pub const PROTOCOL_LCP: u16 = 0xc021;
+pub const PROTOCOL_IPCP: u16 = 0x8021;
+pub const PROTOCOL_IPV6CP: u16 = 0x8057;
+pub const PROTOCOL_CCP: u16 = 0x80fd;
+pub const PROTOCOL_PAP: u16 = 0xc023;
+pub const PROTOCOL_CHAP: u16 = 0xc223;
+
+/// The PPP protocol number at the start of a session payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Lcp,
+    Pap,
+    Chap,
+    Ipcp,
+    Ipv6cp,
+    Ccp,
+    Ipv4,
+    Ipv6,
+    Unknown(u16),
+}
+
+impl Protocol {
+    /// Get the protocol of a session payload, `None` if it is too short.
+    pub fn of_payload(payload: &[u8]) -> Option<Self> {
+        if payload.len() < 2 {
+            return None;
+        }
+        Some(NE::read_u16(payload).into())
+    }
+
+    /// Whether this is a control protocol (LCP, authentication, NCPs), i.e. its number is
+    /// 0x8000 or above (RFC 1661)
+    pub fn is_control(self) -> bool {
+        u16::from(self) >= 0x8000
+    }
+}
+
+impl From<u16> for Protocol {
+    fn from(number: u16) -> Self {
+        match number {
+            PROTOCOL_LCP => Protocol::Lcp,
+            PROTOCOL_PAP => Protocol::Pap,
+            PROTOCOL_CHAP => Protocol::Chap,
+            PROTOCOL_IPCP => Protocol::Ipcp,
+            PROTOCOL_IPV6CP => Protocol::Ipv6cp,
+            PROTOCOL_CCP => Protocol::Ccp,
+            PROTOCOL_IPV4 => Protocol::Ipv4,
+            PROTOCOL_IPV6 => Protocol::Ipv6,
+            number => Protocol::Unknown(number),
+        }
+    }
+}
+
+impl From<Protocol> for u16 {
+    fn from(protocol: Protocol) -> Self {
+        match protocol {
+            Protocol::Lcp => PROTOCOL_LCP,
+            Protocol::Pap => PROTOCOL_PAP,
+            Protocol::Chap => PROTOCOL_CHAP,
+            Protocol::Ipcp => PROTOCOL_IPCP,
+            Protocol::Ipv6cp => PROTOCOL_IPV6CP,
+            Protocol::Ccp => PROTOCOL_CCP,
+            Protocol::Ipv4 => PROTOCOL_IPV4,
+            Protocol::Ipv6 => PROTOCOL_IPV6,
+            Protocol::Unknown(number) => number,
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Protocol::Lcp => "LCP",
+            Protocol::Pap => "PAP",
+            Protocol::Chap => "CHAP",
+            Protocol::Ipcp => "IPCP",
+            Protocol::Ipv6cp => "IPv6CP",
+            Protocol::Ccp => "CCP",
+            Protocol::Ipv4 => "IPv4",
+            Protocol::Ipv6 => "IPv6",
+            Protocol::Unknown(number) => return write!(f, "protocol {:#06x}", number),
+        };
+        f.write_str(name)
+    }
+}
+
+// RFC 1661 codes, shared by all LCP-like protocols
+pub const CONFIGURE_REQUEST: u8 = 1;
+pub const CONFIGURE_ACK: u8 = 2;
+pub const CONFIGURE_NAK: u8 = 3;
+pub const CONFIGURE_REJECT: u8 = 4;
+pub const TERMINATE_REQUEST: u8 = 5;
+pub const TERMINATE_ACK: u8 = 6;
+pub const CODE_REJECT: u8 = 7;
+
+/// Length of the code, identifier and length fields
+pub const CONTROL_HEADER_LEN: usize = 4;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_numbers() {
+        for &number in &[
+            0x0021, 0x0057, 0x8021, 0x8057, 0x80fd, 0xc021, 0xc023, 0xc223, 0x002b,
+        ] {
+            assert_eq!(u16::from(Protocol::from(number)), number);
+        }
+        assert_eq!(Protocol::of_payload(&[0xc0, 0x21, 1]), Some(Protocol::Lcp));
+        assert_eq!(Protocol::of_payload(&[0xc0]), None);
+        assert!(Protocol::Ipv6cp.is_control());
+        assert!(!Protocol::Ipv6.is_control());
+        assert_eq!(Protocol::Ipv6cp.to_string(), "IPv6CP");
+        assert_eq!(Protocol::Unknown(0x2b).to_string(), "protocol 0x002b");
+    }
+}
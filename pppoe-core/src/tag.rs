@@ -0,0 +1,600 @@
+//! PPPoE discovery tags
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, string::String};
+use core::{convert::TryFrom, fmt, num, str};
+
+use crate::error::ParseError;
+
+pub use crate::consts::{
+    TAG_AC_COOKIE, TAG_AC_NAME, TAG_AC_SYSTEM_ERROR, TAG_CREDITS, TAG_CREDIT_SCALE_FACTOR,
+    TAG_END_OF_LIST, TAG_GENERIC_ERROR, TAG_HOST_UNIQ, TAG_METRICS, TAG_PPP_MAX_PAYLOAD,
+    TAG_RELAY_SESSION_ID, TAG_SEQUENCE_NUMBER, TAG_SERVICE_NAME, TAG_SERVICE_NAME_ERROR,
+    TAG_VENDOR_SPECIFIC,
+};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Tag<'a> {
+    EndOfList,
+    ServiceName(&'a [u8]),
+    AcName(&'a [u8]),
+    HostUniq(&'a [u8]),
+    AcCookie(&'a [u8]),
+    VendorSpecific(&'a [u8]),
+    RelaySessionId(&'a [u8]),
+    ServiceNameError(&'a [u8]),
+    AcSystemError(&'a [u8]),
+    GenericError(&'a [u8]),
+    // RFC 4639
+    PppMaxMtu(u16),
+    // RFC 5578
+    Credits((u16, u16)),
+    // TODO: this field requires a little logic
+    Metrics(&'a [u8]),
+    SequenceNumber(u16),
+    CreditScaleFactor(u16),
+    // Unknown
+    Unknown((num::NonZeroU16, &'a [u8])),
+}
+
+fn is_defined_tag_type(tag_type: u16) -> bool {
+    matches!(
+        tag_type,
+        TAG_END_OF_LIST
+            | TAG_SERVICE_NAME
+            | TAG_AC_NAME
+            | TAG_HOST_UNIQ
+            | TAG_AC_COOKIE
+            | TAG_VENDOR_SPECIFIC
+            | TAG_RELAY_SESSION_ID
+            | TAG_SERVICE_NAME_ERROR
+            | TAG_AC_SYSTEM_ERROR
+            | TAG_GENERIC_ERROR
+            | TAG_PPP_MAX_PAYLOAD
+            | TAG_CREDITS
+            | TAG_METRICS
+            | TAG_SEQUENCE_NUMBER
+            | TAG_CREDIT_SCALE_FACTOR
+    )
+}
+
+/// Check that `value` fits into the 16 bit length field of a tag.
+fn checked_value(tag_type: u16, value: &[u8]) -> Result<&[u8], ParseError> {
+    if value.len() > usize::from(u16::MAX) {
+        return Err(ParseError::TagLengthOverflow {
+            tag_type,
+            length: value.len(),
+        });
+    }
+    Ok(value)
+}
+
+impl<'a> Tag<'a> {
+    /// Create a tag of a type this crate doesn't know, e.g. for experimental tags.
+    ///
+    /// Tag types which are defined by the RFCs (including End-Of-List) are rejected, use the
+    /// matching variant for them instead.
+    pub fn custom(tag_type: u16, payload: &'a [u8]) -> Result<Self, ParseError> {
+        let tag = match num::NonZeroU16::new(tag_type) {
+            Some(tag) if !is_defined_tag_type(tag_type) => tag,
+            _ => return Err(ParseError::ReservedTagType(tag_type)),
+        };
+        Ok(Tag::Unknown((tag, checked_value(tag_type, payload)?)))
+    }
+
+    /// Create a Service-Name tag, e.g. from a `&str`.
+    ///
+    /// The value is sent as is, without a terminating NUL.
+    pub fn service_name<T: AsRef<[u8]> + ?Sized>(value: &'a T) -> Result<Self, ParseError> {
+        checked_value(TAG_SERVICE_NAME, value.as_ref()).map(Tag::ServiceName)
+    }
+
+    /// Create an AC-Name tag, e.g. from a `&str`.
+    pub fn ac_name<T: AsRef<[u8]> + ?Sized>(value: &'a T) -> Result<Self, ParseError> {
+        checked_value(TAG_AC_NAME, value.as_ref()).map(Tag::AcName)
+    }
+
+    /// Create a Host-Uniq tag from any byte-like value.
+    pub fn host_uniq<T: AsRef<[u8]> + ?Sized>(value: &'a T) -> Result<Self, ParseError> {
+        checked_value(TAG_HOST_UNIQ, value.as_ref()).map(Tag::HostUniq)
+    }
+
+    pub fn from_buffer(buffer: &[u8]) -> Result<(Tag<'_>, &[u8]), ParseError> {
+        let total_length = buffer.len();
+        if total_length < 4 {
+            return Err(ParseError::IncompleteTag(total_length as u8));
+        }
+
+        let tag = NE::read_u16(buffer);
+        let length = usize::from(NE::read_u16(&buffer[2..])) + 4;
+
+        if length > total_length {
+            return Err(ParseError::TagLengthOutOfBound {
+                expected_tag_length: length as u16,
+                remaining_payload_length: total_length as u16,
+            });
+        }
+
+        let tag_enum = match tag {
+            TAG_END_OF_LIST => {
+                if length != 4 {
+                    return Err(ParseError::TagWithInvalidLength {
+                        tag_type: TAG_END_OF_LIST,
+                        length: length as u16,
+                    });
+                }
+                Tag::EndOfList
+            }
+            TAG_SERVICE_NAME => Tag::ServiceName(&buffer[4..length]),
+            TAG_AC_NAME => Tag::AcName(&buffer[4..length]),
+            TAG_HOST_UNIQ => Tag::HostUniq(&buffer[4..length]),
+            TAG_AC_COOKIE => Tag::AcCookie(&buffer[4..length]),
+            TAG_VENDOR_SPECIFIC => Tag::VendorSpecific(&buffer[4..length]),
+            TAG_RELAY_SESSION_ID => Tag::RelaySessionId(&buffer[4..length]),
+            TAG_SERVICE_NAME_ERROR => Tag::ServiceNameError(&buffer[4..length]),
+            TAG_AC_SYSTEM_ERROR => Tag::AcSystemError(&buffer[4..length]),
+            TAG_GENERIC_ERROR => Tag::GenericError(&buffer[4..length]),
+
+            TAG_PPP_MAX_PAYLOAD => {
+                if length != 6 {
+                    return Err(ParseError::TagWithInvalidLength {
+                        tag_type: TAG_PPP_MAX_PAYLOAD,
+                        length: length as u16,
+                    });
+                }
+                Tag::PppMaxMtu(NE::read_u16(&buffer[4..]))
+            }
+
+            TAG_CREDITS => {
+                if length != 8 {
+                    return Err(ParseError::TagWithInvalidLength {
+                        tag_type: TAG_CREDITS,
+                        length: length as u16,
+                    });
+                }
+                Tag::Credits((NE::read_u16(&buffer[4..]), NE::read_u16(&buffer[6..])))
+            }
+            TAG_SEQUENCE_NUMBER => {
+                if length != 6 {
+                    return Err(ParseError::TagWithInvalidLength {
+                        tag_type: TAG_SEQUENCE_NUMBER,
+                        length: length as u16,
+                    });
+                }
+                Tag::SequenceNumber(NE::read_u16(&buffer[4..]))
+            }
+            TAG_CREDIT_SCALE_FACTOR => {
+                if length != 6 {
+                    return Err(ParseError::TagWithInvalidLength {
+                        tag_type: TAG_CREDIT_SCALE_FACTOR,
+                        length: length as u16,
+                    });
+                }
+                Tag::CreditScaleFactor(NE::read_u16(&buffer[4..]))
+            }
+            // TODO: parsing this is more complex, check RFC for fields
+            TAG_METRICS => Tag::Metrics(&buffer[4..length]),
+            // everything else
+            _ => Tag::Unknown((num::NonZeroU16::new(tag).unwrap(), &buffer[4..length])),
+        };
+
+        Ok((tag_enum, &buffer[length..]))
+    }
+
+    pub fn get_tag_type(&self) -> u16 {
+        match self {
+            Tag::EndOfList => TAG_END_OF_LIST,
+            Tag::ServiceName(_) => TAG_SERVICE_NAME,
+            Tag::AcName(_) => TAG_AC_NAME,
+            Tag::HostUniq(_) => TAG_HOST_UNIQ,
+            Tag::AcCookie(_) => TAG_AC_COOKIE,
+            Tag::VendorSpecific(_) => TAG_VENDOR_SPECIFIC,
+            Tag::RelaySessionId(_) => TAG_RELAY_SESSION_ID,
+            Tag::ServiceNameError(_) => TAG_SERVICE_NAME_ERROR,
+            Tag::AcSystemError(_) => TAG_AC_SYSTEM_ERROR,
+            Tag::GenericError(_) => TAG_GENERIC_ERROR,
+            Tag::PppMaxMtu(_) => TAG_PPP_MAX_PAYLOAD,
+            Tag::Credits(_) => TAG_CREDITS,
+            Tag::Metrics(_) => TAG_METRICS,
+            Tag::SequenceNumber(_) => TAG_SEQUENCE_NUMBER,
+            Tag::CreditScaleFactor(_) => TAG_CREDIT_SCALE_FACTOR,
+            Tag::Unknown((tag, _)) => u16::from(*tag),
+        }
+    }
+
+    pub fn get_message(&self) -> Result<Option<&str>, str::Utf8Error> {
+        match self {
+            Tag::EndOfList => Ok(None),
+            Tag::ServiceName(msg)
+            | Tag::AcName(msg)
+            | Tag::HostUniq(msg)
+            | Tag::AcCookie(msg)
+            | Tag::VendorSpecific(msg)
+            | Tag::RelaySessionId(msg)
+            | Tag::ServiceNameError(msg)
+            | Tag::AcSystemError(msg)
+            | Tag::GenericError(msg)
+            | Tag::Unknown((_, msg)) => {
+                str::from_utf8(msg).map(|msg| if msg.is_empty() { None } else { Some(msg) })
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Get the text of a Service-Name, AC-Name or error tag, decoded lossily.
+    ///
+    /// RFC 2516 asks for UTF-8, but some vendors send Latin-1 or arbitrary bytes.  Invalid
+    /// sequences are replaced by U+FFFD instead of failing like `get_message`, so the result is
+    /// meant for logging and display.  Use `value_eq` to compare the exact bytes.
+    #[cfg(feature = "alloc")]
+    pub fn text_lossy(&self) -> Option<Cow<'a, str>> {
+        match *self {
+            Tag::ServiceName(text)
+            | Tag::AcName(text)
+            | Tag::ServiceNameError(text)
+            | Tag::AcSystemError(text)
+            | Tag::GenericError(text) => Some(String::from_utf8_lossy(text)),
+            _ => None,
+        }
+    }
+
+    /// Compare the value of a tag with variable length to `value`, byte by byte.
+    pub fn value_eq<T: AsRef<[u8]> + ?Sized>(&self, value: &T) -> bool {
+        self.bytes_value() == Some(value.as_ref())
+    }
+
+    /// Like `value_eq`, but ASCII letters match regardless of their case.  Other bytes,
+    /// including non-ASCII ones, have to match exactly.
+    pub fn value_eq_ignore_ascii_case<T: AsRef<[u8]> + ?Sized>(&self, value: &T) -> bool {
+        self.bytes_value()
+            .is_some_and(|bytes| bytes.eq_ignore_ascii_case(value.as_ref()))
+    }
+
+    fn bytes_value(&self) -> Option<&'a [u8]> {
+        match *self {
+            Tag::ServiceName(value)
+            | Tag::AcName(value)
+            | Tag::HostUniq(value)
+            | Tag::AcCookie(value)
+            | Tag::VendorSpecific(value)
+            | Tag::RelaySessionId(value)
+            | Tag::ServiceNameError(value)
+            | Tag::AcSystemError(value)
+            | Tag::GenericError(value)
+            | Tag::Metrics(value)
+            | Tag::Unknown((_, value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the type and the encoded value of a tag.
+    ///
+    /// Tags carrying bytes return them directly, the numeric values of PPP-Max-Payload and the
+    /// RFC 5578 tags (Credits, Sequence-Number and Credit-Scale-Factor) are encoded into
+    /// `scratch`.
+    pub fn get_tuple<'s>(&'s self, scratch: &'s mut [u8; 4]) -> (u16, &'s [u8]) {
+        let len = match *self {
+            Tag::EndOfList => return (TAG_END_OF_LIST, &[]),
+            Tag::ServiceName(msg) => return (TAG_SERVICE_NAME, msg),
+            Tag::AcName(msg) => return (TAG_AC_NAME, msg),
+            Tag::HostUniq(msg) => return (TAG_HOST_UNIQ, msg),
+            Tag::AcCookie(msg) => return (TAG_AC_COOKIE, msg),
+            Tag::VendorSpecific(msg) => return (TAG_VENDOR_SPECIFIC, msg),
+            Tag::RelaySessionId(msg) => return (TAG_RELAY_SESSION_ID, msg),
+            Tag::ServiceNameError(msg) => return (TAG_SERVICE_NAME_ERROR, msg),
+            Tag::AcSystemError(msg) => return (TAG_AC_SYSTEM_ERROR, msg),
+            Tag::GenericError(msg) => return (TAG_GENERIC_ERROR, msg),
+            Tag::Metrics(msg) => return (TAG_METRICS, msg),
+            Tag::Unknown((num, msg)) => return (u16::from(num), msg),
+            Tag::PppMaxMtu(value) | Tag::SequenceNumber(value) | Tag::CreditScaleFactor(value) => {
+                NE::write_u16(scratch, value);
+                2
+            }
+            Tag::Credits((fcn, bcn)) => {
+                NE::write_u16(scratch, fcn);
+                NE::write_u16(&mut scratch[2..], bcn);
+                4
+            }
+        };
+        (self.get_tag_type(), &scratch[..len])
+    }
+
+    /// Get the number of bytes `write` needs for this tag (including the tag header)
+    pub fn encoded_len(&self) -> usize {
+        4 + self.get_tuple(&mut [0u8; 4]).1.len()
+    }
+
+    pub fn write(&self, buffer: &mut [u8]) -> Result<usize, ParseError> {
+        let mut scratch = [0u8; 4];
+        let (tag_id, tag_content) = self.get_tuple(&mut scratch);
+        let length =
+            u16::try_from(tag_content.len()).map_err(|_| ParseError::TagLengthOverflow {
+                tag_type: tag_id,
+                length: tag_content.len(),
+            })?;
+        if buffer.len() < tag_content.len() + 4 {
+            return Err(ParseError::BufferTooSmallForTag {
+                available: u16::try_from(buffer.len()).unwrap_or(u16::MAX),
+                requested: tag_content.len(),
+            });
+        }
+
+        NE::write_u16(buffer, tag_id);
+        NE::write_u16(&mut buffer[2..], length);
+        buffer[4..4 + tag_content.len()].copy_from_slice(tag_content);
+
+        Ok(4 + tag_content.len())
+    }
+
+    /// Append the tag to a `BufMut`, e.g. a `BytesMut` which grows as needed.
+    ///
+    /// Returns the number of bytes written, which is `encoded_len`.
+    #[cfg(feature = "bytes")]
+    pub fn write_to<B: bytes::BufMut>(&self, buffer: &mut B) -> Result<usize, ParseError> {
+        let encoded_len = self.encoded_len();
+        if buffer.remaining_mut() < encoded_len {
+            return Err(ParseError::BufferTooSmallForTag {
+                available: u16::try_from(buffer.remaining_mut()).unwrap_or(u16::MAX),
+                requested: encoded_len - 4,
+            });
+        }
+
+        let mut scratch = [0u8; 4];
+        let (tag_id, tag_content) = self.get_tuple(&mut scratch);
+        let length =
+            u16::try_from(tag_content.len()).map_err(|_| ParseError::TagLengthOverflow {
+                tag_type: tag_id,
+                length: tag_content.len(),
+            })?;
+        buffer.put_u16(tag_id);
+        buffer.put_u16(length);
+        buffer.put_slice(tag_content);
+
+        Ok(encoded_len)
+    }
+}
+
+/// Get the name of a tag type as used in the RFCs
+pub fn tag_name(tag_type: u16) -> Option<&'static str> {
+    Some(match tag_type {
+        TAG_END_OF_LIST => "End-Of-List",
+        TAG_SERVICE_NAME => "Service-Name",
+        TAG_AC_NAME => "AC-Name",
+        TAG_HOST_UNIQ => "Host-Uniq",
+        TAG_AC_COOKIE => "AC-Cookie",
+        TAG_VENDOR_SPECIFIC => "Vendor-Specific",
+        TAG_RELAY_SESSION_ID => "Relay-Session-Id",
+        TAG_SERVICE_NAME_ERROR => "Service-Name-Error",
+        TAG_AC_SYSTEM_ERROR => "AC-System-Error",
+        TAG_GENERIC_ERROR => "Generic-Error",
+        TAG_PPP_MAX_PAYLOAD => "PPP-Max-Payload",
+        TAG_CREDITS => "Credits",
+        TAG_METRICS => "Metrics",
+        TAG_SEQUENCE_NUMBER => "Sequence-Number",
+        TAG_CREDIT_SCALE_FACTOR => "Credit-Scale-Factor",
+        _ => return None,
+    })
+}
+
+/// Displays the name of a tag type, or the type number if it is unknown
+pub struct TagName(pub u16);
+
+impl fmt::Display for TagName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match tag_name(self.0) {
+            Some(name) => f.write_str(name),
+            None => write!(f, "tag {:#06x}", self.0),
+        }
+    }
+}
+
+/// Number of value bytes shown by `Tag::write_summary`
+const SUMMARY_VALUE_LEN: usize = 16;
+
+/// Write a tag value as a quoted string if it is printable, as hex otherwise.  Values longer
+/// than `limit` bytes are cut and end with `..`.
+fn write_value<W: fmt::Write + ?Sized>(w: &mut W, value: &[u8], limit: usize) -> fmt::Result {
+    let mut end = value.len().min(limit);
+    match str::from_utf8(value) {
+        Ok(text) if !text.chars().any(char::is_control) => {
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            write!(w, "{:?}", &text[..end])?;
+        }
+        _ => {
+            write!(w, "{} bytes ", value.len())?;
+            value[..end]
+                .iter()
+                .try_for_each(|byte| write!(w, "{:02x}", byte))?;
+        }
+    }
+    if end < value.len() {
+        w.write_str("..")?;
+    }
+    Ok(())
+}
+
+impl<'a> Tag<'a> {
+    fn write_with_limit<W: fmt::Write + ?Sized>(&self, w: &mut W, limit: usize) -> fmt::Result {
+        write!(w, "{}", TagName(self.get_tag_type()))?;
+        match self {
+            Tag::EndOfList => Ok(()),
+            Tag::PppMaxMtu(mtu) => write!(w, ": {}", mtu),
+            Tag::Credits((fcn, bcn)) => write!(w, ": FCN {}, BCN {}", fcn, bcn),
+            Tag::SequenceNumber(number) => write!(w, ": {}", number),
+            Tag::CreditScaleFactor(factor) => write!(w, ": {}", factor),
+            Tag::ServiceName(value)
+            | Tag::AcName(value)
+            | Tag::HostUniq(value)
+            | Tag::AcCookie(value)
+            | Tag::VendorSpecific(value)
+            | Tag::RelaySessionId(value)
+            | Tag::ServiceNameError(value)
+            | Tag::AcSystemError(value)
+            | Tag::GenericError(value)
+            | Tag::Metrics(value)
+            | Tag::Unknown((_, value)) => {
+                w.write_str(": ")?;
+                write_value(w, value, limit)
+            }
+        }
+    }
+
+    /// Write a one-line summary like `Display`, but with long values cut after 16 bytes.
+    ///
+    /// Doesn't allocate, so it can be used to log into a fixed size buffer on targets without a
+    /// heap.
+    pub fn write_summary(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        self.write_with_limit(w, SUMMARY_VALUE_LEN)
+    }
+}
+
+/// Prints the tag name and its value, e.g. `AC-Name: "bras01.example"`.  Values which are not
+/// printable are written as their length and hex digits.
+impl<'a> fmt::Display for Tag<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_with_limit(f, usize::MAX)
+    }
+}
+
+pub struct TagIterator<'a> {
+    payload: &'a [u8],
+}
+
+impl<'a> TagIterator<'a> {
+    /// Iterate over the tags of a payload which was already validated, e.g. by
+    /// `ParserConfig::parse`.  Iteration ends after the End-Of-List tag or in front of the
+    /// first malformed tag, whatever a lenient parser let through behind it.
+    pub fn new_unchecked(payload: &'a [u8]) -> Self {
+        TagIterator { payload }
+    }
+}
+
+impl<'a> Iterator for TagIterator<'a> {
+    type Item = Tag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.payload.is_empty() {
+            return None;
+        }
+
+        match Tag::from_buffer(self.payload) {
+            Ok((Tag::EndOfList, _)) => {
+                self.payload = &[];
+                Some(Tag::EndOfList)
+            }
+            Ok((tag, payload)) => {
+                self.payload = payload;
+                Some(tag)
+            }
+            Err(_) => {
+                self.payload = &[];
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_tag_roundtrip() {
+        assert_eq!(
+            Tag::custom(TAG_HOST_UNIQ, b""),
+            Err(ParseError::ReservedTagType(TAG_HOST_UNIQ))
+        );
+        assert_eq!(
+            Tag::custom(TAG_END_OF_LIST, b""),
+            Err(ParseError::ReservedTagType(TAG_END_OF_LIST))
+        );
+
+        let long = vec![0u8; usize::from(u16::MAX) + 1];
+        assert_eq!(
+            Tag::custom(0x0fee, &long),
+            Err(ParseError::TagLengthOverflow {
+                tag_type: 0x0fee,
+                length: long.len(),
+            })
+        );
+
+        let tag = Tag::custom(0x0fee, b"exp").unwrap();
+        let mut buffer = [0u8; 16];
+        let len = tag.write(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"\x0f\xee\x00\x03exp");
+        assert_eq!(Tag::from_buffer(&buffer[..len]).unwrap().0, tag);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn write_to_bytes_mut() {
+        let mut buffer = bytes::BytesMut::new();
+        let tags = [
+            Tag::service_name("internet").unwrap(),
+            Tag::PppMaxMtu(1500),
+            Tag::HostUniq(&[1, 2, 3, 4]),
+        ];
+        for tag in &tags {
+            assert_eq!(tag.write_to(&mut buffer).unwrap(), tag.encoded_len());
+        }
+
+        let mut expected = [0u8; 32];
+        let mut len = 0;
+        for tag in &tags {
+            len += tag.write(&mut expected[len..]).unwrap();
+        }
+        assert_eq!(&buffer[..], &expected[..len]);
+
+        let mut small = [0u8; 8];
+        assert!(tags[0].write_to(&mut &mut small[..]).is_err());
+    }
+
+    #[test]
+    fn constructors_from_str_and_bytes() {
+        assert_eq!(
+            Tag::service_name("internet"),
+            Ok(Tag::ServiceName(b"internet"))
+        );
+        assert_eq!(Tag::service_name(""), Ok(Tag::ServiceName(b"")));
+        assert_eq!(Tag::ac_name(&String::from("ac1")), Ok(Tag::AcName(b"ac1")));
+        assert_eq!(Tag::host_uniq(&[1u8, 2, 3]), Ok(Tag::HostUniq(&[1, 2, 3])));
+        assert_eq!(
+            Tag::host_uniq(&vec![7u8; 4][..]),
+            Ok(Tag::HostUniq(&[7; 4]))
+        );
+
+        let too_long = vec![b'a'; usize::from(u16::MAX) + 1];
+        assert_eq!(
+            Tag::ac_name(&too_long),
+            Err(ParseError::TagLengthOverflow {
+                tag_type: TAG_AC_NAME,
+                length: too_long.len(),
+            })
+        );
+        assert!(Tag::ac_name(&too_long[1..]).is_ok());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn binary_safe_text() {
+        // "Café" in Latin-1
+        let latin1 = Tag::AcName(b"Caf\xe9");
+        assert_eq!(latin1.text_lossy().unwrap(), "Caf\u{fffd}");
+        assert_eq!(latin1.get_message().ok(), None);
+        assert!(latin1.value_eq(b"Caf\xe9"));
+        assert!(!latin1.value_eq("Caf\u{e9}"));
+        assert!(latin1.value_eq_ignore_ascii_case(b"CAF\xe9"));
+
+        let utf8 = Tag::service_name("Café").unwrap();
+        assert!(matches!(utf8.text_lossy(), Some(Cow::Borrowed("Café"))));
+        assert!(!utf8.value_eq_ignore_ascii_case("CAFÉ"));
+        assert_eq!(Tag::HostUniq(b"abc").text_lossy(), None);
+        assert!(Tag::HostUniq(b"abc").value_eq("abc"));
+        assert!(!Tag::PppMaxMtu(1500).value_eq(b""));
+    }
+}
@@ -0,0 +1,8 @@
+/* automatically generated by rust-bindgen */
+
+extern "C" {
+    pub fn control_socket_init() -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn control_socket_close();
+}
@@ -0,0 +1,82 @@
+/* automatically generated by rust-bindgen */
+
+pub const PppoeStatus_PPPOE_OK: PppoeStatus = 0;
+pub const PppoeStatus_PPPOE_ERR_SOCKET: PppoeStatus = -1;
+pub const PppoeStatus_PPPOE_ERR_SETSOCKOPT: PppoeStatus = -2;
+pub const PppoeStatus_PPPOE_ERR_IOCTL: PppoeStatus = -3;
+pub const PppoeStatus_PPPOE_ERR_BIND: PppoeStatus = -4;
+pub const PppoeStatus_PPPOE_ERR_CONNECT: PppoeStatus = -5;
+pub const PppoeStatus_PPPOE_ERR_CLOSE: PppoeStatus = -6;
+pub type PppoeStatus = i32;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PppoeConnectionData {
+    pub pppoe_socket: ::std::os::raw::c_int,
+    pub raw_socket: ::std::os::raw::c_int,
+    pub interface_name: [::std::os::raw::c_char; 16usize],
+    pub mac_address: [::std::os::raw::c_uchar; 6usize],
+}
+#[test]
+fn bindgen_test_layout_PppoeConnectionData() {
+    assert_eq!(
+        ::std::mem::size_of::<PppoeConnectionData>(),
+        32usize,
+        concat!("Size of: ", stringify!(PppoeConnectionData))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<PppoeConnectionData>(),
+        4usize,
+        concat!("Alignment of ", stringify!(PppoeConnectionData))
+    );
+}
+extern "C" {
+    pub fn pppoe_create_socket() -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pppoe_connect(
+        data: *const PppoeConnectionData,
+        session_id: u16,
+        remote_mac: *const ::std::os::raw::c_uchar,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pppoe_connect_device(
+        pppoe_socket: ::std::os::raw::c_int,
+        session_id: u16,
+        remote_mac: *const ::std::os::raw::c_uchar,
+        interface_name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pppoe_raw_socket_create(data: *mut PppoeConnectionData) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pppoe_raw_socket_bind(data: *const PppoeConnectionData) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pppoe_raw_socket_send() -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pppoe_connection_data_init(
+        arg1: *mut PppoeConnectionData,
+        arg2: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pppoe_connection_data_set_name(
+        arg1: *mut PppoeConnectionData,
+        arg2: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pppoe_connection_data_clear(arg1: *mut PppoeConnectionData);
+}
+extern "C" {
+    pub fn pppoe_connection_data_close(arg1: *mut PppoeConnectionData) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn ppp_channel_from_fd(fd: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn lookup_hardware_address(data: *mut PppoeConnectionData) -> ::std::os::raw::c_int;
+}
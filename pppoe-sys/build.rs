@@ -1,20 +1,45 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn main() {
     cc::Build::new().file("pppoe/pppoe.c").compile("pppoe");
 
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     for header in &["pppoe", "control"] {
-        let bindings = bindgen::Builder::default()
-            .header(&format!("pppoe/{}.h", header))
-            .parse_callbacks(Box::new(bindgen::CargoCallbacks))
-            .generate()
-            .expect("Unable to generate bindings");
-
-        let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+        write_bindings(header, &out_path.join(&format!("{}_bindings.rs", header)));
+    }
+}
 
-        bindings
-            .write_to_file(out_path.join(&format!("{}_bindings.rs", header)))
-            .expect("Couldn't write bindings");
+#[cfg(feature = "bindgen")]
+fn write_bindings(header: &str, out_file: &Path) {
+    let mut builder = bindgen::Builder::default();
+    // let clang pick the headers of the target's libc when cross compiling, e.g. Bionic
+    let target = env::var("TARGET").unwrap();
+    if target != env::var("HOST").unwrap() {
+        builder = builder.clang_arg(format!("--target={}", target));
     }
+
+    let bindings = builder
+        .header(&format!("pppoe/{}.h", header))
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks))
+        // keep the output identical to the pre-generated bindings in `bindings/`
+        .whitelist_function("pppoe_.*|ppp_.*|lookup_hardware_address|control_socket_.*")
+        .whitelist_type("PppoeStatus")
+        .generate()
+        .expect("Unable to generate bindings");
+
+    bindings
+        .write_to_file(out_file)
+        .expect("Couldn't write bindings");
+}
+
+/// Use the bindings in `bindings/`, so building doesn't require libclang (e.g. when cross
+/// compiling for musl targets).  They have to be regenerated with the `bindgen` feature whenever
+/// the headers change.
+#[cfg(not(feature = "bindgen"))]
+fn write_bindings(header: &str, out_file: &Path) {
+    let pregenerated = format!("bindings/{}_bindings.rs", header);
+    println!("cargo:rerun-if-changed={}", pregenerated);
+    println!("cargo:rerun-if-changed=pppoe/{}.h", header);
+    std::fs::copy(&pregenerated, out_file).expect("Couldn't copy pre-generated bindings");
 }
@@ -35,7 +35,9 @@ static int get_interface_index_from_name(struct PppoeConnectionData const *const
 
 int control_socket_init(void) {
 	if (control_socket == 0) {
-		control_socket = socket(AF_INET, SOCK_DGRAM, IPPROTO_IP);
+		int fd = socket(AF_INET, SOCK_DGRAM, IPPROTO_IP);
+		if (fd < 0) return -1;
+		control_socket = fd;
 	}
 	return control_socket;
 }
@@ -56,11 +58,11 @@ int lookup_hardware_address(struct PppoeConnectionData *const data) {
 
 	strncpy(ifr.ifr_name, data->interface_name, sizeof ifr.ifr_name);
 
-	if (ioctl(control_socket, SIOCGIFHWADDR, &ifr) < 0) return -1;
+	if (ioctl(control_socket, SIOCGIFHWADDR, &ifr) < 0) return PPPOE_ERR_IOCTL;
 
 	memcpy(data->mac_address, ifr.ifr_hwaddr.sa_data, sizeof data->mac_address);
 
-	return 0;
+	return PPPOE_OK;
 }
 
 
@@ -78,27 +80,32 @@ int pppoe_connection_data_set_name(struct PppoeConnectionData *data, char const
 
 int pppoe_connection_data_init(struct PppoeConnectionData *data, char const *const interface_name) {
 	assert(NULL != data);
+	int ret;
+	int saved_errno;
 
 	if ((data->pppoe_socket = pppoe_create_socket()) < 0) {
-		return -1;
+		data->pppoe_socket = 0;
+		return PPPOE_ERR_SOCKET;
 	}
 
 	if (NULL != interface_name) {
 		assert(strlen(interface_name) > 0);
 		assert(strlen(interface_name) <= IFNAMSIZ);
-		if (pppoe_connection_data_set_name(data, interface_name) < 0) {
+		if ((ret = pppoe_connection_data_set_name(data, interface_name)) < 0) {
 			goto PPPOE_CONNECTION_DATA_INIT_CLEANUP;
 		};
 	}
 
-	if ((pppoe_raw_socket_create(data)) < 0) goto PPPOE_CONNECTION_DATA_INIT_CLEANUP;
-	if ((pppoe_raw_socket_bind(data)) < 0) goto PPPOE_CONNECTION_DATA_INIT_CLEANUP;
+	if ((ret = pppoe_raw_socket_create(data)) < 0) goto PPPOE_CONNECTION_DATA_INIT_CLEANUP;
+	if ((ret = pppoe_raw_socket_bind(data)) < 0) goto PPPOE_CONNECTION_DATA_INIT_CLEANUP;
 
-	return 0;
+	return PPPOE_OK;
 
 PPPOE_CONNECTION_DATA_INIT_CLEANUP:
+	saved_errno = errno;
 	pppoe_connection_data_clear(data);
-	return -1;
+	errno = saved_errno;
+	return ret;
 }
 
 
@@ -112,6 +119,28 @@ void pppoe_connection_data_clear(struct PppoeConnectionData *const data) {
 }
 
 
+int pppoe_connection_data_close(struct PppoeConnectionData *const data) {
+	assert(NULL != data);
+	int ret = PPPOE_OK;
+	int saved_errno = 0;
+
+	if (data->pppoe_socket != 0 && close(data->pppoe_socket) < 0) {
+		ret = PPPOE_ERR_CLOSE;
+		saved_errno = errno;
+	}
+	if (data->raw_socket != 0 && close(data->raw_socket) < 0 && ret == 0) {
+		ret = PPPOE_ERR_CLOSE;
+		saved_errno = errno;
+	}
+
+	data->pppoe_socket = 0;
+	data->raw_socket = 0;
+
+	if (ret < 0) errno = saved_errno;
+	return ret;
+}
+
+
 int pppoe_create_socket(void) {
 	return socket(AF_PPPOX, SOCK_STREAM, PX_PROTO_OE);
 }
@@ -120,35 +149,54 @@ int pppoe_create_socket(void) {
 int pppoe_connect(struct PppoeConnectionData const *const data, uint16_t pppoe_session_id, unsigned char const *const remote_mac) {
 	assert(NULL != data);
 
+	return pppoe_connect_device(data->pppoe_socket, pppoe_session_id, remote_mac, data->interface_name);
+}
+
+
+int pppoe_connect_device(int pppoe_socket, uint16_t pppoe_session_id, unsigned char const *const remote_mac, char const *const interface_name) {
+	assert(NULL != remote_mac);
+	assert(NULL != interface_name);
+	assert(strlen(interface_name) > 0);
+	assert(strlen(interface_name) < IFNAMSIZ);
+
 	struct sockaddr_pppox sp;
+	memset(&sp, '\0', sizeof sp);
 
 	sp.sa_family = AF_PPPOX;
 	sp.sa_protocol = PX_PROTO_OE;
 	sp.sa_addr.pppoe.sid = htons(pppoe_session_id);
 
-	memcpy(sp.sa_addr.pppoe.dev, data->interface_name, sizeof sp.sa_addr.pppoe.dev);
+	strncpy(sp.sa_addr.pppoe.dev, interface_name, sizeof sp.sa_addr.pppoe.dev);
 	memcpy(sp.sa_addr.pppoe.remote, remote_mac, sizeof sp.sa_addr.pppoe.remote);
 
-	return connect(data->pppoe_socket, (struct sockaddr *) &sp, sizeof sp);
+	if (connect(pppoe_socket, (struct sockaddr *) &sp, sizeof sp) < 0)
+		return PPPOE_ERR_CONNECT;
+
+	return PPPOE_OK;
 }
 
 
 int pppoe_raw_socket_create(struct PppoeConnectionData *const data) {
 	assert(NULL != data);
 	int optval = 1;
+	int saved_errno;
 
-	if ((data->raw_socket = socket(AF_PACKET, SOCK_RAW, htons(ETH_PPPOE_DISCOVERY))) < 0)
-		return -1;
+	if ((data->raw_socket = socket(AF_PACKET, SOCK_RAW, htons(ETH_PPPOE_DISCOVERY))) < 0) {
+		data->raw_socket = 0;
+		return PPPOE_ERR_SOCKET;
+	}
 
 	if (setsockopt(data->raw_socket, SOL_SOCKET, SO_BROADCAST, &optval, sizeof(optval)) < 0)
 		goto OPEN_INTERFACE_CLOSE_SOCKET;
 
-	return 0;
+	return PPPOE_OK;
 
 OPEN_INTERFACE_CLOSE_SOCKET:
+	saved_errno = errno;
 	close(data->raw_socket);
 	data->raw_socket = 0;
-	return -1;
+	errno = saved_errno;
+	return PPPOE_ERR_SETSOCKOPT;
 }
 
 
@@ -161,10 +209,10 @@ int pppoe_raw_socket_bind(struct PppoeConnectionData const *const data) {
 	sa.sll_protocol = htons(ETH_PPPOE_DISCOVERY);
 
 	if ((sa.sll_ifindex = get_interface_index_from_name(data)) < 0)
-		return -1;
+		return PPPOE_ERR_IOCTL;
 
 	if (bind(data->raw_socket, (struct sockaddr *) &sa, sizeof sa) < 0)
-		return -1;
+		return PPPOE_ERR_BIND;
 
-	return 0;
+	return PPPOE_OK;
 }
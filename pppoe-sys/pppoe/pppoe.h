@@ -1,3 +1,4 @@
+#include <stdint.h>
 #include <net/if.h>
 #include <linux/if_packet.h>
 #include <net/ethernet.h>
@@ -7,6 +8,19 @@
 #define ETH_PPPOE_SESSION   0x8864
 
 
+/* Returned by the functions below (instead of a plain -1) to tell which call failed, errno is
+ * set by that call. */
+enum PppoeStatus {
+	PPPOE_OK = 0,
+	PPPOE_ERR_SOCKET = -1,
+	PPPOE_ERR_SETSOCKOPT = -2,
+	PPPOE_ERR_IOCTL = -3,
+	PPPOE_ERR_BIND = -4,
+	PPPOE_ERR_CONNECT = -5,
+	PPPOE_ERR_CLOSE = -6,
+};
+
+
 struct PppoeConnectionData {
 	int pppoe_socket;
 	int raw_socket;
@@ -16,6 +30,7 @@ struct PppoeConnectionData {
 
 int pppoe_create_socket(void);
 int pppoe_connect(struct PppoeConnectionData const *const data, uint16_t session_id, unsigned char const *const remote_mac); 
+int pppoe_connect_device(int pppoe_socket, uint16_t session_id, unsigned char const *const remote_mac, char const *const interface_name);
 
 int pppoe_raw_socket_create(struct PppoeConnectionData *const data);
 int pppoe_raw_socket_bind(struct PppoeConnectionData const *const data);
@@ -24,6 +39,7 @@ int pppoe_raw_socket_send();
 int pppoe_connection_data_init(struct PppoeConnectionData *, char const *const);
 int pppoe_connection_data_set_name(struct PppoeConnectionData *, char const *const);
 void pppoe_connection_data_clear(struct PppoeConnectionData *const);
+int pppoe_connection_data_close(struct PppoeConnectionData *const);
 
 int ppp_channel_from_fd(int fd);
 int lookup_hardware_address(struct PppoeConnectionData *const data);
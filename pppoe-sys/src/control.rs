@@ -2,13 +2,21 @@
 #![allow(non_camel_case_types)]
 include!(concat!(env!("OUT_DIR"), "/control_bindings.rs"));
 
-use std::io;
+use crate::error::{Call, Operation, SysError};
 
-pub fn init() -> io::Result<()> {
+pub fn init() -> Result<(), SysError> {
     let ret = unsafe { control_socket_init() };
     if ret < 0 {
-        return Err(io::Error::from_raw_os_error(ret));
+        return Err(
+            SysError::last_os_error(Operation::ControlSocket, None).with_call(Some(Call::Socket))
+        );
     }
 
     Ok(())
 }
+
+/// Close the control socket.  The next call to `init` creates a new one in the network namespace
+/// of the calling thread.
+pub fn close() {
+    unsafe { control_socket_close() }
+}
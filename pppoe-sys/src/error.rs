@@ -0,0 +1,168 @@
+use std::{error, fmt, io};
+
+/// The operation of the C layer which failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Creating the `AF_INET` socket used for interface ioctls
+    ControlSocket,
+    /// Validating and storing the interface name
+    InterfaceName,
+    /// Looking up the hardware address of the interface (`SIOCGIFHWADDR`)
+    HardwareAddress,
+    /// Creating the `AF_PPPOX` socket
+    PppoeSocket,
+    /// Creating the `AF_PACKET` socket used for the discovery stage
+    RawSocket,
+    /// Binding the `AF_PACKET` socket to the interface (`SIOCGIFINDEX` + `bind`)
+    RawSocketBind,
+    /// Connecting the `AF_PPPOX` socket to a session
+    Connect,
+    /// Closing the `AF_PPPOX` and `AF_PACKET` sockets
+    Close,
+}
+
+impl Operation {
+    fn description(self) -> &'static str {
+        match self {
+            Operation::ControlSocket => "creating the control socket",
+            Operation::InterfaceName => "setting the interface name",
+            Operation::HardwareAddress => "looking up the hardware address (SIOCGIFHWADDR)",
+            Operation::PppoeSocket => "creating the AF_PPPOX socket",
+            Operation::RawSocket => "creating the AF_PACKET socket",
+            Operation::RawSocketBind => "binding the AF_PACKET socket",
+            Operation::Connect => "connecting the AF_PPPOX socket",
+            Operation::Close => "closing the sockets",
+        }
+    }
+
+    fn permission_hint(self) -> Option<&'static str> {
+        match self {
+            // on Android the SELinux policy has to allow the socket classes as well, even for root
+            Operation::RawSocket | Operation::RawSocketBind if cfg!(target_os = "android") => Some(
+                "AF_PACKET sockets require CAP_NET_RAW and an SELinux policy allowing packet_socket",
+            ),
+            Operation::PppoeSocket | Operation::Connect if cfg!(target_os = "android") => Some(
+                "PPPoE kernel sessions require CAP_NET_ADMIN and an SELinux policy allowing pppox_socket",
+            ),
+            Operation::RawSocket | Operation::RawSocketBind => {
+                Some("AF_PACKET sockets require CAP_NET_RAW")
+            }
+            Operation::PppoeSocket | Operation::Connect => {
+                Some("PPPoE kernel sessions require CAP_NET_ADMIN")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The system call which failed within an `Operation`, see `enum PppoeStatus` in `pppoe.h`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Call {
+    Socket,
+    Setsockopt,
+    Ioctl,
+    Bind,
+    Connect,
+    Close,
+}
+
+impl Call {
+    fn name(self) -> &'static str {
+        match self {
+            Call::Socket => "socket",
+            Call::Setsockopt => "setsockopt",
+            Call::Ioctl => "ioctl",
+            Call::Bind => "bind",
+            Call::Connect => "connect",
+            Call::Close => "close",
+        }
+    }
+}
+
+/// An error of the C layer together with the operation and interface it occurred on
+#[derive(Debug)]
+pub struct SysError {
+    operation: Operation,
+    call: Option<Call>,
+    interface: Option<String>,
+    source: io::Error,
+}
+
+impl SysError {
+    pub fn new(operation: Operation, interface: Option<String>, source: io::Error) -> Self {
+        Self {
+            operation,
+            call: None,
+            interface,
+            source,
+        }
+    }
+
+    pub(crate) fn last_os_error(operation: Operation, interface: Option<String>) -> Self {
+        Self::new(operation, interface, io::Error::last_os_error())
+    }
+
+    pub(crate) fn with_call(mut self, call: Option<Call>) -> Self {
+        self.call = call;
+        self
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// Get the system call which failed, if the C layer reported it.
+    pub fn call(&self) -> Option<Call> {
+        self.call
+    }
+
+    /// Get the `errno` set by the failed call.
+    pub fn errno(&self) -> Option<i32> {
+        self.source.raw_os_error()
+    }
+
+    pub fn interface(&self) -> Option<&str> {
+        self.interface.as_deref()
+    }
+
+    pub fn io_error(&self) -> &io::Error {
+        &self.source
+    }
+
+    pub fn kind(&self) -> io::ErrorKind {
+        self.source.kind()
+    }
+}
+
+impl fmt::Display for SysError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.operation.description())?;
+        if let Some(interface) = &self.interface {
+            write!(f, " on interface {}", interface)?;
+        }
+        write!(f, " failed")?;
+        if let Some(call) = self.call {
+            write!(f, " in {}", call.name())?;
+        }
+        write!(f, ": {}", self.source)?;
+
+        if self.source.kind() == io::ErrorKind::PermissionDenied {
+            if let Some(hint) = self.operation.permission_hint() {
+                write!(f, " ({})", hint)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for SysError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<SysError> for io::Error {
+    fn from(error: SysError) -> Self {
+        io::Error::new(error.kind(), error)
+    }
+}
@@ -1,2 +1,5 @@
 pub mod control;
+pub mod error;
 pub mod pppoe;
+
+pub use error::{Call, Operation, SysError};
@@ -1,14 +1,20 @@
 #![allow(non_snake_case)]
 #![allow(non_camel_case_types)]
+#![allow(non_upper_case_globals)]
+#[allow(dead_code)]
 mod internal {
     include!(concat!(env!("OUT_DIR"), "/pppoe_bindings.rs"));
 }
 
 use std::ffi::CString;
 use std::num::NonZeroU16;
-use std::os::unix::io::{RawFd, FromRawFd};
-use std::{io, mem, ptr, fs};
+use std::os::raw::c_char;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::{fs, io, mem};
 
+use crate::error::{Call, Operation, SysError};
+
+const IFNAMSIZ: usize = 16;
 
 #[derive(Debug)]
 #[repr(transparent)]
@@ -20,20 +26,51 @@ impl Connection {
         Self(unsafe { data.assume_init() })
     }
 
-    pub fn set_interface_name(&mut self, interface_name: &str) -> io::Result<()> {
-        if interface_name.len() > self.0.interface_name.len() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Interface name too long",
-            ));
-        }
+    /// Create a connection from sockets which were created elsewhere, e.g. received from another
+    /// process.
+    ///
+    /// Takes ownership of the sockets.  The hardware address is not looked up, so this doesn't
+    /// need the control socket.
+    pub fn from_raw_parts(
+        interface_name: &str,
+        mac_address: [u8; 6],
+        raw_socket: RawFd,
+        pppoe_socket: RawFd,
+    ) -> Result<Self, SysError> {
+        let mut connection = Self::new();
+        connection.store_interface_name(interface_name)?;
+        connection.0.mac_address = mac_address;
+        connection.0.raw_socket = raw_socket;
+        connection.0.pppoe_socket = pppoe_socket;
+        Ok(connection)
+    }
 
-        self.0.interface_name[..interface_name.len()]
-            .copy_from_slice(unsafe { &*(interface_name.as_bytes() as *const _ as *const [i8]) });
+    pub fn set_interface_name(&mut self, interface_name: &str) -> Result<(), SysError> {
+        self.store_interface_name(interface_name)?;
         get_hardware_address(self)?;
         Ok(())
     }
 
+    fn store_interface_name(&mut self, interface_name: &str) -> Result<(), SysError> {
+        validate_interface_name(interface_name)?;
+
+        self.0.interface_name = [0; IFNAMSIZ];
+        self.0.interface_name[..interface_name.len()].copy_from_slice(unsafe {
+            &*(interface_name.as_bytes() as *const _ as *const [c_char])
+        });
+        Ok(())
+    }
+
+    /// Get the name of the interface, if one was set
+    pub fn interface_name(&self) -> Option<String> {
+        let name = unsafe { &*(&self.0.interface_name[..] as *const _ as *const [u8]) };
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        if len == 0 {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&name[..len]).into_owned())
+    }
+
     pub fn raw_socket(&self) -> RawFd {
         self.0.raw_socket
     }
@@ -42,66 +79,248 @@ impl Connection {
         self.0.pppoe_socket
     }
 
-    pub fn connect(&mut self, session_id: NonZeroU16, remote_mac: [u8; 6]) -> io::Result<()> {
+    pub fn connect(&mut self, session_id: NonZeroU16, remote_mac: [u8; 6]) -> Result<(), SysError> {
         connect(self, session_id, remote_mac)
     }
 
+    /// Connect the PPPoE socket to a session on an explicitly given interface.
+    ///
+    /// `connect` uses the interface of the connection, this allows binding the session to
+    /// another device than the one the discovery ran on (e.g. a VLAN on top of it).
+    pub fn connect_device(
+        &mut self,
+        session_id: NonZeroU16,
+        remote_mac: [u8; 6],
+        interface_name: &str,
+    ) -> Result<(), SysError> {
+        connect_device(self, session_id, remote_mac, interface_name)
+    }
+
     pub fn mac_address(&self) -> [u8; 6] {
         self.0.mac_address
     }
 
+    /// Replace the PPPoE socket by a new one, which drops a connected session.
+    pub fn reset_pppoe_socket(&mut self) -> Result<(), SysError> {
+        if self.0.pppoe_socket != 0 {
+            drop(unsafe { fs::File::from_raw_fd(self.0.pppoe_socket) });
+            self.0.pppoe_socket = 0;
+        }
+
+        let pppoe_socket = unsafe { internal::pppoe_create_socket() };
+        if pppoe_socket < 0 {
+            return Err(status_error(
+                internal::PppoeStatus_PPPOE_ERR_SOCKET,
+                Operation::PppoeSocket,
+                self.interface_name(),
+            ));
+        }
+        self.0.pppoe_socket = pppoe_socket;
+        Ok(())
+    }
+
+    /// Close all sockets and create them again on the same interface.
+    ///
+    /// The hardware address is looked up again, as it may have changed.
+    pub fn reinit(&mut self) -> Result<(), SysError> {
+        let interface_name = self.interface_name();
+        if interface_name.is_none() {
+            return Err(SysError::new(
+                Operation::InterfaceName,
+                None,
+                io::Error::new(io::ErrorKind::InvalidInput, "no interface name set"),
+            ));
+        }
+
+        clear_keeping_interface_name(self);
+        connection_data_init(self, interface_name)
+    }
+
+    /// Close the PPPoE and raw sockets, reporting errors of `close(2)`.
+    ///
+    /// Both sockets are closed even if closing the first one fails.  The interface name and
+    /// hardware address are kept, so `reinit` can open the sockets again.
+    pub fn close(&mut self) -> Result<(), SysError> {
+        let ret = unsafe { internal::pppoe_connection_data_close(&mut self.0 as *mut _) };
+        if ret < 0 {
+            return Err(status_error(ret, Operation::Close, self.interface_name()));
+        }
+        Ok(())
+    }
+
     pub fn close_raw_socket(&mut self) {
         drop(unsafe { fs::File::from_raw_fd(self.0.raw_socket) });
         self.0.raw_socket = 0;
     }
 }
 
+/// Create the PPPoE and raw sockets of the connection.
+///
+/// This does the same as `pppoe_connection_data_init`, but reports which of the steps failed.
 pub fn connection_data_init(
     connection: &mut Connection,
     interface_name: Option<String>,
-) -> io::Result<()> {
-    let interface_name = match interface_name {
-        Some(interface_name) => CString::new(interface_name).unwrap().as_ptr(),
-        None => ptr::null(),
-    };
+) -> Result<(), SysError> {
+    if let Some(interface_name) = interface_name {
+        // reject names with interior NUL bytes, like the C layer would via strlen
+        CString::new(interface_name.as_str()).map_err(|err| {
+            SysError::new(
+                Operation::InterfaceName,
+                Some(interface_name.clone()),
+                err.into(),
+            )
+        })?;
+        connection.set_interface_name(&interface_name)?;
+    }
+
+    let result = create_sockets(connection);
+    if result.is_err() {
+        // keep the name, so the connection can be initialized again later
+        clear_keeping_interface_name(connection);
+    }
+    result
+}
+
+fn create_sockets(connection: &mut Connection) -> Result<(), SysError> {
+    let pppoe_socket = unsafe { internal::pppoe_create_socket() };
+    if pppoe_socket < 0 {
+        return Err(status_error(
+            internal::PppoeStatus_PPPOE_ERR_SOCKET,
+            Operation::PppoeSocket,
+            connection.interface_name(),
+        ));
+    }
+    connection.0.pppoe_socket = pppoe_socket;
+
+    let ret = unsafe { internal::pppoe_raw_socket_create(&mut connection.0 as *mut _) };
+    if ret < 0 {
+        return Err(status_error(
+            ret,
+            Operation::RawSocket,
+            connection.interface_name(),
+        ));
+    }
+
+    let ret = unsafe { internal::pppoe_raw_socket_bind(&connection.0 as *const _) };
+    if ret < 0 {
+        return Err(status_error(
+            ret,
+            Operation::RawSocketBind,
+            connection.interface_name(),
+        ));
+    }
+
+    Ok(())
+}
 
+pub fn connect(
+    connection: &mut Connection,
+    session_id: NonZeroU16,
+    remote_mac: [u8; 6],
+) -> Result<(), SysError> {
     let ret = unsafe {
-        internal::pppoe_connection_data_init(&mut connection.0 as *mut _, interface_name)
+        internal::pppoe_connect(
+            &mut connection.0 as *mut _,
+            u16::from(session_id),
+            &remote_mac as *const _,
+        )
     };
 
     if ret < 0 {
-        return Err(io::Error::last_os_error());
+        return Err(status_error(
+            ret,
+            Operation::Connect,
+            connection.interface_name(),
+        ));
     }
 
     Ok(())
 }
 
-pub fn connect(connection: &mut Connection, session_id: NonZeroU16, remote_mac: [u8; 6]) -> io::Result<()> {
-    let ret =
-        unsafe { internal::pppoe_connect(
-                    &mut connection.0 as *mut _,
-                    u16::from(session_id),
-                    &remote_mac as *const _)
-        };
+pub fn connect_device(
+    connection: &mut Connection,
+    session_id: NonZeroU16,
+    remote_mac: [u8; 6],
+    interface_name: &str,
+) -> Result<(), SysError> {
+    let c_interface_name = validate_interface_name(interface_name)?;
+    let ret = unsafe {
+        internal::pppoe_connect_device(
+            connection.0.pppoe_socket,
+            u16::from(session_id),
+            &remote_mac as *const _,
+            c_interface_name.as_ptr(),
+        )
+    };
 
     if ret < 0 {
-        return Err(io::Error::last_os_error());
+        return Err(status_error(
+            ret,
+            Operation::Connect,
+            Some(interface_name.to_owned()),
+        ));
     }
 
     Ok(())
 }
 
+/// Create the error for a negative `PppoeStatus`, with `errno` as source.
+fn status_error(
+    status: internal::PppoeStatus,
+    operation: Operation,
+    interface: Option<String>,
+) -> SysError {
+    let call = match status {
+        internal::PppoeStatus_PPPOE_ERR_SOCKET => Some(Call::Socket),
+        internal::PppoeStatus_PPPOE_ERR_SETSOCKOPT => Some(Call::Setsockopt),
+        internal::PppoeStatus_PPPOE_ERR_IOCTL => Some(Call::Ioctl),
+        internal::PppoeStatus_PPPOE_ERR_BIND => Some(Call::Bind),
+        internal::PppoeStatus_PPPOE_ERR_CONNECT => Some(Call::Connect),
+        internal::PppoeStatus_PPPOE_ERR_CLOSE => Some(Call::Close),
+        _ => None,
+    };
+    SysError::last_os_error(operation, interface).with_call(call)
+}
+
+/// Check that the name fits into `IFNAMSIZ` including the NUL terminator.
+fn validate_interface_name(interface_name: &str) -> Result<CString, SysError> {
+    let invalid = |message: &str| {
+        SysError::new(
+            Operation::InterfaceName,
+            Some(interface_name.to_owned()),
+            io::Error::new(io::ErrorKind::InvalidInput, message),
+        )
+    };
+
+    if interface_name.is_empty() || interface_name.len() >= IFNAMSIZ {
+        return Err(invalid("invalid interface name length"));
+    }
+    CString::new(interface_name).map_err(|_| invalid("interface name contains a NUL byte"))
+}
+
 pub fn connection_data_clear(connection: &mut Connection) {
     unsafe {
         internal::pppoe_connection_data_clear(&mut connection.0 as *mut _);
     }
 }
 
-fn get_hardware_address(connection: &mut Connection) -> io::Result<()> {
+/// Close the sockets and reset the connection like `connection_data_clear`, apart from the
+/// interface name.
+fn clear_keeping_interface_name(connection: &mut Connection) {
+    let interface_name = connection.0.interface_name;
+    connection_data_clear(connection);
+    connection.0.interface_name = interface_name;
+}
+
+fn get_hardware_address(connection: &mut Connection) -> Result<(), SysError> {
     let ret = unsafe { internal::lookup_hardware_address(&mut connection.0 as *mut _) };
 
     if ret < 0 {
-        return Err(io::Error::last_os_error());
+        return Err(status_error(
+            ret,
+            Operation::HardwareAddress,
+            connection.interface_name(),
+        ));
     }
 
     Ok(())
@@ -0,0 +1,1119 @@
+//! Offline analysis of captured PPPoE traffic
+//!
+//! `Analyzer` groups the frames of a capture into discovery conversations (by client MAC,
+//! Host-Uniq and session id), records a timeline for each of them and flags protocol
+//! violations.  Frames can be fed one by one, or read from a pcap file with `analyze_pcap`.
+//!
+//! The grouping itself is done by `ConversationTracker`, which is also useful on its own to
+//! monitor live traffic.  Single frames can be broken down field by field with
+//! `annotated_hex_dump`.
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, NetworkEndian as NE};
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::num::NonZeroU16;
+use std::time::Duration;
+
+use crate::consts::{ETHERTYPE_VLAN, PPPOE_DISCOVERY, PPPOE_SESSION, SESSION_DATA_CODE};
+use crate::error::{Error, ParseError};
+use crate::header::{code_name, ParserConfigExt, PADI, PADO, PADR, PADS, PADT};
+use crate::ppp::Protocol;
+use crate::tag::TagName;
+use crate::{PacketSummary, ParserConfig, Tag};
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAP_HEADER_LEN: usize = 24;
+const PCAP_RECORD_HEADER_LEN: usize = 16;
+const LINKTYPE_ETHERNET: u32 = 1;
+
+const BROADCAST: [u8; 6] = [0xff; 6];
+
+/// A frame of a pcap file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcapRecord<'a> {
+    /// Time since the Unix epoch
+    pub timestamp: Duration,
+    pub frame: &'a [u8],
+}
+
+/// Iterator over the frames of a pcap file with Ethernet link type
+///
+/// Both byte orders and microsecond and nanosecond timestamps are supported, pcapng is not.
+#[derive(Debug, Clone)]
+pub struct PcapReader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+    big_endian: bool,
+    nanos: bool,
+}
+
+impl<'a> PcapReader<'a> {
+    pub fn new(buffer: &'a [u8]) -> Result<Self, ParseError> {
+        if buffer.len() < PCAP_HEADER_LEN {
+            return Err(ParseError::BufferTooSmall(buffer.len()));
+        }
+
+        let (big_endian, nanos) =
+            match (BigEndian::read_u32(buffer), LittleEndian::read_u32(buffer)) {
+                (PCAP_MAGIC_MICROS, _) => (true, false),
+                (PCAP_MAGIC_NANOS, _) => (true, true),
+                (_, PCAP_MAGIC_MICROS) => (false, false),
+                (_, PCAP_MAGIC_NANOS) => (false, true),
+                (magic, _) => return Err(ParseError::InvalidPcapMagic(magic)),
+            };
+
+        let reader = Self {
+            buffer,
+            offset: PCAP_HEADER_LEN,
+            big_endian,
+            nanos,
+        };
+        let link_type = reader.read_u32(20);
+        if link_type != LINKTYPE_ETHERNET {
+            return Err(ParseError::UnsupportedLinkType(link_type));
+        }
+        Ok(reader)
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        if self.big_endian {
+            BigEndian::read_u32(&self.buffer[offset..])
+        } else {
+            LittleEndian::read_u32(&self.buffer[offset..])
+        }
+    }
+}
+
+impl<'a> Iterator for PcapReader<'a> {
+    type Item = Result<PcapRecord<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offset;
+        if offset == self.buffer.len() {
+            return None;
+        }
+
+        let truncated = ParseError::TruncatedPcapRecord { offset };
+        if self.buffer.len() - offset < PCAP_RECORD_HEADER_LEN {
+            self.offset = self.buffer.len();
+            return Some(Err(truncated));
+        }
+
+        let seconds = self.read_u32(offset);
+        let fraction = self.read_u32(offset + 4);
+        let captured_len = self.read_u32(offset + 8) as usize;
+        let start = offset + PCAP_RECORD_HEADER_LEN;
+        if self.buffer.len() - start < captured_len {
+            self.offset = self.buffer.len();
+            return Some(Err(truncated));
+        }
+        self.offset = start + captured_len;
+
+        let fraction = if self.nanos {
+            Duration::from_nanos(fraction.into())
+        } else {
+            Duration::from_micros(fraction.into())
+        };
+        Some(Ok(PcapRecord {
+            timestamp: Duration::from_secs(seconds.into()) + fraction,
+            frame: &self.buffer[start..self.offset],
+        }))
+    }
+}
+
+/// A discovery or session frame of a conversation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub timestamp: Duration,
+    /// Index of the frame in the capture
+    pub frame: usize,
+    pub code: u8,
+    pub src_mac: [u8; 6],
+    pub dst_mac: [u8; 6],
+}
+
+/// The discovery frames exchanged by a client to set up one session, and the session itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversation {
+    pub client_mac: [u8; 6],
+    pub host_uniq: Option<Vec<u8>>,
+    /// The Access Concentrator the client sent its PADR to
+    pub ac_mac: Option<[u8; 6]>,
+    pub session_id: Option<NonZeroU16>,
+    /// All discovery frames in order of reception
+    pub events: Vec<Event>,
+    pub session_frames: u64,
+    /// Time of the last discovery or session frame
+    pub last_activity: Duration,
+    pub terminated: bool,
+}
+
+impl Conversation {
+    fn new(client_mac: [u8; 6], host_uniq: Option<Vec<u8>>, timestamp: Duration) -> Self {
+        Self {
+            client_mac,
+            host_uniq,
+            ac_mac: None,
+            session_id: None,
+            events: Vec::new(),
+            session_frames: 0,
+            last_activity: timestamp,
+            terminated: false,
+        }
+    }
+
+    /// Time of the first frame
+    pub fn start(&self) -> Duration {
+        self.events
+            .first()
+            .map_or(self.last_activity, |event| event.timestamp)
+    }
+
+    /// Number of PADOs received by the client
+    pub fn offers(&self) -> usize {
+        self.events
+            .iter()
+            .filter(|event| event.code == PADO)
+            .count()
+    }
+
+    /// Time from the first PADI to the PADS
+    pub fn time_to_session(&self) -> Option<Duration> {
+        let pads = self.events.iter().find(|event| event.code == PADS)?;
+        pads.timestamp.checked_sub(self.start())
+    }
+
+    /// Check whether the session is (still) up at the end of the capture
+    pub fn is_established(&self) -> bool {
+        self.session_id.is_some() && !self.terminated
+    }
+
+    fn has_offer_from(&self, ac_mac: [u8; 6]) -> bool {
+        self.events
+            .iter()
+            .any(|event| event.code == PADO && event.src_mac == ac_mac)
+    }
+
+    /// The key of the established session, see `ConversationKey`
+    pub fn key(&self) -> ConversationKey {
+        ConversationKey {
+            client_mac: self.client_mac,
+            ac_mac: self.ac_mac,
+            session_id: self.session_id,
+        }
+    }
+}
+
+/// Kind of a protocol violation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// A frame with a PPPoE ethertype which could not be parsed
+    Malformed(ParseError),
+    PadiNotBroadcast,
+    /// A PADI, PADO or PADR with a session id other than 0
+    NonZeroSessionId(u16),
+    /// A PADS without error tag assigning the session id 0 or 0xffff
+    InvalidSessionId(u16),
+    /// A discovery frame which doesn't fit in any conversation, e.g. a PADR without PADO
+    UnexpectedCode(u8),
+    /// A PADO or PADS without the Host-Uniq tag of the client
+    HostUniqNotEchoed,
+    /// A session frame or PADT for a session which was never established
+    UnknownSession(u16),
+    /// A PADI with the given number of Service-Name tags, probing multiple services at once
+    MultipleServiceNames(usize),
+}
+
+/// A protocol violation found in a capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub timestamp: Duration,
+    /// Index of the frame in the capture
+    pub frame: usize,
+    /// Index into `Analysis::conversations`
+    pub conversation: Option<usize>,
+    pub kind: ViolationKind,
+}
+
+/// Identifies the conversation a frame belongs to
+///
+/// Discovery frames only carry what is known at their stage: a PADI has no Access Concentrator
+/// yet, and only the PADS and the frames after it have a session id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConversationKey {
+    pub client_mac: [u8; 6],
+    pub ac_mac: Option<[u8; 6]>,
+    pub session_id: Option<NonZeroU16>,
+}
+
+impl ConversationKey {
+    /// Get the key of a frame.
+    ///
+    /// The sender of session frames, PADTs and PADQs can't be told from the frame alone, for
+    /// those the source is taken as the client.  `reversed` gives the key of the other direction.
+    pub fn from_summary(summary: &PacketSummary) -> Self {
+        let discovery_code = Some(summary.code).filter(|_| summary.is_discovery());
+        let (client_mac, ac_mac) = match discovery_code {
+            Some(PADI) => (summary.src_mac, None),
+            Some(PADO) | Some(PADS) => (summary.dst_mac, Some(summary.src_mac)),
+            _ => (summary.src_mac, Some(summary.dst_mac)),
+        };
+        let session_id = match discovery_code {
+            Some(PADI) | Some(PADO) | Some(PADR) => None,
+            _ => NonZeroU16::new(summary.session_id),
+        };
+
+        Self {
+            client_mac,
+            ac_mac,
+            session_id,
+        }
+    }
+
+    /// Parse a frame and get its key.
+    pub fn from_frame(frame: &[u8]) -> Result<Self, Error> {
+        PacketSummary::from_bytes(frame).map(|summary| Self::from_summary(&summary))
+    }
+
+    /// The key with client and Access Concentrator swapped, `None` if the Access Concentrator is
+    /// not known.
+    pub fn reversed(&self) -> Option<Self> {
+        self.ac_mac.map(|ac_mac| Self {
+            client_mac: ac_mac,
+            ac_mac: Some(self.client_mac),
+            session_id: self.session_id,
+        })
+    }
+}
+
+/// How `ConversationTracker::track` assigned a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assignment {
+    /// The discovery frame started a new conversation
+    Started(usize),
+    /// A discovery frame of an ongoing conversation
+    Discovery(usize),
+    /// A session frame, PADT or PADQ of an established session
+    Session(usize),
+    /// The frame doesn't belong to any known conversation
+    Unassigned,
+}
+
+impl Assignment {
+    /// Index of the conversation, see `ConversationTracker::conversations`
+    pub fn conversation(&self) -> Option<usize> {
+        match *self {
+            Assignment::Started(index)
+            | Assignment::Discovery(index)
+            | Assignment::Session(index) => Some(index),
+            Assignment::Unassigned => None,
+        }
+    }
+}
+
+fn host_uniq(summary: &PacketSummary) -> Option<Vec<u8>> {
+    summary.tags().find_map(|tag| match tag {
+        Tag::HostUniq(host_uniq) => Some(host_uniq.to_vec()),
+        _ => None,
+    })
+}
+
+fn has_error_tag(summary: &PacketSummary) -> bool {
+    summary.tags().any(|tag| {
+        matches!(
+            tag,
+            Tag::ServiceNameError(_) | Tag::AcSystemError(_) | Tag::GenericError(_)
+        )
+    })
+}
+
+fn valid_session_id(session_id: u16) -> Option<NonZeroU16> {
+    NonZeroU16::new(session_id).filter(|id| id.get() != 0xffff)
+}
+
+/// Assigns frames to discovery and session conversations
+///
+/// Frames have to be tracked in order of reception.  The tracker doesn't judge the frames, it
+/// can be used for live monitoring as well as by `Analyzer`.
+#[derive(Debug, Default)]
+pub struct ConversationTracker {
+    conversations: Vec<Conversation>,
+    sessions: HashMap<ConversationKey, usize>,
+}
+
+impl ConversationTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All conversations in order of their first frame
+    pub fn conversations(&self) -> &[Conversation] {
+        &self.conversations
+    }
+
+    pub fn into_conversations(self) -> Vec<Conversation> {
+        self.conversations
+    }
+
+    /// Find the established session a key belongs to, in either direction.
+    pub fn find_session(&self, key: &ConversationKey) -> Option<usize> {
+        self.sessions
+            .get(key)
+            .or_else(|| key.reversed().and_then(|key| self.sessions.get(&key)))
+            .copied()
+    }
+
+    /// Assign the next frame, `frame` is its index in the capture.
+    pub fn track(
+        &mut self,
+        timestamp: Duration,
+        frame: usize,
+        summary: &PacketSummary,
+    ) -> Assignment {
+        let key = ConversationKey::from_summary(summary);
+
+        if !summary.is_discovery() {
+            let index = match self.find_session(&key) {
+                Some(index) => index,
+                None => return Assignment::Unassigned,
+            };
+            let conversation = &mut self.conversations[index];
+            conversation.session_frames += 1;
+            conversation.last_activity = timestamp;
+            return Assignment::Session(index);
+        }
+
+        let host_uniq = host_uniq(summary);
+        let assignment = match summary.code {
+            PADI => {
+                // retransmissions belong to the same conversation until a PADR was sent
+                match self.find_conversation(key.client_mac, host_uniq.as_deref(), |conversation| {
+                    conversation.ac_mac.is_none()
+                }) {
+                    Some(index) => Assignment::Discovery(index),
+                    None => Assignment::Started(self.start(key.client_mac, host_uniq, timestamp)),
+                }
+            }
+            code @ PADO | code @ PADS => {
+                let ac_mac = key.ac_mac;
+                let index = match self.find_conversation(
+                    key.client_mac,
+                    host_uniq.as_deref(),
+                    |conversation| {
+                        conversation.session_id.is_none()
+                            && (code == PADO || conversation.ac_mac == ac_mac)
+                    },
+                ) {
+                    Some(index) => index,
+                    None => return Assignment::Unassigned,
+                };
+
+                if code == PADS && !has_error_tag(summary) {
+                    if let Some(session_id) = valid_session_id(summary.session_id) {
+                        self.conversations[index].session_id = Some(session_id);
+                        self.sessions.insert(key, index);
+                    }
+                }
+                Assignment::Discovery(index)
+            }
+            PADR => {
+                let ac_mac = summary.dst_mac;
+                let assignment = match self.find_conversation(
+                    key.client_mac,
+                    host_uniq.as_deref(),
+                    |conversation| {
+                        conversation.session_id.is_none() && conversation.has_offer_from(ac_mac)
+                    },
+                ) {
+                    Some(index) => Assignment::Discovery(index),
+                    None => Assignment::Started(self.start(key.client_mac, host_uniq, timestamp)),
+                };
+                if let Some(index) = assignment.conversation() {
+                    self.conversations[index].ac_mac = Some(ac_mac);
+                }
+                assignment
+            }
+            // PADT, PADQ and unknown codes of an established session
+            code => {
+                let index = match self.find_session(&key) {
+                    Some(index) => index,
+                    None => return Assignment::Unassigned,
+                };
+                if code == PADT {
+                    self.sessions.remove(&self.conversations[index].key());
+                    self.conversations[index].terminated = true;
+                }
+                Assignment::Session(index)
+            }
+        };
+
+        if let Some(index) = assignment.conversation() {
+            let conversation = &mut self.conversations[index];
+            conversation.events.push(Event {
+                timestamp,
+                frame,
+                code: summary.code,
+                src_mac: summary.src_mac,
+                dst_mac: summary.dst_mac,
+            });
+            conversation.last_activity = timestamp;
+        }
+        assignment
+    }
+
+    fn start(
+        &mut self,
+        client_mac: [u8; 6],
+        host_uniq: Option<Vec<u8>>,
+        timestamp: Duration,
+    ) -> usize {
+        let conversations = &mut self.conversations;
+        conversations.push(Conversation::new(client_mac, host_uniq, timestamp));
+        conversations.len() - 1
+    }
+
+    /// Find the latest ongoing conversation of a client.  Replies without Host-Uniq match any
+    /// conversation of the client.
+    fn find_conversation<F>(
+        &self,
+        client_mac: [u8; 6],
+        host_uniq: Option<&[u8]>,
+        predicate: F,
+    ) -> Option<usize>
+    where
+        F: Fn(&Conversation) -> bool,
+    {
+        self.conversations.iter().rposition(|conversation| {
+            conversation.client_mac == client_mac
+                && !conversation.terminated
+                && (host_uniq.is_none() || conversation.host_uniq.as_deref() == host_uniq)
+                && predicate(conversation)
+        })
+    }
+}
+
+/// The result of an analysis
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Analysis {
+    /// Number of analyzed frames, including the ones which are not PPPoE
+    pub frames: usize,
+    pub conversations: Vec<Conversation>,
+    pub violations: Vec<Violation>,
+}
+
+/// Reconstructs conversations from frames in order of reception and flags protocol violations
+#[derive(Debug)]
+pub struct Analyzer {
+    frames: usize,
+    tracker: ConversationTracker,
+    violations: Vec<Violation>,
+    parser: ParserConfig,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self {
+            frames: 0,
+            tracker: ConversationTracker::default(),
+            violations: Vec::new(),
+            parser: ParserConfig {
+                allow_multiple_service_names: true,
+                ..ParserConfig::default()
+            },
+        }
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how frames are parsed.  The default is `ParserConfig::default`, except that PADIs
+    /// with multiple Service-Name tags are accepted and flagged as
+    /// `ViolationKind::MultipleServiceNames`.
+    pub fn with_parser_config(mut self, parser: ParserConfig) -> Self {
+        self.parser = parser;
+        self
+    }
+
+    pub fn conversations(&self) -> &[Conversation] {
+        self.tracker.conversations()
+    }
+
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    pub fn finish(self) -> Analysis {
+        Analysis {
+            frames: self.frames,
+            conversations: self.tracker.into_conversations(),
+            violations: self.violations,
+        }
+    }
+
+    /// Analyze the next frame of the capture.  Frames which are not PPPoE are skipped.
+    pub fn add_frame(&mut self, timestamp: Duration, frame: &[u8]) {
+        let index = self.frames;
+        self.frames += 1;
+
+        let summary = match self.parser.summarize(frame) {
+            Ok(summary) => summary,
+            Err(Error::ParseError(ParseError::UnexpectedEtherType(_))) => return,
+            Err(Error::ParseError(error)) => {
+                self.violation(timestamp, index, None, ViolationKind::Malformed(error));
+                return;
+            }
+            Err(_) => return,
+        };
+
+        let assignment = self.tracker.track(timestamp, index, &summary);
+        let conversation = assignment.conversation();
+        let expects_host_uniq = conversation.is_some_and(|conversation| {
+            self.tracker.conversations()[conversation]
+                .host_uniq
+                .is_some()
+        });
+        let mut violation = |kind| self.violation(timestamp, index, conversation, kind);
+
+        if !summary.is_discovery() {
+            if assignment == Assignment::Unassigned {
+                violation(ViolationKind::UnknownSession(summary.session_id));
+            }
+            return;
+        }
+
+        let code = summary.code;
+        if matches!(code, PADI | PADO | PADR) && summary.session_id != 0 {
+            violation(ViolationKind::NonZeroSessionId(summary.session_id));
+        }
+        if code == PADI && summary.dst_mac != BROADCAST {
+            violation(ViolationKind::PadiNotBroadcast);
+        }
+        if code == PADI {
+            let service_names = summary
+                .tags()
+                .filter(|tag| matches!(tag, Tag::ServiceName(_)))
+                .count();
+            if service_names > 1 {
+                violation(ViolationKind::MultipleServiceNames(service_names));
+            }
+        }
+
+        match (code, assignment) {
+            (PADO, Assignment::Unassigned) | (PADS, Assignment::Unassigned) => {
+                violation(ViolationKind::UnexpectedCode(code));
+                return;
+            }
+            (PADR, Assignment::Started(_)) => violation(ViolationKind::UnexpectedCode(code)),
+            (_, Assignment::Unassigned) => {
+                violation(ViolationKind::UnknownSession(summary.session_id));
+                return;
+            }
+            _ => (),
+        }
+
+        if matches!(code, PADO | PADS) && expects_host_uniq && host_uniq(&summary).is_none() {
+            violation(ViolationKind::HostUniqNotEchoed);
+        }
+        if code == PADS
+            && !has_error_tag(&summary)
+            && valid_session_id(summary.session_id).is_none()
+        {
+            violation(ViolationKind::InvalidSessionId(summary.session_id));
+        }
+    }
+
+    fn violation(
+        &mut self,
+        timestamp: Duration,
+        frame: usize,
+        conversation: Option<usize>,
+        kind: ViolationKind,
+    ) {
+        self.violations.push(Violation {
+            timestamp,
+            frame,
+            conversation,
+            kind,
+        });
+    }
+}
+
+/// Analyze frames in order of reception.
+pub fn analyze_frames<'a, I>(frames: I) -> Analysis
+where
+    I: IntoIterator<Item = (Duration, &'a [u8])>,
+{
+    let mut analyzer = Analyzer::new();
+    for (timestamp, frame) in frames {
+        analyzer.add_frame(timestamp, frame);
+    }
+    analyzer.finish()
+}
+
+/// Analyze the frames of a pcap file.
+pub fn analyze_pcap(capture: &[u8]) -> Result<Analysis, ParseError> {
+    let mut analyzer = Analyzer::new();
+    for record in PcapReader::new(capture)? {
+        let record = record?;
+        analyzer.add_frame(record.timestamp, record.frame);
+    }
+    Ok(analyzer.finish())
+}
+
+// bytes per line of an annotated dump, so there is room for the comment
+const ANNOTATED_BYTES_PER_LINE: usize = 8;
+
+/// Writes the lines of an annotated dump, one field after the other
+struct Annotator<'a> {
+    frame: &'a [u8],
+    offset: usize,
+    dump: String,
+}
+
+impl<'a> Annotator<'a> {
+    fn remaining(&self) -> usize {
+        self.frame.len() - self.offset
+    }
+
+    /// Annotate the next `len` bytes (at most the rest of the frame) and return them.
+    fn field(&mut self, len: usize, comment: fmt::Arguments) -> &'a [u8] {
+        let frame = self.frame;
+        let bytes = &frame[self.offset..self.offset + len.min(self.remaining())];
+        if bytes.is_empty() {
+            return bytes;
+        }
+
+        for (line, chunk) in bytes.chunks(ANNOTATED_BYTES_PER_LINE).enumerate() {
+            write!(self.dump, "{:04x} ", self.offset).unwrap();
+            for byte in chunk {
+                write!(self.dump, " {:02x}", byte).unwrap();
+            }
+            if line == 0 {
+                for _ in chunk.len()..ANNOTATED_BYTES_PER_LINE {
+                    self.dump.push_str("   ");
+                }
+                write!(self.dump, "  {}", comment).unwrap();
+            }
+            self.dump.push('\n');
+            self.offset += chunk.len();
+        }
+        bytes
+    }
+
+    fn u16_field(&mut self, name: &str) -> Option<u16> {
+        let value = self.peek_u16();
+        match value {
+            Some(value) => self.field(2, format_args!("{} {}", name, value)),
+            None => self.field(2, format_args!("incomplete {}", name)),
+        };
+        value
+    }
+
+    fn ethernet(&mut self) -> Option<u16> {
+        let dst = self.field(6, format_args!("destination MAC"));
+        let src = self.field(6, format_args!("source MAC"));
+        if src.len() < 6 || dst.len() < 6 {
+            return None;
+        }
+
+        let mut ether_type = self.peek_u16()?;
+        while ether_type == ETHERTYPE_VLAN {
+            self.field(2, format_args!("VLAN tag"));
+            let tci = self.peek_u16()?;
+            self.field(
+                2,
+                format_args!("VLAN id {}, priority {}", tci & 0x0fff, tci >> 13),
+            );
+            ether_type = self.peek_u16()?;
+        }
+
+        let name = match ether_type {
+            PPPOE_DISCOVERY => "PPPoE discovery",
+            PPPOE_SESSION => "PPPoE session",
+            _ => "unknown",
+        };
+        self.field(2, format_args!("ethertype {:#06x} ({})", ether_type, name));
+        Some(ether_type)
+    }
+
+    fn peek_u16(&self) -> Option<u16> {
+        self.frame
+            .get(self.offset..self.offset + 2)
+            .map(NE::read_u16)
+    }
+
+    /// Annotate the PPPoE header and return the declared payload length.
+    fn pppoe_header(&mut self) -> Option<usize> {
+        let version_type = *self.frame.get(self.offset)?;
+        self.field(
+            1,
+            format_args!(
+                "version {}, type {}",
+                version_type >> 4,
+                version_type & 0x0f
+            ),
+        );
+        let code = *self.frame.get(self.offset)?;
+        match code_name(code) {
+            Some(name) => self.field(1, format_args!("code {}", name)),
+            None if code == SESSION_DATA_CODE => self.field(1, format_args!("code session data")),
+            None => self.field(1, format_args!("code {:#04x}", code)),
+        };
+        let session_id = self.peek_u16()?;
+        self.field(2, format_args!("session id {:#06x}", session_id));
+        self.u16_field("payload length").map(usize::from)
+    }
+
+    fn tags(&mut self, payload_len: usize) {
+        let end = self.offset + payload_len.min(self.remaining());
+        while end - self.offset >= 4 {
+            let tag_type = NE::read_u16(&self.frame[self.offset..]);
+            let length = usize::from(NE::read_u16(&self.frame[self.offset + 2..]));
+            if self.offset + 4 + length > end {
+                break;
+            }
+            let tag_frame = &self.frame[self.offset..self.offset + 4 + length];
+            self.field(
+                4,
+                format_args!("{} tag, length {}", TagName(tag_type), length),
+            );
+            match Tag::from_buffer(tag_frame) {
+                Ok((tag, _)) => self.field(length, format_args!("{}", tag)),
+                Err(_) => self.field(length, format_args!("invalid value")),
+            };
+        }
+        self.field(end - self.offset, format_args!("incomplete tag"));
+    }
+
+    fn session_payload(&mut self, payload_len: usize) {
+        let end = self.offset + payload_len.min(self.remaining());
+        let protocol = match self.peek_u16() {
+            Some(protocol) if end - self.offset >= 2 => Protocol::from(protocol),
+            _ => {
+                self.field(end - self.offset, format_args!("incomplete PPP protocol"));
+                return;
+            }
+        };
+        match protocol {
+            Protocol::Unknown(number) => {
+                self.field(2, format_args!("PPP protocol {:#06x}", number))
+            }
+            _ => self.field(
+                2,
+                format_args!("PPP protocol {:#06x} ({})", u16::from(protocol), protocol),
+            ),
+        };
+
+        if protocol.is_control() && end - self.offset >= 4 {
+            let code = self.frame[self.offset];
+            let name = control_code_name(protocol, code);
+            self.field(1, format_args!("{} code {} ({})", protocol, code, name));
+            let identifier = self.frame[self.offset];
+            self.field(1, format_args!("identifier {}", identifier));
+            self.u16_field("length");
+        }
+        self.field(end - self.offset, format_args!("{} data", protocol));
+    }
+}
+
+fn control_code_name(protocol: Protocol, code: u8) -> &'static str {
+    use crate::ppp::*;
+
+    match code {
+        CONFIGURE_REQUEST => "Configure-Request",
+        CONFIGURE_ACK => "Configure-Ack",
+        CONFIGURE_NAK => "Configure-Nak",
+        CONFIGURE_REJECT => "Configure-Reject",
+        TERMINATE_REQUEST => "Terminate-Request",
+        TERMINATE_ACK => "Terminate-Ack",
+        CODE_REJECT => "Code-Reject",
+        _ if protocol != Protocol::Lcp => "unknown",
+        lcp::PROTOCOL_REJECT => "Protocol-Reject",
+        lcp::ECHO_REQUEST => "Echo-Request",
+        lcp::ECHO_REPLY => "Echo-Reply",
+        lcp::DISCARD_REQUEST => "Discard-Request",
+        _ => "unknown",
+    }
+}
+
+/// Format a frame as a hex dump with one line per field, commented with the field name and its
+/// decoded value, e.g. for tickets with vendors:
+///
+/// ```text
+/// 000e  11                       version 1, type 1
+/// 000f  09                       code PADI
+/// ```
+///
+/// Discovery frames are broken down into their tags, session frames into the PPP protocol and,
+/// for control protocols, the code, identifier and length.  Malformed frames are annotated as far
+/// as they can be decoded, the rest is dumped without a breakdown.
+pub fn annotated_hex_dump(frame: &[u8]) -> String {
+    let mut annotator = Annotator {
+        frame,
+        offset: 0,
+        dump: String::new(),
+    };
+
+    let ether_type = annotator.ethernet();
+    if let Some(ether_type @ (PPPOE_DISCOVERY | PPPOE_SESSION)) = ether_type {
+        if let Some(payload_len) = annotator.pppoe_header() {
+            if ether_type == PPPOE_DISCOVERY {
+                annotator.tags(payload_len);
+            } else {
+                annotator.session_payload(payload_len);
+            }
+            annotator.field(usize::MAX, format_args!("padding"));
+        }
+    }
+    annotator.field(usize::MAX, format_args!("not decoded"));
+    annotator.dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{Code, HeaderBuilder};
+
+    const CLIENT: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const AC: [u8; 6] = [0x02, 0, 0, 0, 0, 0xac];
+
+    fn discovery(dst: [u8; 6], src: [u8; 6], code: Code, session_id: u16, tags: &[Tag]) -> Vec<u8> {
+        let mut frame = vec![0u8; 100];
+        frame[..6].copy_from_slice(&dst);
+        frame[6..12].copy_from_slice(&src);
+        frame[12..14].copy_from_slice(&PPPOE_DISCOVERY.to_be_bytes());
+        let mut header = HeaderBuilder::create_packet(&mut frame[14..], code, session_id).unwrap();
+        for tag in tags {
+            header.add_tag(*tag).unwrap();
+        }
+        let len = 14 + header.len();
+        frame.truncate(len);
+        frame
+    }
+
+    fn session(dst: [u8; 6], src: [u8; 6], session_id: u16) -> Vec<u8> {
+        let mut frame = vec![0u8; 24];
+        frame[..6].copy_from_slice(&dst);
+        frame[6..12].copy_from_slice(&src);
+        frame[12..14].copy_from_slice(&PPPOE_SESSION.to_be_bytes());
+        frame[14..20].copy_from_slice(&[0x11, 0x00, 0x00, 0x00, 0x00, 0x02]);
+        frame[16..18].copy_from_slice(&session_id.to_be_bytes());
+        frame
+    }
+
+    fn pcap(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut capture = Vec::new();
+        capture.extend_from_slice(&PCAP_MAGIC_MICROS.to_le_bytes());
+        // version 2.4, time zone, accuracy, snap length
+        capture.extend_from_slice(&[2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0]);
+        capture.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        for (index, frame) in frames.iter().enumerate() {
+            let micros = index as u32 * 100_000;
+            for value in &[10, micros, frame.len() as u32, frame.len() as u32] {
+                capture.extend_from_slice(&value.to_le_bytes());
+            }
+            capture.extend_from_slice(frame);
+        }
+        capture
+    }
+
+    #[test]
+    fn reconstruct_conversation_from_pcap() {
+        let service = Tag::ServiceName(b"");
+        let uniq = Tag::HostUniq(b"42");
+        let ac_name = Tag::AcName(b"ac");
+        let frames = [
+            discovery(BROADCAST, CLIENT, Code::Padi, 0, &[service, uniq]),
+            discovery(BROADCAST, CLIENT, Code::Padi, 0, &[service, uniq]),
+            discovery(CLIENT, AC, Code::Pado, 0, &[service, ac_name, uniq]),
+            discovery(AC, CLIENT, Code::Padr, 0, &[service, uniq]),
+            discovery(CLIENT, AC, Code::Pads, 7, &[service, uniq]),
+            session(AC, CLIENT, 7),
+            session(CLIENT, AC, 7),
+            session(CLIENT, AC, 8),
+            discovery(AC, CLIENT, Code::Padt, 7, &[service]),
+        ];
+        let analysis = analyze_pcap(&pcap(&frames)).unwrap();
+
+        assert_eq!(analysis.frames, 9);
+        assert_eq!(analysis.conversations.len(), 1);
+        let conversation = &analysis.conversations[0];
+        assert_eq!(conversation.client_mac, CLIENT);
+        assert_eq!(conversation.host_uniq.as_deref(), Some(&b"42"[..]));
+        assert_eq!(conversation.ac_mac, Some(AC));
+        assert_eq!(conversation.session_id, NonZeroU16::new(7));
+        assert_eq!(conversation.events.len(), 6);
+        assert_eq!(conversation.offers(), 1);
+        assert_eq!(conversation.session_frames, 2);
+        assert_eq!(
+            conversation.time_to_session(),
+            Some(Duration::from_millis(400))
+        );
+        assert!(conversation.terminated);
+
+        assert_eq!(
+            analysis.violations,
+            [Violation {
+                timestamp: Duration::from_millis(10_700),
+                frame: 7,
+                conversation: None,
+                kind: ViolationKind::UnknownSession(8),
+            }]
+        );
+    }
+
+    #[test]
+    fn flag_violations() {
+        let service = Tag::ServiceName(b"");
+        let uniq = Tag::HostUniq(b"42");
+        let frames = [
+            discovery(AC, CLIENT, Code::Padi, 3, &[service, uniq]),
+            discovery(AC, CLIENT, Code::Padr, 0, &[service, uniq]),
+            discovery(CLIENT, AC, Code::Pads, 0, &[service]),
+            discovery(
+                BROADCAST,
+                [0x02, 0, 0, 0, 0, 9],
+                Code::Padi,
+                0,
+                &[service, Tag::ServiceName(b"video")],
+            ),
+        ];
+        let analysis = analyze_frames(
+            frames
+                .iter()
+                .map(|frame| (Duration::from_secs(0), &frame[..])),
+        );
+
+        let kinds: Vec<_> = analysis.violations.into_iter().map(|v| v.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ViolationKind::NonZeroSessionId(3),
+                ViolationKind::PadiNotBroadcast,
+                ViolationKind::UnexpectedCode(PADR),
+                ViolationKind::HostUniqNotEchoed,
+                ViolationKind::InvalidSessionId(0),
+                ViolationKind::MultipleServiceNames(2),
+            ]
+        );
+        assert_eq!(analysis.conversations.len(), 3);
+    }
+
+    #[test]
+    fn track_live_traffic() {
+        let service = Tag::ServiceName(b"");
+        let ac_name = Tag::AcName(b"ac");
+        let pado = discovery(CLIENT, AC, Code::Pado, 0, &[service, ac_name]);
+        let pads = discovery(CLIENT, AC, Code::Pads, 7, &[service]);
+        let from_client = session(AC, CLIENT, 7);
+
+        assert_eq!(
+            ConversationKey::from_frame(&pado).unwrap(),
+            ConversationKey {
+                client_mac: CLIENT,
+                ac_mac: Some(AC),
+                session_id: None,
+            }
+        );
+        let from_ac = session(CLIENT, AC, 7);
+        let key = ConversationKey::from_frame(&from_ac).unwrap();
+        assert_eq!(key.client_mac, AC);
+        assert_eq!(
+            key.reversed(),
+            Some(ConversationKey::from_frame(&from_client).unwrap())
+        );
+
+        let mut tracker = ConversationTracker::new();
+        let mut track = |frame: &[u8]| {
+            let summary = PacketSummary::from_bytes(frame).unwrap();
+            tracker.track(Duration::from_secs(0), 0, &summary)
+        };
+        assert_eq!(track(&from_ac), Assignment::Unassigned);
+        assert_eq!(
+            track(&discovery(BROADCAST, CLIENT, Code::Padi, 0, &[service])),
+            Assignment::Started(0)
+        );
+        assert_eq!(track(&pado), Assignment::Discovery(0));
+        assert_eq!(
+            track(&discovery(AC, CLIENT, Code::Padr, 0, &[service])),
+            Assignment::Discovery(0)
+        );
+        assert_eq!(track(&pads), Assignment::Discovery(0));
+        assert_eq!(track(&from_ac), Assignment::Session(0));
+        assert_eq!(track(&from_client), Assignment::Session(0));
+        assert_eq!(
+            track(&discovery(CLIENT, AC, Code::Padt, 7, &[service])),
+            Assignment::Session(0)
+        );
+        assert_eq!(track(&from_client), Assignment::Unassigned);
+
+        let conversation = &tracker.conversations()[0];
+        assert_eq!(conversation.session_frames, 2);
+        assert!(conversation.terminated);
+        assert!(tracker.find_session(&conversation.key()).is_none());
+    }
+
+    #[test]
+    fn annotate_frames() {
+        let padi = discovery(
+            BROADCAST,
+            CLIENT,
+            Code::Padi,
+            0,
+            &[Tag::ServiceName(b"isp"), Tag::HostUniq(&[1; 9])],
+        );
+        let dump = annotated_hex_dump(&padi);
+        let lines: Vec<_> = dump.lines().collect();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[4], "000f  09                       code PADI");
+        assert_eq!(
+            lines[8],
+            "0018  69 73 70                 Service-Name: \"isp\""
+        );
+        assert_eq!(lines[11], "0027  01");
+
+        let truncated = annotated_hex_dump(&padi[..30]);
+        assert!(truncated.ends_with("001b  01 03 00                 incomplete tag\n"));
+
+        let mut lcp = session(AC, CLIENT, 0x1234);
+        lcp.truncate(20);
+        lcp[18..20].copy_from_slice(&[0x00, 0x0a]);
+        lcp.extend_from_slice(&[0xc0, 0x21, 0x09, 0x01, 0x00, 0x08, 0, 0, 0, 1, 0, 0]);
+        let dump = annotated_hex_dump(&lcp);
+        let lines: Vec<_> = dump.lines().skip(7).collect();
+        assert_eq!(
+            lines,
+            [
+                "0014  c0 21                    PPP protocol 0xc021 (LCP)",
+                "0016  09                       LCP code 9 (Echo-Request)",
+                "0017  01                       identifier 1",
+                "0018  00 08                    length 8",
+                "001a  00 00 00 01              LCP data",
+                "001e  00 00                    padding",
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_invalid_pcap() {
+        assert_eq!(
+            PcapReader::new(&[0u8; 24]).unwrap_err(),
+            ParseError::InvalidPcapMagic(0)
+        );
+
+        let mut capture = pcap(&[session(AC, CLIENT, 1)]);
+        capture.pop();
+        let records: Vec<_> = PcapReader::new(&capture).unwrap().collect();
+        assert_eq!(
+            records,
+            [Err(ParseError::TruncatedPcapRecord {
+                offset: PCAP_HEADER_LEN
+            })]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Throughput measurements of the discovery state machines
+//!
+//! The measurements run entirely in memory through `MockTransport`, so they show the cost of
+//! the state machines alone.  They are used by `benches/state_machines.rs` and only built with
+//! the `bench` feature, the module is not part of the public API.
+
+use std::fmt;
+use std::num::NonZeroU16;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::cookie::CookieJar;
+use crate::error::{Error, ProtocolError};
+use crate::header::{PADI, PADR};
+use crate::mock::MockTransport;
+use crate::packet::PPPOE_DISCOVERY;
+use crate::server::{AllocationStrategy, SessionIdAllocator};
+use crate::{eth, Discovery, DiscoveryOptions, HeaderBuilder, Packet, Tag};
+
+const CLIENT_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+const AC_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 2];
+
+/// Number of iterations and the time they took
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    pub iterations: u32,
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    pub fn per_second(&self) -> f64 {
+        f64::from(self.iterations) / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} iterations in {:?} ({:.0}/s)",
+            self.iterations,
+            self.elapsed,
+            self.per_second()
+        )
+    }
+}
+
+/// A minimal Access Concentrator answering PADIs and PADRs
+struct Responder {
+    allocator: SessionIdAllocator,
+    cookies: CookieJar,
+    buffer: Vec<u8>,
+}
+
+impl Responder {
+    fn new(strategy: AllocationStrategy) -> Self {
+        Self {
+            allocator: SessionIdAllocator::without_quarantine().with_strategy(strategy),
+            cookies: CookieJar::new([0x42; 16]).with_lifetime(Duration::from_secs(3600)),
+            buffer: vec![0u8; 1514],
+        }
+    }
+
+    /// Answer a PADI with a PADO and a PADR with a PADS.
+    fn respond(&mut self, now: Instant, frame: &[u8]) -> Result<Option<&[u8]>, Error> {
+        let request = Packet::with_buffer(frame)?;
+        let client_mac = request.ethernet_header().src_address();
+        let header = request.pppoe_header();
+
+        let session_id = match header.code() {
+            PADI => None,
+            PADR => {
+                let cookie = header.tags().find_map(|tag| match tag {
+                    Tag::AcCookie(cookie) => Some(cookie),
+                    _ => None,
+                });
+                let valid = cookie.is_some_and(|cookie| {
+                    self.cookies
+                        .verify(SystemTime::now(), cookie, client_mac, b"")
+                        .is_ok()
+                });
+                if !valid {
+                    return Ok(None);
+                }
+                match self.allocator.allocate_for(now, Some(client_mac)) {
+                    Some(session_id) => Some(session_id),
+                    None => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+        let cookie = self.cookies.issue(SystemTime::now(), client_mac, b"");
+
+        let (eth_buf, pppoe_buf) = self.buffer.split_at_mut(14);
+        let mut ethernet = eth::HeaderBuilder::with_buffer(eth_buf)?;
+        ethernet.set_src_address(AC_MAC);
+        ethernet.set_dst_address(client_mac);
+        ethernet.set_ether_type(PPPOE_DISCOVERY);
+
+        let mut reply = match session_id {
+            Some(session_id) => HeaderBuilder::create_pads(pppoe_buf, session_id)?,
+            None => {
+                let mut pado = HeaderBuilder::create_pado(pppoe_buf)?;
+                pado.add_tag(Tag::AcName(b"bench"))?;
+                pado.add_tag(Tag::AcCookie(&cookie))?;
+                pado
+            }
+        };
+
+        reply.add_tag(Tag::ServiceName(b""))?;
+        for tag in header.tags() {
+            if let Tag::HostUniq(_) = tag {
+                reply.add_tag(tag)?;
+            }
+        }
+        let len = 14 + reply.len();
+        Ok(Some(&self.buffer[..len]))
+    }
+
+    fn release(&mut self, now: Instant, session_id: NonZeroU16) {
+        self.allocator.release(now, session_id);
+    }
+}
+
+/// Run `iterations` complete client handshakes (PADI, PADO, PADR, PADS) against an in-memory
+/// Access Concentrator.
+pub fn discovery_handshakes(
+    iterations: u32,
+    options: &DiscoveryOptions,
+) -> Result<Measurement, Error> {
+    let mut responder = Responder::new(AllocationStrategy::default());
+    let mut transport = MockTransport::new();
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT_MAC, options.clone());
+        loop {
+            transport.drive_discovery(&mut discovery, now)?;
+            if discovery.session().is_some() {
+                break;
+            }
+            let sent = transport.take_sent();
+            if sent.is_empty() {
+                return Err(Error::Protocol(ProtocolError::NoOffer));
+            }
+            for frame in &sent {
+                if let Some(reply) = responder.respond(now, frame)? {
+                    transport.inject_raw(reply);
+                }
+            }
+        }
+        let session_id = discovery.session().map(|session| session.session_id);
+        if let Some(session_id) = session_id {
+            responder.release(now, session_id);
+        }
+    }
+
+    Ok(Measurement {
+        iterations,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Let the in-memory Access Concentrator set up `iterations` sessions, i.e. answer a PADI and
+/// a PADR, verifying the AC-Cookie and allocating a session id with `strategy`.
+pub fn session_setups(iterations: u32, strategy: AllocationStrategy) -> Result<Measurement, Error> {
+    let mut responder = Responder::new(strategy);
+
+    let mut padi = vec![0u8; 64];
+    let len = {
+        let mut packet =
+            crate::PacketBuilder::new_discovery_packet(&mut padi, CLIENT_MAC, [0xff; 6])?;
+        packet.pppoe_header().add_tag(Tag::ServiceName(b""))?;
+        packet.len()
+    };
+    padi.truncate(len);
+
+    let now = Instant::now();
+    let pado = responder
+        .respond(now, &padi)?
+        .ok_or(ProtocolError::NoOffer)?
+        .to_vec();
+    let pado = Packet::with_buffer(&pado)?;
+    let mut padr = vec![0u8; 128];
+    let len = {
+        let (eth_buf, pppoe_buf) = padr.split_at_mut(14);
+        let mut ethernet = eth::HeaderBuilder::with_buffer(eth_buf)?;
+        ethernet.set_src_address(CLIENT_MAC);
+        ethernet.set_dst_address(AC_MAC);
+        ethernet.set_ether_type(PPPOE_DISCOVERY);
+        14 + HeaderBuilder::create_padr_from_pado(pppoe_buf, pado.pppoe_header(), None, None)?.len()
+    };
+    padr.truncate(len);
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let now = Instant::now();
+        responder.respond(now, &padi)?;
+        let pads = match responder.respond(now, &padr)? {
+            Some(pads) => Packet::with_buffer(pads)?,
+            None => return Err(Error::Protocol(ProtocolError::NoSessionConfirmation)),
+        };
+        let session_id = NonZeroU16::new(pads.pppoe_header().session_id());
+        if let Some(session_id) = session_id {
+            responder.release(now, session_id);
+        }
+    }
+
+    Ok(Measurement {
+        iterations,
+        elapsed: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_measurements() {
+        let options = DiscoveryOptions {
+            host_uniq: Some(b"bench".to_vec()),
+            ..DiscoveryOptions::default()
+        };
+        assert_eq!(discovery_handshakes(10, &options).unwrap().iterations, 10);
+        for &strategy in &[
+            AllocationStrategy::Sequential,
+            AllocationStrategy::Random,
+            AllocationStrategy::HashedFromMac,
+        ] {
+            assert_eq!(session_setups(10, strategy).unwrap().iterations, 10);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Fixed size packet buffers on the stack
+//!
+//! `StackBuffer` dereferences to `[u8]`, so it can be passed to every builder taking a buffer.
+//! Buffers which can't hold the Ethernet and PPPoE headers are rejected at compile time.
+
+use core::fmt;
+use core::num::NonZeroU16;
+use core::ops::{Deref, DerefMut};
+
+use crate::consts::{ETHERNET_HEADER_LEN, MAX_PAYLOAD_LEN, MIN_FRAME_LEN, PPPOE_HEADER_LEN};
+use crate::error::Error;
+use crate::packet::{PacketBuilder, SessionPacketBuilder};
+
+/// A buffer for a full frame on a 1500 byte Ethernet MTU
+pub type EthernetBuffer = StackBuffer<{ ETHERNET_HEADER_LEN + PPPOE_HEADER_LEN + MAX_PAYLOAD_LEN }>;
+
+/// A zeroed packet buffer of `N` bytes
+///
+/// `N` must be at least `consts::MIN_FRAME_LEN`:
+///
+/// ```compile_fail
+/// let buffer = pppoe::buffer::StackBuffer::<8>::new();
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct StackBuffer<const N: usize> {
+    buffer: [u8; N],
+}
+
+impl<const N: usize> StackBuffer<N> {
+    const MIN_LEN: () = assert!(
+        N >= MIN_FRAME_LEN,
+        "StackBuffer is smaller than the Ethernet and PPPoE headers"
+    );
+
+    #[allow(clippy::let_unit_value)]
+    pub const fn new() -> Self {
+        let () = Self::MIN_LEN;
+        Self { buffer: [0; N] }
+    }
+
+    /// Size of the buffer
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Start a discovery packet in this buffer, see `PacketBuilder::new_discovery_packet`.
+    pub fn discovery_packet(
+        &mut self,
+        src_mac: [u8; 6],
+        dst_mac: [u8; 6],
+    ) -> Result<PacketBuilder<'_>, Error> {
+        PacketBuilder::new_discovery_packet(&mut self.buffer, src_mac, dst_mac)
+    }
+
+    /// Start a session packet in this buffer, see `PacketBuilder::new_session_packet`.
+    pub fn session_packet(
+        &mut self,
+        src_mac: [u8; 6],
+        dst_mac: [u8; 6],
+        session_id: NonZeroU16,
+    ) -> Result<SessionPacketBuilder<'_>, Error> {
+        PacketBuilder::new_session_packet(&mut self.buffer, src_mac, dst_mac, session_id)
+    }
+
+    pub fn into_inner(self) -> [u8; N] {
+        self.buffer
+    }
+}
+
+impl<const N: usize> Default for StackBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Deref for StackBuffer<N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl<const N: usize> DerefMut for StackBuffer<N> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for StackBuffer<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl<const N: usize> AsMut<[u8]> for StackBuffer<N> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer
+    }
+}
+
+impl<const N: usize> fmt::Debug for StackBuffer<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StackBuffer").field("capacity", &N).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Packet, Tag};
+
+    #[test]
+    fn build_on_stack() {
+        let mut buffer = StackBuffer::<64>::new();
+        let len = {
+            let mut padi = buffer
+                .discovery_packet([1, 2, 3, 4, 5, 6], [0xff; 6])
+                .unwrap();
+            padi.pppoe_header()
+                .add_tag(Tag::service_name("").unwrap())
+                .unwrap();
+            padi.len()
+        };
+
+        let packet = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(packet.ethernet_header().src_address(), [1, 2, 3, 4, 5, 6]);
+
+        let mut buffer = EthernetBuffer::default();
+        assert_eq!(buffer.capacity(), 1514);
+        let mut session = buffer
+            .session_packet([1; 6], [2; 6], NonZeroU16::new(7).unwrap())
+            .unwrap();
+        assert_eq!(session.payload_capacity(), 1494);
+        session.payload_mut()[..2].copy_from_slice(&[0xc0, 0x21]);
+        assert_eq!(session.finish(2).unwrap().len(), MIN_FRAME_LEN + 2);
+
+        // the smallest buffer only holds the headers
+        let mut buffer = StackBuffer::<MIN_FRAME_LEN>::new();
+        assert!(buffer.discovery_packet([1; 6], [0xff; 6]).is_ok());
+    }
+}
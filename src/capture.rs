@@ -0,0 +1,115 @@
+//! Capturing of all frames on an interface for debugging
+//!
+//! `Socket::capture_all` rebinds the raw socket to `ETH_P_ALL`, so it receives every frame of the
+//! interface, including the ones send by this host.  `classify` tells PPPoE frames apart from
+//! everything else, e.g. to see that the PADIs leave the host with a VLAN tag the Access
+//! Concentrator doesn't expect.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::convert::TryInto;
+
+use crate::consts::ETHERNET_HEADER_LEN;
+use crate::error::{Error, ParseError};
+use crate::packet::{PPPOE_DISCOVERY, PPPOE_SESSION};
+use crate::summary::{PacketSummary, ETHERTYPE_VLAN};
+
+/// Whether a captured frame was received or send by this host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+/// A captured frame
+#[derive(Debug)]
+// PPPoE frames are the common case, boxing the summary would cost an allocation per frame
+#[allow(clippy::large_enum_variant)]
+pub enum CapturedFrame<'a> {
+    /// A valid PPPoE discovery or session frame
+    Pppoe(PacketSummary),
+    /// A frame with a PPPoE ethertype which could not be parsed
+    Malformed { error: Error, frame: &'a [u8] },
+    /// Any other frame
+    Other {
+        dst_mac: [u8; 6],
+        src_mac: [u8; 6],
+        vlan: Option<u16>,
+        ether_type: u16,
+        frame: &'a [u8],
+    },
+}
+
+/// Sort a captured frame into PPPoE and other traffic.
+pub fn classify(frame: &[u8]) -> CapturedFrame<'_> {
+    if frame.len() < ETHERNET_HEADER_LEN {
+        return CapturedFrame::Malformed {
+            error: ParseError::BufferTooSmall(frame.len()).into(),
+            frame,
+        };
+    }
+
+    let mut ether_type = NE::read_u16(&frame[12..]);
+    let mut vlan = None;
+    if ether_type == ETHERTYPE_VLAN && frame.len() >= ETHERNET_HEADER_LEN + 4 {
+        vlan = Some(NE::read_u16(&frame[ETHERNET_HEADER_LEN..]) & 0x0fff);
+        ether_type = NE::read_u16(&frame[ETHERNET_HEADER_LEN + 2..]);
+    }
+
+    match ether_type {
+        PPPOE_DISCOVERY | PPPOE_SESSION => match PacketSummary::from_bytes(frame) {
+            Ok(summary) => CapturedFrame::Pppoe(summary),
+            Err(error) => CapturedFrame::Malformed { error, frame },
+        },
+        _ => CapturedFrame::Other {
+            dst_mac: frame[..6].try_into().unwrap(),
+            src_mac: frame[6..12].try_into().unwrap(),
+            vlan,
+            ether_type,
+            frame,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PacketBuilder;
+
+    #[test]
+    fn classify_frames() {
+        let mut buffer = [0u8; 64];
+        let mut packet =
+            PacketBuilder::new_discovery_packet(&mut buffer[..], [2, 0, 0, 0, 0, 1], [0xff; 6])
+                .unwrap();
+        packet
+            .pppoe_header()
+            .add_tag(crate::Tag::ServiceName(b""))
+            .unwrap();
+        let len = packet.len();
+        match classify(&buffer[..len]) {
+            CapturedFrame::Pppoe(summary) => assert!(summary.is_discovery()),
+            other => panic!("unexpected classification: {:?}", other),
+        }
+
+        // an ARP request
+        let mut frame = [0u8; 42];
+        frame[..6].copy_from_slice(&[0xff; 6]);
+        NE::write_u16(&mut frame[12..], 0x0806);
+        match classify(&frame) {
+            CapturedFrame::Other {
+                ether_type, vlan, ..
+            } => {
+                assert_eq!(ether_type, 0x0806);
+                assert_eq!(vlan, None);
+            }
+            other => panic!("unexpected classification: {:?}", other),
+        }
+
+        NE::write_u16(&mut frame[12..], PPPOE_SESSION);
+        assert!(matches!(
+            classify(&frame[..16]),
+            CapturedFrame::Malformed { .. }
+        ));
+    }
+}
@@ -0,0 +1,6 @@
+//! Protocol constants used throughout the crate
+//!
+//! They are defined in `pppoe_core::consts` and also re-exported from the module using them,
+//! so all of these paths can be used.
+
+pub use pppoe_core::consts::*;
@@ -0,0 +1,238 @@
+//! Stateless AC-Cookies with replay protection
+//!
+//! The Access Concentrator puts a cookie into its PADO and only accepts PADRs which echo it, so
+//! it doesn't have to keep state for every PADI.  The cookie carries the time it was issued and
+//! a SipHash-2-4 tag over that time, the client MAC and the Service-Name.  PADRs captured and
+//! replayed later are rejected once the cookie is older than the acceptance window.
+
+use byteorder::{ByteOrder, LittleEndian as LE, NetworkEndian as NE};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::CookieError;
+use crate::secret::Secret;
+
+const COOKIE_VERSION: u8 = 1;
+
+/// Length of a cookie: version, timestamp and tag
+pub const COOKIE_LEN: usize = 1 + 4 + 8;
+
+/// Default time a cookie is accepted after it was issued
+pub const DEFAULT_COOKIE_LIFETIME: Duration = Duration::from_secs(60);
+/// Default tolerance for timestamps from the future, e.g. between servers sharing the key
+pub const DEFAULT_CLOCK_SKEW: Duration = Duration::from_secs(2);
+
+/// Issues and checks AC-Cookies
+#[derive(Debug, Clone)]
+pub struct CookieJar {
+    key: Secret,
+    lifetime: Duration,
+    clock_skew: Duration,
+}
+
+impl CookieJar {
+    /// Use a random 128 bit `key`.  Servers sharing the key accept each others cookies.
+    pub fn new(key: [u8; 16]) -> Self {
+        Self {
+            key: Secret::new(key.to_vec()),
+            lifetime: DEFAULT_COOKIE_LIFETIME,
+            clock_skew: DEFAULT_CLOCK_SKEW,
+        }
+    }
+
+    /// Accept cookies for `lifetime` after they were issued.
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Accept cookies which were issued up to `clock_skew` in the future.
+    pub fn with_clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    pub fn lifetime(&self) -> Duration {
+        self.lifetime
+    }
+
+    pub fn clock_skew(&self) -> Duration {
+        self.clock_skew
+    }
+
+    /// Create the cookie for a PADO to `client_mac`.
+    pub fn issue(
+        &self,
+        now: SystemTime,
+        client_mac: [u8; 6],
+        service_name: &[u8],
+    ) -> [u8; COOKIE_LEN] {
+        let mut cookie = [0u8; COOKIE_LEN];
+        cookie[0] = COOKIE_VERSION;
+        NE::write_u32(&mut cookie[1..], unix_seconds(now));
+        let tag = self.tag(&cookie[..5], client_mac, service_name);
+        NE::write_u64(&mut cookie[5..], tag);
+        cookie
+    }
+
+    /// Check the cookie of a PADR from `client_mac`.
+    pub fn verify(
+        &self,
+        now: SystemTime,
+        cookie: &[u8],
+        client_mac: [u8; 6],
+        service_name: &[u8],
+    ) -> Result<(), CookieError> {
+        if cookie.len() != COOKIE_LEN || cookie[0] != COOKIE_VERSION {
+            return Err(CookieError::Malformed);
+        }
+        // compare without an early exit
+        let tag = self.tag(&cookie[..5], client_mac, service_name) ^ NE::read_u64(&cookie[5..]);
+        if tag != 0 {
+            return Err(CookieError::InvalidTag);
+        }
+
+        let issued = i64::from(NE::read_u32(&cookie[1..]));
+        let now = i64::from(unix_seconds(now));
+        let age = now - issued;
+        if age < 0 && Duration::from_secs(age.unsigned_abs()) > self.clock_skew {
+            return Err(CookieError::FromFuture {
+                ahead: Duration::from_secs(age.unsigned_abs()),
+            });
+        }
+        if age > 0 && Duration::from_secs(age as u64) > self.lifetime {
+            return Err(CookieError::Expired {
+                age: Duration::from_secs(age as u64),
+            });
+        }
+        Ok(())
+    }
+
+    fn tag(&self, header: &[u8], client_mac: [u8; 6], service_name: &[u8]) -> u64 {
+        let mut key = [0u8; 16];
+        key.copy_from_slice(self.key.expose());
+
+        let mut message = Vec::with_capacity(header.len() + 6 + service_name.len());
+        message.extend_from_slice(header);
+        message.extend_from_slice(&client_mac);
+        message.extend_from_slice(service_name);
+
+        let tag = siphash24(&key, &message);
+        crate::secret::wipe(&mut key);
+        tag
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u32 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as u32)
+        .unwrap_or(0)
+}
+
+/// SipHash-2-4 (https://www.aumasson.jp/siphash/siphash.pdf)
+fn siphash24(key: &[u8; 16], message: &[u8]) -> u64 {
+    let k0 = LE::read_u64(&key[..8]);
+    let k1 = LE::read_u64(&key[8..]);
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    fn compress(m: u64, v: &mut [u64; 4]) {
+        v[3] ^= m;
+        round(v);
+        round(v);
+        v[0] ^= m;
+    }
+
+    let mut chunks = message.chunks_exact(8);
+    for chunk in &mut chunks {
+        compress(LE::read_u64(chunk), &mut v);
+    }
+    let mut last = [0u8; 8];
+    let remainder = chunks.remainder();
+    last[..remainder.len()].copy_from_slice(remainder);
+    last[7] = message.len() as u8;
+    compress(LE::read_u64(&last), &mut v);
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn siphash_test_vectors() {
+        let mut key = [0u8; 16];
+        for (index, byte) in key.iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+        let message: Vec<u8> = (0..15).collect();
+        assert_eq!(siphash24(&key, &[]), 0x726f_db47_dd0e_0e31);
+        assert_eq!(siphash24(&key, &message), 0xa129_ca61_49be_45e5);
+    }
+
+    #[test]
+    fn reject_replayed_cookies() {
+        let jar = CookieJar::new([7; 16]).with_lifetime(Duration::from_secs(30));
+        let client = [0x02, 0, 0, 0, 0, 1];
+        let issued = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let cookie = &jar.issue(issued, client, b"internet")[..];
+
+        let later = |seconds| issued + Duration::from_secs(seconds);
+        assert_eq!(jar.verify(later(30), cookie, client, b"internet"), Ok(()));
+        assert_eq!(
+            jar.verify(later(31), cookie, client, b"internet"),
+            Err(CookieError::Expired {
+                age: Duration::from_secs(31)
+            })
+        );
+        assert_eq!(
+            jar.verify(issued - Duration::from_secs(3), cookie, client, b"internet"),
+            Err(CookieError::FromFuture {
+                ahead: Duration::from_secs(3)
+            })
+        );
+        assert_eq!(
+            jar.verify(issued - Duration::from_secs(2), cookie, client, b"internet"),
+            Ok(())
+        );
+
+        assert_eq!(
+            jar.verify(later(1), cookie, [0x02, 0, 0, 0, 0, 2], b"internet"),
+            Err(CookieError::InvalidTag)
+        );
+        assert_eq!(
+            jar.verify(later(1), cookie, client, b"iptv"),
+            Err(CookieError::InvalidTag)
+        );
+        assert_eq!(
+            CookieJar::new([8; 16]).verify(later(1), cookie, client, b"internet"),
+            Err(CookieError::InvalidTag)
+        );
+        assert_eq!(
+            jar.verify(later(1), &cookie[..12], client, b"internet"),
+            Err(CookieError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Structured comparison of two packets, e.g. for assertions in interoperability tests
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::fmt;
+
+use crate::tag::*;
+use crate::Packet;
+
+/// A single difference between an expected and an actual packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    DstMac {
+        expected: [u8; 6],
+        actual: [u8; 6],
+    },
+    SrcMac {
+        expected: [u8; 6],
+        actual: [u8; 6],
+    },
+    EtherType {
+        expected: u16,
+        actual: u16,
+    },
+    Code {
+        expected: u8,
+        actual: u8,
+    },
+    SessionId {
+        expected: u16,
+        actual: u16,
+    },
+    /// A tag of the expected packet is missing in the actual packet
+    MissingTag {
+        tag_type: u16,
+        value: Vec<u8>,
+    },
+    /// The actual packet has a tag the expected packet doesn't have
+    UnexpectedTag {
+        tag_type: u16,
+        value: Vec<u8>,
+    },
+    /// Both packets have the tag, but with different values
+    TagValue {
+        tag_type: u16,
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+}
+
+/// All differences between two packets, see `diff`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PacketDiff {
+    pub differences: Vec<Difference>,
+}
+
+impl PacketDiff {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.differences.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Difference> {
+        self.differences.iter()
+    }
+}
+
+/// Compare two packets field by field and tag by tag.
+///
+/// Tags are matched by type in the order they appear, so the n-th Vendor-Specific tag of
+/// `expected` is compared with the n-th Vendor-Specific tag of `actual`.  The order of tags of
+/// different types is not compared.
+pub fn diff(expected: &Packet, actual: &Packet) -> PacketDiff {
+    let mut differences = Vec::new();
+
+    let (expected_eth, actual_eth) = (expected.ethernet_header(), actual.ethernet_header());
+    if expected_eth.dst_address() != actual_eth.dst_address() {
+        differences.push(Difference::DstMac {
+            expected: expected_eth.dst_address(),
+            actual: actual_eth.dst_address(),
+        });
+    }
+    if expected_eth.src_address() != actual_eth.src_address() {
+        differences.push(Difference::SrcMac {
+            expected: expected_eth.src_address(),
+            actual: actual_eth.src_address(),
+        });
+    }
+    if expected_eth.ether_type() != actual_eth.ether_type() {
+        differences.push(Difference::EtherType {
+            expected: expected_eth.ether_type(),
+            actual: actual_eth.ether_type(),
+        });
+    }
+
+    let (expected, actual) = (expected.pppoe_header(), actual.pppoe_header());
+    if expected.code() != actual.code() {
+        differences.push(Difference::Code {
+            expected: expected.code(),
+            actual: actual.code(),
+        });
+    }
+    if expected.session_id() != actual.session_id() {
+        differences.push(Difference::SessionId {
+            expected: expected.session_id(),
+            actual: actual.session_id(),
+        });
+    }
+
+    let expected_tags = raw_tags(&expected.get_ref()[6..expected.len()]);
+    let mut actual_tags: Vec<_> = raw_tags(&actual.get_ref()[6..actual.len()])
+        .into_iter()
+        .map(Some)
+        .collect();
+
+    for (tag_type, expected_value) in expected_tags {
+        let matching = actual_tags
+            .iter_mut()
+            .find(|tag| matches!(tag, Some((actual_type, _)) if *actual_type == tag_type));
+
+        match matching.and_then(Option::take) {
+            Some((_, actual_value)) if actual_value == expected_value => (),
+            Some((_, actual_value)) => differences.push(Difference::TagValue {
+                tag_type,
+                expected: expected_value.to_vec(),
+                actual: actual_value.to_vec(),
+            }),
+            None => differences.push(Difference::MissingTag {
+                tag_type,
+                value: expected_value.to_vec(),
+            }),
+        }
+    }
+
+    for (tag_type, value) in actual_tags.into_iter().flatten() {
+        differences.push(Difference::UnexpectedTag {
+            tag_type,
+            value: value.to_vec(),
+        });
+    }
+
+    PacketDiff { differences }
+}
+
+/// Split a (validated) tag payload into tag types and values
+fn raw_tags(mut payload: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut tags = Vec::new();
+    while payload.len() >= 4 {
+        let tag_type = NE::read_u16(payload);
+        let length = usize::from(NE::read_u16(&payload[2..])).min(payload.len() - 4);
+        tags.push((tag_type, &payload[4..4 + length]));
+        payload = &payload[4 + length..];
+    }
+    tags
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::DstMac { expected, actual } => write!(
+                f,
+                "destination MAC differs (expected {:02x?}, got {:02x?})",
+                expected, actual
+            ),
+            Difference::SrcMac { expected, actual } => write!(
+                f,
+                "source MAC differs (expected {:02x?}, got {:02x?})",
+                expected, actual
+            ),
+            Difference::EtherType { expected, actual } => write!(
+                f,
+                "ethertype differs (expected {:#06x}, got {:#06x})",
+                expected, actual
+            ),
+            Difference::Code { expected, actual } => write!(
+                f,
+                "code differs (expected {:#04x}, got {:#04x})",
+                expected, actual
+            ),
+            Difference::SessionId { expected, actual } => write!(
+                f,
+                "session id differs (expected {:#06x}, got {:#06x})",
+                expected, actual
+            ),
+            Difference::MissingTag { tag_type, .. } => write!(f, "{} missing", TagName(*tag_type)),
+            Difference::UnexpectedTag { tag_type, .. } => {
+                write!(f, "unexpected {}", TagName(*tag_type))
+            }
+            Difference::TagValue { tag_type, .. } => write!(f, "{} differs", TagName(*tag_type)),
+        }
+    }
+}
+
+impl fmt::Display for PacketDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("packets are equal");
+        }
+
+        for (i, difference) in self.differences.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Code, PacketBuilder, Tag};
+
+    fn pads(buffer: &mut [u8], tags: &[Tag]) -> usize {
+        let mut packet =
+            PacketBuilder::new_discovery_packet(buffer, [2, 0, 0, 0, 0, 2], [2, 0, 0, 0, 0, 1])
+                .unwrap();
+        packet.set_code(Code::Pads);
+        for tag in tags {
+            packet.pppoe_header().add_tag(*tag).unwrap();
+        }
+        packet.len()
+    }
+
+    #[test]
+    fn describe_differences() {
+        let mut expected = [0u8; 100];
+        let len = pads(
+            &mut expected,
+            &[
+                Tag::ServiceName(b""),
+                Tag::AcName(b"ac"),
+                Tag::HostUniq(b"hu"),
+            ],
+        );
+        let expected = Packet::with_buffer(&expected[..len]).unwrap();
+
+        let mut actual = [0u8; 100];
+        let len = pads(
+            &mut actual,
+            &[
+                Tag::AcName(b"other"),
+                Tag::ServiceName(b""),
+                Tag::AcCookie(b"c"),
+            ],
+        );
+        let actual = Packet::with_buffer(&actual[..len]).unwrap();
+
+        assert!(diff(&expected, &expected).is_empty());
+
+        let differences = diff(&expected, &actual);
+        assert_eq!(differences.len(), 3);
+        assert_eq!(
+            differences.differences[0],
+            Difference::TagValue {
+                tag_type: TAG_AC_NAME,
+                expected: b"ac".to_vec(),
+                actual: b"other".to_vec(),
+            }
+        );
+        assert_eq!(
+            differences.to_string(),
+            "AC-Name differs; Host-Uniq missing; unexpected AC-Cookie"
+        );
+    }
+}
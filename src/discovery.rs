@@ -0,0 +1,1190 @@
+use std::collections::HashMap;
+use std::num::NonZeroU16;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, ProtocolError};
+use crate::header::{PADO, PADS};
+use crate::logging::DISCOVERY;
+use crate::packet::PPPOE_DISCOVERY;
+use crate::timestamp::Timestamp;
+use crate::{eth, HeaderBuilder, Packet, PacketBuilder, ServiceNameMatcher, Tag};
+
+/// Options for the discovery stage of a PPPoE client
+#[derive(Debug, Clone)]
+pub struct DiscoveryOptions {
+    /// The requested service name, empty means any service is acceptable.
+    pub service_name: Vec<u8>,
+    /// Decides which Service-Names of offers are acceptable.  By default they have to match
+    /// `service_name` exactly, apart from trailing NULs.
+    pub service_name_matcher: Option<ServiceNameMatcher>,
+    /// Only accept offers from an Access Concentrator with this name.
+    pub ac_name: Option<Vec<u8>>,
+    /// Only accept offers from the Access Concentrator with this MAC address.
+    pub ac_mac: Option<[u8; 6]>,
+    /// Host-Uniq value to send with PADI and PADR.  Responses carrying another Host-Uniq are
+    /// ignored.
+    pub host_uniq: Option<Vec<u8>>,
+    /// Time to wait for a PADO or PADS before retransmitting the PADI or PADR.
+    pub timeout: Duration,
+    /// Number of PADIs (and PADRs) to send before giving up.
+    pub attempts: u32,
+    /// Sessions which are already established on this host, as pairs of Access Concentrator MAC
+    /// and session id.  A PADS assigning one of them again is rejected.
+    pub sessions_in_use: Vec<([u8; 6], NonZeroU16)>,
+    /// Number of PADIs after `Discovery::reconnect` during which only offers of the previous
+    /// Access Concentrator are accepted.  Offers of other ACs are accepted afterwards, 0 disables
+    /// the fast reconnect.
+    pub reconnect_attempts: u32,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            service_name: Vec::new(),
+            service_name_matcher: None,
+            ac_name: None,
+            ac_mac: None,
+            host_uniq: None,
+            timeout: Duration::from_secs(3),
+            attempts: 3,
+            sessions_in_use: Vec::new(),
+            reconnect_attempts: 2,
+        }
+    }
+}
+
+/// The result of a successful discovery stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    pub session_id: NonZeroU16,
+    pub local_mac: [u8; 6],
+    pub ac_mac: [u8; 6],
+    pub ac_name: Vec<u8>,
+    pub service_name: Vec<u8>,
+    /// Reception of the PADS, the start of the session for accounting
+    pub established: Timestamp,
+}
+
+/// A PADO received during the discovery stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub ac_mac: [u8; 6],
+    pub ac_name: Vec<u8>,
+    /// Time between the last PADI and the arrival of the PADO
+    pub rtt: Duration,
+}
+
+/// Statistics of the discovery stage, see `Discovery::report`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiscoveryReport {
+    /// Number of PADIs send so far
+    pub padi_count: u32,
+    /// The first PADO of every Access Concentrator which answered, including the ones which
+    /// were not selected
+    pub offers: Vec<Offer>,
+    /// Number of PADOs ignored because a PADR was already sent
+    pub late_offers: u32,
+    /// Number of retransmitted PADSs for the established session
+    pub duplicate_confirmations: u32,
+    /// Number of responses dropped because their Host-Uniq didn't match
+    pub foreign_host_uniq: u32,
+}
+
+impl DiscoveryReport {
+    /// Get the offer of the Access Concentrator which answered the fastest.
+    pub fn fastest_offer(&self) -> Option<&Offer> {
+        self.offers.iter().min_by_key(|offer| offer.rtt)
+    }
+}
+
+/// The offer a session was established with, to skip the PADI when redialing
+///
+/// See `OfferCache` and `Discovery::redial_cached`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedOffer {
+    pado: Vec<u8>,
+    pub ac_mac: [u8; 6],
+    pub ac_name: Vec<u8>,
+    /// The Service-Names of the offer
+    pub services: Vec<Vec<u8>>,
+    /// Whether the offer carried an AC-Cookie, which the AC may consider stale on a redial
+    pub has_cookie: bool,
+    /// Reception of the PADS of the session
+    pub established: Instant,
+}
+
+impl CachedOffer {
+    fn new(pado: Vec<u8>, ac_name: Vec<u8>, established: Instant) -> Result<Self, Error> {
+        let packet = Packet::with_buffer(&pado)?;
+        let ac_mac = packet.ethernet_header().src_address();
+        let mut services = Vec::new();
+        let mut has_cookie = false;
+        for tag in packet.pppoe_header().tags() {
+            match tag {
+                Tag::ServiceName(service_name) => services.push(service_name.to_vec()),
+                Tag::AcCookie(_) => has_cookie = true,
+                _ => (),
+            }
+        }
+
+        Ok(Self {
+            pado,
+            ac_mac,
+            ac_name,
+            services,
+            has_cookie,
+            established,
+        })
+    }
+
+    /// Check whether `service_name` was offered, an empty one matches any offer.
+    pub fn offers(&self, service_name: &[u8]) -> bool {
+        let matcher = ServiceNameMatcher::new(service_name).ignore_trailing_nul();
+        service_name.is_empty() || self.services.iter().any(|offered| matcher.matches(offered))
+    }
+}
+
+/// The last offers sessions were established with, by interface name
+#[derive(Debug, Clone)]
+pub struct OfferCache {
+    max_age: Duration,
+    offers: HashMap<String, CachedOffer>,
+}
+
+impl OfferCache {
+    /// Create a cache whose offers are used for `max_age` after their session was established.
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            offers: HashMap::new(),
+        }
+    }
+
+    /// Remember the offer of an established discovery, see `Discovery::offer`.
+    pub fn insert(&mut self, interface: &str, offer: CachedOffer) {
+        self.offers.insert(interface.to_owned(), offer);
+    }
+
+    /// Get the offer of an interface, unless it is older than the maximum age at `now`.
+    pub fn get(&self, interface: &str, now: Instant) -> Option<&CachedOffer> {
+        self.offers
+            .get(interface)
+            .filter(|offer| now.saturating_duration_since(offer.established) < self.max_age)
+    }
+
+    /// Forget the offer of an interface, e.g. because the AC went away.
+    pub fn remove(&mut self, interface: &str) -> Option<CachedOffer> {
+        self.offers.remove(interface)
+    }
+
+    /// Forget all offers which are older than the maximum age at `now`.
+    pub fn expire(&mut self, now: Instant) {
+        let max_age = self.max_age;
+        self.offers
+            .retain(|_, offer| now.saturating_duration_since(offer.established) < max_age);
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Discovering,
+    Requesting { pado: Vec<u8>, ac_name: Vec<u8> },
+    Established(SessionInfo),
+    Failed,
+}
+
+/// The client side of the PPPoE discovery stage.
+///
+/// The state machine does no I/O on its own: frames which have to be send are requested via
+/// `poll_transmit` and received frames are passed to `handle_frame`.  `poll_timeout` returns the
+/// point in time `poll_transmit` has to be called again, even if no frame was received.
+#[derive(Debug)]
+pub struct Discovery {
+    options: DiscoveryOptions,
+    local_mac: [u8; 6],
+    state: State,
+    attempt: u32,
+    deadline: Option<Instant>,
+    padi_sent: Option<Instant>,
+    report: DiscoveryReport,
+    clock: Timestamp,
+    previous_ac: Option<[u8; 6]>,
+    offer: Option<CachedOffer>,
+    // the PADR was sent for a cached offer, fall back to a PADI if it fails
+    redial: bool,
+}
+
+impl Discovery {
+    pub fn new(local_mac: [u8; 6], options: DiscoveryOptions) -> Self {
+        Self {
+            options,
+            local_mac,
+            state: State::Discovering,
+            attempt: 0,
+            deadline: None,
+            padi_sent: None,
+            report: DiscoveryReport::default(),
+            clock: Timestamp::now(),
+            previous_ac: None,
+            offer: None,
+            redial: false,
+        }
+    }
+
+    /// Set up a new session with the offer cached for `interface`, skipping the PADI.
+    ///
+    /// The PADR is sent right away to the cached Access Concentrator, echoing its old PADO.  If
+    /// the AC doesn't confirm the session within the usual attempts or rejects it, e.g. because
+    /// the AC-Cookie is stale by now, the discovery falls back to a PADI.  Without a fresh offer
+    /// for the requested service this is the same as `new`.
+    pub fn redial_cached(
+        local_mac: [u8; 6],
+        options: DiscoveryOptions,
+        cache: &OfferCache,
+        interface: &str,
+        now: Instant,
+    ) -> Self {
+        let offer = cache.get(interface, now).filter(|offer| {
+            offer.offers(&options.service_name)
+                && options.ac_mac.is_none_or(|ac_mac| ac_mac == offer.ac_mac)
+        });
+        let mut discovery = Self::new(local_mac, options);
+        if let Some(offer) = offer {
+            debug!(
+                target: DISCOVERY,
+                "redialing cached offer of {:02x?}", offer.ac_mac
+            );
+            discovery.state = State::Requesting {
+                pado: offer.pado.clone(),
+                ac_name: offer.ac_name.clone(),
+            };
+            discovery.redial = true;
+        }
+        discovery
+    }
+
+    /// Set up a new session with the Access Concentrator and service of `previous`, e.g. after
+    /// it was terminated by a PADT during a failover of the AC.
+    ///
+    /// The discovery starts with a PADI for the previous Service-Name as usual (an AC-Cookie of
+    /// the old PADO would be stale), but only the previous AC is accepted for the first
+    /// `DiscoveryOptions::reconnect_attempts` PADIs.  So the AC which answers first doesn't
+    /// matter and the subscriber keeps e.g. its address pool.
+    pub fn reconnect(
+        local_mac: [u8; 6],
+        mut options: DiscoveryOptions,
+        previous: &SessionInfo,
+    ) -> Self {
+        options.service_name = previous.service_name.clone();
+        let mut discovery = Self::new(local_mac, options);
+        discovery.previous_ac = Some(previous.ac_mac);
+        discovery
+    }
+
+    /// Convert the `Instant`s passed in to wall-clock time relative to `clock` instead of the
+    /// time `new` was called, e.g. to get reproducible `SessionInfo::established` values.
+    pub fn with_clock(mut self, clock: Timestamp) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Get the statistics collected so far.
+    pub fn report(&self) -> &DiscoveryReport {
+        &self.report
+    }
+
+    /// Get the negotiated session after a PADS was received.
+    pub fn session(&self) -> Option<&SessionInfo> {
+        match &self.state {
+            State::Established(session) => Some(session),
+            _ => None,
+        }
+    }
+
+    /// Get the offer the session was established with, to put it into an `OfferCache`.
+    pub fn offer(&self) -> Option<&CachedOffer> {
+        self.offer.as_ref()
+    }
+
+    /// Check whether a session was established or the discovery failed.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, State::Established(_) | State::Failed)
+    }
+
+    /// Get the point in time at which the next retransmission is due.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        match self.state {
+            State::Discovering | State::Requesting { .. } => self.deadline,
+            _ => None,
+        }
+    }
+
+    /// Write the next frame which has to be send into `buffer` and return its length.
+    ///
+    /// Returns an error once all retransmissions timed out.
+    pub fn poll_transmit(
+        &mut self,
+        now: Instant,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, Error> {
+        match self.deadline {
+            Some(deadline) if now < deadline => return Ok(None),
+            _ => (),
+        }
+
+        if self.attempt >= self.options.attempts && !self.fall_back() {
+            return match self.give_up() {
+                Some(error) => {
+                    debug!(target: DISCOVERY, "giving up after {} attempts: {:?}", self.attempt, error);
+                    Err(error.into())
+                }
+                None => Ok(None),
+            };
+        }
+
+        let len = match &self.state {
+            State::Discovering => {
+                debug!(target: DISCOVERY, "sending PADI, attempt {}", self.attempt + 1);
+                let len = self.write_padi(buffer)?;
+                self.padi_sent = Some(now);
+                self.report.padi_count += 1;
+                len
+            }
+            State::Requesting { pado, .. } => {
+                debug!(target: DISCOVERY, "sending PADR, attempt {}", self.attempt + 1);
+                self.write_padr(pado, buffer)?
+            }
+            _ => return Ok(None),
+        };
+
+        self.attempt += 1;
+        self.deadline = Some(now + self.options.timeout);
+        Ok(Some(len))
+    }
+
+    /// Process a received frame.
+    ///
+    /// Frames which are not addressed to this client or do not belong to the current stage are
+    /// ignored.  An error is only returned if the Access Concentrator rejected the session or
+    /// assigned an invalid session id.
+    pub fn handle_frame(&mut self, now: Instant, frame: &[u8]) -> Result<(), Error> {
+        let packet = match Packet::with_buffer(frame) {
+            Ok(packet) => packet,
+            Err(_) => return Ok(()),
+        };
+
+        let ethernet = packet.ethernet_header();
+        if ethernet.ether_type() != PPPOE_DISCOVERY || ethernet.dst_address() != self.local_mac {
+            return Ok(());
+        }
+
+        let header = packet.pppoe_header();
+        if !self.host_uniq_matches(&packet) {
+            trace!(target: DISCOVERY, "dropping response with foreign Host-Uniq");
+            self.report.foreign_host_uniq += 1;
+            return Ok(());
+        }
+
+        if header.code() == PADO {
+            trace!(target: DISCOVERY, "PADO from {:02x?}", ethernet.src_address());
+            self.record_offer(now, &packet);
+        }
+
+        match (&self.state, header.code()) {
+            (State::Discovering, PADO) => {
+                if self
+                    .options
+                    .ac_mac
+                    .is_some_and(|ac_mac| ethernet.src_address() != ac_mac)
+                {
+                    trace!(target: DISCOVERY, "ignoring PADO of another AC");
+                    return Ok(());
+                }
+                if let Some(previous_ac) = self.previous_ac {
+                    if self.attempt <= self.options.reconnect_attempts
+                        && ethernet.src_address() != previous_ac
+                    {
+                        trace!(target: DISCOVERY, "waiting for the previous AC to reconnect");
+                        return Ok(());
+                    }
+                }
+
+                // the PADR only carries tags of the PADO, so it is never longer
+                let mut padr = vec![0u8; header.len()];
+                let expected_service_name = match &self.options.service_name_matcher {
+                    Some(matcher) => matcher.clone(),
+                    None => {
+                        ServiceNameMatcher::new(&self.options.service_name).ignore_trailing_nul()
+                    }
+                };
+                if HeaderBuilder::create_padr_from_pado(
+                    &mut padr[..],
+                    header,
+                    Some(&expected_service_name),
+                    self.options.ac_name.as_deref(),
+                )
+                .is_err()
+                {
+                    trace!(target: DISCOVERY, "ignoring unacceptable PADO");
+                    return Ok(());
+                }
+
+                let ac_name = ac_name(&packet);
+                debug!(
+                    target: DISCOVERY,
+                    "selected offer of {:02x?} ({})",
+                    ethernet.src_address(),
+                    String::from_utf8_lossy(&ac_name)
+                );
+                self.state = State::Requesting {
+                    pado: packet.as_bytes().to_vec(),
+                    ac_name,
+                };
+                self.attempt = 0;
+                self.deadline = None;
+            }
+
+            (State::Requesting { pado, ac_name }, PADS) => {
+                let ac_mac = eth::Header::with_buffer(pado)?.src_address();
+                if ethernet.src_address() != ac_mac {
+                    return Ok(());
+                }
+
+                for tag in header.tags() {
+                    let error = match tag {
+                        Tag::ServiceNameError(msg) => ProtocolError::ServiceNameError(msg.to_vec()),
+                        Tag::AcSystemError(msg) => ProtocolError::AcSystemError(msg.to_vec()),
+                        Tag::GenericError(msg) => ProtocolError::GenericError(msg.to_vec()),
+                        _ => continue,
+                    };
+                    debug!(target: DISCOVERY, "session rejected: {:?}", error);
+                    if self.fall_back() {
+                        return Ok(());
+                    }
+                    self.state = State::Failed;
+                    return Err(error.into());
+                }
+
+                let session_id = match NonZeroU16::new(header.session_id()) {
+                    Some(session_id)
+                        if session_id.get() != 0xffff
+                            && !self.options.sessions_in_use.contains(&(ac_mac, session_id)) =>
+                    {
+                        session_id
+                    }
+                    _ => {
+                        debug!(
+                            target: DISCOVERY,
+                            "invalid session id {:#06x} assigned",
+                            header.session_id()
+                        );
+                        self.state = State::Failed;
+                        return Err(
+                            ProtocolError::InvalidSessionAssignment(header.session_id()).into()
+                        );
+                    }
+                };
+
+                let service_name = header
+                    .tags()
+                    .find_map(|tag| match tag {
+                        Tag::ServiceName(service_name) => Some(service_name.to_vec()),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+
+                debug!(
+                    target: DISCOVERY,
+                    "session {:#06x} established with {:02x?}",
+                    session_id,
+                    ac_mac
+                );
+                let offer = CachedOffer::new(pado.clone(), ac_name.clone(), now).ok();
+                self.state = State::Established(SessionInfo {
+                    session_id,
+                    local_mac: self.local_mac,
+                    ac_mac,
+                    ac_name: ac_name.clone(),
+                    service_name,
+                    established: self.clock.at(now),
+                });
+                self.offer = offer;
+                self.redial = false;
+                self.deadline = None;
+            }
+
+            (State::Requesting { .. }, PADO) => {
+                trace!(target: DISCOVERY, "ignoring PADO, PADR was already sent");
+                self.report.late_offers += 1;
+            }
+
+            (State::Established(session), PADS) => {
+                // the PADR was retransmitted and the AC answered both
+                if ethernet.src_address() == session.ac_mac
+                    && header.session_id() == session.session_id.get()
+                {
+                    trace!(target: DISCOVERY, "ignoring retransmitted PADS");
+                    self.report.duplicate_confirmations += 1;
+                } else {
+                    trace!(target: DISCOVERY, "ignoring PADS for another session");
+                }
+            }
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Start over with a PADI if the PADR for a cached offer failed.
+    fn fall_back(&mut self) -> bool {
+        if !self.redial {
+            return false;
+        }
+        debug!(target: DISCOVERY, "cached offer failed, falling back to a PADI");
+        self.redial = false;
+        self.state = State::Discovering;
+        self.attempt = 0;
+        self.deadline = None;
+        true
+    }
+
+    /// Fail the current stage, returns `None` if it is already finished.
+    pub(crate) fn give_up(&mut self) -> Option<ProtocolError> {
+        let error = match self.state {
+            State::Discovering => ProtocolError::NoOffer,
+            State::Requesting { .. } => ProtocolError::NoSessionConfirmation,
+            _ => return None,
+        };
+        self.state = State::Failed;
+        Some(error)
+    }
+
+    /// Run the discovery on `socket` and block until a session was established, all attempts
+    /// failed or `deadline` passed.
+    ///
+    /// Waiting for a PADO or PADS is cut short at the deadline, so the whole discovery including
+    /// retransmissions fits into it.  Once the deadline passed, the error of the current stage
+    /// (`ProtocolError::NoOffer` or `ProtocolError::NoSessionConfirmation`) is returned.
+    ///
+    /// ```no_run
+    /// use std::time::{Duration, Instant};
+    /// use pppoe::{Discovery, DiscoveryOptions, Socket};
+    ///
+    /// let socket = Socket::on_interface("eth0")?;
+    /// let mut discovery = Discovery::new(socket.mac_address(), DiscoveryOptions::default());
+    /// let session = discovery.run_blocking(&socket, Instant::now() + Duration::from_secs(5))?;
+    /// # Ok::<(), pppoe::error::Error>(())
+    /// ```
+    #[cfg(feature = "socket")]
+    pub fn run_blocking(
+        &mut self,
+        socket: &crate::Socket,
+        deadline: Instant,
+    ) -> Result<SessionInfo, Error> {
+        self.run(socket, Some(deadline))
+    }
+
+    #[cfg(feature = "socket")]
+    fn run(
+        &mut self,
+        socket: &crate::Socket,
+        deadline: Option<Instant>,
+    ) -> Result<SessionInfo, Error> {
+        let mut tx_buffer = vec![0u8; crate::consts::MAX_FRAME_LEN];
+        let mut rx_buffer = vec![0u8; crate::consts::MAX_FRAME_LEN];
+
+        loop {
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                if let Some(error) = self.give_up() {
+                    debug!(target: DISCOVERY, "deadline passed: {:?}", error);
+                    return Err(error.into());
+                }
+            }
+            if let Some(len) = self.poll_transmit(now, &mut tx_buffer[..])? {
+                socket.send(&tx_buffer[..len])?;
+            }
+
+            let wakeup = match (self.poll_timeout(), deadline) {
+                (Some(timeout), Some(deadline)) => Some(timeout.min(deadline)),
+                (timeout, deadline) => timeout.or(deadline),
+            };
+            let timeout = wakeup
+                .map(|wakeup| wakeup.saturating_duration_since(now))
+                .unwrap_or_default();
+
+            if wait_readable(socket, timeout)? {
+                let len = socket.recv(&mut rx_buffer[..])?;
+                self.handle_frame(Instant::now(), &rx_buffer[..len])?;
+            }
+
+            if let Some(session) = self.session() {
+                return Ok(session.clone());
+            }
+        }
+    }
+
+    fn record_offer(&mut self, now: Instant, pado: &Packet) {
+        let padi_sent = match self.padi_sent {
+            Some(padi_sent) => padi_sent,
+            None => return,
+        };
+
+        let ac_mac = pado.ethernet_header().src_address();
+        if self
+            .report
+            .offers
+            .iter()
+            .any(|offer| offer.ac_mac == ac_mac)
+        {
+            return;
+        }
+
+        self.report.offers.push(Offer {
+            ac_mac,
+            ac_name: ac_name(pado),
+            rtt: now.saturating_duration_since(padi_sent),
+        });
+    }
+
+    /// Check that the response carries our Host-Uniq, or none if we didn't send one.
+    fn host_uniq_matches(&self, packet: &Packet) -> bool {
+        let mut host_uniqs = packet.pppoe_header().tags().filter_map(|tag| match tag {
+            Tag::HostUniq(host_uniq) => Some(host_uniq),
+            _ => None,
+        });
+
+        match &self.options.host_uniq {
+            Some(expected) => host_uniqs.any(|host_uniq| host_uniq == &expected[..]),
+            None => host_uniqs.next().is_none(),
+        }
+    }
+
+    fn write_padi(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let mut packet = PacketBuilder::new_discovery_packet(buffer, self.local_mac, [0xff; 6])?;
+        let header = packet.pppoe_header();
+        header.add_tag(Tag::ServiceName(&self.options.service_name))?;
+        if let Some(host_uniq) = &self.options.host_uniq {
+            header.add_tag(Tag::HostUniq(host_uniq))?;
+        }
+        Ok(packet.len())
+    }
+
+    fn write_padr(&self, pado: &[u8], buffer: &mut [u8]) -> Result<usize, Error> {
+        let pado = Packet::with_buffer(pado)?;
+        if buffer.len() < 20 {
+            return Err(crate::error::ParseError::BufferTooSmall(buffer.len()).into());
+        }
+
+        let (eth_buf, pppoe_buf) = buffer.split_at_mut(14);
+        let mut ethernet = eth::HeaderBuilder::with_buffer(eth_buf)?;
+        ethernet.set_src_address(self.local_mac);
+        ethernet.set_dst_address(pado.ethernet_header().src_address());
+        ethernet.set_ether_type(PPPOE_DISCOVERY);
+
+        // the service name was already validated on reception of the PADO
+        let mut header =
+            HeaderBuilder::create_padr_from_pado(pppoe_buf, pado.pppoe_header(), None, None)?;
+        if let Some(host_uniq) = &self.options.host_uniq {
+            header.add_tag(Tag::HostUniq(host_uniq))?;
+        }
+        Ok(14 + header.len())
+    }
+}
+
+fn ac_name(packet: &Packet) -> Vec<u8> {
+    packet.pppoe_header().ac_name().unwrap_or_default().to_vec()
+}
+
+#[cfg(feature = "socket")]
+pub(crate) fn wait_readable(socket: &crate::Socket, timeout: Duration) -> std::io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut pollfd = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // round up, waking up early would only spin until the timeout is due
+    let timeout = timeout.as_micros().div_ceil(1000);
+    let timeout = timeout.min(libc::c_int::MAX as u128) as libc::c_int;
+
+    let ret = unsafe { libc::poll(&mut pollfd, 1, timeout) };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(ret > 0)
+}
+
+/// Run the complete discovery stage on an interface and block until a session was established.
+///
+/// This sends a PADI, selects the first Access Concentrator matching the `options`, and sends a
+/// PADR to it.  The kernel session is not connected, use `Socket::connect_session` with the
+/// returned session information for that.
+#[cfg(feature = "socket")]
+pub fn discover(interface: &str, options: DiscoveryOptions) -> Result<SessionInfo, Error> {
+    let socket = crate::Socket::on_interface(interface)?;
+    Discovery::new(socket.mac_address(), options).run(&socket, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{ByteOrder, NetworkEndian as NE};
+
+    const CLIENT: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const AC: [u8; 6] = [0x02, 0, 0, 0, 0, 2];
+
+    fn options() -> DiscoveryOptions {
+        DiscoveryOptions {
+            host_uniq: Some(b"hu".to_vec()),
+            ..Default::default()
+        }
+    }
+
+    fn reply(buffer: &mut [u8], code: crate::Code, session_id: u16, tags: &[Tag]) -> usize {
+        let mut packet = PacketBuilder::new_discovery_packet(buffer, AC, CLIENT).unwrap();
+        packet.set_code(code);
+        NE::write_u16(&mut packet.pppoe_header().get_ref_mut()[2..], session_id);
+        for tag in tags {
+            packet.pppoe_header().add_tag(*tag).unwrap();
+        }
+        packet.len()
+    }
+
+    #[test]
+    fn full_handshake() {
+        let now = Instant::now();
+        let clock = Timestamp::now();
+        let mut discovery = Discovery::new(CLIENT, options()).with_clock(clock);
+        let mut buffer = [0u8; 1500];
+
+        let len = discovery.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padi = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padi.pppoe_header().code(), crate::header::PADI);
+        assert_eq!(discovery.poll_transmit(now, &mut buffer).unwrap(), None);
+
+        let tags = [
+            Tag::ServiceName(b""),
+            Tag::AcName(b"ac"),
+            Tag::AcCookie(b"cookie"),
+            Tag::HostUniq(b"hu"),
+        ];
+        let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+
+        let len = discovery.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padr = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padr.pppoe_header().code(), crate::header::PADR);
+        assert_eq!(padr.ethernet_header().dst_address(), AC);
+        assert!(padr
+            .pppoe_header()
+            .tags()
+            .any(|tag| tag == Tag::AcCookie(b"cookie")));
+
+        let len = reply(
+            &mut buffer,
+            crate::Code::Pads,
+            7,
+            &[Tag::ServiceName(b""), Tag::HostUniq(b"hu")],
+        );
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+
+        let session = discovery.session().unwrap();
+        assert_eq!(session.session_id.get(), 7);
+        assert_eq!(session.ac_mac, AC);
+        assert_eq!(session.ac_name, b"ac");
+        assert_eq!(session.established, clock.at(now));
+    }
+
+    #[test]
+    fn match_offered_service_name() {
+        let now = Instant::now();
+        let mut discovery = Discovery::new(
+            CLIENT,
+            DiscoveryOptions {
+                service_name: b"internet".to_vec(),
+                ..options()
+            },
+        );
+        let mut buffer = [0u8; 1500];
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+
+        for service_name in [&b"INTERNET"[..], b"internet\0"] {
+            let tags = [
+                Tag::ServiceName(service_name),
+                Tag::AcName(b"ac"),
+                Tag::HostUniq(b"hu"),
+            ];
+            let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+            discovery.handle_frame(now, &buffer[..len]).unwrap();
+        }
+
+        let len = discovery.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padr = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert!(padr
+            .pppoe_header()
+            .tags()
+            .any(|tag| tag == Tag::ServiceName(b"internet\0")));
+    }
+
+    #[test]
+    fn jumbo_pado() {
+        let now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT, options());
+        let mut buffer = vec![0u8; 4000];
+        discovery.poll_transmit(now, &mut buffer).unwrap().unwrap();
+
+        let cookie = vec![0x5a; 3900];
+        let tags = [
+            Tag::ServiceName(b""),
+            Tag::AcName(b"ac"),
+            Tag::AcCookie(&cookie),
+            Tag::HostUniq(b"hu"),
+        ];
+        let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+        assert!(len > 3900);
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+
+        let len = discovery.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padr = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padr.pppoe_header().code(), crate::header::PADR);
+        assert!(padr
+            .pppoe_header()
+            .tags()
+            .any(|tag| tag == Tag::AcCookie(&cookie)));
+    }
+
+    #[test]
+    fn measure_pado_rtt() {
+        let now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT, options());
+        let mut buffer = [0u8; 1500];
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+
+        let tags = [
+            Tag::ServiceName(b""),
+            Tag::AcName(b"ac1"),
+            Tag::HostUniq(b"hu"),
+        ];
+        let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+        discovery
+            .handle_frame(now + Duration::from_millis(30), &buffer[..len])
+            .unwrap();
+
+        let tags = [
+            Tag::ServiceName(b""),
+            Tag::AcName(b"ac2"),
+            Tag::HostUniq(b"hu"),
+        ];
+        let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+        // another Access Concentrator
+        buffer[11] = 3;
+        discovery
+            .handle_frame(now + Duration::from_millis(40), &buffer[..len])
+            .unwrap();
+
+        let report = discovery.report();
+        assert_eq!(report.padi_count, 1);
+        assert_eq!(report.offers.len(), 2);
+        assert_eq!(report.offers[0].rtt, Duration::from_millis(30));
+        assert_eq!(report.fastest_offer().unwrap().ac_name, b"ac1");
+        assert_eq!(report.offers[1].ac_mac, [0x02, 0, 0, 0, 0, 3]);
+        assert_eq!(report.offers[1].ac_name, b"ac2");
+    }
+
+    #[test]
+    fn ignore_pado_with_foreign_host_uniq() {
+        let now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT, options());
+        let mut buffer = [0u8; 1500];
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+
+        let tags = [
+            Tag::ServiceName(b""),
+            Tag::AcName(b"ac"),
+            Tag::HostUniq(b"xx"),
+        ];
+        let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+
+        let len = discovery
+            .poll_transmit(now + Duration::from_secs(3), &mut buffer)
+            .unwrap()
+            .unwrap();
+        let padi = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padi.pppoe_header().code(), crate::header::PADI);
+    }
+
+    #[test]
+    fn late_and_duplicate_responses() {
+        let now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT, options());
+        let mut buffer = [0u8; 1500];
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+
+        let pado = |buffer: &mut [u8], ac_name: &[u8]| {
+            let tags = [
+                Tag::ServiceName(b""),
+                Tag::AcName(ac_name),
+                Tag::HostUniq(b"hu"),
+            ];
+            reply(buffer, crate::Code::Pado, 0, &tags)
+        };
+        let len = pado(&mut buffer, b"ac1");
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+        let len = discovery.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padr = buffer[..len].to_vec();
+
+        // a second AC answers after the PADR was sent
+        let len = pado(&mut buffer, b"ac2");
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+        assert_eq!(discovery.poll_transmit(now, &mut buffer).unwrap(), None);
+        assert_eq!(discovery.report().late_offers, 1);
+
+        // the PADR is retransmitted and the AC answers both with the same session id
+        let later = now + Duration::from_secs(3);
+        let len = discovery
+            .poll_transmit(later, &mut buffer)
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buffer[..len], &padr[..]);
+        let pads = [Tag::ServiceName(b""), Tag::HostUniq(b"hu")];
+        for _ in 0..2 {
+            let len = reply(&mut buffer, crate::Code::Pads, 7, &pads);
+            discovery.handle_frame(later, &buffer[..len]).unwrap();
+        }
+        let len = reply(&mut buffer, crate::Code::Pads, 8, &pads);
+        discovery.handle_frame(later, &buffer[..len]).unwrap();
+
+        assert_eq!(discovery.session().unwrap().session_id.get(), 7);
+        assert_eq!(discovery.report().duplicate_confirmations, 1);
+        assert_eq!(discovery.poll_transmit(later, &mut buffer).unwrap(), None);
+    }
+
+    #[test]
+    fn ignore_host_uniq_without_request() {
+        let now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT, DiscoveryOptions::default());
+        let mut buffer = [0u8; 1500];
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+
+        let tags = [
+            Tag::ServiceName(b""),
+            Tag::AcName(b"ac"),
+            Tag::HostUniq(b"other client"),
+        ];
+        let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+        assert_eq!(discovery.poll_transmit(now, &mut buffer).unwrap(), None);
+        assert_eq!(discovery.report().foreign_host_uniq, 1);
+    }
+
+    #[test]
+    fn fast_reconnect() {
+        let now = Instant::now();
+        let previous = SessionInfo {
+            session_id: NonZeroU16::new(7).unwrap(),
+            local_mac: CLIENT,
+            ac_mac: AC,
+            ac_name: b"ac".to_vec(),
+            service_name: b"internet".to_vec(),
+            established: Timestamp::now(),
+        };
+        let single_attempt = DiscoveryOptions {
+            reconnect_attempts: 1,
+            ..options()
+        };
+        let mut discovery = Discovery::reconnect(CLIENT, single_attempt, &previous);
+        let mut buffer = [0u8; 1500];
+        let len = discovery.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padi = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert!(padi
+            .pppoe_header()
+            .tags()
+            .any(|tag| tag == Tag::ServiceName(b"internet")));
+
+        let tags = [
+            Tag::ServiceName(b"internet"),
+            Tag::AcName(b"standby"),
+            Tag::HostUniq(b"hu"),
+        ];
+        let mut standby = [0u8; 1500];
+        let standby_len = reply(&mut standby, crate::Code::Pado, 0, &tags);
+        standby[6..12].copy_from_slice(&[0x02, 0, 0, 0, 0, 3]);
+        discovery
+            .handle_frame(now, &standby[..standby_len])
+            .unwrap();
+        assert_eq!(discovery.poll_transmit(now, &mut buffer).unwrap(), None);
+
+        // the previous AC didn't answer the first PADI, so any AC is accepted now
+        let later = now + Duration::from_secs(3);
+        let len = discovery
+            .poll_transmit(later, &mut buffer)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Packet::with_buffer(&buffer[..len])
+                .unwrap()
+                .pppoe_header()
+                .code(),
+            crate::header::PADI
+        );
+        discovery
+            .handle_frame(later, &standby[..standby_len])
+            .unwrap();
+        let len = discovery
+            .poll_transmit(later, &mut buffer)
+            .unwrap()
+            .unwrap();
+        let padr = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padr.ethernet_header().dst_address(), [0x02, 0, 0, 0, 0, 3]);
+
+        let mut discovery = Discovery::reconnect(CLIENT, options(), &previous);
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+        discovery
+            .handle_frame(now, &standby[..standby_len])
+            .unwrap();
+        let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+        let len = discovery.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padr = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padr.ethernet_header().dst_address(), AC);
+    }
+
+    #[test]
+    fn give_up_after_all_attempts() {
+        let mut now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT, options());
+        let mut buffer = [0u8; 1500];
+
+        for _ in 0..3 {
+            assert!(discovery.poll_transmit(now, &mut buffer).unwrap().is_some());
+            now = discovery.poll_timeout().unwrap();
+        }
+
+        match discovery.poll_transmit(now, &mut buffer) {
+            Err(Error::Protocol(ProtocolError::NoOffer)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_invalid_session_ids() {
+        let in_use = NonZeroU16::new(7).unwrap();
+        for &session_id in &[0, 0xffff, in_use.get()] {
+            let now = Instant::now();
+            let mut discovery = Discovery::new(
+                CLIENT,
+                DiscoveryOptions {
+                    sessions_in_use: vec![(AC, in_use)],
+                    ..options()
+                },
+            );
+            let mut buffer = [0u8; 1500];
+            discovery.poll_transmit(now, &mut buffer).unwrap();
+
+            let tags = [
+                Tag::ServiceName(b""),
+                Tag::AcName(b"ac"),
+                Tag::HostUniq(b"hu"),
+            ];
+            let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+            discovery.handle_frame(now, &buffer[..len]).unwrap();
+            discovery.poll_transmit(now, &mut buffer).unwrap();
+
+            let tags = [Tag::ServiceName(b""), Tag::HostUniq(b"hu")];
+            let len = reply(&mut buffer, crate::Code::Pads, session_id, &tags);
+            match discovery.handle_frame(now, &buffer[..len]) {
+                Err(Error::Protocol(ProtocolError::InvalidSessionAssignment(id))) => {
+                    assert_eq!(id, session_id)
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+            assert!(discovery.session().is_none());
+        }
+    }
+
+    #[test]
+    fn redial_cached_offer() {
+        let now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT, options());
+        let mut buffer = [0u8; 1500];
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+        let tags = [
+            Tag::ServiceName(b""),
+            Tag::AcName(b"ac"),
+            Tag::AcCookie(b"cookie"),
+            Tag::HostUniq(b"hu"),
+        ];
+        let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+        let len = reply(&mut buffer, crate::Code::Pads, 1, &tags);
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+
+        let mut cache = OfferCache::new(Duration::from_secs(60));
+        cache.insert("eth0", discovery.offer().unwrap().clone());
+        let offer = cache.get("eth0", now).unwrap();
+        assert_eq!(offer.ac_mac, AC);
+        assert!(offer.has_cookie);
+        assert!(offer.offers(b""));
+        assert!(cache.get("eth1", now).is_none());
+
+        // the PADR is sent right away
+        let mut redial = Discovery::redial_cached(CLIENT, options(), &cache, "eth0", now);
+        let len = redial.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padr = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padr.pppoe_header().code(), crate::header::PADR);
+        assert_eq!(padr.pppoe_header().ac_cookie(), Some(&b"cookie"[..]));
+
+        // a rejection falls back to a PADI
+        let rejection = [Tag::HostUniq(b"hu"), Tag::GenericError(b"stale cookie")];
+        let len = reply(&mut buffer, crate::Code::Pads, 0, &rejection);
+        redial.handle_frame(now, &buffer[..len]).unwrap();
+        let len = redial.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padi = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padi.pppoe_header().code(), crate::header::PADI);
+
+        // stale offers are not used
+        let later = now + Duration::from_secs(60);
+        let mut discovery = Discovery::redial_cached(CLIENT, options(), &cache, "eth0", later);
+        let len = discovery
+            .poll_transmit(later, &mut buffer)
+            .unwrap()
+            .unwrap();
+        let padi = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padi.pppoe_header().code(), crate::header::PADI);
+        cache.expire(later);
+        assert!(cache.remove("eth0").is_none());
+    }
+
+    #[test]
+    fn pads_with_service_name_error() {
+        let now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT, options());
+        let mut buffer = [0u8; 1500];
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+
+        let tags = [
+            Tag::ServiceName(b""),
+            Tag::AcName(b"ac"),
+            Tag::HostUniq(b"hu"),
+        ];
+        let len = reply(&mut buffer, crate::Code::Pado, 0, &tags);
+        discovery.handle_frame(now, &buffer[..len]).unwrap();
+        discovery.poll_transmit(now, &mut buffer).unwrap();
+
+        let tags = [
+            Tag::ServiceName(b""),
+            Tag::HostUniq(b"hu"),
+            Tag::ServiceNameError(b"nope"),
+        ];
+        let len = reply(&mut buffer, crate::Code::Pads, 0, &tags);
+        match discovery.handle_frame(now, &buffer[..len]) {
+            Err(Error::Protocol(ProtocolError::ServiceNameError(msg))) => assert_eq!(msg, b"nope"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! A receive loop which hands discovery and session frames to registered handlers
+//!
+//! ```no_run
+//! use pppoe::dispatch::{Dispatcher, Flow};
+//!
+//! let socket = pppoe::Socket::on_interface("eth0")?;
+//! Dispatcher::new(socket)
+//!     .on_discovery(|_socket, packet| {
+//!         println!("{:?}", packet.pppoe_header().code());
+//!         Ok(Flow::Continue)
+//!     })
+//!     .on_session(|_socket, frame| {
+//!         println!("session frame of {} bytes", frame.len());
+//!         Ok(Flow::Continue)
+//!     })
+//!     .run()?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use crate::consts::{ETHERNET_HEADER_LEN, MAX_FRAME_LEN};
+use crate::error::Error;
+use crate::logging::SOCKET;
+use crate::tap::{Tap, TapPoint, Taps};
+use crate::{Packet, Socket};
+
+/// Whether `Dispatcher::run` should continue after a handler returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Stop,
+}
+
+type DiscoveryHandler<'h> = Box<dyn FnMut(&Socket, &Packet) -> io::Result<Flow> + 'h>;
+type SessionHandler<'h> = Box<dyn FnMut(&Socket, &[u8]) -> io::Result<Flow> + 'h>;
+type InvalidHandler<'h> = Box<dyn FnMut(&[u8], &Error) + 'h>;
+
+/// Receives frames from a `Socket` and calls the handler registered for their ethertype
+///
+/// Discovery frames are parsed before they are handed on, frames which fail to parse go to the
+/// `on_invalid` handler.  Session frames are received on the separate session socket (see
+/// `Socket::open_session_socket`) and handed on as they are.  The handlers get the socket to
+/// send replies.
+pub struct Dispatcher<'h> {
+    socket: Socket,
+    discovery: Option<DiscoveryHandler<'h>>,
+    session: Option<SessionHandler<'h>>,
+    invalid: Option<InvalidHandler<'h>>,
+    taps: Taps<'h>,
+    buffer: Vec<u8>,
+}
+
+impl<'h> Dispatcher<'h> {
+    pub fn new(socket: Socket) -> Self {
+        Self {
+            socket,
+            discovery: None,
+            session: None,
+            invalid: None,
+            taps: Taps::new(),
+            buffer: vec![0u8; MAX_FRAME_LEN],
+        }
+    }
+
+    pub fn on_discovery<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&Socket, &Packet) -> io::Result<Flow> + 'h,
+    {
+        self.discovery = Some(Box::new(handler));
+        self
+    }
+
+    /// Handle session frames, which opens the session socket when `run` starts.
+    pub fn on_session<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&Socket, &[u8]) -> io::Result<Flow> + 'h,
+    {
+        self.session = Some(Box::new(handler));
+        self
+    }
+
+    /// Handle discovery frames which could not be parsed, they are dropped otherwise.
+    pub fn on_invalid<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&[u8], &Error) + 'h,
+    {
+        self.invalid = Some(Box::new(handler));
+        self
+    }
+
+    /// Mirror received session frames to `tap` before they are handed to the session handler.
+    pub fn with_tap<T: Tap + 'h>(mut self, tap: T) -> Self {
+        self.taps.register(tap);
+        self
+    }
+
+    /// Get the taps, e.g. to register a tap for a single session once it is established.
+    pub fn taps_mut(&mut self) -> &mut Taps<'h> {
+        &mut self.taps
+    }
+
+    pub fn socket(&self) -> &Socket {
+        &self.socket
+    }
+
+    pub fn into_socket(self) -> Socket {
+        self.socket
+    }
+
+    /// Receive and dispatch frames until a handler returns `Flow::Stop` or an error.
+    pub fn run(&mut self) -> io::Result<()> {
+        if self.session.is_some() {
+            self.socket.open_session_socket()?;
+        }
+
+        loop {
+            let (discovery_ready, session_ready) = self.wait()?;
+            if discovery_ready && self.dispatch_discovery()? == Flow::Stop {
+                return Ok(());
+            }
+            if session_ready && self.dispatch_session()? == Flow::Stop {
+                return Ok(());
+            }
+        }
+    }
+
+    fn wait(&self) -> io::Result<(bool, bool)> {
+        let session_fd = match (&self.session, self.socket.session_socket()) {
+            (Some(_), Some(fd)) => fd,
+            _ => -1,
+        };
+        // a negative fd is ignored by poll(2)
+        let mut pollfds = [pollfd(self.socket.as_raw_fd()), pollfd(session_fd)];
+
+        let ret = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, -1) };
+        if ret < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                return Ok((false, false));
+            }
+            return Err(error);
+        }
+        let ready = |pollfd: &libc::pollfd| pollfd.revents & libc::POLLIN != 0;
+        Ok((ready(&pollfds[0]), ready(&pollfds[1])))
+    }
+
+    fn dispatch_discovery(&mut self) -> io::Result<Flow> {
+        let len = self.socket.recv(&mut self.buffer)?;
+        let frame = &self.buffer[..len];
+
+        match Packet::with_buffer(frame) {
+            Ok(packet) => match &mut self.discovery {
+                Some(handler) => handler(&self.socket, &packet),
+                None => Ok(Flow::Continue),
+            },
+            Err(error) => {
+                trace!(target: SOCKET, "dropping invalid discovery frame: {:?}", error);
+                if let Some(handler) = &mut self.invalid {
+                    handler(frame, &error);
+                }
+                Ok(Flow::Continue)
+            }
+        }
+    }
+
+    fn dispatch_session(&mut self) -> io::Result<Flow> {
+        let len = self.socket.recv_session(&mut self.buffer)?;
+        if len < ETHERNET_HEADER_LEN {
+            return Ok(Flow::Continue);
+        }
+        self.taps.mirror(TapPoint::Received, &self.buffer[..len]);
+        match &mut self.session {
+            Some(handler) => handler(&self.socket, &self.buffer[..len]),
+            None => Ok(Flow::Continue),
+        }
+    }
+}
+
+fn pollfd(fd: RawFd) -> libc::pollfd {
+    libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{PADI, PADR};
+    use crate::{Code, PacketBuilder};
+    use pppoe_sys::pppoe;
+
+    use std::fs;
+    use std::num::NonZeroU16;
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+    use std::os::unix::net::UnixDatagram;
+
+    const CLIENT: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const AC: [u8; 6] = [0x02, 0, 0, 0, 0, 2];
+
+    /// A socket whose raw and session sockets are datagram socket pairs, fed by the returned
+    /// peers.
+    fn socket_pair() -> (Socket, UnixDatagram, UnixDatagram) {
+        let (raw, raw_peer) = UnixDatagram::pair().unwrap();
+        let (session, session_peer) = UnixDatagram::pair().unwrap();
+        let pppoe_socket = fs::File::open("/dev/null").unwrap().into_raw_fd();
+        let connection =
+            pppoe::Connection::from_raw_parts("lo", AC, raw.into_raw_fd(), pppoe_socket).unwrap();
+        let session = unsafe { fs::File::from_raw_fd(session.into_raw_fd()) };
+        (
+            Socket::from_parts(connection, Some(session), None),
+            raw_peer,
+            session_peer,
+        )
+    }
+
+    fn discovery_frame(code: Code) -> Vec<u8> {
+        let mut buffer = [0u8; 64];
+        let mut packet = PacketBuilder::new_discovery_packet(&mut buffer, CLIENT, AC).unwrap();
+        packet.set_code(code);
+        packet
+            .pppoe_header()
+            .add_tag(crate::Tag::ServiceName(b""))
+            .unwrap();
+        packet.as_bytes().to_vec()
+    }
+
+    fn session_frame(session_id: u16) -> Vec<u8> {
+        let mut buffer = [0u8; 64];
+        let session_id = NonZeroU16::new(session_id).unwrap();
+        let mut packet =
+            PacketBuilder::new_session_packet(&mut buffer, CLIENT, AC, session_id).unwrap();
+        packet.payload_mut()[..2].copy_from_slice(&[0xc0, 0x21]);
+        packet.finish(2).unwrap().to_vec()
+    }
+
+    fn session_id(frame: &[u8]) -> u16 {
+        u16::from_be_bytes([frame[16], frame[17]])
+    }
+
+    #[test]
+    fn route_by_ethertype_and_code() {
+        let (socket, raw_peer, session_peer) = socket_pair();
+        raw_peer.send(&discovery_frame(Code::Padi)).unwrap();
+        raw_peer.send(&[0u8; 20]).unwrap();
+        raw_peer.send(&discovery_frame(Code::Padr)).unwrap();
+        session_peer.send(&session_frame(0x2a)).unwrap();
+        session_peer.send(&session_frame(0x2b)).unwrap();
+
+        let mut codes = Vec::new();
+        let mut sessions = Vec::new();
+        let mut invalid = 0;
+        Dispatcher::new(socket)
+            .on_discovery(|_socket, packet| {
+                let code = packet.pppoe_header().code();
+                codes.push(code);
+                Ok(if code == PADR {
+                    Flow::Stop
+                } else {
+                    Flow::Continue
+                })
+            })
+            .on_session(|_socket, frame| {
+                sessions.push(session_id(frame));
+                Ok(Flow::Continue)
+            })
+            .on_invalid(|_frame, _error| invalid += 1)
+            .run()
+            .unwrap();
+
+        assert_eq!(codes, [PADI, PADR]);
+        assert_eq!(invalid, 1);
+        // discovery and session frames are handled alternately
+        assert_eq!(sessions, [0x2a, 0x2b]);
+    }
+
+    #[test]
+    fn unknown_session_frames() {
+        let (socket, _raw_peer, session_peer) = socket_pair();
+        // too short for an Ethernet header, dropped before the session handler
+        session_peer.send(&[0u8; 10]).unwrap();
+        session_peer.send(&session_frame(0x2a)).unwrap();
+
+        let mut sessions = Vec::new();
+        Dispatcher::new(socket)
+            .on_session(|_socket, frame| {
+                sessions.push(session_id(frame));
+                Ok(Flow::Stop)
+            })
+            .run()
+            .unwrap();
+        assert_eq!(sessions, [0x2a]);
+
+        // without a session handler the session socket isn't read at all
+        let (socket, raw_peer, session_peer) = socket_pair();
+        session_peer.send(&session_frame(0x2b)).unwrap();
+        raw_peer.send(&discovery_frame(Code::Padi)).unwrap();
+        let mut dispatcher =
+            Dispatcher::new(socket).on_discovery(|_socket, _packet| Ok(Flow::Stop));
+        dispatcher.run().unwrap();
+        let mut buffer = [0u8; 64];
+        let len = dispatcher.socket().recv_session(&mut buffer).unwrap();
+        assert_eq!(session_id(&buffer[..len]), 0x2b);
+    }
+}
@@ -1,73 +1,48 @@
 use std::io;
+use std::time::Duration;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
-pub enum ParseError {
-    BufferTooSmall(usize),
-
-    BufferTooSmallForTag {
-        available: u16,
-        requested: usize,
-    },
-
-    InvalidPppoeVersion(u8),
-    InvalidPppoeType(u8),
-    InvalidPppoeCode(u8),
-
-    UnexpectedCode(u8),
-
-    PayloadLengthOutOfBound {
-        actual_packet_length: u16,
-        payload_length: u16,
-    },
-    TagLengthOutOfBound {
-        expected_tag_length: u16,
-        remaining_payload_length: u16,
-    },
-
-    IncompleteTagAtPacketEnd {
-        total_packet_length: u16,
-        left_over_bytes: u16,
-    },
-
-    DataBehindEolTag,
-    IncompleteTag(u8),
-    TagWithInvalidLength {
-        tag_type: u16,
-        length: u16,
-    },
+pub use pppoe_core::error::ParseError;
 
-    #[cfg(feature = "tr101")]
-    InvalidTr101TagLength {
-        tag_type: u8,
-        expected_min_length: u16,
-        expected_max_length: u16,
-        actual_length: u16,
-    },
-    #[cfg(feature = "tr101")]
-    Tr101LengthOutOfBound {
-        remaining_packet_length: u16,
-        requested_tag_length: u16,
-    },
-    #[cfg(feature = "tr101")]
-    InvalidTr101Id(u32),
-    #[cfg(feature = "tr101")]
-    TagIsNotVendorSpecific,
-    #[cfg(feature = "tr101")]
-    InvalidTr101VendorId(u32),
+/// Errors of the discovery stage which are not caused by a single invalid packet.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ProtocolError {
+    /// No acceptable PADO was received before all PADI retransmissions timed out.
+    NoOffer,
+    /// No PADS was received before all PADR retransmissions timed out.
+    NoSessionConfirmation,
 
-    DuplicateTag(u16),
+    /// The Access Concentrator rejected the session with a Service-Name-Error tag.
+    ServiceNameError(Vec<u8>),
+    /// The Access Concentrator rejected the session with an AC-System-Error tag.
+    AcSystemError(Vec<u8>),
+    /// The Access Concentrator rejected the session with a Generic-Error tag.
+    GenericError(Vec<u8>),
 
-    MissingServiceName,
-    MissingAcName,
+    /// The PADS carried a session id which is reserved or already used by another local session
+    /// with the same Access Concentrator.
+    InvalidSessionAssignment(u16),
+}
 
-    ServiceNameMismatch,
-    AcNameMismatch,
+/// Reasons an AC-Cookie of a PADR is not accepted, see `cookie::CookieJar::verify`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum CookieError {
+    /// The cookie has the wrong length or version.
+    Malformed,
+    /// The cookie was issued for another client or Service-Name, or with another key.
+    InvalidTag,
+    /// The cookie is older than the acceptance window, e.g. because the PADR is replayed.
+    Expired { age: Duration },
+    /// The cookie was issued further in the future than the clock skew tolerance allows.
+    FromFuture { ahead: Duration },
 }
 
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
     ParseError(ParseError),
+    Protocol(ProtocolError),
+    #[cfg(feature = "socket")]
+    MissingCapability(crate::socket::Capability),
     TODO,
 }
 
@@ -77,6 +52,12 @@ impl From<ParseError> for Error {
     }
 }
 
+impl From<ProtocolError> for Error {
+    fn from(error: ProtocolError) -> Self {
+        Error::Protocol(error)
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(error: io::Error) -> Self {
         Error::Io(error)
@@ -3,6 +3,7 @@ use byteorder::{ByteOrder, NetworkEndian as NE};
 use std::convert::TryInto;
 
 use crate::error::ParseError;
+use crate::summary::ETHERTYPE_VLAN;
 
 /// A valid Ethernet Header
 #[derive(Debug)]
@@ -18,6 +19,11 @@ impl<'a> Header<'a> {
         Ok(Self(buffer))
     }
 
+    /// Wrap the first 14 bytes of a frame which was already validated.
+    pub(crate) fn new_unchecked(buffer: &'a [u8]) -> Self {
+        Self(&buffer[..14])
+    }
+
     /// get the source mac address
     pub fn src_address(&self) -> [u8; 6] {
         (&self.0[6..12]).try_into().unwrap()
@@ -35,7 +41,7 @@ impl<'a> Header<'a> {
 
     /// get the full ethernet header as bytes
     pub fn as_bytes(&self) -> &[u8] {
-        &self.0
+        self.0
     }
 }
 
@@ -68,14 +74,19 @@ impl<'a> HeaderBuilder<'a> {
 
     /// get the full ethernet header as bytes
     pub fn as_bytes(&self) -> &[u8] {
-        &self.0
+        self.0
     }
 
     /// get the full ethernet header as mutable bytes
     ///
     /// This should be rarely useful.
     pub fn as_mut_bytes(&mut self) -> &mut [u8] {
-        &mut self.0
+        self.0
+    }
+
+    /// Give the buffer back, e.g. to join it with the following PPPoE header again.
+    pub(crate) fn into_buffer(self) -> &'a mut [u8] {
+        self.0
     }
 
     /// Create a valid Ethernet Header
@@ -83,3 +94,82 @@ impl<'a> HeaderBuilder<'a> {
         Header::with_buffer(self.0)
     }
 }
+
+/// An 802.1Q tag, e.g. one stripped from a received frame by VLAN offload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VlanTag {
+    /// Usually `ETHERTYPE_VLAN`, 0x88a8 for the outer tag of QinQ
+    pub tpid: u16,
+    /// Priority, drop eligible indicator and VLAN id
+    pub tci: u16,
+}
+
+impl VlanTag {
+    pub fn id(&self) -> u16 {
+        self.tci & 0x0fff
+    }
+}
+
+/// Insert an 802.1Q tag behind the addresses of a frame of `len` bytes in place and return the
+/// new length, e.g. to restore a tag the kernel stripped on receive.
+pub fn insert_vlan_tag(buffer: &mut [u8], len: usize, tag: VlanTag) -> Result<usize, ParseError> {
+    if len < 14 {
+        return Err(ParseError::BufferTooSmall(len));
+    }
+    if len + 4 > buffer.len() {
+        return Err(ParseError::BufferTooSmall(buffer.len()));
+    }
+    buffer.copy_within(12..len, 16);
+    NE::write_u16(&mut buffer[12..], tag.tpid);
+    NE::write_u16(&mut buffer[14..], tag.tci);
+    Ok(len + 4)
+}
+
+/// Remove an 802.1Q tag from a received frame of `len` bytes in place and return the new
+/// length.  Frames without a tag are left as they are.
+///
+/// `len` may be larger than `buffer` if the frame was truncated, e.g. with `MSG_TRUNC`.
+pub fn strip_vlan_tag(buffer: &mut [u8], len: usize) -> usize {
+    let available = len.min(buffer.len());
+    if available < 18 || NE::read_u16(&buffer[12..]) != ETHERTYPE_VLAN {
+        return len;
+    }
+    buffer.copy_within(16..available, 12);
+    len - 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_tag() {
+        let mut frame = [0u8; 24];
+        NE::write_u16(&mut frame[12..], ETHERTYPE_VLAN);
+        NE::write_u16(&mut frame[14..], 835);
+        NE::write_u16(&mut frame[16..], 0x8863);
+        frame[18] = 0x11;
+        assert_eq!(strip_vlan_tag(&mut frame, 24), 20);
+        assert_eq!(&frame[12..15], [0x88, 0x63, 0x11]);
+        // untagged now
+        assert_eq!(strip_vlan_tag(&mut frame, 20), 20);
+
+        let tag = VlanTag {
+            tpid: ETHERTYPE_VLAN,
+            tci: 0x2000 | 835,
+        };
+        assert_eq!(tag.id(), 835);
+        assert_eq!(insert_vlan_tag(&mut frame, 20, tag), Ok(24));
+        assert_eq!(&frame[12..19], [0x81, 0x00, 0x23, 0x43, 0x88, 0x63, 0x11]);
+        assert!(insert_vlan_tag(&mut frame, 21, tag).is_err());
+
+        let mut truncated = [0u8; 20];
+        NE::write_u16(&mut truncated[12..], ETHERTYPE_VLAN);
+        NE::write_u16(&mut truncated[16..], 0x8863);
+        assert_eq!(strip_vlan_tag(&mut truncated, 60), 56);
+        assert_eq!(
+            Header::with_buffer(&truncated).unwrap().ether_type(),
+            0x8863
+        );
+    }
+}
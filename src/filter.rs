@@ -0,0 +1,189 @@
+//! Classic BPF socket filters
+//!
+//! Filters run in the kernel before a frame is queued on the socket, so frames dropped by a
+//! filter never have to be copied to userspace.  This makes discovery floods (e.g. of PADIs on
+//! a client, which never has to process them) considerably cheaper.
+
+use std::io;
+
+use crate::header::Code;
+use crate::uapi;
+
+// frame offsets of the PPPoE version/type and code fields
+const VERSION_TYPE_OFFSET: u32 = 14;
+const CODE_OFFSET: u32 = 15;
+// fanout programs see the frame from the network header on, so this is relative to the PPPoE
+// header
+const FANOUT_SESSION_ID_OFFSET: u32 = 2;
+// length of an 802.1Q tag in front of the ethertype
+const VLAN_TAG_LEN: u32 = 4;
+
+/// Maximal number of codes of a discovery filter, the jump over all of them has to fit into the
+/// 8 bit jump offset
+pub const MAX_FILTER_CODES: usize = 254;
+
+const ACCEPT: u32 = 0xffff_ffff;
+const DROP: u32 = 0;
+
+fn statement(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Create a filter program only accepting PPPoE discovery frames with a valid version and type
+/// and one of the given codes.
+///
+/// Fails with `InvalidInput` if more than `MAX_FILTER_CODES` codes are given.
+pub fn discovery_filter(codes: &[Code]) -> io::Result<Vec<libc::sock_filter>> {
+    discovery_filter_at(codes, 0)
+}
+
+/// Like `discovery_filter`, for frames carrying a single 802.1Q tag, e.g. those received on a
+/// VLAN sub-interface with `reorder_hdr off`.
+pub fn tagged_discovery_filter(codes: &[Code]) -> io::Result<Vec<libc::sock_filter>> {
+    discovery_filter_at(codes, VLAN_TAG_LEN)
+}
+
+fn discovery_filter_at(codes: &[Code], offset: u32) -> io::Result<Vec<libc::sock_filter>> {
+    if codes.len() > MAX_FILTER_CODES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "too many codes for a BPF jump offset",
+        ));
+    }
+    let n = codes.len() as u8;
+
+    let mut program = Vec::with_capacity(codes.len() + 5);
+    program.push(statement(
+        uapi::BPF_LD | uapi::BPF_B | uapi::BPF_ABS,
+        VERSION_TYPE_OFFSET + offset,
+    ));
+    program.push(jump(
+        uapi::BPF_JMP | uapi::BPF_JEQ | uapi::BPF_K,
+        0x11,
+        0,
+        n + 1,
+    ));
+    program.push(statement(
+        uapi::BPF_LD | uapi::BPF_B | uapi::BPF_ABS,
+        CODE_OFFSET + offset,
+    ));
+    for (i, &code) in codes.iter().enumerate() {
+        program.push(jump(
+            uapi::BPF_JMP | uapi::BPF_JEQ | uapi::BPF_K,
+            u32::from(code as u8),
+            n - i as u8,
+            0,
+        ));
+    }
+    program.push(statement(uapi::BPF_RET | uapi::BPF_K, DROP));
+    program.push(statement(uapi::BPF_RET | uapi::BPF_K, ACCEPT));
+
+    Ok(program)
+}
+
+/// Create a `PACKET_FANOUT_CBPF` program which distributes frames by their PPPoE session id.
+///
+/// The kernel takes the returned value modulo the number of sockets in the fanout group, so all
+/// frames of a session end up on the same socket.  Discovery frames without a session id (PADI,
+/// PADO, PADR) all go to the first socket.
+///
+/// Unlike socket filters, fanout programs see the frame from the network header on, so the
+/// session id is loaded relative to the PPPoE header.
+pub fn session_fanout_program() -> Vec<libc::sock_filter> {
+    vec![
+        statement(
+            uapi::BPF_LD | uapi::BPF_H | uapi::BPF_ABS,
+            FANOUT_SESSION_ID_OFFSET,
+        ),
+        statement(uapi::BPF_RET | uapi::BPF_A, 0),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // minimal interpreter for the instructions used above
+    fn run(program: &[libc::sock_filter], frame: &[u8]) -> u32 {
+        let mut a = 0u32;
+        let mut pc = 0;
+        loop {
+            let insn = &program[pc];
+            let code = u32::from(insn.code);
+            pc += 1;
+            if code == uapi::BPF_LD | uapi::BPF_B | uapi::BPF_ABS {
+                a = u32::from(frame[insn.k as usize]);
+            } else if code == uapi::BPF_LD | uapi::BPF_H | uapi::BPF_ABS {
+                let k = insn.k as usize;
+                a = u32::from(frame[k]) << 8 | u32::from(frame[k + 1]);
+            } else if code == uapi::BPF_RET | uapi::BPF_A {
+                return a;
+            } else if code == uapi::BPF_JMP | uapi::BPF_JEQ | uapi::BPF_K {
+                pc += usize::from(if a == insn.k { insn.jt } else { insn.jf });
+            } else if code == uapi::BPF_RET | uapi::BPF_K {
+                return insn.k;
+            } else {
+                unreachable!();
+            }
+        }
+    }
+
+    fn frame(version_type: u8, code: u8) -> [u8; 20] {
+        let mut frame = [0u8; 20];
+        frame[14] = version_type;
+        frame[15] = code;
+        frame
+    }
+
+    #[test]
+    fn only_accept_given_codes() {
+        let program = discovery_filter(&[Code::Pado, Code::Pads, Code::Padt]).unwrap();
+
+        assert_eq!(run(&program, &frame(0x11, crate::header::PADO)), ACCEPT);
+        assert_eq!(run(&program, &frame(0x11, crate::header::PADS)), ACCEPT);
+        assert_eq!(run(&program, &frame(0x11, crate::header::PADT)), ACCEPT);
+        assert_eq!(run(&program, &frame(0x11, crate::header::PADI)), DROP);
+        assert_eq!(run(&program, &frame(0x11, 0x42)), DROP);
+        assert_eq!(run(&program, &frame(0x12, crate::header::PADO)), DROP);
+
+        let program = tagged_discovery_filter(&[Code::Pado]).unwrap();
+        let mut tagged = [0u8; 24];
+        tagged[18..20].copy_from_slice(&frame(0x11, crate::header::PADO)[14..16]);
+        assert_eq!(run(&program, &tagged), ACCEPT);
+        assert_eq!(run(&program, &frame(0x11, crate::header::PADO)), DROP);
+
+        // the code list may repeat codes, but not overflow the jump offsets
+        let codes = [Code::Pado; MAX_FILTER_CODES + 1];
+        let program = discovery_filter(&codes[..MAX_FILTER_CODES]).unwrap();
+        assert_eq!(run(&program, &frame(0x11, crate::header::PADI)), DROP);
+        assert_eq!(run(&program, &frame(0x12, crate::header::PADO)), DROP);
+        assert_eq!(
+            discovery_filter(&codes).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn fanout_by_session_id() {
+        let program = session_fanout_program();
+        let mut frame = frame(0x11, 0x00);
+        frame[16] = 0x12;
+        frame[17] = 0x34;
+        // the data of the skb starts at the network header
+        assert_eq!(run(&program, &frame[14..]), 0x1234);
+    }
+}
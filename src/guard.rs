@@ -0,0 +1,340 @@
+//! Rate limiting of PADIs, to survive discovery floods
+//!
+//! A flood of PADIs makes an Access Concentrator answer each of them with a PADO, which costs
+//! CPU and uplink bandwidth.  `DiscoveryGuard` limits the PADIs which are processed, globally
+//! and per client MAC address, with token buckets.
+//!
+//! Classic BPF programs have no state, so the rate limit runs in userspace.  The kernel part is
+//! the code filter of `Socket::attach_discovery_filter`, which drops everything a server doesn't
+//! process (e.g. PADOs and malformed frames) before it is copied to the socket.
+//!
+//! With the `xdp` feature, `xdp::XdpFilter` drops the PADIs of exhausted limits in the driver
+//! already.  The guard stays in charge of the limits, `XdpFilter::sync` copies which of them are
+//! exhausted into the maps of the XDP program.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use crate::header::PADI;
+use crate::packet::PPPOE_DISCOVERY;
+
+/// Default number of clients `DiscoveryGuard` tracks
+pub const DEFAULT_MAX_CLIENTS: usize = 4096;
+
+/// A token bucket limit: `burst` PADIs at once, refilled with `rate` PADIs per second
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub rate: u32,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(rate: u32, burst: u32) -> Self {
+        Self { rate, burst }
+    }
+}
+
+/// Tokens are counted in thousandths, so a rate of 1 refills one thousandth per millisecond.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: u64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: u64::from(limit.burst) * 1000,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_millis();
+        let elapsed = u64::try_from(elapsed).unwrap_or(u64::MAX);
+        let added = elapsed.saturating_mul(u64::from(limit.rate));
+        self.tokens = self
+            .tokens
+            .saturating_add(added)
+            .min(u64::from(limit.burst) * 1000);
+        // only whole milliseconds are used up, frequent calls must not lose the rest
+        self.updated += Duration::from_millis(elapsed);
+    }
+
+    fn is_full(&self, limit: RateLimit) -> bool {
+        self.tokens >= u64::from(limit.burst) * 1000
+    }
+
+    fn has_token(&self) -> bool {
+        self.tokens >= 1000
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1000;
+    }
+}
+
+/// Counters of a `DiscoveryGuard`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GuardStats {
+    /// PADIs within the limits
+    pub accepted: u64,
+    /// PADIs dropped by the global limit
+    pub dropped_global: u64,
+    /// PADIs dropped by the limit of their client
+    pub dropped_client: u64,
+    /// PADIs of clients which couldn't be tracked because `max_clients` was reached, only the
+    /// global limit applies to them
+    pub untracked: u64,
+}
+
+/// Decides which received PADIs a server processes
+///
+/// Other frames are not limited.  Without limits (`DiscoveryGuard::new`) every PADI is accepted.
+#[derive(Debug, Clone)]
+pub struct DiscoveryGuard {
+    global_limit: Option<RateLimit>,
+    global: Option<Bucket>,
+    client_limit: Option<RateLimit>,
+    clients: HashMap<[u8; 6], Bucket>,
+    max_clients: usize,
+    stats: GuardStats,
+}
+
+impl Default for DiscoveryGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiscoveryGuard {
+    pub fn new() -> Self {
+        Self {
+            global_limit: None,
+            global: None,
+            client_limit: None,
+            clients: HashMap::new(),
+            max_clients: DEFAULT_MAX_CLIENTS,
+            stats: GuardStats::default(),
+        }
+    }
+
+    /// Limit the PADIs of all clients together.
+    pub fn with_global_limit(mut self, limit: RateLimit) -> Self {
+        self.global_limit = Some(limit);
+        self.global = None;
+        self
+    }
+
+    /// Limit the PADIs of every client MAC address.
+    pub fn with_client_limit(mut self, limit: RateLimit) -> Self {
+        self.client_limit = Some(limit);
+        self.clients.clear();
+        self
+    }
+
+    /// Track at most `max_clients` MAC addresses, so a flood with random source addresses can't
+    /// grow the table without bounds.  Clients whose bucket is full again are forgotten first.
+    pub fn with_max_clients(mut self, max_clients: usize) -> Self {
+        self.max_clients = max_clients;
+        self
+    }
+
+    /// Check whether a received frame should be processed, taking a token if it is a PADI.
+    pub fn check(&mut self, now: Instant, frame: &[u8]) -> bool {
+        if frame.len() < 16 || NE::read_u16(&frame[12..]) != PPPOE_DISCOVERY || frame[15] != PADI {
+            return true;
+        }
+        let mut source = [0u8; 6];
+        source.copy_from_slice(&frame[6..12]);
+
+        let global = match self.global_limit {
+            Some(limit) => {
+                let bucket = self.global.get_or_insert_with(|| Bucket::full(limit, now));
+                bucket.refill(limit, now);
+                if !bucket.has_token() {
+                    self.stats.dropped_global += 1;
+                    return false;
+                }
+                Some(bucket)
+            }
+            None => None,
+        };
+
+        if let Some(limit) = self.client_limit {
+            if !self.clients.contains_key(&source) && self.clients.len() >= self.max_clients {
+                self.clients.retain(|_, bucket| {
+                    bucket.refill(limit, now);
+                    !bucket.is_full(limit)
+                });
+            }
+            if self.clients.len() < self.max_clients || self.clients.contains_key(&source) {
+                let client = self
+                    .clients
+                    .entry(source)
+                    .or_insert_with(|| Bucket::full(limit, now));
+                client.refill(limit, now);
+                if !client.has_token() {
+                    self.stats.dropped_client += 1;
+                    return false;
+                }
+                client.take();
+            } else {
+                self.stats.untracked += 1;
+            }
+        }
+
+        if let Some(bucket) = global {
+            bucket.take();
+        }
+        self.stats.accepted += 1;
+        true
+    }
+
+    /// Whether the global limit currently drops every PADI
+    pub fn is_exhausted(&mut self, now: Instant) -> bool {
+        match (self.global_limit, self.global.as_mut()) {
+            (Some(limit), Some(bucket)) => {
+                bucket.refill(limit, now);
+                !bucket.has_token()
+            }
+            _ => false,
+        }
+    }
+
+    /// Clients whose PADIs are currently dropped by their limit
+    pub fn exhausted_clients(&mut self, now: Instant) -> Vec<[u8; 6]> {
+        let limit = match self.client_limit {
+            Some(limit) => limit,
+            None => return Vec::new(),
+        };
+        self.clients
+            .iter_mut()
+            .filter_map(|(source, bucket)| {
+                bucket.refill(limit, now);
+                if bucket.has_token() {
+                    None
+                } else {
+                    Some(*source)
+                }
+            })
+            .collect()
+    }
+
+    /// Number of client MAC addresses currently tracked
+    pub fn tracked_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn stats(&self) -> &GuardStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::PADR;
+
+    fn frame(source: u8, code: u8) -> [u8; 20] {
+        let mut frame = [0u8; 20];
+        frame[6..12].copy_from_slice(&[0x02, 0, 0, 0, 0, source]);
+        frame[12..14].copy_from_slice(&[0x88, 0x63]);
+        frame[14] = 0x11;
+        frame[15] = code;
+        frame
+    }
+
+    #[test]
+    fn client_limit() {
+        let now = Instant::now();
+        let mut guard = DiscoveryGuard::new().with_client_limit(RateLimit::new(2, 2));
+
+        assert!(guard.check(now, &frame(1, PADI)));
+        assert!(guard.check(now, &frame(1, PADI)));
+        assert!(!guard.check(now, &frame(1, PADI)));
+        // other clients and other codes are not affected
+        assert!(guard.check(now, &frame(2, PADI)));
+        assert!(guard.check(now, &frame(1, PADR)));
+        // neither are other ether types with a 0x09 at the code offset
+        let mut session = frame(1, PADI);
+        session[12..14].copy_from_slice(&[0x88, 0x64]);
+        assert!(guard.check(now, &session));
+
+        // one token every 500 ms, which also works in small steps
+        for step in 1..5 {
+            assert!(!guard.check(now + Duration::from_millis(step * 100), &frame(1, PADI)));
+        }
+        assert!(guard.check(now + Duration::from_millis(500), &frame(1, PADI)));
+
+        assert_eq!(
+            *guard.stats(),
+            GuardStats {
+                accepted: 4,
+                dropped_global: 0,
+                dropped_client: 5,
+                untracked: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn global_limit() {
+        let now = Instant::now();
+        let mut guard = DiscoveryGuard::new()
+            .with_global_limit(RateLimit::new(10, 3))
+            .with_client_limit(RateLimit::new(1, 1));
+
+        for client in 1..=3 {
+            assert!(guard.check(now, &frame(client, PADI)));
+        }
+        assert!(!guard.check(now, &frame(4, PADI)));
+        // the dropped PADI didn't take a token of its client
+        assert!(guard.check(now + Duration::from_millis(100), &frame(4, PADI)));
+        assert_eq!(guard.stats().dropped_global, 1);
+    }
+
+    #[test]
+    fn exhausted_limits() {
+        let now = Instant::now();
+        let mut guard = DiscoveryGuard::new()
+            .with_global_limit(RateLimit::new(1, 3))
+            .with_client_limit(RateLimit::new(1, 2));
+        assert!(!guard.is_exhausted(now));
+        assert!(guard.exhausted_clients(now).is_empty());
+
+        guard.check(now, &frame(1, PADI));
+        guard.check(now, &frame(1, PADI));
+        guard.check(now, &frame(2, PADI));
+        assert!(guard.is_exhausted(now));
+        assert_eq!(guard.exhausted_clients(now), [[0x02, 0, 0, 0, 0, 1]]);
+
+        let later = now + Duration::from_secs(1);
+        assert!(!guard.is_exhausted(later));
+        assert!(guard.exhausted_clients(later).is_empty());
+    }
+
+    #[test]
+    fn bounded_clients() {
+        let now = Instant::now();
+        let mut guard = DiscoveryGuard::new()
+            .with_client_limit(RateLimit::new(1, 1))
+            .with_max_clients(2);
+
+        assert!(guard.check(now, &frame(1, PADI)));
+        assert!(guard.check(now, &frame(2, PADI)));
+        assert!(guard.check(now, &frame(3, PADI)));
+        assert!(guard.check(now, &frame(3, PADI)));
+        assert_eq!(guard.stats().untracked, 2);
+        assert_eq!(guard.tracked_clients(), 2);
+
+        // idle clients make room
+        let later = now + Duration::from_secs(1);
+        assert!(guard.check(later, &frame(3, PADI)));
+        assert!(!guard.check(later, &frame(3, PADI)));
+        assert_eq!(guard.tracked_clients(), 1);
+    }
+}
@@ -0,0 +1,303 @@
+//! Handing established sessions to another process
+//!
+//! A control-plane process runs the discovery and connects the session in the kernel, then
+//! passes the sockets with `SCM_RIGHTS` over a Unix domain socket to a data-plane process, which
+//! continues with an equivalent `Socket`.  Besides the file descriptors only the `SessionState`
+//! is transferred.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+use pppoe_sys::pppoe;
+
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::{fs, io, mem, num, ptr};
+
+use crate::socket::ConnectionState;
+use crate::Socket;
+
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 16;
+// the PPPoE and raw sockets, plus the session socket if it was opened
+const MAX_FDS: usize = 3;
+const MAX_STATE_LEN: usize = HEADER_LEN + 15;
+const CONTROL_LEN: usize =
+    unsafe { libc::CMSG_SPACE((MAX_FDS * mem::size_of::<RawFd>()) as libc::c_uint) } as usize;
+
+/// Room for the `SCM_RIGHTS` control message, aligned for `cmsghdr`
+#[repr(C, align(8))]
+struct ControlBuffer([u8; CONTROL_LEN]);
+
+/// The state transferred together with the sockets of a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionState {
+    pub interface_name: String,
+    pub local_mac: [u8; 6],
+    pub remote_mac: [u8; 6],
+    pub session_id: num::NonZeroU16,
+}
+
+impl SessionState {
+    /// Get the state of a socket with a connected session.
+    pub fn of(socket: &Socket) -> io::Result<Self> {
+        let (session_id, remote_mac) = match socket.state() {
+            ConnectionState::Session {
+                session_id,
+                remote_mac,
+            } => (session_id, remote_mac),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "socket has no connected session",
+                ))
+            }
+        };
+        let interface_name = socket.interface_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "socket has no interface name")
+        })?;
+
+        Ok(Self {
+            interface_name,
+            local_mac: socket.mac_address(),
+            remote_mac,
+            session_id,
+        })
+    }
+
+    /// Serialize the state: a version byte, the session id, both MACs, the length of the
+    /// interface name and the name itself.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.interface_name.as_bytes();
+        let mut bytes = vec![0u8; HEADER_LEN + name.len()];
+        bytes[0] = VERSION;
+        NE::write_u16(&mut bytes[1..3], self.session_id.get());
+        bytes[3..9].copy_from_slice(&self.local_mac);
+        bytes[9..15].copy_from_slice(&self.remote_mac);
+        bytes[15] = name.len() as u8;
+        bytes[HEADER_LEN..].copy_from_slice(name);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        if bytes.len() < HEADER_LEN {
+            return Err(invalid("session state too short"));
+        }
+        if bytes[0] != VERSION {
+            return Err(invalid("unsupported session state version"));
+        }
+        if bytes.len() != HEADER_LEN + usize::from(bytes[15]) {
+            return Err(invalid("invalid interface name length"));
+        }
+
+        let session_id = num::NonZeroU16::new(NE::read_u16(&bytes[1..3]))
+            .ok_or_else(|| invalid("session id 0"))?;
+        let interface_name = String::from_utf8(bytes[HEADER_LEN..].to_vec())
+            .map_err(|_| invalid("interface name is not UTF-8"))?;
+        let mut local_mac = [0u8; 6];
+        local_mac.copy_from_slice(&bytes[3..9]);
+        let mut remote_mac = [0u8; 6];
+        remote_mac.copy_from_slice(&bytes[9..15]);
+
+        Ok(Self {
+            interface_name,
+            local_mac,
+            remote_mac,
+            session_id,
+        })
+    }
+}
+
+/// Pass the sockets of a connected session to the process at the other end of `stream`.
+///
+/// The file descriptors of `socket` are closed afterwards, without detaching filters or dropping
+/// memberships, as these are shared with the receiving process.  The kernel session stays up.
+pub fn send_session(stream: &UnixStream, socket: Socket) -> io::Result<()> {
+    let state = SessionState::of(&socket)?;
+
+    let mut fds = vec![socket.pppoe_socket(), socket.as_raw_fd()];
+    fds.extend(socket.session_socket());
+    send_with_fds(stream, &state.to_bytes(), &fds)?;
+
+    debug!(
+        target: crate::logging::SOCKET,
+        "handed off session {:#06x}", state.session_id
+    );
+    socket.close_fds()
+}
+
+/// Receive the sockets of a session passed with `send_session`.
+pub fn recv_session(stream: &UnixStream) -> io::Result<(Socket, SessionState)> {
+    let mut buffer = [0u8; MAX_STATE_LEN];
+    let mut fds = Vec::with_capacity(MAX_FDS);
+    let len = recv_with_fds(stream, &mut buffer, &mut fds)?;
+    let state = SessionState::from_bytes(&buffer[..len])?;
+
+    let mut fds = fds.into_iter();
+    let (pppoe_socket, raw_socket) = match (fds.next(), fds.next()) {
+        (Some(pppoe_socket), Some(raw_socket)) => (pppoe_socket, raw_socket),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "sockets missing in session hand-off",
+            ))
+        }
+    };
+
+    let connection = pppoe::Connection::from_raw_parts(
+        &state.interface_name,
+        state.local_mac,
+        raw_socket.as_raw_fd(),
+        pppoe_socket.as_raw_fd(),
+    )?;
+    // the connection owns the sockets now
+    let _ = (raw_socket.into_raw_fd(), pppoe_socket.into_raw_fd());
+
+    let socket = Socket::from_parts(
+        connection,
+        fds.next(),
+        Some((state.session_id, state.remote_mac)),
+    );
+    debug!(
+        target: crate::logging::SOCKET,
+        "took over session {:#06x}", state.session_id
+    );
+    Ok((socket, state))
+}
+
+/// Send `data` together with `fds` in a single message.
+fn send_with_fds(stream: &UnixStream, data: &[u8], fds: &[RawFd]) -> io::Result<()> {
+    if fds.len() > MAX_FDS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "too many file descriptors",
+        ));
+    }
+    let fds_len = mem::size_of_val(fds) as libc::c_uint;
+    let mut control = ControlBuffer([0; CONTROL_LEN]);
+    let mut iovec = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_iov = &mut iovec;
+    message.msg_iovlen = 1;
+    message.msg_control = control.0.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = unsafe { libc::CMSG_SPACE(fds_len) } as _;
+
+    unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(fds_len) as _;
+        ptr::copy_nonoverlapping(
+            fds.as_ptr() as *const u8,
+            libc::CMSG_DATA(header),
+            fds_len as usize,
+        );
+    }
+
+    let ret = unsafe { libc::sendmsg(stream.as_raw_fd(), &message, 0) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if ret as usize != data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            "session state only partially sent",
+        ));
+    }
+    Ok(())
+}
+
+/// Receive a message into `data` and the descriptors passed with it into `fds`.
+fn recv_with_fds(
+    stream: &UnixStream,
+    data: &mut [u8],
+    fds: &mut Vec<fs::File>,
+) -> io::Result<usize> {
+    let mut control = ControlBuffer([0; CONTROL_LEN]);
+    let mut iovec = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_iov = &mut iovec;
+    message.msg_iovlen = 1;
+    message.msg_control = control.0.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = CONTROL_LEN as _;
+
+    let ret = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut message, libc::MSG_CMSG_CLOEXEC) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut header = unsafe { libc::CMSG_FIRSTHDR(&message) };
+    while !header.is_null() {
+        let (level, kind, len) = unsafe {
+            (
+                (*header).cmsg_level,
+                (*header).cmsg_type,
+                (*header).cmsg_len,
+            )
+        };
+        if level == libc::SOL_SOCKET && kind == libc::SCM_RIGHTS {
+            // `cmsg_len` is a `socklen_t` on musl
+            #[allow(clippy::unnecessary_cast)]
+            let len = len as usize;
+            let count = (len - unsafe { libc::CMSG_LEN(0) } as usize) / mem::size_of::<RawFd>();
+            let data = unsafe { libc::CMSG_DATA(header) } as *const RawFd;
+            for i in 0..count {
+                let fd = unsafe { ptr::read_unaligned(data.add(i)) };
+                fds.push(unsafe { fs::File::from_raw_fd(fd) });
+            }
+        }
+        header = unsafe { libc::CMSG_NXTHDR(&message, header) };
+    }
+
+    if message.msg_flags & (libc::MSG_TRUNC | libc::MSG_CTRUNC) != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "session hand-off message truncated",
+        ));
+    }
+    Ok(ret as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn pass_state_and_fds() {
+        let state = SessionState {
+            interface_name: "eth0.7".to_owned(),
+            local_mac: [0x02, 0, 0, 0, 0, 1],
+            remote_mac: [0x02, 0, 0, 0, 0, 2],
+            session_id: num::NonZeroU16::new(0x1234).unwrap(),
+        };
+        let bytes = state.to_bytes();
+        assert_eq!(SessionState::from_bytes(&bytes).unwrap(), state);
+        assert!(SessionState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (mut reader, writer) = UnixStream::pair().unwrap();
+        send_with_fds(&sender, &bytes, &[writer.as_raw_fd()]).unwrap();
+        drop(writer);
+
+        let mut buffer = [0u8; MAX_STATE_LEN];
+        let mut fds = Vec::new();
+        let len = recv_with_fds(&receiver, &mut buffer, &mut fds).unwrap();
+        assert_eq!(&buffer[..len], &bytes[..]);
+        assert_eq!(fds.len(), 1);
+
+        fds[0].write_all(b"passed").unwrap();
+        drop(fds);
+        let mut received = Vec::new();
+        reader.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"passed");
+    }
+}
@@ -1,231 +1,122 @@
 use byteorder::{ByteOrder, NetworkEndian as NE};
 
 use core::num::NonZeroU16;
-use core::{convert::TryFrom, u16};
-
-use crate::error::ParseError;
-use crate::{tag, Tag, TagIterator};
-
-pub const PADI: u8 = 0x09;
-pub const PADO: u8 = 0x07;
-pub const PADR: u8 = 0x19;
-pub const PADS: u8 = 0x65;
-pub const PADT: u8 = 0xa7;
-
-#[repr(u8)]
-#[derive(PartialEq, Eq, Copy, Clone)]
-pub enum Code {
-    Padi = PADI,
-    Pado = PADO,
-    Padr = PADR,
-    Pads = PADS,
-    Padt = PADT,
-}
+use std::convert::TryFrom;
 
-impl Code {
-    fn try_from(code: u8) -> Result<Self, ParseError> {
-        Ok(match code {
-            PADI => Code::Padi,
-            PADO => Code::Pado,
-            PADR => Code::Padr,
-            PADS => Code::Pads,
-            PADT => Code::Padt,
-            _ => return Err(ParseError::InvalidPppoeCode(code)),
-        })
-    }
-}
+use crate::consts::{PPPOE_HEADER_LEN, VERSION_TYPE};
+use crate::error::{Error, ParseError};
+use crate::{tag, ServiceNameMatcher, Tag, TagBuf, TagIterator};
+
+pub use pppoe_core::header::{
+    code_name, Code, Header, ParserConfig, PADC, PADG, PADI, PADO, PADQ, PADR, PADS, PADT,
+};
 
 fn ensure_minimal_buffer_length(buffer: &[u8]) -> Result<(), ParseError> {
-    if buffer.len() < 6 {
+    if buffer.len() < PPPOE_HEADER_LEN {
         return Err(ParseError::BufferTooSmall(buffer.len()));
     }
     Ok(())
 }
 
-#[derive(Debug)]
-pub struct Header<'a>(&'a [u8]);
-
-impl<'a> Header<'a> {
-    pub fn with_buffer(buffer: &'a [u8]) -> Result<Self, ParseError> {
-        Self::with_buffer_and_code(buffer, None)
-    }
-
-    pub fn with_buffer_and_code(
-        buffer: &'a [u8],
-        expected_code: Option<Code>,
-    ) -> Result<Header<'a>, ParseError> {
-        ensure_minimal_buffer_length(buffer)?;
-        if buffer[0] != 0x11 {
-            let version = buffer[0] >> 4;
-            let r#type = buffer[0] & 0x0f;
-            return if buffer[0] >> 4 != 1 {
-                Err(ParseError::InvalidPppoeVersion(version))
-            } else {
-                Err(ParseError::InvalidPppoeType(r#type))
-            };
-        }
-
-        let code = Code::try_from(buffer[1])?;
-        if let Some(expected_code) = expected_code {
-            if code != expected_code {
-                return Err(ParseError::UnexpectedCode(code as u8));
-            }
-        }
-
-        let length = usize::from(NE::read_u16(&buffer[4..]));
-        if length + 6 > buffer.len() {
-            return Err(ParseError::PayloadLengthOutOfBound {
-                actual_packet_length: buffer.len() as u16,
-                payload_length: length as u16,
-            });
-        } else if length == 0 {
-            return Err(ParseError::MissingServiceName);
-        }
+/// Parsing into the types of this crate, `ParserConfig` itself is defined in `pppoe-core`
+pub trait ParserConfigExt {
+    /// Parse an Ethernet frame carrying a PPPoE packet, see `Packet::with_buffer`.
+    fn parse_packet<'a>(&self, buffer: &'a [u8]) -> Result<crate::Packet<'a>, Error>;
 
-        Self::validate_tags(&buffer[6..6 + length])?;
-
-        Ok(Header(buffer))
-    }
-
-    pub fn padi_with_buffer(buffer: &'a [u8]) -> Result<Self, ParseError> {
-        Self::with_buffer_and_code(buffer, Some(Code::Padi))
-    }
-
-    pub fn pado_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
-        Self::with_buffer_and_code(buffer, Some(Code::Pado))
-    }
-
-    pub fn padr_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
-        Self::with_buffer_and_code(buffer, Some(Code::Padr))
-    }
-
-    pub fn pads_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
-        Self::with_buffer_and_code(buffer, Some(Code::Pads))
-    }
+    /// Parse a frame into an owned summary, see `PacketSummary::from_bytes`.
+    fn summarize(&self, buffer: &[u8]) -> Result<crate::PacketSummary, Error>;
+}
 
-    pub fn padt_with_buffer(buffer: &'a [u8]) -> Result<Header<'a>, ParseError> {
-        Self::with_buffer_and_code(buffer, Some(Code::Padt))
+impl ParserConfigExt for ParserConfig {
+    fn parse_packet<'a>(&self, buffer: &'a [u8]) -> Result<crate::Packet<'a>, Error> {
+        crate::Packet::with_config(buffer, self)
     }
 
-    pub fn as_bytes(&self) -> &[u8] {
-        &self.0
+    fn summarize(&self, buffer: &[u8]) -> Result<crate::PacketSummary, Error> {
+        crate::PacketSummary::with_config(buffer, self)
     }
+}
 
-    pub fn get_ref(&self) -> &[u8] {
-        &self.0
-    }
+/// A header owning its tags, e.g. to store decoded packets or to serialize them with the
+/// `serde` feature
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderBuf {
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub type_: u8,
+    pub code: u8,
+    pub session_id: u16,
+    pub tags: Vec<TagBuf>,
+}
 
-    fn check_duplicate(tag: u16, exists: &mut bool) -> Result<(), ParseError> {
-        if *exists {
-            return Err(ParseError::DuplicateTag(tag));
+impl HeaderBuf {
+    /// Write the header and its tags into `buffer` and return the length.
+    ///
+    /// The fields are written as they are, the result is not validated.
+    pub fn write(&self, buffer: &mut [u8]) -> Result<usize, ParseError> {
+        let mut header = HeaderBuilder::create_packet(buffer, Code::Padi, self.session_id)?;
+        header.set_version_type(self.version, self.type_);
+        header.get_ref_mut()[1] = self.code;
+        for tag in &self.tags {
+            header.add_tag(tag.as_tag())?;
         }
-
-        *exists = true;
-        Ok(())
+        Ok(header.len())
     }
+}
 
-    pub(crate) fn validate_tags(mut payload: &[u8]) -> Result<(), ParseError> {
-        let mut tag;
-        let mut length;
-        let total_packet_length = payload.len() as u16;
-
-        // these tags must only exists once
-        let mut service_name = false;
-        let mut host_uniq = false;
-        let mut ac_name = false;
-        let mut relay_session_id = false;
-        let mut ppp_max_payload = false;
-        let mut ac_cookie = false;
-
-        loop {
-            match payload.len() {
-                0 => {
-                    return Ok(());
-                }
-                // tags must be at least 4 bytes
-                x if x < 4 => {
-                    return Err(ParseError::IncompleteTagAtPacketEnd {
-                        total_packet_length,
-                        left_over_bytes: x as u16,
-                    });
-                }
-
-                total_length => {
-                    tag = NE::read_u16(payload);
-
-                    // check for duplicates
-                    match tag {
-                        tag::TAG_SERVICE_NAME => Self::check_duplicate(tag, &mut service_name)?,
-                        tag::TAG_AC_NAME => Self::check_duplicate(tag, &mut ac_name)?,
-                        tag::TAG_AC_COOKIE => Self::check_duplicate(tag, &mut ac_cookie)?,
-                        tag::TAG_HOST_UNIQ => Self::check_duplicate(tag, &mut host_uniq)?,
-                        tag::TAG_RELAY_SESSION_ID => {
-                            Self::check_duplicate(tag, &mut relay_session_id)?
-                        }
-                        tag::TAG_PPP_MAX_PAYLOAD => {
-                            Self::check_duplicate(tag, &mut ppp_max_payload)?
-                        }
-                        _ => (),
-                    }
-
-                    length = usize::from(NE::read_u16(&payload[2..]));
-                    if tag == tag::TAG_END_OF_LIST {
-                        break;
-                    }
-
-                    if length + 4 > total_length {
-                        return Err(ParseError::TagLengthOutOfBound {
-                            expected_tag_length: length as u16,
-                            remaining_payload_length: total_length as u16,
-                        });
-                    };
-                    payload = &payload[4 + length..]
-                }
-            }
-        }
-
-        // Found End-of-List tag, nothing should be behind this
-        if length != 0 || payload.len() != 4 {
-            return Err(ParseError::DataBehindEolTag);
-        }
-
-        if !service_name {
-            return Err(ParseError::MissingServiceName);
+impl<'a> From<&Header<'a>> for HeaderBuf {
+    fn from(header: &Header<'a>) -> Self {
+        Self {
+            version: header.version(),
+            type_: header.type_(),
+            code: header.code(),
+            session_id: header.session_id(),
+            tags: header.tags().map(TagBuf::from).collect(),
         }
-
-        Ok(())
     }
+}
 
-    pub fn code(&self) -> u8 {
-        self.0[1]
-    }
+/// Conversion of a `Header`, which is defined in `pppoe-core`, into the owned `HeaderBuf`
+pub trait HeaderExt {
+    /// Copy the header and its tags into a `HeaderBuf`.
+    fn to_header_buf(&self) -> HeaderBuf;
+}
 
-    pub fn session_id(&self) -> u16 {
-        NE::read_u16(&self.0[2..])
+impl<'a> HeaderExt for Header<'a> {
+    fn to_header_buf(&self) -> HeaderBuf {
+        HeaderBuf::from(self)
     }
+}
 
-    pub fn len(&self) -> usize {
-        usize::from(6 + NE::read_u16(&self.0[4..]))
-    }
+pub struct HeaderBuilder<'a>(&'a mut [u8]);
 
-    pub fn is_empty(&mut self) -> bool {
-        self.len() == 6
-    }
+/// What `HeaderBuilder::edit_tags` should do with a tag.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TagEdit<'a> {
+    Keep,
+    Remove,
+    Replace(Tag<'a>),
+}
 
-    pub fn payload(&self) -> &[u8] {
-        &self.0[6..]
+impl<'a> HeaderBuilder<'a> {
+    pub fn version(&self) -> u8 {
+        self.0[0] >> 4
     }
 
-    pub fn tags(&self) -> TagIterator {
-        TagIterator {
-            payload: &self.0[6..self.len()],
-        }
+    pub fn type_(&self) -> u8 {
+        self.0[0] & 0x0f
     }
-}
 
-pub struct HeaderBuilder<'a>(&'a mut [u8]);
+    /// Overwrite the version and type fields, e.g. to test how an AC handles packets of an
+    /// unknown version.  Only the lower 4 bits of each are used.
+    ///
+    /// `build` rejects anything but version 1 type 1, parse such packets with
+    /// `ParserConfig::allow_any_version_type`.
+    pub fn set_version_type(&mut self, version: u8, type_: u8) {
+        self.0[0] = (version & 0x0f) << 4 | (type_ & 0x0f);
+    }
 
-impl<'a> HeaderBuilder<'a> {
     pub fn code(&self) -> u8 {
         self.0[1]
     }
@@ -235,10 +126,10 @@ impl<'a> HeaderBuilder<'a> {
     }
 
     pub fn len(&self) -> usize {
-        usize::from(6 + NE::read_u16(&self.0[4..]))
+        6 + usize::from(NE::read_u16(&self.0[4..]))
     }
 
-    pub fn is_empty(&mut self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.len() == 6
     }
 
@@ -246,10 +137,8 @@ impl<'a> HeaderBuilder<'a> {
         &self.0[6..]
     }
 
-    pub fn tags(&self) -> TagIterator {
-        TagIterator {
-            payload: &self.0[6..self.len()],
-        }
+    pub fn tags(&self) -> TagIterator<'_> {
+        TagIterator::new_unchecked(&self.0[6..self.len()])
     }
     pub fn set_code(&mut self, code: Code) {
         self.0[1] = code as u8;
@@ -259,16 +148,45 @@ impl<'a> HeaderBuilder<'a> {
         NE::write_u16(&mut self.0[4..], new_length)
     }
 
+    /// Set the payload length field of the header.
+    ///
+    /// Fails if the length doesn't fit into the 16 bit field or the payload would exceed the
+    /// buffer.
+    pub fn set_payload_len(&mut self, payload_len: usize) -> Result<(), ParseError> {
+        let length = u16::try_from(payload_len)
+            .map_err(|_| ParseError::PayloadLengthOverflow(payload_len))?;
+        if 6 + payload_len > self.0.len() {
+            return Err(ParseError::PayloadLengthOutOfBound {
+                actual_packet_length: u16::try_from(self.0.len()).unwrap_or(u16::MAX),
+                payload_length: length,
+            });
+        }
+
+        unsafe { self.set_len(length) };
+        Ok(())
+    }
+
     pub fn clear_payload(&mut self) {
         unsafe { self.set_len(0) };
     }
 
     pub fn clear_eol(&mut self) {
         if Some(tag::Tag::EndOfList) == self.tags().last() {
-            unsafe { self.set_len(self.len() as u16 - 10) }
+            let payload_len = NE::read_u16(&self.0[4..]);
+            unsafe { self.set_len(payload_len - 4) }
         }
     }
 
+    /// Wrap an existing header for editing, e.g. to strip a tag from a received packet.
+    ///
+    /// The buffer is validated like in `Header::with_buffer`.  `Header` only borrows its buffer
+    /// immutably, so this takes the buffer again instead of converting a `Header`.  Bytes after
+    /// the payload (e.g. Ethernet padding) are used as room for new tags.
+    pub fn with_buffer(buffer: &'a mut [u8]) -> Result<Self, ParseError> {
+        Header::with_buffer(buffer)?;
+        Ok(HeaderBuilder(buffer))
+    }
+
     pub fn create_packet(
         buffer: &'a mut [u8],
         code: Code,
@@ -276,8 +194,7 @@ impl<'a> HeaderBuilder<'a> {
     ) -> Result<Self, ParseError> {
         ensure_minimal_buffer_length(buffer)?;
 
-        // set version and type
-        buffer[0] = 0x11;
+        buffer[0] = VERSION_TYPE;
         buffer[1] = code as u8;
         NE::write_u16(&mut buffer[2..], session_id);
         NE::write_u16(&mut buffer[4..], 0);
@@ -305,10 +222,25 @@ impl<'a> HeaderBuilder<'a> {
         Self::create_packet(buffer, Code::Padt, u16::from(session_id))
     }
 
+    /// Create a PADG (PPPoE Active Discovery Grant) for a session, carrying a Credits and a Sequence-Number
+    /// tag.
+    pub fn create_padg(buffer: &'a mut [u8], session_id: NonZeroU16) -> Result<Self, ParseError> {
+        Self::create_packet(buffer, Code::Padg, u16::from(session_id))
+    }
+
+    /// Create a PADC (PPPoE Active Discovery Credit), answering the PADG with the same Sequence-Number.
+    pub fn create_padc(buffer: &'a mut [u8], session_id: NonZeroU16) -> Result<Self, ParseError> {
+        Self::create_packet(buffer, Code::Padc, u16::from(session_id))
+    }
+
+    pub fn create_padq(buffer: &'a mut [u8], session_id: NonZeroU16) -> Result<Self, ParseError> {
+        Self::create_packet(buffer, Code::Padq, u16::from(session_id))
+    }
+
     pub fn create_padr_from_pado(
         buffer: &'a mut [u8],
         pado: &Header,
-        expected_service_name: Option<&[u8]>,
+        expected_service_name: Option<&ServiceNameMatcher>,
         expected_ac_name: Option<&[u8]>,
     ) -> Result<Self, ParseError> {
         let mut padr = Self::create_padr(buffer)?;
@@ -322,7 +254,7 @@ impl<'a> HeaderBuilder<'a> {
             match &tag {
                 Tag::ServiceName(service_name) => {
                     if let Some(expected_service_name) = expected_service_name {
-                        if service_name != &expected_service_name {
+                        if !expected_service_name.matches(service_name) {
                             return Err(ParseError::ServiceNameMismatch);
                         }
                     }
@@ -361,8 +293,159 @@ impl<'a> HeaderBuilder<'a> {
         let packet_length = self.len();
 
         let tag_length = tag.write(&mut self.0[packet_length..])?;
-        unsafe { self.set_len((packet_length - 6 + tag_length) as u16) };
-        Ok(())
+        self.set_payload_len(packet_length - 6 + tag_length)
+    }
+
+    /// Insert a tag in front of the `index`th tag, moving all following tags back.
+    ///
+    /// An `index` equal to the number of tags appends the tag like `add_tag`.  Nothing may
+    /// follow an End-Of-List tag, so an `index` behind it is out of bound.
+    pub fn insert_tag_at(&mut self, index: usize, tag: Tag) -> Result<(), ParseError> {
+        let mut offset = 6;
+        let mut tag_count = 0;
+        while tag_count < index {
+            if offset + 4 > self.len() {
+                return Err(ParseError::TagIndexOutOfBound { index, tag_count });
+            }
+            if NE::read_u16(&self.0[offset..]) == tag::TAG_END_OF_LIST {
+                return Err(ParseError::TagIndexOutOfBound {
+                    index,
+                    tag_count: tag_count + 1,
+                });
+            }
+            offset += 4 + usize::from(NE::read_u16(&self.0[offset + 2..]));
+            tag_count += 1;
+        }
+        if offset > self.len() {
+            return Err(ParseError::IncompleteTagAtPacketEnd {
+                total_packet_length: u16::try_from(self.len()).unwrap_or(u16::MAX),
+                left_over_bytes: u16::try_from(offset - self.len()).unwrap_or(u16::MAX),
+            });
+        }
+
+        self.insert_tag_at_offset(offset, tag)
+    }
+
+    /// Insert a tag in front of the first tag of type `tag_type` (e.g. `TAG_END_OF_LIST`).
+    ///
+    /// If there is no such tag, the new tag is appended.
+    pub fn insert_before(&mut self, tag_type: u16, tag: Tag) -> Result<(), ParseError> {
+        let packet_length = self.len();
+        let mut offset = 6;
+        while offset + 4 <= packet_length && NE::read_u16(&self.0[offset..]) != tag_type {
+            offset += 4 + usize::from(NE::read_u16(&self.0[offset + 2..]));
+        }
+
+        self.insert_tag_at_offset(offset.min(packet_length), tag)
+    }
+
+    fn insert_tag_at_offset(&mut self, offset: usize, tag: Tag) -> Result<(), ParseError> {
+        let packet_length = self.len();
+        let tag_length = tag.encoded_len();
+        let available = self.0.len() - packet_length;
+        if tag_length > available || packet_length - 6 + tag_length > usize::from(u16::MAX) {
+            return Err(ParseError::BufferTooSmallForTag {
+                available: u16::try_from(available).unwrap_or(u16::MAX),
+                requested: tag_length,
+            });
+        }
+
+        self.0
+            .copy_within(offset..packet_length, offset + tag_length);
+        tag.write(&mut self.0[offset..offset + tag_length])?;
+        self.set_payload_len(packet_length - 6 + tag_length)
+    }
+
+    /// Visit all tags in order and keep, remove or replace each of them.
+    ///
+    /// Replacements which are longer than the original tag move the remaining tags back.
+    /// All tags are visited and the result is checked before anything is modified, so a
+    /// malformed payload or a replacement which doesn't fit leaves the header as is.
+    pub fn edit_tags<'b, F>(&mut self, mut edit: F) -> Result<(), ParseError>
+    where
+        F: FnMut(&Tag<'_>) -> TagEdit<'b>,
+    {
+        let end = self.len();
+        let mut edits = Vec::new();
+        let mut new_len = 6;
+        let mut offset = 6;
+        while offset < end {
+            let (tag, rest) = Tag::from_buffer(&self.0[offset..end])?;
+            let next = end - rest.len();
+            let action = edit(&tag);
+            new_len += match action {
+                TagEdit::Keep => next - offset,
+                TagEdit::Remove => 0,
+                TagEdit::Replace(tag) => {
+                    let tag_length = tag.encoded_len();
+                    if tag_length - 4 > usize::from(u16::MAX) {
+                        return Err(ParseError::TagLengthOverflow {
+                            tag_type: tag.get_tag_type(),
+                            length: tag_length - 4,
+                        });
+                    }
+                    tag_length
+                }
+            };
+            edits.push((offset - 6..next - 6, action));
+            offset = next;
+        }
+        if new_len > self.0.len() || new_len - 6 > usize::from(u16::MAX) {
+            return Err(ParseError::BufferTooSmallForTag {
+                available: u16::try_from(self.0.len() - end).unwrap_or(u16::MAX),
+                requested: new_len - end,
+            });
+        }
+
+        let payload = self.0[6..end].to_vec();
+        let mut write = 6;
+        for (range, action) in edits {
+            match action {
+                TagEdit::Keep => {
+                    let tag = &payload[range];
+                    self.0[write..write + tag.len()].copy_from_slice(tag);
+                    write += tag.len();
+                }
+                TagEdit::Remove => {}
+                TagEdit::Replace(tag) => write += tag.write(&mut self.0[write..])?,
+            }
+        }
+
+        self.set_payload_len(write - 6)
+    }
+
+    /// Remove the first tag of type `tag_type`, moving the following tags forward.
+    ///
+    /// Returns `false` if there is no such tag.
+    pub fn remove_tag(&mut self, tag_type: u16) -> Result<bool, ParseError> {
+        let mut removed = false;
+        self.edit_tags(|tag| {
+            if !removed && tag.get_tag_type() == tag_type {
+                removed = true;
+                TagEdit::Remove
+            } else {
+                TagEdit::Keep
+            }
+        })?;
+        Ok(removed)
+    }
+
+    /// Replace the first tag of the same type as `tag`, e.g. to turn a PADI into a PADR with
+    /// another Host-Uniq.  The following tags are moved if the length changes.
+    ///
+    /// Returns `false` if there is no such tag, nothing is added then.
+    pub fn replace_tag(&mut self, tag: Tag) -> Result<bool, ParseError> {
+        let tag_type = tag.get_tag_type();
+        let mut replaced = false;
+        self.edit_tags(|old| {
+            if !replaced && old.get_tag_type() == tag_type {
+                replaced = true;
+                TagEdit::Replace(tag)
+            } else {
+                TagEdit::Keep
+            }
+        })?;
+        Ok(replaced)
     }
 
     pub fn add_vendor_tag_with_callback<F>(&mut self, callback: F) -> Result<(), ParseError>
@@ -372,33 +455,56 @@ impl<'a> HeaderBuilder<'a> {
         let packet_length = self.len();
 
         let payload_end = &mut self.0[packet_length..];
+        if payload_end.len() < 4 {
+            return Err(ParseError::BufferTooSmallForTag {
+                available: u16::try_from(payload_end.len()).unwrap_or(u16::MAX),
+                requested: 4,
+            });
+        }
 
         let vendor_tag_length = callback(&mut payload_end[4..])?;
+        let length =
+            u16::try_from(vendor_tag_length).map_err(|_| ParseError::TagLengthOverflow {
+                tag_type: tag::TAG_VENDOR_SPECIFIC,
+                length: vendor_tag_length,
+            })?;
         NE::write_u16(payload_end, tag::TAG_VENDOR_SPECIFIC);
-        NE::write_u16(&mut payload_end[2..], vendor_tag_length as u16);
+        NE::write_u16(&mut payload_end[2..], length);
 
-        unsafe { self.set_len((packet_length - 6 + vendor_tag_length + 4) as u16) };
-
-        Ok(())
+        self.set_payload_len(packet_length - 6 + vendor_tag_length + 4)
     }
 
     pub fn add_end_tag(&mut self) -> Result<(), ParseError> {
         self.add_tag(Tag::EndOfList)
     }
 
+    pub(crate) fn get_ref(&self) -> &[u8] {
+        self.0
+    }
+
     pub fn get_ref_mut(&mut self) -> &mut [u8] {
-        &mut self.0
+        self.0
     }
 
     pub fn build(self) -> Result<Header<'a>, ParseError> {
         Header::with_buffer(self.0)
     }
+
+    /// Give the buffer back, e.g. to join it with the preceding Ethernet header again.
+    pub(crate) fn into_buffer(self) -> &'a mut [u8] {
+        self.0
+    }
+
+    /// Validate the header like `build` without giving up the builder, e.g. between edits.
+    pub fn try_build(&self) -> Result<Header<'_>, ParseError> {
+        Header::with_buffer(self.0)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::u8;
+    use core::fmt;
 
     macro_rules! create_tags {
         ($content:expr, $($tag:path),* $(,)? ) => {{
@@ -432,11 +538,340 @@ mod tests {
     }
 
     fn expect_parse_error(buffer: &[u8]) -> ParseError {
-        let should_be_error = Header::with_buffer(&buffer[..]);
+        let should_be_error = Header::with_buffer(buffer);
         assert!(should_be_error.is_err());
         should_be_error.unwrap_err()
     }
 
+    #[test]
+    fn insert_tags_in_between() {
+        let mut buffer = [0u8; 64];
+        let mut header = minimal_header_with_eol(&mut buffer, Some(b"svc"));
+
+        header
+            .insert_before(tag::TAG_END_OF_LIST, Tag::HostUniq(b"hu"))
+            .unwrap();
+        header.insert_tag_at(0, Tag::AcName(b"ac")).unwrap();
+        header.insert_tag_at(3, Tag::AcCookie(b"c")).unwrap();
+        // nothing can be inserted behind the End-Of-List tag
+        assert!(matches!(
+            header.insert_tag_at(5, Tag::AcCookie(b"c")),
+            Err(ParseError::TagIndexOutOfBound {
+                index: 5,
+                tag_count: 5
+            })
+        ));
+        assert!(matches!(
+            header.insert_tag_at(6, Tag::AcCookie(b"c")),
+            Err(ParseError::TagIndexOutOfBound {
+                index: 6,
+                tag_count: 5
+            })
+        ));
+
+        let tags: Vec<_> = header.tags().collect();
+        assert_eq!(
+            tags,
+            [
+                Tag::AcName(b"ac"),
+                Tag::ServiceName(b"svc"),
+                Tag::HostUniq(b"hu"),
+                Tag::AcCookie(b"c"),
+                Tag::EndOfList,
+            ]
+        );
+        assert!(header.try_build().is_ok());
+    }
+
+    #[test]
+    fn edit_received_header() {
+        let mut buffer = [0u8; 64];
+        let len = {
+            let mut header = minimal_header(&mut buffer, Some(b"svc"));
+            header.add_tag(Tag::RelaySessionId(b"relay")).unwrap();
+            header.len()
+        };
+        // trailing padding
+        let received = &mut buffer[..len + 4];
+
+        assert!(HeaderBuilder::with_buffer(&mut [0x11, PADO, 0, 0, 0, 4][..]).is_err());
+
+        let mut header = HeaderBuilder::with_buffer(received).unwrap();
+        header
+            .edit_tags(|tag| match tag {
+                Tag::RelaySessionId(_) => TagEdit::Remove,
+                _ => TagEdit::Keep,
+            })
+            .unwrap();
+        assert_eq!(
+            header.try_build().unwrap().tags().collect::<Vec<_>>(),
+            [Tag::ServiceName(b"svc")]
+        );
+
+        // the padding is reused
+        header.add_tag(Tag::AcCookie(&[1; 9])).unwrap();
+        assert_eq!(header.try_build().unwrap().len(), len - 9 + 13);
+
+        header.get_ref_mut()[1] = 0;
+        assert_eq!(
+            header.try_build().unwrap_err(),
+            ParseError::InvalidPppoeCode(0)
+        );
+    }
+
+    #[test]
+    fn edit_tags_in_one_pass() {
+        let mut buffer = [0u8; 64];
+        let mut header = minimal_header(&mut buffer, Some(b"svc"));
+        header
+            .add_tag(Tag::VendorSpecific(b"\x00\x00\x0d\xe9"))
+            .unwrap();
+        header.add_tag(Tag::RelaySessionId(b"r")).unwrap();
+        header.add_tag(Tag::HostUniq(b"hu")).unwrap();
+
+        header
+            .edit_tags(|tag| match tag {
+                Tag::VendorSpecific(_) => TagEdit::Remove,
+                Tag::RelaySessionId(_) => TagEdit::Replace(Tag::RelaySessionId(b"relay-1")),
+                _ => TagEdit::Keep,
+            })
+            .unwrap();
+
+        let tags: Vec<_> = header.tags().collect();
+        assert_eq!(
+            tags,
+            [
+                Tag::ServiceName(b"svc"),
+                Tag::RelaySessionId(b"relay-1"),
+                Tag::HostUniq(b"hu"),
+            ]
+        );
+        assert_eq!(header.len(), 6 + 7 + 11 + 6);
+    }
+
+    #[test]
+    fn failed_edit_leaves_tags() {
+        let mut buffer = [0u8; 32];
+        let mut header = minimal_header(&mut buffer, Some(b"svc"));
+        header.add_tag(Tag::HostUniq(b"hu")).unwrap();
+        header.add_tag(Tag::RelaySessionId(b"r")).unwrap();
+        let len = header.len();
+
+        // the removal frees 7 bytes, which are not enough for the longer relay session id
+        assert!(matches!(
+            header.edit_tags(|tag| match tag {
+                Tag::ServiceName(_) => TagEdit::Remove,
+                Tag::RelaySessionId(_) => TagEdit::Replace(Tag::RelaySessionId(&[1; 24])),
+                _ => TagEdit::Keep,
+            }),
+            Err(ParseError::BufferTooSmallForTag {
+                available: 8,
+                requested: 16
+            })
+        ));
+        assert_eq!(header.len(), len);
+        assert_eq!(
+            header.tags().collect::<Vec<_>>(),
+            [
+                Tag::ServiceName(b"svc"),
+                Tag::HostUniq(b"hu"),
+                Tag::RelaySessionId(b"r"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rebuild_padi_as_padr() {
+        let mut buffer = [0u8; 64];
+        let mut header = minimal_header(&mut buffer, Some(b"svc"));
+        header.add_tag(Tag::HostUniq(b"1")).unwrap();
+        header.add_tag(Tag::PppMaxMtu(1500)).unwrap();
+        header.add_end_tag().unwrap();
+
+        header.set_code(Code::Padr);
+        assert!(header.replace_tag(Tag::HostUniq(b"client-2")).unwrap());
+        assert!(header.remove_tag(tag::TAG_PPP_MAX_PAYLOAD).unwrap());
+        assert!(!header.remove_tag(tag::TAG_PPP_MAX_PAYLOAD).unwrap());
+        assert!(!header.replace_tag(Tag::AcCookie(b"c")).unwrap());
+        header
+            .insert_before(tag::TAG_END_OF_LIST, Tag::AcCookie(b"cookie"))
+            .unwrap();
+
+        let padr = header.build().unwrap();
+        assert_eq!(padr.code(), Code::Padr as u8);
+        assert_eq!(
+            padr.tags().collect::<Vec<_>>(),
+            [
+                Tag::ServiceName(b"svc"),
+                Tag::HostUniq(b"client-2"),
+                Tag::AcCookie(b"cookie"),
+                Tag::EndOfList,
+            ]
+        );
+        assert_eq!(padr.len(), 6 + 7 + 12 + 10 + 4);
+    }
+
+    #[test]
+    fn typed_tag_accessors() {
+        let mut buffer = [0u8; 64];
+        let len = {
+            let mut header = minimal_header(&mut buffer, Some(b"svc"));
+            header.set_code(Code::Pado);
+            header.add_tag(Tag::AcName(b"ac")).unwrap();
+            header.add_tag(Tag::AcCookie(b"")).unwrap();
+            header.add_tag(Tag::HostUniq(b"hu")).unwrap();
+            header.len()
+        };
+
+        let header = Header::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(header.service_name(), Some(&b"svc"[..]));
+        assert_eq!(header.ac_name(), Some(&b"ac"[..]));
+        assert_eq!(header.ac_cookie(), Some(&b""[..]));
+        assert_eq!(header.host_uniq(), Some(&b"hu"[..]));
+        assert_eq!(header.relay_session_id(), None);
+        assert_eq!(header.tag_value(tag::TAG_END_OF_LIST), None);
+    }
+
+    #[test]
+    fn tag_value_stops_at_end_of_list() {
+        let mut buffer = [0u8; 64];
+        let len = {
+            let mut header = minimal_header_with_eol(&mut buffer, Some(b"svc"));
+            header.add_tag(Tag::HostUniq(b"hu")).unwrap();
+            header.len()
+        };
+
+        let header = ParserConfig::lenient().parse(&buffer[..len]).unwrap();
+        assert_eq!(header.service_name(), Some(&b"svc"[..]));
+        assert_eq!(header.host_uniq(), None);
+
+        // garbage behind the list isn't validated by a lenient parser
+        buffer[len - 3] = 0xff;
+        let header = ParserConfig::lenient().parse(&buffer[..len]).unwrap();
+        assert_eq!(header.tag_value(tag::TAG_HOST_UNIQ), None);
+    }
+
+    #[test]
+    fn tags_stop_at_end_of_list() {
+        let mut buffer = [0u8; 64];
+        let len = minimal_header_with_eol(&mut buffer, Some(b"svc")).len();
+        // two bytes behind the End-Of-List tag aren't even a tag header
+        NE::write_u16(&mut buffer[4..], (len - 6 + 2) as u16);
+        buffer[len..len + 2].copy_from_slice(&[0xff, 0xff]);
+
+        assert!(matches!(
+            Header::with_buffer(&buffer[..len + 2]),
+            Err(ParseError::DataBehindEolTag)
+        ));
+        let header = ParserConfig::lenient().parse(&buffer[..len + 2]).unwrap();
+        let tags: Vec<_> = header.tags().collect();
+        assert_eq!(tags, [Tag::ServiceName(b"svc"), Tag::EndOfList]);
+    }
+
+    #[test]
+    fn display() {
+        let mut buffer = [0u8; 64];
+        let len = {
+            let mut header = minimal_header(&mut buffer, Some(b""));
+            header.set_code(Code::Pado);
+            header.add_tag(Tag::AcName(b"bras01.example")).unwrap();
+            header.add_tag(Tag::AcCookie(&[0xde, 0xad, 0x0a])).unwrap();
+            header.add_tag(Tag::PppMaxMtu(1500)).unwrap();
+            header.add_end_tag().unwrap();
+            header.len()
+        };
+
+        let header = Header::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(header.to_string(), "PADO session-id 0x0000 length 39");
+        assert_eq!(
+            format!("{:#}", header),
+            "PADO session-id 0x0000 length 39\n  \
+             Service-Name: \"\"\n  \
+             AC-Name: \"bras01.example\"\n  \
+             AC-Cookie: 3 bytes dead0a\n  \
+             PPP-Max-Payload: 1500\n  \
+             End-Of-List"
+        );
+        assert_eq!(Tag::Credits((1, 2)).to_string(), "Credits: FCN 1, BCN 2");
+        assert_eq!(
+            Tag::custom(0x4242, b"a\tb").unwrap().to_string(),
+            "tag 0x4242: 3 bytes 610962"
+        );
+
+        // into a fixed buffer, without allocating
+        struct Line {
+            buffer: [u8; 160],
+            len: usize,
+        }
+        impl fmt::Write for Line {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                let end = self.len + s.len();
+                self.buffer
+                    .get_mut(self.len..end)
+                    .ok_or(fmt::Error)?
+                    .copy_from_slice(s.as_bytes());
+                self.len = end;
+                Ok(())
+            }
+        }
+        let mut line = Line {
+            buffer: [0; 160],
+            len: 0,
+        };
+        header.write_summary(&mut line).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&line.buffer[..line.len]).unwrap(),
+            "PADO session-id 0x0000 length 39: Service-Name: \"\"; \
+             AC-Name: \"bras01.example\"; AC-Cookie: 3 bytes dead0a; \
+             PPP-Max-Payload: 1500; End-Of-List"
+        );
+        let mut summary = String::new();
+        Tag::HostUniq(&[0xab; 20])
+            .write_summary(&mut summary)
+            .unwrap();
+        assert_eq!(
+            summary,
+            format!("Host-Uniq: 20 bytes {}..", "ab".repeat(16))
+        );
+        summary.clear();
+        Tag::AcName("ä".repeat(10).as_bytes())
+            .write_summary(&mut summary)
+            .unwrap();
+        assert_eq!(summary, format!("AC-Name: {:?}..", "ä".repeat(8)));
+    }
+
+    #[test]
+    fn header_buf_roundtrip() {
+        let mut buffer = [0u8; 64];
+        let len = {
+            let mut header = minimal_header(&mut buffer, Some(b"svc"));
+            header.add_tag(Tag::PppMaxMtu(1500)).unwrap();
+            header.add_tag(Tag::HostUniq(&[0, 1])).unwrap();
+            header.len()
+        };
+        let header = Header::with_buffer(&buffer[..len]).unwrap();
+        let owned = header.to_header_buf();
+        assert_eq!(owned.tags[1], TagBuf::PppMaxMtu(1500));
+
+        #[cfg(feature = "serde")]
+        let owned = {
+            let json = serde_json::to_string(&owned).unwrap();
+            assert_eq!(
+                json,
+                r#"{"version":1,"type":1,"code":9,"session_id":0,"tags":[{"ServiceName":[115,118,99]},{"PppMaxMtu":1500},{"HostUniq":[0,1]}]}"#
+            );
+            assert_eq!(
+                serde_json::to_string(&header.tags().nth(1).unwrap()).unwrap(),
+                r#"{"PppMaxMtu":1500}"#
+            );
+            serde_json::from_str::<HeaderBuf>(&json).unwrap()
+        };
+
+        let mut rebuilt = [0u8; 64];
+        let rebuilt_len = owned.write(&mut rebuilt).unwrap();
+        assert_eq!(&rebuilt[..rebuilt_len], &buffer[..len]);
+    }
+
     #[test]
     fn duplicate_tag_detection() {
         let buffer = &mut [0u8; 200][..];
@@ -466,20 +901,14 @@ mod tests {
         unsafe { header.set_len(555) };
 
         let err = expect_parse_error(buffer);
-        assert!(match err {
-            ParseError::PayloadLengthOutOfBound { .. } => true,
-            _ => false,
-        });
+        assert!(matches!(err, ParseError::PayloadLengthOutOfBound { .. }));
     }
 
     #[test]
     fn buffer_less_than_minimal_required_size_for_parsing() {
         let buffer = &mut [0u8; 4];
         let err = expect_parse_error(buffer);
-        assert!(match err {
-            ParseError::BufferTooSmall(_) => true,
-            _ => false,
-        });
+        assert!(matches!(err, ParseError::BufferTooSmall(_)));
     }
 
     #[test]
@@ -492,10 +921,7 @@ mod tests {
     fn invalid_pppoe_version() {
         let buffer = &mut [0u8; 20];
         let err = expect_parse_error(buffer);
-        assert!(match err {
-            ParseError::InvalidPppoeVersion(_) => true,
-            _ => false,
-        })
+        assert!(matches!(err, ParseError::InvalidPppoeVersion(_)))
     }
 
     #[test]
@@ -503,10 +929,28 @@ mod tests {
         let buffer = &mut [0u8; 20];
         buffer[0] = 0x01 << 4;
         let err = expect_parse_error(buffer);
-        assert!(match err {
-            ParseError::InvalidPppoeType(_) => true,
-            _ => false,
-        });
+        assert!(matches!(err, ParseError::InvalidPppoeType(_)));
+    }
+
+    #[test]
+    fn other_version_and_type() {
+        let mut buffer = [0u8; 20];
+        let mut header = minimal_header(&mut buffer, Some(b""));
+        assert_eq!((header.version(), header.type_()), (1, 1));
+        header.set_version_type(2, 3);
+        assert_eq!(header.get_ref_mut()[0], 0x23);
+        assert_eq!(
+            header.try_build().unwrap_err(),
+            ParseError::InvalidPppoeVersion(2)
+        );
+
+        let config = ParserConfig {
+            allow_any_version_type: true,
+            ..ParserConfig::default()
+        };
+        let header = config.parse(&buffer).unwrap();
+        assert_eq!((header.version(), header.type_()), (2, 3));
+        assert_eq!(header.tags().next(), Some(Tag::ServiceName(b"")));
     }
 
     #[test]
@@ -516,13 +960,10 @@ mod tests {
         for code in 0..=u8::MAX {
             buffer[1] = code;
             match code {
-                PADI | PADO | PADR | PADS | PADT => continue,
+                PADI | PADO | PADR | PADS | PADT | PADG | PADC | PADQ => continue,
                 _ => {
                     let err = expect_parse_error(buffer);
-                    assert!(match err {
-                        ParseError::InvalidPppoeCode(_) => true,
-                        _ => false,
-                    });
+                    assert!(matches!(err, ParseError::InvalidPppoeCode(_)));
                 }
             }
         }
@@ -534,10 +975,7 @@ mod tests {
         buffer[0] = 0x11;
         buffer[1] = PADI;
         let err = expect_parse_error(buffer);
-        assert!(match err {
-            ParseError::MissingServiceName => true,
-            _ => false,
-        });
+        assert!(matches!(err, ParseError::MissingServiceName));
 
         HeaderBuilder::create_padi(buffer)
             .unwrap()
@@ -545,10 +983,7 @@ mod tests {
             .unwrap();
 
         let err = expect_parse_error(buffer);
-        assert!(match err {
-            ParseError::MissingServiceName => true,
-            _ => false,
-        });
+        assert!(matches!(err, ParseError::MissingServiceName));
     }
 
     #[test]
@@ -572,4 +1007,123 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn payload_without_padding() {
+        let buffer = &mut [0xaau8; 46];
+        minimal_header(buffer, Some(b"isp"));
+
+        let header = Header::with_buffer(buffer).unwrap();
+        assert_eq!(header.declared_payload_len(), 7);
+        assert_eq!(header.len(), 13);
+        assert_eq!(header.payload(), [0x01, 0x01, 0x00, 0x03, b'i', b's', b'p']);
+        assert_eq!(header.trailing_bytes(), [0xaa; 33]);
+    }
+
+    #[test]
+    fn lengths_near_64_kib_never_wrap() {
+        let mut buffer = vec![0u8; 0x1_1000];
+        let big_value = vec![0x42; 0x1_0000];
+
+        // xorshift, to get reproducible tag lengths around the limit of the length field
+        let mut seed = 0x2545_f491u32;
+        for _ in 0..64 {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let first_len = 0xfe00 + (seed & 0x1fb) as usize;
+            let second_len = (seed >> 16) as usize & 0x1ff;
+
+            let mut header = HeaderBuilder::create_padi(&mut buffer[..]).unwrap();
+            header
+                .add_tag(Tag::HostUniq(&big_value[..first_len]))
+                .unwrap();
+            let result = header.add_tag(Tag::ServiceName(&big_value[..second_len]));
+
+            let payload_len = first_len + 4 + second_len + 4;
+            if payload_len <= usize::from(u16::MAX) {
+                assert_eq!(result, Ok(()));
+                let header = Header::with_buffer(&buffer).unwrap();
+                assert_eq!(header.declared_payload_len(), payload_len);
+            } else {
+                assert_eq!(result, Err(ParseError::PayloadLengthOverflow(payload_len)));
+                assert_eq!(header.len(), 6 + first_len + 4);
+            }
+        }
+
+        let mut header = HeaderBuilder::create_padi(&mut buffer[..]).unwrap();
+        assert_eq!(
+            header.add_tag(Tag::HostUniq(&big_value)),
+            Err(ParseError::TagLengthOverflow {
+                tag_type: tag::TAG_HOST_UNIQ,
+                length: 0x1_0000
+            })
+        );
+        assert_eq!(
+            header.add_vendor_tag_with_callback(|_| Ok(0x1_0000)),
+            Err(ParseError::TagLengthOverflow {
+                tag_type: tag::TAG_VENDOR_SPECIFIC,
+                length: 0x1_0000
+            })
+        );
+        assert_eq!(
+            header.set_payload_len(0x1_0000),
+            Err(ParseError::PayloadLengthOverflow(0x1_0000))
+        );
+        assert!(header.is_empty());
+    }
+
+    #[test]
+    fn parser_config() {
+        let mut buffer = [0u8; 64];
+        let mut builder = minimal_header(&mut buffer, None);
+        builder.add_tag(Tag::HostUniq(b"1")).unwrap();
+        builder.add_tag(Tag::HostUniq(b"2")).unwrap();
+        let len = builder.len();
+        let strict = ParserConfig::default();
+        assert_eq!(
+            strict.parse(&buffer[..len]).unwrap_err(),
+            Header::with_buffer(&buffer[..len]).unwrap_err()
+        );
+        let lenient = ParserConfig::lenient();
+        assert_eq!(lenient.parse(&buffer[..len]).unwrap().tags().count(), 3);
+
+        let limited = ParserConfig {
+            max_tags: Some(2),
+            ..lenient
+        };
+        assert_eq!(
+            limited.parse(&buffer[..len]).unwrap_err(),
+            ParseError::TooManyTags(3)
+        );
+
+        // padding behind the declared length
+        let no_padding = ParserConfig {
+            tolerate_padding: false,
+            ..lenient
+        };
+        assert!(lenient.parse(&buffer[..len + 2]).is_ok());
+        assert_eq!(
+            no_padding.parse(&buffer[..len + 2]).unwrap_err(),
+            ParseError::TrailingBytes(2)
+        );
+
+        buffer[1] = 0x42;
+        assert!(Header::with_buffer(&buffer[..len]).is_err());
+        assert_eq!(lenient.parse(&buffer[..len]).unwrap().code(), 0x42);
+        assert_eq!(
+            lenient
+                .parse_with_code(&buffer[..len], Some(Code::Padi))
+                .unwrap_err(),
+            ParseError::UnexpectedCode(0x42)
+        );
+
+        let mut buffer = [0u8; 64];
+        let len = HeaderBuilder::create_padi(&mut buffer[..]).unwrap().len();
+        assert_eq!(
+            strict.parse(&buffer[..len]).unwrap_err(),
+            ParseError::MissingServiceName
+        );
+        assert!(lenient.parse(&buffer[..len]).is_ok());
+    }
 }
@@ -0,0 +1,145 @@
+//! Conversion between frames and hex dumps, e.g. as pasted into bug reports
+//!
+//! The parser accepts Wireshark's "Copy as Hex Dump" format (with or without the ASCII column),
+//! text2pcap input with offsets and plain hex streams.
+
+use std::fmt::Write;
+
+use crate::error::ParseError;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Format bytes like Wireshark's "Copy as Hex Dump": offset, hex bytes and ASCII column.
+pub fn to_hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (line, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        write!(dump, "{:04x}  ", line * BYTES_PER_LINE).unwrap();
+        for byte in chunk {
+            write!(dump, " {:02x}", byte).unwrap();
+        }
+        for _ in chunk.len()..BYTES_PER_LINE {
+            dump.push_str("   ");
+        }
+
+        dump.push_str("   ");
+        for &byte in chunk {
+            dump.push(if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            });
+        }
+        dump.push('\n');
+    }
+    dump
+}
+
+fn is_hex(token: &str) -> bool {
+    !token.is_empty() && token.bytes().all(|c| c.is_ascii_hexdigit())
+}
+
+fn decode_pair(pair: &[u8]) -> u8 {
+    let digit = |c: u8| (c as char).to_digit(16).unwrap() as u8;
+    digit(pair[0]) << 4 | digit(pair[1])
+}
+
+/// Parse a hex dump into `buffer` and return the number of bytes.
+///
+/// Offsets are checked against the number of bytes read so far.
+pub fn from_hex_str(hex: &str, buffer: &mut [u8]) -> Result<usize, ParseError> {
+    let mut len = 0;
+    let mut push = |byte: u8, len: &mut usize| {
+        if *len >= buffer.len() {
+            return Err(ParseError::BufferTooSmall(buffer.len()));
+        }
+        buffer[*len] = byte;
+        *len += 1;
+        Ok(())
+    };
+
+    for (line_number, line) in hex.lines().enumerate() {
+        let invalid = ParseError::InvalidHexDump {
+            line: line_number + 1,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace();
+        let first = tokens.next().unwrap();
+
+        // a plain hex stream
+        if tokens.next().is_none() && first.len() > 2 {
+            if !is_hex(first) || first.len() % 2 != 0 {
+                return Err(invalid);
+            }
+            for pair in first.as_bytes().chunks(2) {
+                push(decode_pair(pair), &mut len)?;
+            }
+            continue;
+        }
+
+        let mut bytes = line;
+        let offset = first.trim_end_matches(':');
+        if offset.len() >= 4 && is_hex(offset) {
+            if usize::from_str_radix(offset, 16).ok() != Some(len) {
+                return Err(invalid);
+            }
+            bytes = line[first.len()..].trim_start();
+        }
+
+        // the ASCII column is separated by at least three spaces
+        if let Some(end) = bytes.find("   ") {
+            bytes = &bytes[..end];
+        }
+
+        for token in bytes.split_whitespace() {
+            if token.len() != 2 || !is_hex(token) {
+                return Err(invalid);
+            }
+            push(decode_pair(token.as_bytes()), &mut len)?;
+        }
+    }
+
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wireshark_dump_roundtrip() {
+        let frame: Vec<u8> = (0..=40).collect();
+        let dump = to_hex_dump(&frame);
+        assert!(dump.starts_with("0000   00 01 02 03"));
+
+        let mut buffer = [0u8; 64];
+        let len = from_hex_str(&dump, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &frame[..]);
+    }
+
+    #[test]
+    fn other_formats() {
+        let mut buffer = [0u8; 8];
+        assert_eq!(from_hex_str("ffff0a0b\n", &mut buffer), Ok(4));
+        assert_eq!(&buffer[..4], [0xff, 0xff, 0x0a, 0x0b]);
+
+        assert_eq!(
+            from_hex_str("000000 11 22\n000002 33\n", &mut buffer),
+            Ok(3)
+        );
+        assert_eq!(&buffer[..3], [0x11, 0x22, 0x33]);
+
+        assert_eq!(
+            from_hex_str("0000 11 22\n0004 33\n", &mut buffer),
+            Err(ParseError::InvalidHexDump { line: 2 })
+        );
+        assert_eq!(
+            from_hex_str("0000 11 2g", &mut buffer),
+            Err(ParseError::InvalidHexDump { line: 1 })
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! Lookup of network interfaces
+
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const SYSFS_NET: &str = "/sys/class/net";
+// `/proc/net` is the namespace of the process, `thread-self` the one of the calling thread,
+// which differs while a socket is opened in another namespace
+const PROC_NET_VLAN: &str = "/proc/thread-self/net/vlan";
+
+/// Get the index of the interface with the given name.
+pub fn index_from_name(name: &str) -> io::Result<u32> {
+    let name = CString::new(name)?;
+
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(index)
+}
+
+/// Get the name of the interface with the given index.
+pub fn name_from_index(index: u32) -> io::Result<String> {
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+
+    let ret = unsafe { libc::if_indextoname(index, name.as_mut_ptr()) };
+    if ret.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+/// Get the name of the interface with the given hardware address.
+///
+/// The interfaces are enumerated with `getifaddrs(3)` (which queries the kernel via rtnetlink).
+/// Returns an error if no or more than one interface has this address, which happens for VLAN
+/// sub-interfaces and bridges sharing the address of their lower device.
+pub fn name_from_mac(mac: [u8; 6]) -> io::Result<String> {
+    let mut found: Option<String> = None;
+
+    for_each_link(|name, address| {
+        if address != mac {
+            return Ok(());
+        }
+        if found.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "multiple interfaces have this hardware address",
+            ));
+        }
+        found = Some(name.to_owned());
+        Ok(())
+    })?;
+
+    found.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "no interface has this hardware address",
+        )
+    })
+}
+
+/// Call `callback` with the name and hardware address of every Ethernet like interface.
+fn for_each_link<F>(mut callback: F) -> io::Result<()>
+where
+    F: FnMut(&str, [u8; 6]) -> io::Result<()>,
+{
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut result = Ok(());
+    let mut current = addrs;
+    while !current.is_null() && result.is_ok() {
+        let ifaddr = unsafe { &*current };
+        current = ifaddr.ifa_next;
+
+        if ifaddr.ifa_addr.is_null()
+            || i32::from(unsafe { (*ifaddr.ifa_addr).sa_family }) != libc::AF_PACKET
+        {
+            continue;
+        }
+
+        let link = unsafe { &*(ifaddr.ifa_addr as *const libc::sockaddr_ll) };
+        if link.sll_halen != 6 {
+            continue;
+        }
+
+        let mut address = [0u8; 6];
+        address.copy_from_slice(&link.sll_addr[..6]);
+        let name = unsafe { CStr::from_ptr(ifaddr.ifa_name) }.to_string_lossy();
+
+        result = callback(&name, address);
+    }
+
+    unsafe { libc::freeifaddrs(addrs) };
+    result
+}
+
+/// Type of a network interface, as far as binding a PPPoE socket is concerned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkKind {
+    /// A physical interface or anything else which is not listed below
+    Ethernet,
+    Bridge,
+    Bond,
+    /// A VLAN sub-interface on top of `lower`
+    Vlan {
+        lower: Option<String>,
+    },
+}
+
+/// Properties of an interface read from sysfs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkInfo {
+    pub kind: LinkKind,
+    /// The bridge or bond the interface is enslaved to
+    pub master: Option<Master>,
+    /// Whether the operational state is `up`
+    pub up: bool,
+}
+
+/// A bridge or bond an interface is enslaved to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Master {
+    Bridge(String),
+    Bond(String),
+}
+
+impl Master {
+    pub fn name(&self) -> &str {
+        match self {
+            Master::Bridge(name) | Master::Bond(name) => name,
+        }
+    }
+}
+
+/// Configurations which commonly make the discovery stage time out without a PADO
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindingWarning {
+    /// The interface is a port of the given bridge
+    BridgePort(String),
+    /// The interface is a slave of the given bond, replies may arrive on another slave
+    BondSlave(String),
+    /// The bridge filters VLANs, untagged frames only reach it for its PVID
+    VlanFilteringBridge,
+    /// The given interface (the bound one or the lower device of a VLAN) is not up
+    LinkDown(String),
+}
+
+impl BindingWarning {
+    /// Get a hint on how to fix the configuration
+    pub fn hint(&self) -> String {
+        match self {
+            BindingWarning::BridgePort(bridge) => {
+                format!("bind to the bridge `{}` instead of its port", bridge)
+            }
+            BindingWarning::BondSlave(bond) => {
+                format!("bind to the bond `{}` instead of its slave", bond)
+            }
+            BindingWarning::VlanFilteringBridge => {
+                "bind to a VLAN sub-interface of the bridge or make the PPPoE VLAN its PVID"
+                    .to_owned()
+            }
+            BindingWarning::LinkDown(name) => format!("bring `{}` up", name),
+        }
+    }
+}
+
+/// Read the type, master and state of an interface from `/sys/class/net`.
+pub fn link_info(name: &str) -> io::Result<LinkInfo> {
+    link_info_in(Path::new(SYSFS_NET), name)
+}
+
+/// Check an interface for configurations which commonly prevent discovery.
+///
+/// Binding to a bridge port or a bond slave bypasses the master, so frames are send on a
+/// single port while the replies may be delivered elsewhere.  An empty list doesn't mean that
+/// discovery works, only that none of the known pitfalls apply.
+pub fn binding_warnings(name: &str) -> io::Result<Vec<BindingWarning>> {
+    binding_warnings_in(Path::new(SYSFS_NET), name)
+}
+
+/// Get the name of the interface a PPPoE socket should be bound to instead of `name`.
+///
+/// This is the bridge or bond `name` is enslaved to, or `name` itself.
+pub fn binding_interface(name: &str) -> io::Result<String> {
+    Ok(match link_info(name)?.master {
+        Some(master) => master.name().to_owned(),
+        None => name.to_owned(),
+    })
+}
+
+/// Configuration of a VLAN sub-interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VlanConfig {
+    pub id: u16,
+    /// Whether the kernel removes the tag from received frames.  Without it frames received on
+    /// the sub-interface still carry their 802.1Q header.
+    pub reorder_hdr: bool,
+}
+
+/// Read the configuration of a VLAN sub-interface from `/proc/net/vlan`.
+///
+/// The interface is looked up in the network namespace of the calling thread.  Returns `None`
+/// for other interfaces, and if the `8021q` module is not loaded.
+pub fn vlan_config(name: &str) -> io::Result<Option<VlanConfig>> {
+    vlan_config_in(Path::new(PROC_NET_VLAN), name)
+}
+
+fn vlan_config_in(proc_net_vlan: &Path, name: &str) -> io::Result<Option<VlanConfig>> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid interface name",
+        ));
+    }
+    let contents = match fs::read_to_string(proc_net_vlan.join(name)) {
+        Ok(contents) => contents,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    // e.g. "eth0.835  VID: 835  REORDER_HDR: 1  dev->priv_flags: 1021"
+    let mut id = None;
+    let mut reorder_hdr = true;
+    let mut words = contents.lines().next().unwrap_or("").split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "VID:" => id = words.next().and_then(|id| id.parse().ok()),
+            "REORDER_HDR:" => reorder_hdr = words.next() != Some("0"),
+            _ => (),
+        }
+    }
+
+    match id {
+        Some(id) => Ok(Some(VlanConfig { id, reorder_hdr })),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no VLAN id in /proc/net/vlan",
+        )),
+    }
+}
+
+fn link_info_in(sysfs: &Path, name: &str) -> io::Result<LinkInfo> {
+    let dir = sysfs.join(name);
+
+    let uevent = fs::read_to_string(dir.join("uevent"))?;
+    let devtype = uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("DEVTYPE="))
+        .unwrap_or("");
+    let kind = match devtype {
+        "bridge" => LinkKind::Bridge,
+        "bond" => LinkKind::Bond,
+        "vlan" => {
+            let mut lower = None;
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?.file_name();
+                if let Some(name) = entry.to_string_lossy().strip_prefix("lower_") {
+                    lower = Some(name.to_owned());
+                    break;
+                }
+            }
+            LinkKind::Vlan { lower }
+        }
+        _ => LinkKind::Ethernet,
+    };
+
+    let master = match fs::read_link(dir.join("master")) {
+        Ok(target) => {
+            let master = target
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if dir.join("brport").exists() {
+                Some(Master::Bridge(master))
+            } else if dir.join("bonding_slave").exists() {
+                Some(Master::Bond(master))
+            } else {
+                None
+            }
+        }
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err),
+    };
+
+    let up = fs::read_to_string(dir.join("operstate"))?.trim() == "up";
+
+    Ok(LinkInfo { kind, master, up })
+}
+
+fn binding_warnings_in(sysfs: &Path, name: &str) -> io::Result<Vec<BindingWarning>> {
+    let info = link_info_in(sysfs, name)?;
+    let mut warnings = Vec::new();
+
+    match info.master {
+        Some(Master::Bridge(bridge)) => warnings.push(BindingWarning::BridgePort(bridge)),
+        Some(Master::Bond(bond)) => warnings.push(BindingWarning::BondSlave(bond)),
+        None => (),
+    }
+
+    if !info.up {
+        warnings.push(BindingWarning::LinkDown(name.to_owned()));
+    }
+
+    let lower = match info.kind {
+        LinkKind::Bridge => {
+            let path = sysfs.join(name).join("bridge/vlan_filtering");
+            if fs::read_to_string(path)?.trim() == "1" {
+                warnings.push(BindingWarning::VlanFilteringBridge);
+            }
+            None
+        }
+        LinkKind::Vlan { lower } => lower,
+        _ => None,
+    };
+    if let Some(lower) = lower {
+        if !link_info_in(sysfs, &lower)?.up {
+            warnings.push(BindingWarning::LinkDown(lower));
+        }
+    }
+
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_link(sysfs: &Path, name: &str, devtype: Option<&str>, up: bool) {
+        let dir = sysfs.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        let uevent = match devtype {
+            Some(devtype) => format!("DEVTYPE={}\nINTERFACE={}\n", devtype, name),
+            None => format!("INTERFACE={}\n", name),
+        };
+        fs::write(dir.join("uevent"), uevent).unwrap();
+        fs::write(dir.join("operstate"), if up { "up\n" } else { "down\n" }).unwrap();
+    }
+
+    fn enslave(sysfs: &Path, name: &str, master: &str, marker: &str) {
+        let dir = sysfs.join(name);
+        std::os::unix::fs::symlink(sysfs.join(master), dir.join("master")).unwrap();
+        fs::create_dir(dir.join(marker)).unwrap();
+    }
+
+    #[test]
+    fn detect_stacked_interfaces() {
+        let sysfs = std::env::temp_dir().join(format!("pppoe-sysfs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&sysfs);
+
+        add_link(&sysfs, "eth0", None, true);
+        add_link(&sysfs, "eth1", None, false);
+        add_link(&sysfs, "eth2", None, true);
+        add_link(&sysfs, "br0", Some("bridge"), true);
+        fs::create_dir(sysfs.join("br0/bridge")).unwrap();
+        fs::write(sysfs.join("br0/bridge/vlan_filtering"), "1\n").unwrap();
+        add_link(&sysfs, "bond0", Some("bond"), true);
+        add_link(&sysfs, "eth1.7", Some("vlan"), true);
+        std::os::unix::fs::symlink(sysfs.join("eth1"), sysfs.join("eth1.7/lower_eth1")).unwrap();
+        enslave(&sysfs, "eth0", "br0", "brport");
+        enslave(&sysfs, "eth2", "bond0", "bonding_slave");
+
+        let info = link_info_in(&sysfs, "eth1.7").unwrap();
+        assert_eq!(
+            info.kind,
+            LinkKind::Vlan {
+                lower: Some("eth1".to_owned())
+            }
+        );
+        assert_eq!(
+            binding_warnings_in(&sysfs, "eth1.7").unwrap(),
+            [BindingWarning::LinkDown("eth1".to_owned())]
+        );
+
+        let info = link_info_in(&sysfs, "eth0").unwrap();
+        assert_eq!(info.kind, LinkKind::Ethernet);
+        assert_eq!(info.master, Some(Master::Bridge("br0".to_owned())));
+        assert_eq!(
+            binding_warnings_in(&sysfs, "eth2").unwrap(),
+            [BindingWarning::BondSlave("bond0".to_owned())]
+        );
+        assert_eq!(
+            binding_warnings_in(&sysfs, "br0").unwrap(),
+            [BindingWarning::VlanFilteringBridge]
+        );
+        assert_eq!(link_info_in(&sysfs, "bond0").unwrap().kind, LinkKind::Bond);
+
+        fs::remove_dir_all(&sysfs).unwrap();
+    }
+
+    #[test]
+    fn read_vlan_config() {
+        let proc_net_vlan =
+            std::env::temp_dir().join(format!("pppoe-proc-vlan-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&proc_net_vlan);
+        fs::create_dir_all(&proc_net_vlan).unwrap();
+        fs::write(
+            proc_net_vlan.join("eth0.835"),
+            "eth0.835  VID: 835\t REORDER_HDR: 0  dev->priv_flags: 1021\n\
+             \x20        total frames received            0\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            vlan_config_in(&proc_net_vlan, "eth0.835").unwrap(),
+            Some(VlanConfig {
+                id: 835,
+                reorder_hdr: false
+            })
+        );
+        assert_eq!(vlan_config_in(&proc_net_vlan, "eth0").unwrap(), None);
+        assert!(vlan_config_in(&proc_net_vlan, "../eth0").is_err());
+
+        fs::remove_dir_all(&proc_net_vlan).unwrap();
+    }
+}
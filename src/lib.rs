@@ -1,26 +1,104 @@
+#[macro_use]
+mod logging;
+
+#[cfg(feature = "log")]
+pub use log;
+
 #[cfg(feature = "socket")]
 pub mod socket;
 #[cfg(feature = "socket")]
 pub use socket::Socket;
 
+#[cfg(feature = "socket")]
+pub mod dispatch;
+#[cfg(feature = "socket")]
+pub mod filter;
+#[cfg(feature = "socket")]
+pub mod handoff;
+#[cfg(feature = "socket")]
+pub mod interface;
+#[cfg(feature = "socket")]
+mod uapi;
+
+#[cfg(feature = "capture")]
+pub mod capture;
+
+#[cfg(feature = "xdp")]
+pub mod xdp;
+
+pub mod analysis;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+
+pub mod buffer;
+pub use buffer::StackBuffer;
+
 pub mod header;
-pub use header::{Code, Header, HeaderBuilder};
+pub use header::{
+    Code, Header, HeaderBuf, HeaderBuilder, HeaderExt, ParserConfig, ParserConfigExt, TagEdit,
+};
 
 pub mod packet;
-pub use packet::{Packet, PacketBuilder};
+pub use packet::{OwnedPacket, Packet, PacketBuilder, SessionBatch, SessionPacketBuilder};
+
+pub mod consts;
+pub mod cookie;
+
+pub mod discovery;
+#[cfg(feature = "socket")]
+pub use discovery::discover;
+pub use discovery::{
+    CachedOffer, Discovery, DiscoveryOptions, DiscoveryReport, OfferCache, SessionInfo,
+};
+
+pub mod diff;
+pub use diff::{diff, PacketDiff};
 
 pub mod error;
 pub mod eth;
+pub mod guard;
+pub use guard::DiscoveryGuard;
+pub mod hex;
+pub mod mock;
+pub mod mss;
+
+pub mod server;
+pub use server::{AllocationStrategy, SessionIdAllocator};
+
+pub mod summary;
+pub use summary::PacketSummary;
+
+pub mod tap;
+
+pub mod poll;
+pub mod ppp;
+pub mod prelude;
+pub mod profiles;
+pub mod scheduler;
+pub mod secret;
+pub mod sequence;
+
+pub mod services;
+pub use services::ServiceDiscovery;
+
+pub mod stats;
+
+pub mod timestamp;
+pub use timestamp::Timestamp;
 
 mod tags;
-#[cfg(features = "tr101")]
-pub use tag::Tr101Information;
-pub use tags::*;
+pub use tags::tag;
+pub use tags::{
+    DataRateScalar, Metrics, ServiceNameMatcher, Tag, TagBuf, TagExt, TagIterator, VendorTagBuilder,
+};
+
+#[cfg(feature = "tr101")]
+pub use tags::{AccessLoopEncapsulation, DataLink, Encaps1, Encaps2, Tr101Information};
 
-#[cfg(test)]
+#[cfg(all(test, feature = "socket", feature = "tr101"))]
 mod tests {
     use super::*;
-    #[cfg(feature = "tr101")]
     #[test]
     fn send_packet() {
         let sock = Socket::on_interface("pppoe");
@@ -57,13 +135,13 @@ mod tests {
         assert!(ret.is_ok());
 
         let len = sock.recv(&mut receive_buffer[..]).unwrap();
-        let pado = Packet::with_buffer(&mut receive_buffer[..len]).unwrap();
+        let pado = Packet::with_buffer(&receive_buffer[..len]).unwrap();
 
         {
             let dst = pado.ethernet_header().src_address();
             packet.ethernet_header().set_dst_address(dst);
+            packet.set_code(Code::Padr);
             let pppoe_header = packet.pppoe_header();
-            pppoe_header.set_code(Code::Padr);
             pppoe_header.clear_eol();
 
             for tag in pado.pppoe_header().tags() {
@@ -0,0 +1,36 @@
+//! Internal logging macros
+//!
+//! With the `log` feature the macros forward to the `log` crate, otherwise they expand to
+//! nothing.  The targets are named after the modules: `pppoe::discovery` and `pppoe::socket`.
+
+pub(crate) const DISCOVERY: &str = "pppoe::discovery";
+#[cfg(feature = "socket")]
+pub(crate) const SOCKET: &str = "pppoe::socket";
+
+#[cfg(feature = "socket")]
+macro_rules! warn {
+    (target: $target:expr, $($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::warn!(target: $target, $($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = ($target, format_args!($($arg)+));
+    };
+}
+
+macro_rules! debug {
+    (target: $target:expr, $($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::debug!(target: $target, $($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = ($target, format_args!($($arg)+));
+    };
+}
+
+macro_rules! trace {
+    (target: $target:expr, $($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::trace!(target: $target, $($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = ($target, format_args!($($arg)+));
+    };
+}
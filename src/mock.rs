@@ -0,0 +1,219 @@
+//! An in-memory transport and frame corruption for testing the state machines
+//!
+//! `MockTransport` stands in for a `Socket`: frames injected with `inject_raw` are handed to the
+//! state machine, frames it transmits are recorded.  Together with `corrupt` this allows to feed
+//! systematically broken frames into e.g. `Discovery` to exercise its error handling.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::consts::{ETHERNET_HEADER_LEN, MAX_FRAME_LEN};
+use crate::error::{Error, ParseError};
+use crate::Discovery;
+
+const PPPOE_OFFSET: usize = ETHERNET_HEADER_LEN;
+const TAGS_OFFSET: usize = PPPOE_OFFSET + 6;
+
+/// A change to a single field of a valid discovery frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corruption {
+    EtherType(u16),
+    VersionType(u8),
+    Code(u8),
+    /// Set the PPPoE payload length without changing the payload
+    PayloadLength(u16),
+    /// Set the length field of the `index`th tag without changing its value
+    TagLength {
+        index: usize,
+        length: u16,
+    },
+    /// Cut the frame after `keep` bytes of the value of the `index`th tag
+    TruncateTag {
+        index: usize,
+        keep: usize,
+    },
+    /// Cut the frame after `len` bytes
+    Truncate(usize),
+}
+
+/// Apply `corruption` to a copy of `frame`.
+///
+/// Fails if the frame is too short for the field or doesn't have enough tags.
+pub fn corrupt(frame: &[u8], corruption: Corruption) -> Result<Vec<u8>, ParseError> {
+    let mut frame = frame.to_vec();
+    if frame.len() < TAGS_OFFSET {
+        return Err(ParseError::BufferTooSmall(frame.len()));
+    }
+
+    match corruption {
+        Corruption::EtherType(ether_type) => NE::write_u16(&mut frame[12..], ether_type),
+        Corruption::VersionType(version_type) => frame[PPPOE_OFFSET] = version_type,
+        Corruption::Code(code) => frame[PPPOE_OFFSET + 1] = code,
+        Corruption::PayloadLength(length) => NE::write_u16(&mut frame[PPPOE_OFFSET + 4..], length),
+        Corruption::TagLength { index, length } => {
+            let offset = tag_offset(&frame, index)?;
+            NE::write_u16(&mut frame[offset + 2..], length);
+        }
+        Corruption::TruncateTag { index, keep } => {
+            let offset = tag_offset(&frame, index)?;
+            frame.truncate(offset + 4 + keep);
+        }
+        Corruption::Truncate(len) => frame.truncate(len),
+    }
+    Ok(frame)
+}
+
+/// Get the offset of the header of the `index`th tag.
+fn tag_offset(frame: &[u8], index: usize) -> Result<usize, ParseError> {
+    let mut offset = TAGS_OFFSET;
+    for tag_count in 0..=index {
+        if offset + 4 > frame.len() {
+            return Err(ParseError::TagIndexOutOfBound { index, tag_count });
+        }
+        if tag_count == index {
+            break;
+        }
+        offset += 4 + usize::from(NE::read_u16(&frame[offset + 2..]));
+    }
+    Ok(offset)
+}
+
+/// Frames going to and coming from a state machine, without a network
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    inbound: VecDeque<Vec<u8>>,
+    sent: Vec<Vec<u8>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a frame for reception as it is, without any validation.
+    pub fn inject_raw(&mut self, frame: &[u8]) {
+        self.inbound.push_back(frame.to_vec());
+    }
+
+    /// Queue a copy of `frame` with `corruption` applied.
+    pub fn inject_corrupted(
+        &mut self,
+        frame: &[u8],
+        corruption: Corruption,
+    ) -> Result<(), ParseError> {
+        self.inbound.push_back(corrupt(frame, corruption)?);
+        Ok(())
+    }
+
+    /// Number of frames waiting for reception
+    pub fn pending(&self) -> usize {
+        self.inbound.len()
+    }
+
+    /// Take the next injected frame.
+    pub fn recv(&mut self) -> Option<Vec<u8>> {
+        self.inbound.pop_front()
+    }
+
+    /// Record a transmitted frame.
+    pub fn send(&mut self, frame: &[u8]) {
+        self.sent.push(frame.to_vec());
+    }
+
+    /// Get all frames transmitted so far.
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+
+    /// Take the frames transmitted so far.
+    pub fn take_sent(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.sent)
+    }
+
+    /// Let `discovery` transmit, then hand it all injected frames, then let it transmit again.
+    ///
+    /// Stops at the first error of `handle_frame` or `poll_transmit` and leaves the remaining
+    /// frames queued.
+    pub fn drive_discovery(
+        &mut self,
+        discovery: &mut Discovery,
+        now: Instant,
+    ) -> Result<(), Error> {
+        let mut buffer = [0u8; MAX_FRAME_LEN];
+        self.transmit(discovery, now, &mut buffer)?;
+        while let Some(frame) = self.recv() {
+            discovery.handle_frame(now, &frame)?;
+        }
+        self.transmit(discovery, now, &mut buffer)
+    }
+
+    fn transmit(
+        &mut self,
+        discovery: &mut Discovery,
+        now: Instant,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        while let Some(len) = discovery.poll_transmit(now, buffer)? {
+            self.send(&buffer[..len]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Code, DiscoveryOptions, PacketBuilder, Tag};
+
+    const CLIENT: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const AC: [u8; 6] = [0x02, 0, 0, 0, 0, 2];
+
+    #[test]
+    fn ignore_corrupted_pados() {
+        let mut buffer = [0u8; 128];
+        let mut pado = PacketBuilder::new_discovery_packet(&mut buffer, AC, CLIENT).unwrap();
+        pado.set_code(Code::Pado);
+        pado.pppoe_header().add_tag(Tag::ServiceName(b"")).unwrap();
+        pado.pppoe_header().add_tag(Tag::AcName(b"ac")).unwrap();
+        let pado = pado.as_bytes();
+
+        let now = Instant::now();
+        let mut discovery = Discovery::new(CLIENT, DiscoveryOptions::default());
+        let mut transport = MockTransport::new();
+        transport.drive_discovery(&mut discovery, now).unwrap();
+        assert_eq!(transport.take_sent().len(), 1);
+
+        let corruptions = [
+            Corruption::EtherType(0x0800),
+            Corruption::VersionType(0x21),
+            Corruption::Code(Code::Pads as u8),
+            Corruption::PayloadLength(0xff),
+            Corruption::TagLength {
+                index: 1,
+                length: 0x100,
+            },
+            Corruption::TruncateTag { index: 1, keep: 1 },
+            Corruption::Truncate(18),
+        ];
+        for &corruption in &corruptions {
+            transport.inject_corrupted(pado, corruption).unwrap();
+        }
+        assert_eq!(
+            corrupt(pado, Corruption::TruncateTag { index: 2, keep: 0 }),
+            Err(ParseError::TagIndexOutOfBound {
+                index: 2,
+                tag_count: 2
+            })
+        );
+        transport.drive_discovery(&mut discovery, now).unwrap();
+        assert!(transport.sent().is_empty());
+        assert_eq!(transport.pending(), 0);
+
+        transport.inject_raw(pado);
+        transport.drive_discovery(&mut discovery, now).unwrap();
+        let padr = crate::Packet::with_buffer(&transport.sent()[0]).unwrap();
+        assert_eq!(padr.pppoe_header().code(), crate::header::PADR);
+    }
+}
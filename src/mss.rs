@@ -0,0 +1,159 @@
+//! TCP MSS clamping for IP packets forwarded over a PPPoE session
+//!
+//! Hosts behind a PPPoE router assume an Ethernet MTU, so TCP connections stall once full sized
+//! segments are send and the ICMP "fragmentation needed" messages get lost.  Clamping the MSS
+//! option of SYN segments to the session MRU avoids that.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+const IPPROTO_TCP: u8 = 6;
+const TCP_FLAG_SYN: u8 = 0x02;
+const TCP_OPTION_END: u8 = 0;
+const TCP_OPTION_NOP: u8 = 1;
+const TCP_OPTION_MSS: u8 = 2;
+
+/// Get the largest MSS for TCP over IPv4 (or IPv6) for a session with the given MRU.
+pub fn mss_for_mru(mru: u16, ipv6: bool) -> u16 {
+    // IP + TCP header without options
+    let headers = if ipv6 { 40 + 20 } else { 20 + 20 };
+    mru.saturating_sub(headers)
+}
+
+/// Lower the MSS option of a TCP SYN segment in an IPv4 or IPv6 packet to `max_mss`.
+///
+/// The TCP checksum is updated incrementally.  Returns `true` if the packet was changed.
+/// Anything which is not an unfragmented TCP SYN with an MSS option above `max_mss` is left as
+/// is, including IPv6 packets with extension headers.
+pub fn clamp_mss(packet: &mut [u8], max_mss: u16) -> bool {
+    let (protocol, header_len) = match packet.first().map(|byte| byte >> 4) {
+        Some(4) if packet.len() >= 20 => {
+            // only the first fragment contains the TCP header
+            if NE::read_u16(&packet[6..]) & 0x1fff != 0 {
+                return false;
+            }
+            (packet[9], usize::from(packet[0] & 0x0f) * 4)
+        }
+        Some(6) if packet.len() >= 40 => (packet[6], 40),
+        _ => return false,
+    };
+    if protocol != IPPROTO_TCP || header_len < 20 || packet.len() < header_len + 20 {
+        return false;
+    }
+
+    let tcp = &mut packet[header_len..];
+    if tcp[13] & TCP_FLAG_SYN == 0 {
+        return false;
+    }
+    let data_offset = usize::from(tcp[12] >> 4) * 4;
+    if data_offset < 20 || data_offset > tcp.len() {
+        return false;
+    }
+
+    let mut offset = 20;
+    while offset < data_offset {
+        match tcp[offset] {
+            TCP_OPTION_END => return false,
+            TCP_OPTION_NOP => offset += 1,
+            kind => {
+                if offset + 1 >= data_offset {
+                    return false;
+                }
+                let len = usize::from(tcp[offset + 1]);
+                if len < 2 || offset + len > data_offset {
+                    return false;
+                }
+
+                if kind == TCP_OPTION_MSS && len == 4 {
+                    let mss = NE::read_u16(&tcp[offset + 2..]);
+                    if mss <= max_mss {
+                        return false;
+                    }
+                    NE::write_u16(&mut tcp[offset + 2..], max_mss);
+                    let checksum = update_checksum(NE::read_u16(&tcp[16..]), mss, max_mss);
+                    NE::write_u16(&mut tcp[16..], checksum);
+                    return true;
+                }
+                offset += len;
+            }
+        }
+    }
+    false
+}
+
+/// Incremental checksum update as described in RFC 1624, equation 3
+fn update_checksum(checksum: u16, old: u16, new: u16) -> u16 {
+    let mut sum = u32::from(!checksum) + u32::from(!old) + u32::from(new);
+    sum = (sum & 0xffff) + (sum >> 16);
+    sum = (sum & 0xffff) + (sum >> 16);
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ones_complement_sum(data: &[u8], mut sum: u32) -> u32 {
+        for chunk in data.chunks(2) {
+            let word = if chunk.len() == 2 {
+                NE::read_u16(chunk)
+            } else {
+                u16::from(chunk[0]) << 8
+            };
+            sum += u32::from(word);
+        }
+        sum
+    }
+
+    fn tcp_checksum(packet: &[u8]) -> u16 {
+        let tcp = &packet[20..];
+        let mut sum = ones_complement_sum(&packet[12..20], 0);
+        sum += u32::from(IPPROTO_TCP) + tcp.len() as u32;
+        sum = ones_complement_sum(&tcp[..16], sum);
+        sum = ones_complement_sum(&tcp[18..], sum);
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+
+    fn ipv4_syn(mss: u16) -> Vec<u8> {
+        let mut packet = vec![0u8; 20 + 28];
+        packet[0] = 0x45;
+        packet[9] = IPPROTO_TCP;
+        packet[12..16].copy_from_slice(&[192, 0, 2, 1]);
+        packet[16..20].copy_from_slice(&[198, 51, 100, 7]);
+
+        let tcp = &mut packet[20..];
+        NE::write_u16(&mut tcp[0..], 40000);
+        NE::write_u16(&mut tcp[2..], 443);
+        tcp[12] = 7 << 4;
+        tcp[13] = TCP_FLAG_SYN;
+        // NOP, NOP, SACK permitted, MSS
+        tcp[20..28].copy_from_slice(&[1, 1, 4, 2, 2, 4, 0, 0]);
+        NE::write_u16(&mut tcp[26..], mss);
+
+        let checksum = tcp_checksum(&packet);
+        NE::write_u16(&mut packet[36..], checksum);
+        packet
+    }
+
+    #[test]
+    fn clamp_ipv4_syn() {
+        let max_mss = mss_for_mru(1492, false);
+        assert_eq!(max_mss, 1452);
+
+        let mut packet = ipv4_syn(1460);
+        assert!(clamp_mss(&mut packet, max_mss));
+        assert_eq!(NE::read_u16(&packet[46..]), 1452);
+        assert_eq!(NE::read_u16(&packet[36..]), tcp_checksum(&packet));
+
+        // already small enough
+        let mut packet = ipv4_syn(1400);
+        assert!(!clamp_mss(&mut packet, max_mss));
+
+        // not a SYN
+        let mut packet = ipv4_syn(1460);
+        packet[33] = 0x10;
+        assert!(!clamp_mss(&mut packet, max_mss));
+    }
+}
@@ -1,14 +1,19 @@
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use crate::consts::{MIN_FRAME_LEN, VERSION_TYPE};
 use crate::error::*;
-use crate::{self as pppoe, eth};
+use crate::{self as pppoe, eth, Tag};
 
+use std::cell::OnceCell;
+use std::convert::TryFrom;
+use std::num::NonZeroU16;
+use std::ops::Range;
 use std::slice;
 
-pub const PPPOE_DISCOVERY: u16 = 0x8863;
-pub const PPPOE_SESSION: u16 = 0x8864;
+pub use pppoe_core::consts::{PPPOE_DISCOVERY, PPPOE_SESSION, SESSION_DATA_CODE};
 
 fn ensure_minimal_buffer_size(buffer: &[u8]) -> Result<(), ParseError> {
-    // minimal eth + pppoe header size
-    if buffer.len() < 20 {
+    if buffer.len() < MIN_FRAME_LEN {
         return Err(ParseError::BufferTooSmall(buffer.len()));
     }
     Ok(())
@@ -17,6 +22,10 @@ fn ensure_minimal_buffer_size(buffer: &[u8]) -> Result<(), ParseError> {
 /// A (valid) PPPoE Packet
 #[derive(Debug)]
 pub struct Packet<'a> {
+    // the complete frame, unless the Packet was assembled from its layers
+    buffer: Option<&'a [u8]>,
+    // the layers copied together, only once `as_bytes` is needed for an assembled Packet
+    joined: OnceCell<Box<[u8]>>,
     ethernet: eth::Header<'a>,
     pppoe: pppoe::Header<'a>,
 }
@@ -27,17 +36,50 @@ impl<'a> Packet<'a> {
     /// The buffer is expected contain a valid Ethernet Packet (with an ethertype for PPPoE) and a
     /// PPPoE Packet.  Therefore the buffer must be greater than 20 bytes.
     pub fn with_buffer(buffer: &'a [u8]) -> Result<Self, Error> {
+        Self::with_config(buffer, &pppoe::ParserConfig::default())
+    }
+
+    pub(crate) fn with_config(
+        buffer: &'a [u8],
+        config: &pppoe::ParserConfig,
+    ) -> Result<Self, Error> {
         ensure_minimal_buffer_size(buffer)?;
         let (eth_buf, pppoe_buf) = buffer.split_at(14);
 
         Ok(Self {
+            buffer: Some(buffer),
+            joined: OnceCell::new(),
             ethernet: eth::Header::with_buffer(eth_buf)?,
-            pppoe: pppoe::Header::with_buffer(pppoe_buf)?,
+            pppoe: config.parse(pppoe_buf)?,
+        })
+    }
+
+    /// Create a PPPoE discovery Packet from an Ethernet header and a PPPoE packet in separate
+    /// buffers, e.g. as handed over layer by layer by a capture library.
+    ///
+    /// `eth` has to be exactly the 14 bytes of the Ethernet header, with the ethertype of PPPoE
+    /// discovery.  `pppoe` is validated like in `with_buffer`.  The headers borrow the parts,
+    /// they are only copied together if the complete frame is requested with `as_bytes`.
+    pub fn from_parts_bytes(eth: &'a [u8], pppoe: &'a [u8]) -> Result<Self, Error> {
+        if eth.len() != 14 {
+            return Err(ParseError::InvalidEthernetHeaderLength(eth.len()).into());
+        }
+        let ethernet = eth::Header::with_buffer(eth)?;
+        if ethernet.ether_type() != PPPOE_DISCOVERY {
+            return Err(ParseError::UnexpectedEtherType(ethernet.ether_type()).into());
+        }
+        let pppoe = pppoe::Header::with_buffer(pppoe)?;
+
+        Ok(Self {
+            buffer: None,
+            joined: OnceCell::new(),
+            ethernet,
+            pppoe,
         })
     }
 
     /// Get the PPPoE Header from the Packet
-    pub fn pppoe_header(&self) -> &pppoe::Header {
+    pub fn pppoe_header(&self) -> &pppoe::Header<'a> {
         &self.pppoe
     }
 
@@ -59,11 +101,218 @@ impl<'a> Packet<'a> {
     /// Get the Packet in byte representation.  The slice is a valid PPPoE Packet and can be send
     /// over an (raw) socket.
     pub fn as_bytes(&self) -> &[u8] {
-        let ptr = self.ethernet.dst_address().as_ptr();
-        unsafe { slice::from_raw_parts(ptr, self.len()) }
+        match self.buffer {
+            Some(buffer) => &buffer[..self.len()],
+            None => self.joined.get_or_init(|| {
+                let pppoe = &self.pppoe.get_ref()[..self.pppoe.len()];
+                [self.ethernet.as_bytes(), pppoe]
+                    .concat()
+                    .into_boxed_slice()
+            }),
+        }
+    }
+
+    /// Format the Packet like Wireshark's "Copy as Hex Dump", see `hex::to_hex_dump`.
+    pub fn to_hex_dump(&self) -> String {
+        crate::hex::to_hex_dump(self.as_bytes())
+    }
+
+    /// Parse a Packet from a hex dump into `buffer`, see `hex::from_hex_str` for the accepted
+    /// formats.
+    pub fn from_hex_str(hex: &str, buffer: &'a mut [u8]) -> Result<Self, Error> {
+        let len = crate::hex::from_hex_str(hex, buffer)?;
+        Self::with_buffer(&buffer[..len])
+    }
+
+    /// Copy the Packet into an `OwnedPacket`, which doesn't borrow the receive buffer.
+    pub fn to_owned_packet(&self) -> OwnedPacket {
+        // keep the padding, unless the Packet was assembled from its layers without it
+        let buffer = match self.buffer {
+            Some(buffer) => &buffer[..(14 + self.pppoe.get_ref().len()).min(buffer.len())],
+            None => self.as_bytes(),
+        };
+        OwnedPacket {
+            buffer: buffer.into(),
+        }
+    }
+}
+
+/// A (valid) PPPoE Packet owning its buffer
+///
+/// Unlike `Packet` it can be send to other threads or tasks, e.g. through a channel.  The frame
+/// was validated when the `OwnedPacket` was created, the accessors just wrap the buffer again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedPacket {
+    // the complete frame, including the Ethernet padding
+    buffer: Box<[u8]>,
+}
+
+// a parsed packet is never empty
+#[allow(clippy::len_without_is_empty)]
+impl OwnedPacket {
+    /// Parse and copy a frame, see `Packet::with_buffer`.
+    pub fn with_buffer(buffer: &[u8]) -> Result<Self, Error> {
+        Ok(Packet::with_buffer(buffer)?.to_owned_packet())
+    }
+
+    /// Parse a frame with a non-default `ParserConfig` and copy it.
+    pub fn with_config(buffer: &[u8], config: &pppoe::ParserConfig) -> Result<Self, Error> {
+        Ok(Packet::with_config(buffer, config)?.to_owned_packet())
+    }
+
+    /// Borrow the Packet, e.g. to pass it to functions taking a `Packet`.
+    pub fn packet(&self) -> Packet<'_> {
+        Packet {
+            buffer: Some(&self.buffer),
+            joined: OnceCell::new(),
+            ethernet: self.ethernet_header(),
+            pppoe: self.pppoe_header(),
+        }
+    }
+
+    /// Get the PPPoE Header from the Packet
+    pub fn pppoe_header(&self) -> pppoe::Header<'_> {
+        pppoe::Header::new_unchecked(&self.buffer[14..])
+    }
+
+    /// Get the Ethernet Header from the Packet
+    pub fn ethernet_header(&self) -> eth::Header<'_> {
+        eth::Header::new_unchecked(&self.buffer[..14])
+    }
+
+    /// Get the total Packet length
+    pub fn len(&self) -> usize {
+        14 + self.pppoe_header().len()
+    }
+
+    /// Get the Packet in byte representation, see `Packet::as_bytes`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.len()]
+    }
+
+    /// Format the Packet like Wireshark's "Copy as Hex Dump", see `hex::to_hex_dump`.
+    pub fn to_hex_dump(&self) -> String {
+        crate::hex::to_hex_dump(self.as_bytes())
+    }
+
+    /// Get the complete received frame back, including any Ethernet padding.
+    pub fn into_inner(self) -> Box<[u8]> {
+        self.buffer
+    }
+}
+
+impl<'a> From<Packet<'a>> for OwnedPacket {
+    fn from(mut packet: Packet<'a>) -> Self {
+        match packet.joined.take() {
+            Some(buffer) => OwnedPacket { buffer },
+            None => packet.to_owned_packet(),
+        }
+    }
+}
+
+impl<'a> From<&Packet<'a>> for OwnedPacket {
+    fn from(packet: &Packet<'a>) -> Self {
+        packet.to_owned_packet()
+    }
+}
+
+#[cfg(feature = "socket")]
+impl<'a> Packet<'a> {
+    /// Forward a received frame to another host.
+    ///
+    /// Only the Ethernet addresses of the frame are rewritten in place, see
+    /// `prepare_forwarding`.  Returns the number of bytes send.
+    pub fn forward_into(
+        frame: &mut [u8],
+        socket: &crate::Socket,
+        new_dst_mac: [u8; 6],
+        new_src_mac: [u8; 6],
+    ) -> Result<usize, Error> {
+        let len = prepare_forwarding(frame, frame.len(), new_dst_mac, new_src_mac, None)?;
+        Ok(socket.send(&frame[..len])?)
+    }
+
+    /// Forward a received discovery frame to another host and insert a tag (e.g. a
+    /// Relay-Session-Id) on the way.
+    ///
+    /// `buffer` holds the received frame in its first `frame_len` bytes and needs enough spare
+    /// room for the tag.
+    pub fn forward_with_tag_into(
+        buffer: &mut [u8],
+        frame_len: usize,
+        tag: Tag,
+        socket: &crate::Socket,
+        new_dst_mac: [u8; 6],
+        new_src_mac: [u8; 6],
+    ) -> Result<usize, Error> {
+        let len = prepare_forwarding(buffer, frame_len, new_dst_mac, new_src_mac, Some(tag))?;
+        Ok(socket.send(&buffer[..len])?)
     }
 }
 
+/// Rewrite a received PPPoE frame in place for forwarding and return the length of the new frame.
+///
+/// The frame occupies the first `frame_len` bytes of `buffer`.  Only the Ethernet addresses are
+/// changed, trailing Ethernet padding is cut off.  If a tag is given (only valid for discovery
+/// frames), it is inserted in front of all other tags, which shifts the existing tags once.
+pub fn prepare_forwarding(
+    buffer: &mut [u8],
+    frame_len: usize,
+    new_dst_mac: [u8; 6],
+    new_src_mac: [u8; 6],
+    tag: Option<Tag>,
+) -> Result<usize, Error> {
+    let frame = &buffer[..frame_len.min(buffer.len())];
+    ensure_minimal_buffer_size(frame)?;
+
+    let ether_type = NE::read_u16(&frame[12..]);
+    if ether_type != PPPOE_DISCOVERY && ether_type != PPPOE_SESSION {
+        return Err(ParseError::UnexpectedEtherType(ether_type).into());
+    }
+
+    let payload_length = usize::from(NE::read_u16(&frame[18..]));
+    let mut len = MIN_FRAME_LEN + payload_length;
+    if len > frame.len() {
+        return Err(ParseError::PayloadLengthOutOfBound {
+            actual_packet_length: u16::try_from(frame.len() - MIN_FRAME_LEN).unwrap_or(u16::MAX),
+            payload_length: payload_length as u16,
+        }
+        .into());
+    }
+
+    if let Some(tag) = tag {
+        if ether_type != PPPOE_DISCOVERY {
+            return Err(ParseError::UnexpectedEtherType(ether_type).into());
+        }
+
+        let tag_length = tag.encoded_len();
+        let new_payload_length = u16::try_from(payload_length + tag_length).map_err(|_| {
+            ParseError::BufferTooSmallForTag {
+                available: u16::MAX - payload_length as u16,
+                requested: tag_length,
+            }
+        })?;
+        if len + tag_length > buffer.len() {
+            return Err(ParseError::BufferTooSmallForTag {
+                available: u16::try_from(buffer.len() - len).unwrap_or(u16::MAX),
+                requested: tag_length,
+            }
+            .into());
+        }
+
+        buffer.copy_within(MIN_FRAME_LEN..len, MIN_FRAME_LEN + tag_length);
+        tag.write(&mut buffer[MIN_FRAME_LEN..MIN_FRAME_LEN + tag_length])?;
+        NE::write_u16(&mut buffer[18..], new_payload_length);
+        len += tag_length;
+    }
+
+    let mut ethernet = eth::HeaderBuilder::with_buffer(&mut buffer[..14])?;
+    ethernet.set_dst_address(new_dst_mac);
+    ethernet.set_src_address(new_src_mac);
+
+    Ok(len)
+}
+
 /// A Builder to create PPPoE Packets
 ///
 /// The Builder is directly using the supplied buffer.  It is therefore possible to create
@@ -73,6 +322,19 @@ pub struct PacketBuilder<'a> {
     pppoe: pppoe::HeaderBuilder<'a>,
 }
 
+// The headers of a `PacketBuilder` are the two halves of one buffer split by `split_at_mut`, so
+// they can be joined into a single slice again.  The header accessors hand out `&mut` builders,
+// which could be swapped for builders of another buffer, hence the check.
+fn join_headers<'b>(ethernet: &'b [u8], pppoe: &'b [u8]) -> &'b [u8] {
+    assert_eq!(ethernet.as_ptr_range().end, pppoe.as_ptr());
+    unsafe { slice::from_raw_parts(ethernet.as_ptr(), ethernet.len() + pppoe.len()) }
+}
+
+fn join_headers_mut<'b>(ethernet: &'b mut [u8], pppoe: &'b mut [u8]) -> &'b mut [u8] {
+    assert_eq!(ethernet.as_ptr_range().end, pppoe.as_ptr());
+    unsafe { slice::from_raw_parts_mut(ethernet.as_mut_ptr(), ethernet.len() + pppoe.len()) }
+}
+
 impl<'a> PacketBuilder<'a> {
     /// Create a new PPPoE PADI
     pub fn new_discovery_packet(
@@ -114,6 +376,19 @@ impl<'a> PacketBuilder<'a> {
         &mut self.ethernet
     }
 
+    /// Change the PPPoE code while keeping the Ethernet Header and all tags.
+    pub fn set_code(&mut self, code: pppoe::Code) {
+        self.pppoe.set_code(code);
+    }
+
+    /// Remove all tags from the Packet.
+    ///
+    /// The Ethernet Header, the PPPoE code and the session id are kept, so the builder can be
+    /// reused for the next Packet of the discovery stage (e.g. a PADI retransmission or the PADR).
+    pub fn reset_tags(&mut self) {
+        self.pppoe.clear_payload();
+    }
+
     /// Get the Packet in byte representation.  The slice is a valid PPPoE Packet and can be send
     /// over an (raw) socket.
     ///
@@ -121,8 +396,8 @@ impl<'a> PacketBuilder<'a> {
     /// packet should be send, consider calling `build` to validate the current Packet and use the
     /// resulting Packet instead of this builder.
     pub fn as_bytes(&self) -> &[u8] {
-        let ptr = self.ethernet.as_bytes().as_ptr();
-        unsafe { slice::from_raw_parts(ptr, self.len()) }
+        let pppoe_len = self.pppoe.len();
+        join_headers(self.ethernet.as_bytes(), &self.pppoe.get_ref()[..pppoe_len])
     }
 
     /// Get the Packet in byte representation.  The slice is a valid PPPoE Packet and can be send
@@ -131,16 +406,492 @@ impl<'a> PacketBuilder<'a> {
     /// This function can be used to directly access the underlying buffer mutable. Only useful for
     /// to deliberately corrupting PPPoE Packets.
     pub fn as_mut_bytes(&mut self) -> &mut [u8] {
-        let ptr = self.ethernet.as_mut_bytes().as_mut_ptr();
-        // TODO: this could be undefined behaviour... need to check
-        unsafe { slice::from_raw_parts_mut(ptr, self.len()) }
+        let pppoe_len = self.pppoe.len();
+        join_headers_mut(
+            self.ethernet.as_mut_bytes(),
+            &mut self.pppoe.get_ref_mut()[..pppoe_len],
+        )
     }
 
     /// validate the currently build Packet and return a `Packet` on success.
     pub fn build(self) -> Result<Packet<'a>, Error> {
+        let pppoe_len = self.pppoe.len();
+        let eth_buf: &'a [u8] = self.ethernet.into_buffer();
+        let pppoe_buf: &'a [u8] = self.pppoe.into_buffer();
+        let ethernet = eth::Header::with_buffer(eth_buf)?;
+        let pppoe = pppoe::Header::with_buffer(pppoe_buf)?;
         Ok(Packet {
-            ethernet: self.ethernet.build()?,
-            pppoe: self.pppoe.build()?,
+            buffer: Some(join_headers(eth_buf, &pppoe_buf[..pppoe_len])),
+            joined: OnceCell::new(),
+            ethernet,
+            pppoe,
+        })
+    }
+
+    /// Create a new PPPoE Session Packet
+    ///
+    /// Unlike discovery packets, session packets carry an opaque PPP payload instead of tags.
+    /// The payload is written directly into the remaining buffer via
+    /// `SessionPacketBuilder::payload_mut` and the PPPoE length field is fixed once the payload
+    /// length is known.
+    pub fn new_session_packet(
+        buffer: &'a mut [u8],
+        src_mac: [u8; 6],
+        dst_mac: [u8; 6],
+        session_id: NonZeroU16,
+    ) -> Result<SessionPacketBuilder<'a>, Error> {
+        ensure_minimal_buffer_size(buffer)?;
+
+        {
+            let mut ethernet = eth::HeaderBuilder::with_buffer(&mut buffer[..14])?;
+            ethernet.set_src_address(src_mac);
+            ethernet.set_dst_address(dst_mac);
+            ethernet.set_ether_type(PPPOE_SESSION);
+        }
+
+        buffer[14] = VERSION_TYPE;
+        buffer[15] = SESSION_DATA_CODE;
+        NE::write_u16(&mut buffer[16..], u16::from(session_id));
+        NE::write_u16(&mut buffer[18..], 0);
+
+        Ok(SessionPacketBuilder { buffer })
+    }
+}
+
+/// A Builder to create PPPoE Session Packets
+///
+/// Created by `PacketBuilder::new_session_packet`.  The Ethernet and PPPoE headers are already
+/// written, only the payload has to be filled in before calling `finish`.
+pub struct SessionPacketBuilder<'a> {
+    buffer: &'a mut [u8],
+}
+
+impl<'a> SessionPacketBuilder<'a> {
+    /// Get the session id of the Packet
+    pub fn session_id(&self) -> u16 {
+        NE::read_u16(&self.buffer[16..])
+    }
+
+    /// Get the maximal payload length which fits into the buffer
+    pub fn payload_capacity(&self) -> usize {
+        self.buffer.len() - 20
+    }
+
+    /// Get the payload region of the buffer.  The region spans the whole remaining buffer, only
+    /// the first `payload_len` bytes passed to `finish` are part of the Packet.
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer[20..]
+    }
+
+    /// Set the PPPoE length field and return the complete Packet in byte representation.
+    pub fn finish(self, payload_len: usize) -> Result<&'a [u8], Error> {
+        let available = self.payload_capacity();
+        let length = match u16::try_from(payload_len) {
+            Ok(length) if payload_len <= available => length,
+            _ => {
+                return Err(ParseError::PayloadLengthOutOfBound {
+                    actual_packet_length: u16::try_from(available).unwrap_or(u16::MAX),
+                    payload_length: u16::try_from(payload_len).unwrap_or(u16::MAX),
+                }
+                .into())
+            }
+        };
+
+        NE::write_u16(&mut self.buffer[18..], length);
+        Ok(&self.buffer[..20 + payload_len])
+    }
+}
+
+/// A batch of session packets to a single peer
+///
+/// All packets are stored back to back in one allocation, which is reused after `clear`.  The
+/// batch can be send with `Socket::send_batch` using a single syscall.
+pub struct SessionBatch {
+    src_mac: [u8; 6],
+    dst_mac: [u8; 6],
+    session_id: NonZeroU16,
+    buffer: Vec<u8>,
+    frames: Vec<Range<usize>>,
+}
+
+impl SessionBatch {
+    pub fn new(src_mac: [u8; 6], dst_mac: [u8; 6], session_id: NonZeroU16) -> Self {
+        Self {
+            src_mac,
+            dst_mac,
+            session_id,
+            buffer: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Append a session packet carrying `payload`.
+    pub fn push(&mut self, payload: &[u8]) -> Result<(), Error> {
+        self.push_with(payload.len(), |buffer| {
+            buffer.copy_from_slice(payload);
+            payload.len()
         })
     }
+
+    /// Append a session packet whose payload is written by `callback`.
+    ///
+    /// The callback gets a payload region of `max_payload_len` bytes and returns the number of
+    /// bytes it actually wrote.
+    pub fn push_with<F>(&mut self, max_payload_len: usize, callback: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut [u8]) -> usize,
+    {
+        let start = self.buffer.len();
+        self.buffer.resize(start + 20 + max_payload_len, 0);
+
+        let result = (|| {
+            let mut builder = PacketBuilder::new_session_packet(
+                &mut self.buffer[start..],
+                self.src_mac,
+                self.dst_mac,
+                self.session_id,
+            )?;
+            let payload_len = callback(builder.payload_mut());
+            builder.finish(payload_len).map(<[u8]>::len)
+        })();
+
+        match result {
+            Ok(len) => {
+                self.buffer.truncate(start + len);
+                self.frames.push(start..start + len);
+                Ok(())
+            }
+            Err(err) => {
+                self.buffer.truncate(start);
+                Err(err)
+            }
+        }
+    }
+
+    /// Get the number of packets in the batch
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Iterate over all packets in byte representation
+    pub fn frames(&self) -> impl Iterator<Item = &[u8]> {
+        self.frames
+            .iter()
+            .map(move |range| &self.buffer[range.clone()])
+    }
+
+    /// Remove the first `count` packets, e.g. after they were send.
+    ///
+    /// The remaining packets are moved to the start of the buffer, so partial sends don't grow
+    /// it.
+    pub fn consume(&mut self, count: usize) {
+        self.frames.drain(..count.min(self.frames.len()));
+        let offset = match self.frames.first() {
+            Some(first) => first.start,
+            None => self.buffer.len(),
+        };
+        self.buffer.drain(..offset);
+        for range in &mut self.frames {
+            *range = range.start - offset..range.end - offset;
+        }
+    }
+
+    /// Remove all packets but keep the allocation.
+    pub fn clear(&mut self) {
+        self.consume(self.frames.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const DST: [u8; 6] = [0x02, 0, 0, 0, 0, 2];
+
+    #[test]
+    fn builder_bytes_span_both_headers() {
+        let mut buffer = [0u8; 64];
+        let start = buffer.as_ptr();
+        let mut builder = PacketBuilder::new_discovery_packet(&mut buffer, SRC, DST).unwrap();
+        builder
+            .pppoe_header()
+            .add_tag(Tag::ServiceName(b"a"))
+            .unwrap();
+        assert_eq!(builder.as_bytes().as_ptr(), start);
+        assert_eq!(builder.as_mut_bytes().len(), builder.len());
+        let packet = builder.build().unwrap();
+        assert_eq!(packet.as_bytes().as_ptr(), start);
+        assert_eq!(packet.as_bytes().len(), 14 + 6 + 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn swapped_header_builder() {
+        let mut buffer = [0u8; 64];
+        let mut other = [0u8; 14];
+        let mut builder = PacketBuilder::new_discovery_packet(&mut buffer, SRC, DST).unwrap();
+        let mut foreign = eth::HeaderBuilder::with_buffer(&mut other).unwrap();
+        std::mem::swap(builder.ethernet_header(), &mut foreign);
+        builder.as_bytes();
+    }
+
+    #[test]
+    fn send_owned_packet() {
+        fn assert_send<T: Send + 'static>(_: &T) {}
+
+        let mut buffer = [0u8; 64];
+        let mut builder = PacketBuilder::new_discovery_packet(&mut buffer, SRC, DST).unwrap();
+        builder
+            .pppoe_header()
+            .add_tag(Tag::ServiceName(b"a"))
+            .unwrap();
+        let len = builder.len();
+        // received with Ethernet padding
+        let owned = OwnedPacket::with_buffer(&buffer[..60]).unwrap();
+        assert_send(&owned);
+        assert!(OwnedPacket::with_buffer(&buffer[..len - 1]).is_err());
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || sender.send(owned).unwrap());
+        let owned = receiver.recv().unwrap();
+
+        assert_eq!(owned.len(), len);
+        assert_eq!(owned.as_bytes(), &buffer[..len]);
+        assert_eq!(owned.ethernet_header().src_address(), SRC);
+        assert_eq!(owned.pppoe_header().trailing_bytes().len(), 60 - len);
+        assert_eq!(
+            owned.packet().pppoe_header().tags().next(),
+            Some(Tag::ServiceName(b"a"))
+        );
+        let packet = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(OwnedPacket::from(packet).as_bytes(), owned.as_bytes());
+        assert_eq!(owned.into_inner().len(), 60);
+    }
+
+    #[test]
+    fn parse_split_layers() {
+        let mut buffer = [0u8; 64];
+        let len = {
+            let mut packet = PacketBuilder::new_discovery_packet(&mut buffer, SRC, DST).unwrap();
+            packet
+                .pppoe_header()
+                .add_tag(Tag::ServiceName(b"a"))
+                .unwrap();
+            packet.len()
+        };
+        let (eth, pppoe) = buffer.split_at(14);
+
+        // the PPPoE layer may come with the Ethernet padding
+        let packet = Packet::from_parts_bytes(eth, pppoe).unwrap();
+        // the layers are parsed where they are
+        assert_eq!(packet.pppoe_header().get_ref().as_ptr(), pppoe.as_ptr());
+        assert_eq!(packet.as_bytes(), &buffer[..len]);
+        assert_eq!(packet.ethernet_header().src_address(), SRC);
+        assert_eq!(
+            packet.pppoe_header().tags().next(),
+            Some(Tag::ServiceName(b"a"))
+        );
+        assert_eq!(packet.to_owned_packet().as_bytes(), &buffer[..len]);
+        // the joined layers are reused
+        let joined = packet.as_bytes().as_ptr();
+        assert_eq!(OwnedPacket::from(packet).as_bytes().as_ptr(), joined);
+
+        assert!(matches!(
+            Packet::from_parts_bytes(&eth[..12], pppoe),
+            Err(Error::ParseError(ParseError::InvalidEthernetHeaderLength(
+                12
+            )))
+        ));
+        let mut session = eth.to_vec();
+        session[13] = 0x64;
+        assert!(matches!(
+            Packet::from_parts_bytes(&session, pppoe),
+            Err(Error::ParseError(ParseError::UnexpectedEtherType(
+                PPPOE_SESSION
+            )))
+        ));
+        assert!(Packet::from_parts_bytes(eth, &pppoe[..len - 15]).is_err());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn parse_shared_bytes() {
+        let mut buffer = [0u8; 64];
+        let len = {
+            let mut packet =
+                PacketBuilder::new_discovery_packet(&mut buffer[..], SRC, DST).unwrap();
+            packet
+                .pppoe_header()
+                .add_tag(Tag::ServiceName(b"a"))
+                .unwrap();
+            packet.len()
+        };
+
+        let frame = bytes::Bytes::copy_from_slice(&buffer[..len]);
+        let logged = frame.clone();
+        let packet = Packet::with_buffer(&frame).unwrap();
+        assert_eq!(packet.ethernet_header().src_address(), SRC);
+        assert_eq!(packet.as_bytes().as_ptr(), logged.as_ptr());
+    }
+
+    #[test]
+    fn reuse_builder_for_padr() {
+        let mut buffer = [0u8; 100];
+        let mut packet = PacketBuilder::new_discovery_packet(&mut buffer[..], SRC, DST).unwrap();
+        packet
+            .pppoe_header()
+            .add_tag(Tag::ServiceName(b"a"))
+            .unwrap();
+        packet
+            .pppoe_header()
+            .add_tag(Tag::HostUniq(b"123"))
+            .unwrap();
+        assert_eq!(packet.len(), 32);
+
+        packet.ethernet_header().set_dst_address(SRC);
+        packet.set_code(pppoe::Code::Padr);
+        packet.reset_tags();
+        assert_eq!(packet.len(), 20);
+        packet
+            .pppoe_header()
+            .add_tag(Tag::ServiceName(b"b"))
+            .unwrap();
+
+        let packet = packet.build().unwrap();
+        assert_eq!(packet.ethernet_header().dst_address(), SRC);
+        assert_eq!(packet.ethernet_header().src_address(), SRC);
+        assert_eq!(packet.pppoe_header().code(), pppoe::header::PADR);
+        let tags: Vec<_> = packet.pppoe_header().tags().collect();
+        assert_eq!(tags, [Tag::ServiceName(b"b")]);
+    }
+
+    #[test]
+    fn forward_with_relay_session_id() {
+        let mut buffer = [0u8; 100];
+        let mut packet = PacketBuilder::new_discovery_packet(&mut buffer[..], SRC, DST).unwrap();
+        packet
+            .pppoe_header()
+            .add_tag(Tag::ServiceName(b"a"))
+            .unwrap();
+        assert_eq!(packet.len(), 25);
+        // received frames may carry Ethernet padding
+        let frame_len = 60;
+
+        let new_dst = [0x02, 0, 0, 0, 0, 3];
+        let len = prepare_forwarding(
+            &mut buffer[..],
+            frame_len,
+            new_dst,
+            DST,
+            Some(Tag::RelaySessionId(b"rs")),
+        )
+        .unwrap();
+        assert_eq!(len, 25 + 6);
+
+        let packet = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(packet.ethernet_header().dst_address(), new_dst);
+        assert_eq!(packet.ethernet_header().src_address(), DST);
+        let tags: Vec<_> = packet.pppoe_header().tags().collect();
+        assert_eq!(tags, [Tag::RelaySessionId(b"rs"), Tag::ServiceName(b"a")]);
+    }
+
+    #[test]
+    fn session_packet_length_is_fixed_on_finish() {
+        let mut buffer = [0u8; 100];
+        let session_id = NonZeroU16::new(0x1234).unwrap();
+        let mut builder =
+            PacketBuilder::new_session_packet(&mut buffer[..], SRC, DST, session_id).unwrap();
+        assert_eq!(builder.payload_capacity(), 80);
+        builder.payload_mut()[..4].copy_from_slice(&[0xc0, 0x21, 0x09, 0x01]);
+
+        let frame = builder.finish(4).unwrap();
+        assert_eq!(frame.len(), 24);
+        assert_eq!(&frame[..6], &DST);
+        assert_eq!(&frame[6..12], &SRC);
+        assert_eq!(NE::read_u16(&frame[12..]), PPPOE_SESSION);
+        assert_eq!(&frame[14..20], &[0x11, 0x00, 0x12, 0x34, 0x00, 0x04]);
+        assert_eq!(&frame[20..], &[0xc0, 0x21, 0x09, 0x01]);
+    }
+
+    #[test]
+    fn session_batch() {
+        let session_id = NonZeroU16::new(7).unwrap();
+        let mut batch = SessionBatch::new(SRC, DST, session_id);
+        batch.push(&[1, 2, 3]).unwrap();
+        batch
+            .push_with(100, |payload| {
+                payload[0] = 4;
+                1
+            })
+            .unwrap();
+        assert_eq!(batch.len(), 2);
+
+        let frames: Vec<_> = batch.frames().map(<[u8]>::to_vec).collect();
+        assert_eq!(frames[0].len(), 23);
+        assert_eq!(&frames[0][18..], &[0, 3, 1, 2, 3]);
+        assert_eq!(frames[1].len(), 21);
+        assert_eq!(&frames[1][18..], &[0, 1, 4]);
+
+        batch.consume(1);
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch.frames().next().unwrap(), &frames[1][..]);
+        assert_eq!(batch.buffer.len(), 21);
+        batch.push(&[5]).unwrap();
+        batch.consume(1);
+        assert_eq!(batch.frames().next().unwrap()[18..], [0, 1, 5]);
+        assert_eq!(batch.buffer.len(), 21);
+        batch.clear();
+        assert!(batch.buffer.is_empty());
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn jumbo_frames() {
+        let mut buffer = vec![0u8; 4000];
+        let cookie = vec![0x5a; 3950];
+        let mut packet = PacketBuilder::new_discovery_packet(&mut buffer[..], SRC, DST).unwrap();
+        packet.set_code(pppoe::Code::Pado);
+        let header = packet.pppoe_header();
+        header.add_tag(Tag::ServiceName(b"")).unwrap();
+        header.add_tag(Tag::AcName(b"ac")).unwrap();
+        header.add_tag(Tag::AcCookie(&cookie)).unwrap();
+        header.add_tag(Tag::PppMaxMtu(3980)).unwrap();
+        assert_eq!(packet.len(), 20 + 4 + 6 + 3954 + 6);
+
+        let packet = packet.build().unwrap();
+        let len = packet.len();
+        let packet = Packet::with_buffer(&buffer[..len]).unwrap();
+        let tags: Vec<_> = packet.pppoe_header().tags().collect();
+        assert_eq!(tags[2], Tag::AcCookie(&cookie));
+        assert_eq!(tags[3], Tag::PppMaxMtu(3980));
+
+        // a full 4000 byte session frame
+        let session_id = NonZeroU16::new(1).unwrap();
+        let mut builder =
+            PacketBuilder::new_session_packet(&mut buffer[..], SRC, DST, session_id).unwrap();
+        assert_eq!(builder.payload_capacity(), 3980);
+        builder.payload_mut().fill(0xa5);
+        let frame = builder.finish(3980).unwrap();
+        assert_eq!(frame.len(), 4000);
+        let summary = crate::PacketSummary::from_bytes(frame).unwrap();
+        assert_eq!(summary.payload_length, 3980);
+
+        let mut batch = SessionBatch::new(SRC, DST, session_id);
+        batch.push(&frame[20..]).unwrap();
+        assert_eq!(batch.frames().next().unwrap(), frame);
+    }
+
+    #[test]
+    fn session_packet_payload_bigger_than_buffer() {
+        let mut buffer = [0u8; 30];
+        let session_id = NonZeroU16::new(1).unwrap();
+        let builder =
+            PacketBuilder::new_session_packet(&mut buffer[..], SRC, DST, session_id).unwrap();
+        assert!(builder.finish(11).is_err());
+    }
 }
@@ -0,0 +1,113 @@
+//! Driving the state machines from a superloop, see `pppoe_core::poll`
+//!
+//! The state machines of this crate run on `std::time::Instant`, so their `Poller` needs the
+//! current time as epoch, e.g. `Poller::new(discovery, Instant::now())`.
+
+use std::time::Instant;
+
+use crate::error::Error;
+use crate::ppp::lcp::MruProbe;
+use crate::ppp::ControlPacket;
+use crate::Discovery;
+
+pub use pppoe_core::poll::{Machine, Millis, PollResult, Poller};
+
+impl Machine for Discovery {
+    type Instant = Instant;
+    type Error = Error;
+
+    fn handle(&mut self, now: Instant, rx: &[u8]) -> Result<(), Error> {
+        self.handle_frame(now, rx)
+    }
+
+    fn poll_transmit(&mut self, now: Instant, tx: &mut [u8]) -> Result<Option<usize>, Error> {
+        Discovery::poll_transmit(self, now, tx)
+    }
+
+    fn poll_timeout(&self) -> Option<Instant> {
+        Discovery::poll_timeout(self)
+    }
+
+    fn is_finished(&self) -> bool {
+        Discovery::is_finished(self)
+    }
+}
+
+/// Takes LCP packets without the PPP protocol field, like `MruProbe::handle_packet`.
+impl Machine for MruProbe {
+    type Instant = Instant;
+    type Error = Error;
+
+    fn handle(&mut self, _now: Instant, rx: &[u8]) -> Result<(), Error> {
+        // not every received packet is for the probe
+        if let Ok(packet) = ControlPacket::with_buffer(rx) {
+            self.handle_packet(&packet);
+        }
+        Ok(())
+    }
+
+    fn poll_transmit(&mut self, now: Instant, tx: &mut [u8]) -> Result<Option<usize>, Error> {
+        Ok(MruProbe::poll_transmit(self, now, tx)?)
+    }
+
+    fn poll_timeout(&self) -> Option<Instant> {
+        MruProbe::poll_timeout(self)
+    }
+
+    fn is_finished(&self) -> bool {
+        MruProbe::is_finished(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ProtocolError;
+    use crate::ppp::lcp::{write_echo_reply, ProbeOptions};
+    use crate::DiscoveryOptions;
+
+    #[test]
+    fn superloop() {
+        let mut tx = [0u8; 1500];
+        let mut discovery = Poller::new(
+            Discovery::new([0x02, 0, 0, 0, 0, 1], DiscoveryOptions::default()),
+            Instant::now(),
+        );
+        assert!(matches!(
+            discovery.poll(0, None, &mut tx),
+            Ok(PollResult::Transmit(_))
+        ));
+        assert!(matches!(
+            discovery.poll(10, None, &mut tx),
+            Ok(PollResult::Wait(Some(3000)))
+        ));
+        assert!(matches!(
+            discovery.poll(3000, None, &mut tx),
+            Ok(PollResult::Transmit(_))
+        ));
+        discovery.poll(6000, None, &mut tx).unwrap();
+        assert!(matches!(
+            discovery.poll(9000, None, &mut tx),
+            Err(Error::Protocol(ProtocolError::NoOffer))
+        ));
+
+        let options = ProbeOptions {
+            min: 1400,
+            max: 1401,
+            ..ProbeOptions::default()
+        };
+        let mut probe = Poller::new(MruProbe::new(0x1234, options), Instant::now());
+        let len = match probe.poll(0, None, &mut tx) {
+            Ok(PollResult::Transmit(len)) => len,
+            other => panic!("unexpected result: {:?}", other),
+        };
+        let request = ControlPacket::with_buffer(&tx[..len]).unwrap();
+        let mut rx = [0u8; 1500];
+        let len = write_echo_reply(&request, 0x5678, &mut rx).unwrap();
+        assert_eq!(
+            probe.poll(5, Some(&rx[..len]), &mut tx).unwrap(),
+            PollResult::Finished
+        );
+        assert_eq!(probe.into_inner().report().largest_working, 1401);
+    }
+}
@@ -0,0 +1,327 @@
+//! Credentials for PAP/CHAP authentication of the client
+//!
+//! The credentials are requested through a `CredentialProvider` when they are needed, so they
+//! can come from a keyring, the environment or a prompt instead of a config struct.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::env;
+use std::future::{self, Future};
+use std::io;
+use std::pin::Pin;
+
+use crate::error::ParseError;
+use crate::secret::Secret;
+
+use super::{ControlPacket, CONTROL_HEADER_LEN};
+
+pub const PAP_AUTHENTICATE_REQUEST: u8 = 1;
+pub const PAP_AUTHENTICATE_ACK: u8 = 2;
+pub const PAP_AUTHENTICATE_NAK: u8 = 3;
+
+pub const CHAP_CHALLENGE: u8 = 1;
+pub const CHAP_RESPONSE: u8 = 2;
+pub const CHAP_SUCCESS: u8 = 3;
+pub const CHAP_FAILURE: u8 = 4;
+
+/// The authentication protocol the peer asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    Pap,
+    Chap,
+}
+
+/// Context of a credential request, e.g. to pick the account for an Access Concentrator
+#[derive(Debug, Clone, Copy)]
+pub struct CredentialRequest<'a> {
+    pub method: AuthMethod,
+    pub ac_name: Option<&'a [u8]>,
+    pub service_name: Option<&'a [u8]>,
+}
+
+/// A username and password, the password is wiped on drop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret,
+}
+
+impl Credentials {
+    pub fn new<P: Into<Secret>>(username: impl Into<String>, password: P) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+}
+
+/// Supplies the credentials whenever the peer requests authentication
+pub trait CredentialProvider {
+    fn credentials(&mut self, request: &CredentialRequest) -> io::Result<Credentials>;
+}
+
+/// Future returned by `AsyncCredentialProvider`
+pub type CredentialFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Credentials>> + Send + 'a>>;
+
+/// Like `CredentialProvider`, for sources which have to wait, e.g. for a user prompt
+///
+/// The future doesn't depend on a specific runtime.  Every `CredentialProvider` is also an
+/// `AsyncCredentialProvider` which resolves immediately.
+pub trait AsyncCredentialProvider {
+    fn credentials<'a>(&'a mut self, request: &CredentialRequest) -> CredentialFuture<'a>;
+}
+
+impl<T: CredentialProvider + Send> AsyncCredentialProvider for T {
+    fn credentials<'a>(&'a mut self, request: &CredentialRequest) -> CredentialFuture<'a> {
+        let credentials = CredentialProvider::credentials(self, request);
+        Box::pin(future::ready(credentials))
+    }
+}
+
+/// Always use the same credentials
+impl CredentialProvider for Credentials {
+    fn credentials(&mut self, _request: &CredentialRequest) -> io::Result<Credentials> {
+        Ok(self.clone())
+    }
+}
+
+/// Read the credentials from environment variables on every request
+#[derive(Debug, Clone)]
+pub struct EnvCredentials {
+    username_var: String,
+    password_var: String,
+}
+
+impl EnvCredentials {
+    pub fn new(username_var: impl Into<String>, password_var: impl Into<String>) -> Self {
+        Self {
+            username_var: username_var.into(),
+            password_var: password_var.into(),
+        }
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn credentials(&mut self, _request: &CredentialRequest) -> io::Result<Credentials> {
+        let read = |name: &str| {
+            env::var(name).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("environment variable {} is not set", name),
+                )
+            })
+        };
+        Ok(Credentials::new(
+            read(&self.username_var)?,
+            read(&self.password_var)?,
+        ))
+    }
+}
+
+/// Use a closure as `CredentialProvider`
+pub struct FromFn<F>(F);
+
+pub fn from_fn<F>(callback: F) -> FromFn<F>
+where
+    F: FnMut(&CredentialRequest) -> io::Result<Credentials>,
+{
+    FromFn(callback)
+}
+
+impl<F> CredentialProvider for FromFn<F>
+where
+    F: FnMut(&CredentialRequest) -> io::Result<Credentials>,
+{
+    fn credentials(&mut self, request: &CredentialRequest) -> io::Result<Credentials> {
+        (self.0)(request)
+    }
+}
+
+/// Write a PAP Authenticate-Request (RFC 1334) without the PPP protocol field.
+///
+/// Returns the length of the packet.  The password ends up in `buffer`, wipe it with
+/// `secret::wipe` once the packet is sent.
+pub fn write_pap_request(
+    buffer: &mut [u8],
+    identifier: u8,
+    credentials: &Credentials,
+) -> Result<usize, ParseError> {
+    let username = credentials.username.as_bytes();
+    let password = credentials.password.expose();
+    if username.len() > usize::from(u8::MAX) {
+        return Err(ParseError::PayloadLengthOverflow(username.len()));
+    }
+    if password.len() > usize::from(u8::MAX) {
+        return Err(ParseError::PayloadLengthOverflow(password.len()));
+    }
+
+    let length = CONTROL_HEADER_LEN + 1 + username.len() + 1 + password.len();
+    if buffer.len() < length {
+        return Err(ParseError::BufferTooSmall(buffer.len()));
+    }
+
+    buffer[0] = PAP_AUTHENTICATE_REQUEST;
+    buffer[1] = identifier;
+    NE::write_u16(&mut buffer[2..], length as u16);
+    let mut offset = CONTROL_HEADER_LEN;
+    for field in &[username, password] {
+        buffer[offset] = field.len() as u8;
+        buffer[offset + 1..offset + 1 + field.len()].copy_from_slice(field);
+        offset += 1 + field.len();
+    }
+    Ok(length)
+}
+
+/// A CHAP Challenge (RFC 1994)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChapChallenge<'a> {
+    pub identifier: u8,
+    pub value: &'a [u8],
+    pub name: &'a [u8],
+}
+
+impl<'a> ChapChallenge<'a> {
+    pub fn from_packet(packet: &ControlPacket<'a>) -> Result<Self, ParseError> {
+        if packet.code() != CHAP_CHALLENGE {
+            return Err(ParseError::UnexpectedCode(packet.code()));
+        }
+        let data = packet.data();
+        let value_len = match data.first() {
+            Some(&value_len) if data.len() > usize::from(value_len) => usize::from(value_len),
+            _ => {
+                return Err(ParseError::InvalidPppLength {
+                    length: packet.len() as u16,
+                    available: packet.len(),
+                })
+            }
+        };
+        Ok(ChapChallenge {
+            identifier: packet.identifier(),
+            value: &data[1..1 + value_len],
+            name: &data[1 + value_len..],
+        })
+    }
+
+    /// Get the input of the hash of a CHAP-MD5 response: identifier, password and challenge.
+    ///
+    /// The hashing is left to the caller.  The input contains the password, so it is returned
+    /// as `Secret`.
+    pub fn md5_input(&self, credentials: &Credentials) -> Secret {
+        let password = credentials.password.expose();
+        let mut input = Vec::with_capacity(1 + password.len() + self.value.len());
+        input.push(self.identifier);
+        input.extend_from_slice(password);
+        input.extend_from_slice(self.value);
+        Secret::new(input)
+    }
+}
+
+/// Write a CHAP Response with the hash `value` (e.g. of `ChapChallenge::md5_input`) and the
+/// name of the client, without the PPP protocol field.
+///
+/// Returns the length of the packet.
+pub fn write_chap_response(
+    buffer: &mut [u8],
+    identifier: u8,
+    value: &Secret,
+    name: &[u8],
+) -> Result<usize, ParseError> {
+    let value = value.expose();
+    if value.len() > usize::from(u8::MAX) {
+        return Err(ParseError::PayloadLengthOverflow(value.len()));
+    }
+    let length = CONTROL_HEADER_LEN + 1 + value.len() + name.len();
+    if length > usize::from(u16::MAX) {
+        return Err(ParseError::PayloadLengthOverflow(length));
+    }
+    if buffer.len() < length {
+        return Err(ParseError::BufferTooSmall(buffer.len()));
+    }
+
+    buffer[0] = CHAP_RESPONSE;
+    buffer[1] = identifier;
+    NE::write_u16(&mut buffer[2..], length as u16);
+    buffer[4] = value.len() as u8;
+    buffer[5..5 + value.len()].copy_from_slice(value);
+    buffer[5 + value.len()..length].copy_from_slice(name);
+    Ok(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    }
+
+    #[test]
+    fn provide_credentials() {
+        let request = CredentialRequest {
+            method: AuthMethod::Pap,
+            ac_name: Some(b"bras1"),
+            service_name: None,
+        };
+
+        let mut provider = from_fn(|request: &CredentialRequest| {
+            assert_eq!(request.ac_name, Some(&b"bras1"[..]));
+            Ok(Credentials::new("user@isp", "hunter2"))
+        });
+        let credentials = CredentialProvider::credentials(&mut provider, &request).unwrap();
+        assert_eq!(
+            format!("{:?}", credentials),
+            r#"Credentials { username: "user@isp", password: Secret(7 bytes) }"#
+        );
+
+        let mut buffer = [0u8; 32];
+        let len = write_pap_request(&mut buffer, 1, &credentials).unwrap();
+        assert_eq!(&buffer[..len], b"\x01\x01\x00\x15\x08user@isp\x07hunter2");
+        crate::secret::wipe(&mut buffer[..len]);
+        assert_eq!(buffer, [0; 32]);
+
+        let challenge = [
+            CHAP_CHALLENGE,
+            9,
+            0,
+            12,
+            3,
+            0xa,
+            0xb,
+            0xc,
+            b'b',
+            b'r',
+            b'a',
+            b's',
+        ];
+        let challenge =
+            ChapChallenge::from_packet(&ControlPacket::with_buffer(&challenge).unwrap()).unwrap();
+        assert_eq!(challenge.value, [0xa, 0xb, 0xc]);
+        assert_eq!(challenge.name, b"bras");
+        assert_eq!(
+            challenge.md5_input(&credentials).expose(),
+            b"\x09hunter2\x0a\x0b\x0c"
+        );
+        let len =
+            write_chap_response(&mut buffer, 9, &Secret::from(vec![0x55; 16]), b"user").unwrap();
+        assert_eq!(len, 4 + 1 + 16 + 4);
+        assert_eq!(&buffer[..5], [CHAP_RESPONSE, 9, 0, 25, 16]);
+        assert_eq!(&buffer[21..len], b"user");
+        let truncated = [CHAP_CHALLENGE, 9, 0, 7, 3, 0xa, 0xb];
+        assert!(
+            ChapChallenge::from_packet(&ControlPacket::with_buffer(&truncated).unwrap()).is_err()
+        );
+
+        let mut future = AsyncCredentialProvider::credentials(&mut provider, &request);
+        let waker = noop_waker();
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(Ok(async_credentials)) => assert_eq!(async_credentials, credentials),
+            _ => panic!("credentials are not ready"),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Compression Control Protocol (RFC 1962) which never agrees to compression
+//!
+//! Peers proposing Deflate, MPPC etc. retransmit their Configure-Request until they get an
+//! answer, so silently dropping CCP stalls their negotiation.  `Rejector` refuses CCP as a whole
+//! with an LCP Protocol-Reject, after which the peer stops negotiating compression.
+
+use crate::error::ParseError;
+
+use super::lcp::write_protocol_reject;
+use super::{ControlPacket, PROTOCOL_CCP};
+
+pub const RESET_REQUEST: u8 = 14;
+pub const RESET_ACK: u8 = 15;
+
+// option types of common compression methods
+pub const OPTION_OUI: u8 = 0;
+pub const OPTION_PREDICTOR_1: u8 = 1;
+pub const OPTION_PREDICTOR_2: u8 = 2;
+pub const OPTION_STAC_LZS: u8 = 17;
+pub const OPTION_MPPC: u8 = 18;
+pub const OPTION_BSD_COMPRESS: u8 = 21;
+pub const OPTION_DEFLATE: u8 = 26;
+
+/// Answers CCP packets of the peer without ever enabling compression
+#[derive(Debug, Default, Clone)]
+pub struct Rejector {
+    identifier: u8,
+}
+
+impl Rejector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle a received CCP packet and write the LCP Protocol-Reject for it (without the PPP
+    /// protocol field) to `buffer`.
+    ///
+    /// Returns the length of the answer, which has to be sent with `PROTOCOL_LCP`.  Every CCP
+    /// packet is rejected, the peer then treats CCP as closed (RFC 1661 section 5.7).
+    pub fn handle(
+        &mut self,
+        packet: &ControlPacket,
+        buffer: &mut [u8],
+    ) -> Result<usize, ParseError> {
+        self.identifier = self.identifier.wrapping_add(1);
+        write_protocol_reject(PROTOCOL_CCP, packet.as_bytes(), self.identifier, buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppp::lcp::PROTOCOL_REJECT;
+
+    #[test]
+    fn reject_all_compression() {
+        let mut rejector = Rejector::new();
+        let mut buffer = [0u8; 64];
+
+        // Deflate and MPPC
+        let request = [
+            &[1, 3, 0, 14][..],
+            &[OPTION_DEFLATE, 4, 0x78, 0],
+            &[OPTION_MPPC, 6, 1, 0, 0, 0x40],
+        ]
+        .concat();
+        let request = ControlPacket::with_buffer(&request).unwrap();
+        let len = rejector.handle(&request, &mut buffer).unwrap();
+        let reject = ControlPacket::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(reject.code(), PROTOCOL_REJECT);
+        assert_eq!(reject.identifier(), 1);
+        assert_eq!(&reject.data()[..2], [0x80, 0xfd]);
+        assert_eq!(&reject.data()[2..], request.as_bytes());
+
+        // a new identifier for every reject
+        let reset = ControlPacket::with_buffer(&[RESET_REQUEST, 6, 0, 4]).unwrap();
+        let len = rejector.handle(&reset, &mut buffer).unwrap();
+        assert_eq!(
+            &buffer[..len],
+            [
+                PROTOCOL_REJECT,
+                2,
+                0,
+                10,
+                0x80,
+                0xfd,
+                RESET_REQUEST,
+                6,
+                0,
+                4
+            ]
+        );
+
+        // the rejected packet is truncated to the buffer
+        let len = rejector.handle(&request, &mut buffer[..10]).unwrap();
+        assert_eq!(
+            &buffer[..len],
+            [PROTOCOL_REJECT, 3, 0, 10, 0x80, 0xfd, 1, 3, 0, 14]
+        );
+        assert!(rejector.handle(&request, &mut buffer[..5]).is_err());
+    }
+}
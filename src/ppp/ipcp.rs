@@ -0,0 +1,162 @@
+//! Option policy for the IP Control Protocol (RFC 1332, RFC 1877)
+//!
+//! Older BRAS devices propose Van Jacobson TCP/IP header compression.  An IPCP implementation
+//! which doesn't support it has to Configure-Reject the option, otherwise the address
+//! negotiation never completes.  `OptionPolicy` decides which options of a Configure-Request get
+//! rejected, everything else is left to the caller.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use crate::error::ParseError;
+
+use super::{write_configure_reject, ConfigOption, ControlPacket};
+
+pub const OPTION_IP_ADDRESSES: u8 = 1;
+pub const OPTION_IP_COMPRESSION_PROTOCOL: u8 = 2;
+pub const OPTION_IP_ADDRESS: u8 = 3;
+pub const OPTION_PRIMARY_DNS: u8 = 129;
+pub const OPTION_PRIMARY_NBNS: u8 = 130;
+pub const OPTION_SECONDARY_DNS: u8 = 131;
+pub const OPTION_SECONDARY_NBNS: u8 = 132;
+
+/// IP-Compression-Protocol value of Van Jacobson compressed TCP/IP
+pub const VAN_JACOBSON: u16 = 0x002d;
+
+/// Parameters of a Van Jacobson IP-Compression-Protocol option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VjParameters {
+    pub max_slot_id: u8,
+    pub comp_slot_id: bool,
+}
+
+impl VjParameters {
+    /// Get the parameters of a Van Jacobson IP-Compression-Protocol option
+    pub fn from_option(option: &ConfigOption) -> Option<Self> {
+        if option.option_type != OPTION_IP_COMPRESSION_PROTOCOL
+            || option.data.len() != 4
+            || NE::read_u16(option.data) != VAN_JACOBSON
+        {
+            return None;
+        }
+        Some(VjParameters {
+            max_slot_id: option.data[2],
+            comp_slot_id: option.data[3] != 0,
+        })
+    }
+}
+
+/// What to do with a proposed IP-Compression-Protocol option
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionPolicy {
+    /// Reject every compression protocol
+    #[default]
+    Reject,
+    /// Leave Van Jacobson compression to the caller, which has to implement it on the data
+    /// path.  Other compression protocols are still rejected.
+    AcceptVanJacobson,
+}
+
+/// Decides which options of a received IPCP Configure-Request are rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionPolicy {
+    pub compression: CompressionPolicy,
+    /// Reject the deprecated IP-Addresses option and options not listed in this module
+    pub reject_unknown: bool,
+}
+
+impl Default for OptionPolicy {
+    fn default() -> Self {
+        Self {
+            compression: CompressionPolicy::default(),
+            reject_unknown: true,
+        }
+    }
+}
+
+impl OptionPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether an option of a Configure-Request has to be rejected.
+    pub fn rejects(&self, option: &ConfigOption) -> bool {
+        match option.option_type {
+            OPTION_IP_COMPRESSION_PROTOCOL => match self.compression {
+                CompressionPolicy::Reject => true,
+                CompressionPolicy::AcceptVanJacobson => VjParameters::from_option(option).is_none(),
+            },
+            OPTION_IP_ADDRESS
+            | OPTION_PRIMARY_DNS
+            | OPTION_PRIMARY_NBNS
+            | OPTION_SECONDARY_DNS
+            | OPTION_SECONDARY_NBNS => false,
+            _ => self.reject_unknown,
+        }
+    }
+
+    /// Write a Configure-Reject (without the PPP protocol field) for the options of `request`
+    /// which the policy rejects.
+    ///
+    /// Returns `None` if all options are acceptable, the request then has to be answered with a
+    /// Configure-Ack or -Nak by the caller.
+    pub fn write_reject(
+        &self,
+        request: &ControlPacket,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, ParseError> {
+        write_configure_reject(request, buffer, |option| self.rejects(option))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppp::CONFIGURE_REJECT;
+
+    #[test]
+    fn reject_vj_compression() {
+        // IP-Address 0.0.0.0, VJ with 15 slots, primary DNS
+        let request = [
+            &[1, 9, 0, 22][..],
+            &[OPTION_IP_ADDRESS, 6, 0, 0, 0, 0],
+            &[OPTION_IP_COMPRESSION_PROTOCOL, 6, 0x00, 0x2d, 15, 1],
+            &[OPTION_PRIMARY_DNS, 6, 0, 0, 0, 0],
+        ]
+        .concat();
+        let request = ControlPacket::with_buffer(&request).unwrap();
+        let mut buffer = [0u8; 32];
+
+        let policy = OptionPolicy::new();
+        let len = policy.write_reject(&request, &mut buffer).unwrap().unwrap();
+        assert_eq!(
+            &buffer[..len],
+            [CONFIGURE_REJECT, 9, 0, 10, 2, 6, 0x00, 0x2d, 15, 1]
+        );
+
+        let policy = OptionPolicy {
+            compression: CompressionPolicy::AcceptVanJacobson,
+            ..OptionPolicy::default()
+        };
+        assert_eq!(policy.write_reject(&request, &mut buffer), Ok(None));
+        let vj = request.options().unwrap().nth(1).unwrap();
+        assert_eq!(
+            VjParameters::from_option(&vj),
+            Some(VjParameters {
+                max_slot_id: 15,
+                comp_slot_id: true
+            })
+        );
+
+        // unknown compression protocol and a deprecated option
+        let request = [
+            &[1, 10, 0, 16][..],
+            &[OPTION_IP_COMPRESSION_PROTOCOL, 4, 0x00, 0x61],
+            &[OPTION_IP_ADDRESSES, 4, 0, 0],
+            &[OPTION_IP_ADDRESS, 4, 0, 0],
+        ]
+        .concat();
+        let request = ControlPacket::with_buffer(&request).unwrap();
+        let len = policy.write_reject(&request, &mut buffer).unwrap().unwrap();
+        assert_eq!(&buffer[4..len], &request.data()[..8]);
+    }
+}
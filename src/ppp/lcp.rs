@@ -0,0 +1,316 @@
+//! LCP echo packets and probing of the usable MRU
+//!
+//! A peer may accept a large MRU (e.g. with RFC 4638) while something on the path still drops
+//! large frames.  `MruProbe` sends Echo-Requests padded to different sizes after the session is
+//! up and reports the largest one which got an Echo-Reply.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::time::{Duration, Instant};
+
+use crate::error::ParseError;
+
+use super::{ControlPacket, CONTROL_HEADER_LEN};
+
+pub const PROTOCOL_REJECT: u8 = 8;
+pub const ECHO_REQUEST: u8 = 9;
+pub const ECHO_REPLY: u8 = 10;
+pub const DISCARD_REQUEST: u8 = 11;
+
+/// Smallest Echo-Request: header and magic number
+pub const MIN_ECHO_LEN: usize = CONTROL_HEADER_LEN + 4;
+
+/// Write an Echo-Request padded to `len` bytes, without the PPP protocol field.
+pub fn write_echo_request(
+    buffer: &mut [u8],
+    identifier: u8,
+    magic_number: u32,
+    len: usize,
+) -> Result<usize, ParseError> {
+    if len < MIN_ECHO_LEN || len > usize::from(u16::MAX) {
+        return Err(ParseError::PayloadLengthOverflow(len));
+    }
+    if buffer.len() < len {
+        return Err(ParseError::BufferTooSmall(buffer.len()));
+    }
+
+    buffer[0] = ECHO_REQUEST;
+    buffer[1] = identifier;
+    NE::write_u16(&mut buffer[2..], len as u16);
+    NE::write_u32(&mut buffer[4..], magic_number);
+    for (index, byte) in buffer[MIN_ECHO_LEN..len].iter_mut().enumerate() {
+        *byte = index as u8;
+    }
+    Ok(len)
+}
+
+/// Write the Echo-Reply to `request`, echoing its data with our magic number.
+pub fn write_echo_reply(
+    request: &ControlPacket,
+    magic_number: u32,
+    buffer: &mut [u8],
+) -> Result<usize, ParseError> {
+    let len = request.len();
+    if request.code() != ECHO_REQUEST {
+        return Err(ParseError::UnexpectedCode(request.code()));
+    }
+    if len < MIN_ECHO_LEN {
+        return Err(ParseError::InvalidPppLength {
+            length: len as u16,
+            available: len,
+        });
+    }
+    if buffer.len() < len {
+        return Err(ParseError::BufferTooSmall(buffer.len()));
+    }
+
+    buffer[..len].copy_from_slice(request.as_bytes());
+    buffer[0] = ECHO_REPLY;
+    NE::write_u32(&mut buffer[4..], magic_number);
+    Ok(len)
+}
+
+/// Write a Protocol-Reject for a packet of an unsupported PPP protocol, without the PPP protocol
+/// field.
+///
+/// `rejected` is the rejected packet after its protocol field, it is truncated to fit into
+/// `buffer` as allowed by RFC 1661 section 5.7.
+pub fn write_protocol_reject(
+    protocol: u16,
+    rejected: &[u8],
+    identifier: u8,
+    buffer: &mut [u8],
+) -> Result<usize, ParseError> {
+    let header_len = CONTROL_HEADER_LEN + 2;
+    if buffer.len() < header_len {
+        return Err(ParseError::BufferTooSmall(buffer.len()));
+    }
+    let available = buffer.len().min(usize::from(u16::MAX)) - header_len;
+    let rejected = &rejected[..rejected.len().min(available)];
+    let len = header_len + rejected.len();
+
+    buffer[0] = PROTOCOL_REJECT;
+    buffer[1] = identifier;
+    NE::write_u16(&mut buffer[2..], len as u16);
+    NE::write_u16(&mut buffer[4..], protocol);
+    buffer[header_len..len].copy_from_slice(rejected);
+    Ok(len)
+}
+
+/// Options of a `MruProbe`
+#[derive(Debug, Clone)]
+pub struct ProbeOptions {
+    /// A size which is known to work, e.g. 1492 on Ethernet.  It isn't probed.
+    pub min: u16,
+    /// The largest size to probe, usually the negotiated MRU
+    pub max: u16,
+    /// The search stops once the largest working and the smallest failing size are at most
+    /// this far apart.
+    pub granularity: u16,
+    /// Time to wait for an Echo-Reply
+    pub timeout: Duration,
+    /// Number of Echo-Requests of one size before it is considered failing
+    pub attempts: u32,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        Self {
+            min: 1492,
+            max: 1500,
+            granularity: 1,
+            timeout: Duration::from_secs(1),
+            attempts: 3,
+        }
+    }
+}
+
+/// Result of a `MruProbe`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProbeReport {
+    /// Largest size which got a reply (or `ProbeOptions::min`)
+    pub largest_working: u16,
+    /// Smallest size which got no reply
+    pub smallest_failing: Option<u16>,
+    /// Number of Echo-Requests send so far
+    pub requests_sent: u32,
+}
+
+/// Binary search for the largest LCP packet which passes the path end-to-end
+///
+/// Like `Discovery`, the probe does no I/O on its own: Echo-Requests are requested via
+/// `poll_transmit` (as LCP packets without the PPP protocol field) and the received LCP packets
+/// are passed to `handle_packet`.
+#[derive(Debug)]
+pub struct MruProbe {
+    options: ProbeOptions,
+    magic_number: u32,
+    identifier: u8,
+    size: Option<u16>,
+    attempt: u32,
+    deadline: Option<Instant>,
+    report: ProbeReport,
+}
+
+impl MruProbe {
+    pub fn new(magic_number: u32, options: ProbeOptions) -> Self {
+        let report = ProbeReport {
+            largest_working: options.min,
+            ..ProbeReport::default()
+        };
+        Self {
+            options,
+            magic_number,
+            identifier: 0,
+            size: None,
+            attempt: 0,
+            deadline: None,
+            report,
+        }
+    }
+
+    pub fn report(&self) -> &ProbeReport {
+        &self.report
+    }
+
+    /// Check whether the largest working size is known.
+    pub fn is_finished(&self) -> bool {
+        self.next_size().is_none()
+    }
+
+    /// Get the point in time at which the next Echo-Request is due.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        if self.is_finished() {
+            return None;
+        }
+        self.deadline
+    }
+
+    fn next_size(&self) -> Option<u16> {
+        let low = self.report.largest_working;
+        let high = match self.report.smallest_failing {
+            // the largest size is probed first, as it usually works
+            None if low < self.options.max => return Some(self.options.max),
+            None => return None,
+            Some(high) => high,
+        };
+        if high - low <= self.options.granularity.max(1) {
+            return None;
+        }
+        Some(low + (high - low) / 2)
+    }
+
+    /// Write the next Echo-Request into `buffer` and return its length.
+    pub fn poll_transmit(
+        &mut self,
+        now: Instant,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, ParseError> {
+        match self.deadline {
+            Some(deadline) if now < deadline => return Ok(None),
+            _ => (),
+        }
+
+        if let Some(size) = self.size {
+            if self.attempt >= self.options.attempts {
+                self.report.smallest_failing = Some(size);
+                self.size = None;
+            }
+        }
+        let size = match self.size.or_else(|| self.next_size()) {
+            Some(size) => size,
+            None => return Ok(None),
+        };
+        if self.size != Some(size) {
+            self.size = Some(size);
+            self.attempt = 0;
+        }
+
+        self.identifier = self.identifier.wrapping_add(1);
+        let len = write_echo_request(
+            buffer,
+            self.identifier,
+            self.magic_number,
+            usize::from(size),
+        )?;
+        self.attempt += 1;
+        self.report.requests_sent += 1;
+        self.deadline = Some(now + self.options.timeout);
+        Ok(Some(len))
+    }
+
+    /// Process a received LCP packet.  Returns `true` if it was the reply to the current probe.
+    pub fn handle_packet(&mut self, packet: &ControlPacket) -> bool {
+        let size = match self.size {
+            Some(size) => size,
+            None => return false,
+        };
+        if packet.code() != ECHO_REPLY
+            || packet.identifier() != self.identifier
+            || packet.len() != usize::from(size)
+        {
+            return false;
+        }
+
+        self.report.largest_working = size;
+        self.size = None;
+        self.deadline = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_reply() {
+        let mut request = [0u8; 16];
+        let len = write_echo_request(&mut request, 3, 0x1234_5678, 12).unwrap();
+        let request = ControlPacket::with_buffer(&request[..len]).unwrap();
+
+        let mut reply = [0u8; 16];
+        let len = write_echo_reply(&request, 0xaabb_ccdd, &mut reply).unwrap();
+        assert_eq!(
+            &reply[..len],
+            [ECHO_REPLY, 3, 0, 12, 0xaa, 0xbb, 0xcc, 0xdd, 0, 1, 2, 3]
+        );
+        assert!(write_echo_request(&mut [0u8; 16], 1, 0, 7).is_err());
+    }
+
+    #[test]
+    fn probe_largest_size() {
+        // the path drops everything above 1496 bytes
+        let limit = 1496;
+        let options = ProbeOptions {
+            min: 1492,
+            max: 1500,
+            ..ProbeOptions::default()
+        };
+        let mut probe = MruProbe::new(1, options.clone());
+        let mut now = Instant::now();
+        let mut buffer = [0u8; 1600];
+        let mut reply = [0u8; 1600];
+
+        while !probe.is_finished() {
+            if let Some(len) = probe.poll_transmit(now, &mut buffer).unwrap() {
+                let request = ControlPacket::with_buffer(&buffer[..len]).unwrap();
+                if len <= limit {
+                    let len = write_echo_reply(&request, 2, &mut reply).unwrap();
+                    assert!(
+                        probe.handle_packet(&ControlPacket::with_buffer(&reply[..len]).unwrap())
+                    );
+                    continue;
+                }
+            }
+            now = probe.poll_timeout().unwrap_or(now);
+        }
+
+        let report = probe.report();
+        assert_eq!(report.largest_working, 1496);
+        assert_eq!(report.smallest_failing, Some(1497));
+        // 1500, 1498 and 1497 fail three times each, 1496 works
+        assert_eq!(report.requests_sent, 10);
+        assert_eq!(probe.poll_transmit(now, &mut buffer), Ok(None));
+    }
+}
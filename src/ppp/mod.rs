@@ -0,0 +1,248 @@
+//! PPP control protocol packets carried in PPPoE session frames (RFC 1661)
+//!
+//! Only what is needed next to a PPP implementation is covered: parsing the packets of LCP-like
+//! control protocols and answering the ones a PPPoE endpoint has to answer correctly, e.g.
+//! rejecting compression.  The buffers start after the PPP protocol field.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use crate::error::ParseError;
+
+pub mod auth;
+pub mod ccp;
+pub mod ipcp;
+pub mod lcp;
+
+pub use pppoe_core::ppp::{
+    Protocol, CODE_REJECT, CONFIGURE_ACK, CONFIGURE_NAK, CONFIGURE_REJECT, CONFIGURE_REQUEST,
+    CONTROL_HEADER_LEN, PROTOCOL_CCP, PROTOCOL_CHAP, PROTOCOL_IPCP, PROTOCOL_IPV4, PROTOCOL_IPV6,
+    PROTOCOL_IPV6CP, PROTOCOL_LCP, PROTOCOL_PAP, TERMINATE_ACK, TERMINATE_REQUEST,
+};
+
+/// A control protocol packet (code, identifier, length and data)
+#[derive(Debug, Clone, Copy)]
+pub struct ControlPacket<'a>(&'a [u8]);
+
+// never empty, the header is validated on parsing
+#[allow(clippy::len_without_is_empty)]
+impl<'a> ControlPacket<'a> {
+    /// Parse a control packet.  Bytes after the length given in the packet are ignored.
+    pub fn with_buffer(buffer: &'a [u8]) -> Result<Self, ParseError> {
+        if buffer.len() < CONTROL_HEADER_LEN {
+            return Err(ParseError::BufferTooSmall(buffer.len()));
+        }
+        let length = NE::read_u16(&buffer[2..]);
+        if usize::from(length) < CONTROL_HEADER_LEN || usize::from(length) > buffer.len() {
+            return Err(ParseError::InvalidPppLength {
+                length,
+                available: buffer.len(),
+            });
+        }
+        Ok(ControlPacket(&buffer[..usize::from(length)]))
+    }
+
+    pub fn code(&self) -> u8 {
+        self.0[0]
+    }
+
+    pub fn identifier(&self) -> u8 {
+        self.0[1]
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        &self.0[CONTROL_HEADER_LEN..]
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Iterate over the options of a Configure-Request, -Ack, -Nak or -Reject.
+    ///
+    /// The options are checked first, so the iterator doesn't have to deal with malformed ones.
+    pub fn options(&self) -> Result<OptionIterator<'a>, ParseError> {
+        let mut data = self.data();
+        while !data.is_empty() {
+            let (_, rest) = ConfigOption::from_buffer(data)?;
+            data = rest;
+        }
+        Ok(OptionIterator { data: self.data() })
+    }
+}
+
+/// A configuration option (type, length and data)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ConfigOption<'a> {
+    pub option_type: u8,
+    pub data: &'a [u8],
+}
+
+impl<'a> ConfigOption<'a> {
+    fn from_buffer(buffer: &'a [u8]) -> Result<(Self, &'a [u8]), ParseError> {
+        if buffer.len() < 2 {
+            return Err(ParseError::BufferTooSmall(buffer.len()));
+        }
+        let length = buffer[1];
+        if length < 2 || usize::from(length) > buffer.len() {
+            return Err(ParseError::InvalidPppOptionLength {
+                option_type: buffer[0],
+                length,
+            });
+        }
+        let (option, rest) = buffer.split_at(usize::from(length));
+        let option = ConfigOption {
+            option_type: option[0],
+            data: &option[2..],
+        };
+        Ok((option, rest))
+    }
+
+    /// Get the number of bytes the option occupies in a packet
+    pub fn encoded_len(&self) -> usize {
+        2 + self.data.len()
+    }
+}
+
+pub struct OptionIterator<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for OptionIterator<'a> {
+    type Item = ConfigOption<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        // options should be already validated at this point
+        let (option, rest) = ConfigOption::from_buffer(self.data).unwrap();
+        self.data = rest;
+        Some(option)
+    }
+}
+
+/// Write a control packet with the given code and identifier whose data is `data`.
+///
+/// Returns the length of the packet.
+pub fn write_control_packet(
+    buffer: &mut [u8],
+    code: u8,
+    identifier: u8,
+    data: &[u8],
+) -> Result<usize, ParseError> {
+    let length = CONTROL_HEADER_LEN + data.len();
+    if length > usize::from(u16::MAX) {
+        return Err(ParseError::PayloadLengthOverflow(length));
+    }
+    if buffer.len() < length {
+        return Err(ParseError::BufferTooSmall(buffer.len()));
+    }
+
+    buffer[0] = code;
+    buffer[1] = identifier;
+    NE::write_u16(&mut buffer[2..], length as u16);
+    buffer[CONTROL_HEADER_LEN..length].copy_from_slice(data);
+    Ok(length)
+}
+
+/// Write a Configure-Reject for all options of `request` for which `reject` returns `true`.
+///
+/// The rejected options are copied unmodified as required by RFC 1661 section 5.4.  Returns
+/// `None` if no option was rejected, so the request can be processed further.
+pub fn write_configure_reject<F>(
+    request: &ControlPacket,
+    buffer: &mut [u8],
+    mut reject: F,
+) -> Result<Option<usize>, ParseError>
+where
+    F: FnMut(&ConfigOption) -> bool,
+{
+    let mut length = CONTROL_HEADER_LEN;
+    for option in request.options()? {
+        if !reject(&option) {
+            continue;
+        }
+        let end = length + option.encoded_len();
+        if buffer.len() < end {
+            return Err(ParseError::BufferTooSmall(buffer.len()));
+        }
+        buffer[length] = option.option_type;
+        buffer[length + 1] = option.encoded_len() as u8;
+        buffer[length + 2..end].copy_from_slice(option.data);
+        length = end;
+    }
+    if length == CONTROL_HEADER_LEN {
+        return Ok(None);
+    }
+
+    buffer[0] = CONFIGURE_REJECT;
+    buffer[1] = request.identifier();
+    NE::write_u16(&mut buffer[2..], length as u16);
+    Ok(Some(length))
+}
+
+/// Write a Code-Reject for a packet with an unknown code.
+///
+/// The rejected packet is truncated to fit into `buffer`, as allowed by RFC 1661 section 5.6.
+pub fn write_code_reject(
+    rejected: &ControlPacket,
+    identifier: u8,
+    buffer: &mut [u8],
+) -> Result<usize, ParseError> {
+    let available = buffer.len().saturating_sub(CONTROL_HEADER_LEN);
+    let rejected = rejected.as_bytes();
+    let rejected = &rejected[..rejected.len().min(available)];
+    write_control_packet(buffer, CODE_REJECT, identifier, rejected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_selected_options() {
+        // Configure-Request with MRU, a 3 byte option and magic number, followed by padding
+        let request = [
+            1, 7, 0, 17, 1, 4, 0x05, 0xd4, 0x80, 3, 0xaa, 5, 6, 1, 2, 3, 4, 0, 0,
+        ];
+        let request = ControlPacket::with_buffer(&request).unwrap();
+        assert_eq!(request.len(), 17);
+        assert_eq!(
+            request
+                .options()
+                .unwrap()
+                .map(|option| option.option_type)
+                .collect::<Vec<_>>(),
+            [1, 0x80, 5]
+        );
+
+        let mut buffer = [0u8; 32];
+        let len =
+            write_configure_reject(&request, &mut buffer, |option| option.option_type == 0x80)
+                .unwrap()
+                .unwrap();
+        assert_eq!(&buffer[..len], [CONFIGURE_REJECT, 7, 0, 7, 0x80, 3, 0xaa]);
+        assert_eq!(
+            write_configure_reject(&request, &mut buffer, |_| false),
+            Ok(None)
+        );
+
+        let truncated = [1, 7, 0, 6, 1, 4];
+        assert_eq!(
+            ControlPacket::with_buffer(&truncated)
+                .unwrap()
+                .options()
+                .err(),
+            Some(ParseError::InvalidPppOptionLength {
+                option_type: 1,
+                length: 4
+            })
+        );
+        assert!(ControlPacket::with_buffer(&[1, 7, 0, 8, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,21 @@
+//! The types needed by most users of the crate
+//!
+//! ```
+//! use pppoe::prelude::*;
+//! ```
+//!
+//! Only items which are part of the stable API are included, everything else has to be imported
+//! from its module.
+
+pub use crate::error::{Error, ParseError, ProtocolError};
+pub use crate::header::{Code, Header, HeaderBuilder, HeaderExt, ParserConfigExt};
+pub use crate::packet::{Packet, PacketBuilder, SessionPacketBuilder};
+pub use crate::tags::{Tag, TagExt, TagIterator};
+
+pub use crate::discovery::{Discovery, DiscoveryOptions, SessionInfo};
+
+#[cfg(feature = "socket")]
+pub use crate::socket::Socket;
+
+#[cfg(feature = "tr101")]
+pub use crate::tags::Tr101Information;
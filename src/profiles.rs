@@ -0,0 +1,158 @@
+//! Ready made discovery packets for common deployments
+//!
+//! All constructors create a broadcast PADI requesting any service.  The returned builder can be
+//! used to add further tags.
+
+use crate::error::Error;
+use crate::{PacketBuilder, Tag};
+
+#[cfg(feature = "tr101")]
+use crate::Tr101Information;
+
+const BROADCAST: [u8; 6] = [0xff; 6];
+
+/// A PADI with an empty Service-Name and no other tags
+pub fn plain_padi(buffer: &mut [u8], src_mac: [u8; 6]) -> Result<PacketBuilder<'_>, Error> {
+    let mut packet = PacketBuilder::new_discovery_packet(buffer, src_mac, BROADCAST)?;
+    packet.pppoe_header().add_tag(Tag::ServiceName(b""))?;
+    Ok(packet)
+}
+
+/// A PADI asking for a bigger MTU with the PPP-Max-Payload tag of RFC 4638 (e.g. 1500 for baby
+/// jumbo frames)
+pub fn padi_with_max_payload(
+    buffer: &mut [u8],
+    src_mac: [u8; 6],
+    max_payload: u16,
+) -> Result<PacketBuilder<'_>, Error> {
+    let mut packet = plain_padi(buffer, src_mac)?;
+    packet.pppoe_header().add_tag(Tag::PppMaxMtu(max_payload))?;
+    Ok(packet)
+}
+
+/// A PADI with a Host-Uniq tag, to tell apart the answers for multiple clients on one host
+pub fn padi_with_host_uniq<'a>(
+    buffer: &'a mut [u8],
+    src_mac: [u8; 6],
+    host_uniq: &[u8],
+) -> Result<PacketBuilder<'a>, Error> {
+    let mut packet = plain_padi(buffer, src_mac)?;
+    packet.pppoe_header().add_tag(Tag::HostUniq(host_uniq))?;
+    Ok(packet)
+}
+
+/// A PADI probing multiple services at once, with one Service-Name tag for each of them
+///
+/// This is not allowed by RFC 2516 but sent by some clients.  The default parser rejects such
+/// PADIs, they are accepted with `ParserConfig::allow_multiple_service_names` (and by
+/// `ParserConfig::lenient`).
+pub fn padi_with_service_names<'a>(
+    buffer: &'a mut [u8],
+    src_mac: [u8; 6],
+    service_names: &[&[u8]],
+) -> Result<PacketBuilder<'a>, Error> {
+    let mut packet = PacketBuilder::new_discovery_packet(buffer, src_mac, BROADCAST)?;
+    for service_name in service_names {
+        packet
+            .pppoe_header()
+            .add_tag(Tag::ServiceName(service_name))?;
+    }
+    Ok(packet)
+}
+
+/// A PADI as send on by an intermediate agent, with the TR-101 circuit id and remote id
+#[cfg(feature = "tr101")]
+pub fn ia_tagged_padi<'a>(
+    buffer: &'a mut [u8],
+    src_mac: [u8; 6],
+    circuit_id: &str,
+    remote_id: &str,
+) -> Result<PacketBuilder<'a>, Error> {
+    let tr101 = Tr101Information::with_both_ids(circuit_id, remote_id)?;
+
+    let mut packet = plain_padi(buffer, src_mac)?;
+    packet
+        .pppoe_header()
+        .add_vendor_tag_with_callback(|buffer| tr101.write(buffer))?;
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::PADI;
+    use crate::{Packet, ParserConfig, ParserConfigExt};
+
+    const MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+
+    #[test]
+    fn padi_with_max_payload_and_host_uniq() {
+        let mut buffer = [0u8; 64];
+        let mut packet = padi_with_max_payload(&mut buffer, MAC, 1500).unwrap();
+        packet.pppoe_header().add_tag(Tag::HostUniq(b"1")).unwrap();
+        let packet = packet.build().unwrap();
+
+        assert_eq!(packet.pppoe_header().code(), PADI);
+        assert_eq!(packet.ethernet_header().dst_address(), BROADCAST);
+        let tags: Vec<_> = packet.pppoe_header().tags().collect();
+        assert_eq!(
+            tags,
+            [
+                Tag::ServiceName(b""),
+                Tag::PppMaxMtu(1500),
+                Tag::HostUniq(b"1")
+            ]
+        );
+    }
+
+    #[test]
+    fn probe_multiple_services() {
+        let mut buffer = [0u8; 64];
+        let packet = padi_with_service_names(&mut buffer, MAC, &[b"internet", b"video"]).unwrap();
+        let frame = packet.as_bytes();
+
+        assert!(Packet::with_buffer(frame).is_err());
+        let probing = ParserConfig {
+            allow_multiple_service_names: true,
+            ..ParserConfig::default()
+        };
+        let packet = probing.parse_packet(frame).unwrap();
+        let service_names: Vec<_> = packet.pppoe_header().service_names().collect();
+        assert_eq!(service_names, [&b"internet"[..], b"video"]);
+        assert!(ParserConfig::lenient().parse_packet(frame).is_ok());
+
+        // only in PADIs
+        let mut frame = frame.to_vec();
+        frame[15] = crate::header::PADR;
+        assert!(probing.parse_packet(&frame).is_err());
+    }
+
+    #[cfg(feature = "tr101")]
+    #[test]
+    fn ia_tagged() {
+        let mut buffer = [0u8; 256];
+        let len = ia_tagged_padi(&mut buffer, MAC, "eth 0/1:100", "subscriber")
+            .unwrap()
+            .len();
+        let packet = Packet::with_buffer(&buffer[..len]).unwrap();
+        let vendor_tag = packet
+            .pppoe_header()
+            .tags()
+            .find_map(|tag| match tag {
+                Tag::VendorSpecific(value) => Some(value),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(&vendor_tag[..4], [0x00, 0x00, 0x0d, 0xe9]);
+        assert_eq!(&vendor_tag[4..6], [0x01, 11]);
+        assert_eq!(&vendor_tag[6..17], b"eth 0/1:100");
+    }
+
+    #[test]
+    fn plain() {
+        let mut buffer = [0u8; 64];
+        let len = plain_padi(&mut buffer, MAC).unwrap().len();
+        let packet = Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(packet.len(), 24);
+    }
+}
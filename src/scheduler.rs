@@ -0,0 +1,243 @@
+//! Prioritized sending of control and data frames sharing one socket
+//!
+//! Under saturation a socket's send queue is full of session data, and a discovery
+//! retransmission, PADT or LCP echo queued behind it may be delayed long enough for the peer to
+//! drop the session.  `SendScheduler` keeps control frames in a separate queue which is always
+//! drained first, and bounds the data queue so producers notice back-pressure instead of piling
+//! up frames.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::collections::VecDeque;
+use std::io;
+
+use crate::packet::{PPPOE_DISCOVERY, PPPOE_SESSION};
+use crate::ppp::Protocol;
+
+/// Default number of queued data frames
+pub const DEFAULT_DATA_QUEUE_LEN: usize = 256;
+/// Default number of data frames sent per `send_with` call
+pub const DEFAULT_DATA_BURST: usize = 32;
+
+/// The queue a frame is scheduled in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Discovery frames and PPP control protocols (LCP, authentication, NCPs)
+    Control,
+    /// Everything else, e.g. IP packets in session frames
+    Data,
+}
+
+impl Priority {
+    /// Classify an Ethernet frame.
+    ///
+    /// PPP protocols from 0x8000 on are control protocols (RFC 1661), the ones below carry
+    /// network layer data.  Frames too short to tell are `Data`.
+    pub fn of(frame: &[u8]) -> Self {
+        if frame.len() < 14 {
+            return Priority::Data;
+        }
+        match NE::read_u16(&frame[12..]) {
+            PPPOE_DISCOVERY => Priority::Control,
+            PPPOE_SESSION => match frame.get(20..).and_then(Protocol::of_payload) {
+                Some(protocol) if protocol.is_control() => Priority::Control,
+                _ => Priority::Data,
+            },
+            _ => Priority::Data,
+        }
+    }
+}
+
+/// Queues of frames waiting to be sent, control frames first
+#[derive(Debug)]
+pub struct SendScheduler {
+    control: VecDeque<Vec<u8>>,
+    data: VecDeque<Vec<u8>>,
+    max_data: usize,
+    data_burst: usize,
+    dropped: u64,
+}
+
+impl Default for SendScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SendScheduler {
+    pub fn new() -> Self {
+        Self {
+            control: VecDeque::new(),
+            data: VecDeque::new(),
+            max_data: DEFAULT_DATA_QUEUE_LEN,
+            data_burst: DEFAULT_DATA_BURST,
+            dropped: 0,
+        }
+    }
+
+    /// Set the number of data frames which can be queued.
+    pub fn with_data_queue_len(mut self, max_data: usize) -> Self {
+        self.max_data = max_data;
+        self
+    }
+
+    /// Set the number of data frames sent per `send_with` call, so the caller gets to queue new
+    /// control frames in between.
+    pub fn with_data_burst(mut self, data_burst: usize) -> Self {
+        self.data_burst = data_burst.max(1);
+        self
+    }
+
+    /// Queue a frame with the priority given by `Priority::of`.
+    ///
+    /// Returns `false` if it was a data frame and the data queue is full, the frame is dropped
+    /// then.  Control frames are always queued.
+    pub fn push(&mut self, frame: &[u8]) -> bool {
+        self.push_with_priority(Priority::of(frame), frame)
+    }
+
+    /// Queue a frame with an explicit priority, see `push`.
+    pub fn push_with_priority(&mut self, priority: Priority, frame: &[u8]) -> bool {
+        match priority {
+            Priority::Control => self.control.push_back(frame.to_vec()),
+            Priority::Data if self.data.len() < self.max_data => {
+                self.data.push_back(frame.to_vec())
+            }
+            Priority::Data => {
+                self.dropped += 1;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether another data frame can be queued.  Producers should pause while this is `false`.
+    pub fn has_data_capacity(&self) -> bool {
+        self.data.len() < self.max_data
+    }
+
+    /// Number of queued frames of a priority
+    pub fn queued(&self, priority: Priority) -> usize {
+        match priority {
+            Priority::Control => self.control.len(),
+            Priority::Data => self.data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.control.is_empty() && self.data.is_empty()
+    }
+
+    /// Number of data frames dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Send all control frames, then up to the data burst of data frames with `send`.
+    ///
+    /// Stops without an error at the first `WouldBlock`, the frame stays queued.  Other errors
+    /// are returned and the frame is dropped, so a broken frame doesn't block the queue.
+    /// Returns the number of frames sent.
+    pub fn send_with<F>(&mut self, mut send: F) -> io::Result<usize>
+    where
+        F: FnMut(&[u8]) -> io::Result<usize>,
+    {
+        let mut sent = 0;
+        while let Some(frame) = self.control.front() {
+            match send(frame) {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(sent),
+                result => {
+                    self.control.pop_front();
+                    result?;
+                    sent += 1;
+                }
+            }
+        }
+
+        for _ in 0..self.data_burst {
+            let frame = match self.data.front() {
+                Some(frame) => frame,
+                None => break,
+            };
+            match send(frame) {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                result => {
+                    self.data.pop_front();
+                    result?;
+                    sent += 1;
+                }
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Send queued frames on the raw socket without blocking, see `send_with`.
+    #[cfg(feature = "socket")]
+    pub fn send_pending(&mut self, socket: &crate::Socket) -> io::Result<usize> {
+        self.send_with(|frame| socket.send_with_flags(frame, libc::MSG_DONTWAIT))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppp::{PROTOCOL_IPV4, PROTOCOL_LCP};
+    use crate::{Code, PacketBuilder};
+    use std::num::NonZeroU16;
+
+    const SRC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const DST: [u8; 6] = [0x02, 0, 0, 0, 0, 2];
+
+    fn session_frame(protocol: u16) -> Vec<u8> {
+        let mut buffer = [0u8; 64];
+        let mut builder =
+            PacketBuilder::new_session_packet(&mut buffer, SRC, DST, NonZeroU16::new(1).unwrap())
+                .unwrap();
+        NE::write_u16(builder.payload_mut(), protocol);
+        builder.finish(4).unwrap().to_vec()
+    }
+
+    #[test]
+    fn control_frames_first() {
+        let mut buffer = [0u8; 64];
+        let mut padt = PacketBuilder::new_discovery_packet(&mut buffer, SRC, DST).unwrap();
+        padt.set_code(Code::Padt);
+        let padt = padt.as_bytes().to_vec();
+        let lcp = session_frame(PROTOCOL_LCP);
+        let ip = session_frame(PROTOCOL_IPV4);
+        assert_eq!(Priority::of(&padt), Priority::Control);
+        assert_eq!(Priority::of(&lcp), Priority::Control);
+        assert_eq!(Priority::of(&ip), Priority::Data);
+        assert_eq!(Priority::of(&ip[..10]), Priority::Data);
+
+        let mut scheduler = SendScheduler::new()
+            .with_data_queue_len(2)
+            .with_data_burst(1);
+        assert!(scheduler.push(&ip));
+        assert!(scheduler.push(&ip));
+        assert!(!scheduler.has_data_capacity());
+        assert!(!scheduler.push(&ip));
+        assert!(scheduler.push(&lcp));
+        assert!(scheduler.push(&padt));
+        assert_eq!(scheduler.dropped(), 1);
+
+        let mut sent = Vec::new();
+        let count = scheduler
+            .send_with(|frame| {
+                sent.push(Priority::of(frame));
+                Ok(frame.len())
+            })
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(sent, [Priority::Control, Priority::Control, Priority::Data]);
+
+        // a full socket keeps the frame queued
+        let count = scheduler
+            .send_with(|_| Err(io::ErrorKind::WouldBlock.into()))
+            .unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(scheduler.queued(Priority::Data), 1);
+        scheduler.send_with(|frame| Ok(frame.len())).unwrap();
+        assert!(scheduler.is_empty());
+    }
+}
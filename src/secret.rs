@@ -0,0 +1,101 @@
+//! Buffers for passwords and other secrets which are wiped when dropped
+//!
+//! With the `zeroize` feature the wiping is done by the `zeroize` crate and `Secret` implements
+//! its traits, so it can be part of other zeroizing types.
+
+use std::fmt;
+#[cfg(not(feature = "zeroize"))]
+use std::ptr;
+#[cfg(not(feature = "zeroize"))]
+use std::sync::atomic::{self, Ordering};
+
+/// Overwrite `buffer` with zeros in a way the compiler doesn't optimize away.
+#[cfg(not(feature = "zeroize"))]
+pub fn wipe(buffer: &mut [u8]) {
+    for byte in buffer.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    atomic::compiler_fence(Ordering::SeqCst);
+}
+
+/// Overwrite `buffer` with zeros in a way the compiler doesn't optimize away.
+#[cfg(feature = "zeroize")]
+pub fn wipe(buffer: &mut [u8]) {
+    zeroize::Zeroize::zeroize(buffer);
+}
+
+/// Secret bytes, e.g. a password, which are wiped on drop
+///
+/// The `Debug` output doesn't contain the secret.  Copies made with `expose` are not tracked,
+/// so they have to be wiped by the caller.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Secret(Vec<u8>);
+
+impl Secret {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Secret(secret)
+    }
+
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether the secret is empty, e.g. when none was configured
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for Secret {
+    fn from(secret: Vec<u8>) -> Self {
+        Secret(secret)
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Secret(secret.into_bytes())
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Secret(secret.as_bytes().to_vec())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({} bytes)", self.0.len())
+    }
+}
+
+impl Drop for Secret {
+    #[cfg(not(feature = "zeroize"))]
+    fn drop(&mut self) {
+        // include the spare capacity, it may hold a previous copy
+        let capacity = self.0.capacity();
+        self.0.resize(capacity, 0);
+        wipe(&mut self.0);
+    }
+
+    #[cfg(feature = "zeroize")]
+    fn drop(&mut self) {
+        // also wipes the spare capacity
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Secret {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for Secret {}
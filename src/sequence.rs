@@ -0,0 +1,293 @@
+//! Tracking of RFC 5578 sequence numbers
+//!
+//! Radio links may reorder or lose frames.  `SequenceTracker` counts gaps, late and duplicate
+//! frames of one session, `ReorderBuffer` can be used to hand frames on in order.
+
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU16;
+
+use crate::{Header, Tag};
+
+/// Number of missing sequence numbers remembered to detect late frames
+pub const DEFAULT_WINDOW: usize = 64;
+
+/// Get the value of the Sequence-Number tag of a packet.
+pub fn sequence_number(header: &Header) -> Option<u16> {
+    header.tags().find_map(|tag| match tag {
+        Tag::SequenceNumber(sequence_number) => Some(sequence_number),
+        _ => None,
+    })
+}
+
+/// Classification of a received sequence number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrival {
+    InOrder,
+    /// The frame skipped the given number of sequence numbers
+    Gap(u16),
+    /// The frame arrived after frames with higher sequence numbers
+    Late,
+    Duplicate,
+}
+
+/// Counters of a `SequenceTracker`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SequenceStats {
+    pub received: u64,
+    pub in_order: u64,
+    /// Number of sequence numbers which were skipped, late frames are not subtracted
+    pub skipped: u64,
+    pub late: u64,
+    pub duplicates: u64,
+    /// Skipped sequence numbers which didn't arrive within the window
+    pub lost: u64,
+}
+
+/// Detects gaps, reordering and duplicates in the sequence numbers of one session
+#[derive(Debug, Clone)]
+pub struct SequenceTracker {
+    next: Option<u16>,
+    missing: VecDeque<u16>,
+    window: usize,
+    stats: SequenceStats,
+}
+
+impl Default for SequenceTracker {
+    fn default() -> Self {
+        Self::with_window(DEFAULT_WINDOW)
+    }
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember up to `window` missing sequence numbers.  Older ones are counted as lost.
+    pub fn with_window(window: usize) -> Self {
+        Self {
+            next: None,
+            missing: VecDeque::with_capacity(window),
+            window,
+            stats: SequenceStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> &SequenceStats {
+        &self.stats
+    }
+
+    /// Sequence numbers which were skipped and may still arrive
+    pub fn missing(&self) -> impl Iterator<Item = u16> + '_ {
+        self.missing.iter().copied()
+    }
+
+    /// Record the reception of a sequence number.
+    pub fn track(&mut self, sequence_number: u16) -> Arrival {
+        self.stats.received += 1;
+
+        let next = match self.next {
+            Some(next) => next,
+            None => {
+                self.next = Some(sequence_number.wrapping_add(1));
+                self.stats.in_order += 1;
+                return Arrival::InOrder;
+            }
+        };
+
+        let distance = sequence_number.wrapping_sub(next) as i16;
+        if distance == 0 {
+            self.next = Some(sequence_number.wrapping_add(1));
+            self.stats.in_order += 1;
+            Arrival::InOrder
+        } else if distance > 0 {
+            let skipped = distance as u16;
+            for offset in 0..skipped {
+                self.remember_missing(next.wrapping_add(offset));
+            }
+            self.next = Some(sequence_number.wrapping_add(1));
+            self.stats.skipped += u64::from(skipped);
+            Arrival::Gap(skipped)
+        } else if let Some(index) = self.missing.iter().position(|&n| n == sequence_number) {
+            self.missing.remove(index);
+            self.stats.late += 1;
+            Arrival::Late
+        } else {
+            self.stats.duplicates += 1;
+            Arrival::Duplicate
+        }
+    }
+
+    fn remember_missing(&mut self, sequence_number: u16) {
+        if self.window == 0 {
+            self.stats.lost += 1;
+            return;
+        }
+        if self.missing.len() == self.window {
+            self.missing.pop_front();
+            self.stats.lost += 1;
+        }
+        self.missing.push_back(sequence_number);
+    }
+}
+
+/// `SequenceTracker`s for all sessions of a host
+#[derive(Debug, Clone, Default)]
+pub struct SessionSequences {
+    sessions: HashMap<NonZeroU16, SequenceTracker>,
+}
+
+impl SessionSequences {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track(&mut self, session_id: NonZeroU16, sequence_number: u16) -> Arrival {
+        self.sessions
+            .entry(session_id)
+            .or_default()
+            .track(sequence_number)
+    }
+
+    pub fn get(&self, session_id: NonZeroU16) -> Option<&SequenceTracker> {
+        self.sessions.get(&session_id)
+    }
+
+    /// Forget a session, e.g. after its PADT
+    pub fn remove(&mut self, session_id: NonZeroU16) -> Option<SequenceTracker> {
+        self.sessions.remove(&session_id)
+    }
+}
+
+/// A small buffer which releases items in sequence number order
+///
+/// Items are held back until the gap before them is filled, or until the buffer is full, in
+/// which case the missing sequence numbers are given up.
+#[derive(Debug, Clone)]
+pub struct ReorderBuffer<T> {
+    next: Option<u16>,
+    pending: Vec<(u16, T)>,
+    capacity: usize,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            next: None,
+            pending: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Number of items held back
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether no item is held back
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Add an item and append all items which are now in order to `ready`.
+    ///
+    /// Items older than the already released ones (late or duplicate) are dropped.
+    pub fn push(&mut self, sequence_number: u16, item: T, ready: &mut Vec<T>) {
+        let next = *self.next.get_or_insert(sequence_number);
+        if (sequence_number.wrapping_sub(next) as i16) < 0
+            || self.pending.iter().any(|(n, _)| *n == sequence_number)
+        {
+            return;
+        }
+
+        self.pending.push((sequence_number, item));
+        self.release(ready);
+
+        if self.pending.len() > self.capacity {
+            // give up on the gap and continue with the oldest held back item
+            let oldest = self.oldest_pending();
+            self.next = Some(oldest);
+            self.release(ready);
+        }
+    }
+
+    /// Release all held back items in order, skipping the gaps.
+    pub fn flush(&mut self, ready: &mut Vec<T>) {
+        while !self.pending.is_empty() {
+            self.next = Some(self.oldest_pending());
+            self.release(ready);
+        }
+    }
+
+    fn oldest_pending(&self) -> u16 {
+        let next = self.next.unwrap_or(0);
+        self.pending
+            .iter()
+            .map(|(n, _)| *n)
+            .min_by_key(|n| n.wrapping_sub(next))
+            .unwrap()
+    }
+
+    fn release(&mut self, ready: &mut Vec<T>) {
+        while let Some(next) = self.next {
+            match self.pending.iter().position(|(n, _)| *n == next) {
+                Some(index) => {
+                    ready.push(self.pending.swap_remove(index).1);
+                    self.next = Some(next.wrapping_add(1));
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_gaps_and_reordering() {
+        let mut tracker = SequenceTracker::with_window(2);
+        assert_eq!(tracker.track(0xfffe), Arrival::InOrder);
+        assert_eq!(tracker.track(0xffff), Arrival::InOrder);
+        assert_eq!(tracker.track(2), Arrival::Gap(2));
+        assert_eq!(tracker.track(0), Arrival::Late);
+        assert_eq!(tracker.track(0), Arrival::Duplicate);
+        assert_eq!(tracker.track(6), Arrival::Gap(3));
+
+        let stats = tracker.stats();
+        assert_eq!(stats.received, 6);
+        assert_eq!(stats.skipped, 5);
+        assert_eq!(stats.late, 1);
+        assert_eq!(stats.duplicates, 1);
+        // 1 and 3 got pushed out of the window
+        assert_eq!(stats.lost, 2);
+        assert_eq!(tracker.missing().collect::<Vec<_>>(), [4, 5]);
+    }
+
+    #[test]
+    fn reorder_buffer() {
+        let mut buffer = ReorderBuffer::new(2);
+        let mut ready = Vec::new();
+
+        buffer.push(10, 'a', &mut ready);
+        buffer.push(12, 'c', &mut ready);
+        buffer.push(11, 'b', &mut ready);
+        assert_eq!(ready, ['a', 'b', 'c']);
+
+        // 13 never arrives
+        ready.clear();
+        buffer.push(14, 'e', &mut ready);
+        buffer.push(15, 'f', &mut ready);
+        assert!(ready.is_empty());
+        buffer.push(16, 'g', &mut ready);
+        assert_eq!(ready, ['e', 'f', 'g']);
+
+        // late
+        ready.clear();
+        buffer.push(13, 'd', &mut ready);
+        buffer.push(18, 'i', &mut ready);
+        buffer.flush(&mut ready);
+        assert_eq!(ready, ['i']);
+    }
+}
@@ -0,0 +1,1295 @@
+//! Building blocks for the Access Concentrator side of PPPoE
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::CStr;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::num::NonZeroU16;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, ParseError};
+use crate::packet::PPPOE_DISCOVERY;
+use crate::timestamp::Timestamp;
+use crate::{eth, HeaderBuilder, Packet, PacketBuilder, ServiceNameMatcher, Tag};
+
+#[cfg(feature = "socket")]
+use std::{mem, sync::Arc, thread};
+
+#[cfg(feature = "socket")]
+use crate::Socket;
+
+/// Default time a released session id is kept out of use.
+pub const DEFAULT_SESSION_ID_QUARANTINE: Duration = Duration::from_secs(60);
+
+/// 0xffff is reserved by RFC 2516 and never handed out.
+const MAX_SESSION_ID: u16 = 0xfffe;
+
+/// Random candidates tried before `AllocationStrategy::Random` falls back to a scan
+const RANDOM_ATTEMPTS: usize = 32;
+
+/// How `SessionIdAllocator` picks the next session id
+///
+/// Sequential ids are easy to guess for anyone on a shared segment, who can then inject frames
+/// (e.g. a PADT) into a foreign session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AllocationStrategy {
+    /// Hand out ids in ascending order
+    Sequential,
+    /// Pick ids at random
+    #[default]
+    Random,
+    /// Derive the id from a keyed hash of the client MAC, so a client tends to get the same id
+    /// while the allocator lives, but other clients can't predict it.  Falls back to `Random`
+    /// without a MAC.
+    HashedFromMac,
+}
+
+/// Hands out unique session ids for new PPPoE sessions.
+///
+/// Released ids are quarantined for a while before they are reused, so a peer which missed the
+/// PADT doesn't receive frames of a new session as its own.
+#[derive(Debug)]
+pub struct SessionIdAllocator {
+    quarantine: Duration,
+    strategy: AllocationStrategy,
+    keys: RandomState,
+    rng: u64,
+    next: u16,
+    in_use: HashSet<u16>,
+    quarantined: HashSet<u16>,
+    released: VecDeque<(Instant, u16)>,
+}
+
+impl Default for SessionIdAllocator {
+    fn default() -> Self {
+        Self::with_quarantine(DEFAULT_SESSION_ID_QUARANTINE)
+    }
+}
+
+impl SessionIdAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_quarantine(quarantine: Duration) -> Self {
+        let keys = RandomState::new();
+        let rng = keys.build_hasher().finish();
+        Self {
+            quarantine,
+            strategy: AllocationStrategy::default(),
+            keys,
+            rng,
+            next: 1,
+            in_use: HashSet::new(),
+            quarantined: HashSet::new(),
+            released: VecDeque::new(),
+        }
+    }
+
+    /// Reuse released ids right away.  Only meant for test harnesses.
+    pub fn without_quarantine() -> Self {
+        Self::with_quarantine(Duration::from_secs(0))
+    }
+
+    pub fn with_strategy(mut self, strategy: AllocationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn quarantine(&self) -> Duration {
+        self.quarantine
+    }
+
+    pub fn strategy(&self) -> AllocationStrategy {
+        self.strategy
+    }
+
+    /// Number of ids currently handed out
+    pub fn len(&self) -> usize {
+        self.in_use.len()
+    }
+
+    /// Whether no id is handed out
+    pub fn is_empty(&self) -> bool {
+        self.in_use.is_empty()
+    }
+
+    pub fn is_allocated(&self, session_id: NonZeroU16) -> bool {
+        self.in_use.contains(&session_id.get())
+    }
+
+    pub fn is_quarantined(&self, session_id: NonZeroU16) -> bool {
+        self.quarantined.contains(&session_id.get())
+    }
+
+    /// Get an unused session id, or `None` if all ids are either in use or quarantined.
+    pub fn allocate(&mut self, now: Instant) -> Option<NonZeroU16> {
+        self.allocate_for(now, None)
+    }
+
+    /// Like `allocate`, with the MAC of the client for `AllocationStrategy::HashedFromMac`.
+    pub fn allocate_for(
+        &mut self,
+        now: Instant,
+        client_mac: Option<[u8; 6]>,
+    ) -> Option<NonZeroU16> {
+        self.expire_quarantine(now);
+
+        let candidate = match (self.strategy, client_mac) {
+            (AllocationStrategy::Sequential, _) => {
+                let candidate = self.scan(self.next)?;
+                self.next = if candidate == MAX_SESSION_ID {
+                    1
+                } else {
+                    candidate + 1
+                };
+                candidate
+            }
+            (AllocationStrategy::HashedFromMac, Some(client_mac)) => {
+                let mut hasher = self.keys.build_hasher();
+                hasher.write(&client_mac);
+                self.scan(to_session_id(hasher.finish()))?
+            }
+            (AllocationStrategy::Random, _) | (AllocationStrategy::HashedFromMac, None) => {
+                self.random_candidate()?
+            }
+        };
+
+        self.in_use.insert(candidate);
+        NonZeroU16::new(candidate)
+    }
+
+    fn random_candidate(&mut self) -> Option<u16> {
+        for _ in 0..RANDOM_ATTEMPTS {
+            let candidate = to_session_id(self.next_random());
+            if self.is_free(candidate) {
+                return Some(candidate);
+            }
+        }
+        // most ids are taken, use the next free one from a random start
+        let start = to_session_id(self.next_random());
+        self.scan(start)
+    }
+
+    fn is_free(&self, session_id: u16) -> bool {
+        !self.in_use.contains(&session_id) && !self.quarantined.contains(&session_id)
+    }
+
+    /// Find the first free id from `start` on, wrapping around.
+    fn scan(&self, start: u16) -> Option<u16> {
+        (start..=MAX_SESSION_ID)
+            .chain(1..start)
+            .find(|&candidate| self.is_free(candidate))
+    }
+
+    /// splitmix64, seeded from the random keys of the std hasher
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Give back a session id.  Returns `false` if the id was not allocated.
+    pub fn release(&mut self, now: Instant, session_id: NonZeroU16) -> bool {
+        let session_id = session_id.get();
+        if !self.in_use.remove(&session_id) {
+            return false;
+        }
+
+        if self.quarantine > Duration::from_secs(0) {
+            self.quarantined.insert(session_id);
+            self.released.push_back((now + self.quarantine, session_id));
+        }
+        true
+    }
+
+    /// Make a quarantined id available again right away.
+    pub fn release_quarantined(&mut self, session_id: NonZeroU16) -> bool {
+        let session_id = session_id.get();
+        self.released.retain(|&(_, id)| id != session_id);
+        self.quarantined.remove(&session_id)
+    }
+
+    fn expire_quarantine(&mut self, now: Instant) {
+        while let Some(&(until, session_id)) = self.released.front() {
+            if until > now {
+                break;
+            }
+            self.released.pop_front();
+            self.quarantined.remove(&session_id);
+        }
+    }
+}
+
+fn to_session_id(value: u64) -> u16 {
+    (value % u64::from(MAX_SESSION_ID)) as u16 + 1
+}
+
+/// Default maximal length of a rendered AC-Name.  The AC-Name often ends up in the RADIUS
+/// NAS-Identifier, whose value is limited to 253 bytes.
+pub const DEFAULT_MAX_AC_NAME_LEN: usize = 253;
+
+/// Values for the placeholders of an `AcNameTemplate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemInfo {
+    pub hostname: String,
+    pub interface: String,
+    pub chassis_id: Option<String>,
+}
+
+impl SystemInfo {
+    pub fn new(hostname: &str, interface: &str) -> Self {
+        Self {
+            hostname: hostname.to_owned(),
+            interface: interface.to_owned(),
+            chassis_id: None,
+        }
+    }
+
+    /// Get the info of this host, using the hostname of the system.
+    pub fn local(interface: &str) -> io::Result<Self> {
+        let mut hostname = [0 as libc::c_char; 256];
+        let ret = unsafe { libc::gethostname(hostname.as_mut_ptr(), hostname.len() - 1) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let hostname = unsafe { CStr::from_ptr(hostname.as_ptr()) };
+        Ok(Self::new(&hostname.to_string_lossy(), interface))
+    }
+
+    pub fn with_chassis_id(mut self, chassis_id: &str) -> Self {
+        self.chassis_id = Some(chassis_id.to_owned());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Hostname,
+    ShortHostname,
+    Interface,
+    ChassisId,
+}
+
+/// A template for the AC-Name advertised by a server, e.g. `"{short_hostname}-{interface}"`
+///
+/// Supported placeholders are `{hostname}`, `{short_hostname}` (the hostname up to the first
+/// dot), `{interface}` and `{chassis_id}`.  Literal braces are written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcNameTemplate {
+    segments: Vec<Segment>,
+    max_len: usize,
+}
+
+impl AcNameTemplate {
+    /// Parse a template.  Fails on unknown placeholders and unbalanced braces, the error carries
+    /// the byte offset of the offending brace (and the length of an unknown placeholder name).
+    pub fn parse(template: &str) -> Result<Self, ParseError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.char_indices().peekable();
+
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '{' if chars.peek().map(|&(_, c)| c) == Some('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek().map(|&(_, c)| c) == Some('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = &template[offset + 1..];
+                    let end = rest
+                        .find('}')
+                        .ok_or(ParseError::InvalidAcNameTemplate(offset))?;
+                    let segment = match &rest[..end] {
+                        "hostname" => Segment::Hostname,
+                        "short_hostname" => Segment::ShortHostname,
+                        "interface" => Segment::Interface,
+                        "chassis_id" => Segment::ChassisId,
+                        name => {
+                            return Err(ParseError::UnknownPlaceholder {
+                                offset,
+                                length: name.len(),
+                            })
+                        }
+                    };
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(segment);
+                    while chars.next().map(|(_, c)| c) != Some('}') {}
+                }
+                '}' => return Err(ParseError::InvalidAcNameTemplate(offset)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self {
+            segments,
+            max_len: DEFAULT_MAX_AC_NAME_LEN,
+        })
+    }
+
+    /// Change the maximal length of the rendered AC-Name.  It can't exceed the 65535 bytes of a
+    /// tag value.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len.min(usize::from(u16::MAX));
+        self
+    }
+
+    /// Substitute the placeholders and check the length of the result.
+    pub fn render(&self, info: &SystemInfo) -> Result<String, ParseError> {
+        let mut ac_name = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => ac_name.push_str(literal),
+                Segment::Hostname => ac_name.push_str(&info.hostname),
+                Segment::ShortHostname => {
+                    let short = info.hostname.split('.').next().unwrap_or_default();
+                    ac_name.push_str(short);
+                }
+                Segment::Interface => ac_name.push_str(&info.interface),
+                Segment::ChassisId => {
+                    let chassis_id = info
+                        .chassis_id
+                        .as_ref()
+                        .ok_or(ParseError::MissingPlaceholderValue("chassis_id"))?;
+                    ac_name.push_str(chassis_id);
+                }
+            }
+        }
+
+        if ac_name.len() > self.max_len {
+            return Err(ParseError::AcNameTooLong {
+                length: ac_name.len(),
+                max_length: self.max_len,
+            });
+        }
+        Ok(ac_name)
+    }
+}
+
+/// Default gap between two PADTs of `ClearSessions`
+pub const DEFAULT_PADT_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A session as known to the Access Concentrator, the input of `ClearSessions`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionEntry {
+    pub session_id: NonZeroU16,
+    pub client_mac: [u8; 6],
+    pub service_name: Vec<u8>,
+    /// Time the session was set up, for accounting
+    pub started: Timestamp,
+    /// Time the last frame of the session was received
+    pub last_activity: Instant,
+}
+
+/// Selects sessions by client MAC prefix, Service-Name and idle time
+///
+/// A session has to match all configured criteria, a filter without criteria matches every
+/// session.
+#[derive(Debug, Clone, Default)]
+pub struct SessionFilter {
+    mac_prefix: Vec<u8>,
+    service_name: Option<ServiceNameMatcher>,
+    idle_for: Option<Duration>,
+}
+
+impl SessionFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match clients whose MAC starts with `prefix`, e.g. the OUI of a CPE vendor.
+    pub fn with_mac_prefix(mut self, prefix: &[u8]) -> Self {
+        self.mac_prefix = prefix.to_vec();
+        self
+    }
+
+    pub fn with_service_name(mut self, service_name: ServiceNameMatcher) -> Self {
+        self.service_name = Some(service_name);
+        self
+    }
+
+    /// Only match sessions without activity for at least `idle_for`.
+    pub fn with_idle_for(mut self, idle_for: Duration) -> Self {
+        self.idle_for = Some(idle_for);
+        self
+    }
+
+    pub fn matches(&self, session: &SessionEntry, now: Instant) -> bool {
+        session.client_mac.starts_with(&self.mac_prefix)
+            && self
+                .service_name
+                .as_ref()
+                .is_none_or(|matcher| matcher.matches(&session.service_name))
+            && self.idle_for.is_none_or(|idle_for| {
+                now.saturating_duration_since(session.last_activity) >= idle_for
+            })
+    }
+}
+
+/// Terminates a batch of sessions, e.g. to clear all subscribers on a port
+///
+/// The PADTs are paced, so clearing thousands of sessions doesn't flood the access network or
+/// the RADIUS accounting behind it.  Like `Discovery`, it does no I/O on its own: the PADTs are
+/// requested via `poll_transmit` and `poll_timeout` returns the time the next one is due.  The
+/// selected sessions are known up front, the caller should tear down its own state for them.
+#[derive(Debug)]
+pub struct ClearSessions {
+    local_mac: [u8; 6],
+    interval: Duration,
+    reason: Option<Vec<u8>>,
+    selected: Vec<([u8; 6], NonZeroU16)>,
+    sent: usize,
+    next: Option<Instant>,
+}
+
+impl ClearSessions {
+    /// Select the `sessions` matching `filter`.
+    pub fn new<'s, I>(local_mac: [u8; 6], sessions: I, filter: &SessionFilter, now: Instant) -> Self
+    where
+        I: IntoIterator<Item = &'s SessionEntry>,
+    {
+        let selected = sessions
+            .into_iter()
+            .filter(|session| filter.matches(session, now))
+            .map(|session| (session.client_mac, session.session_id))
+            .collect();
+        Self {
+            local_mac,
+            interval: DEFAULT_PADT_INTERVAL,
+            reason: None,
+            selected,
+            sent: 0,
+            next: None,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Send `reason` in a Generic-Error tag with every PADT.
+    pub fn with_reason(mut self, reason: &[u8]) -> Self {
+        self.reason = Some(reason.to_vec());
+        self
+    }
+
+    /// Get the client MAC and session id of all selected sessions.
+    pub fn selected(&self) -> &[([u8; 6], NonZeroU16)] {
+        &self.selected
+    }
+
+    /// Number of PADTs which still have to be send
+    pub fn remaining(&self) -> usize {
+        self.selected.len() - self.sent
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Get the point in time at which the next PADT is due.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        if self.is_finished() {
+            return None;
+        }
+        self.next
+    }
+
+    /// Write the next PADT into `buffer` and return its length.
+    pub fn poll_transmit(
+        &mut self,
+        now: Instant,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, ParseError> {
+        match self.next {
+            Some(next) if now < next => return Ok(None),
+            _ => (),
+        }
+        let (client_mac, session_id) = match self.selected.get(self.sent) {
+            Some(&session) => session,
+            None => return Ok(None),
+        };
+        let len = write_padt(
+            buffer,
+            self.local_mac,
+            client_mac,
+            session_id,
+            self.reason.as_deref(),
+        )?;
+
+        self.sent += 1;
+        self.next = Some(now + self.interval);
+        Ok(Some(len))
+    }
+}
+
+/// Write a PADT, with `reason` in a Generic-Error tag.
+fn write_padt(
+    buffer: &mut [u8],
+    local_mac: [u8; 6],
+    client_mac: [u8; 6],
+    session_id: NonZeroU16,
+    reason: Option<&[u8]>,
+) -> Result<usize, ParseError> {
+    if buffer.len() < 14 {
+        return Err(ParseError::BufferTooSmall(buffer.len()));
+    }
+
+    let (eth_buf, pppoe_buf) = buffer.split_at_mut(14);
+    let mut ethernet = eth::HeaderBuilder::with_buffer(eth_buf)?;
+    ethernet.set_src_address(local_mac);
+    ethernet.set_dst_address(client_mac);
+    ethernet.set_ether_type(PPPOE_DISCOVERY);
+    let mut padt = HeaderBuilder::create_padt(pppoe_buf, session_id)?;
+    if let Some(reason) = reason {
+        padt.add_tag(Tag::GenericError(reason))?;
+    }
+    Ok(14 + padt.len())
+}
+
+/// Why a session timed out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutReason {
+    /// No frame of the session was received for the idle timeout
+    Idle,
+    /// The session reached its maximum lifetime
+    Lifetime,
+}
+
+impl TimeoutReason {
+    /// Get the message sent in the Generic-Error tag of the PADT.
+    pub fn message(&self) -> &'static [u8] {
+        match self {
+            TimeoutReason::Idle => b"session idle timeout",
+            TimeoutReason::Lifetime => b"session lifetime exceeded",
+        }
+    }
+}
+
+/// Idle timeout and maximum lifetime of a session, `None` means no limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionTimeouts {
+    /// Terminate sessions without a received frame for this long
+    pub idle: Option<Duration>,
+    /// Terminate sessions this long after they were set up, regardless of their activity
+    pub lifetime: Option<Duration>,
+}
+
+impl SessionTimeouts {
+    // timeouts too large for an `Instant`, e.g. `Duration::MAX` for "no limit", never expire
+    fn idle_deadline(&self, session: &SessionEntry) -> Option<Instant> {
+        self.idle
+            .and_then(|idle| session.last_activity.checked_add(idle))
+    }
+
+    fn lifetime_deadline(&self, session: &SessionEntry) -> Option<Instant> {
+        self.lifetime
+            .and_then(|lifetime| session.started.monotonic.checked_add(lifetime))
+    }
+
+    /// Get the point in time at which `session` times out, unless there is activity before.
+    pub fn deadline(&self, session: &SessionEntry) -> Option<Instant> {
+        match (self.idle_deadline(session), self.lifetime_deadline(session)) {
+            (Some(idle), Some(lifetime)) => Some(idle.min(lifetime)),
+            (deadline, None) | (None, deadline) => deadline,
+        }
+    }
+
+    /// Check whether `session` timed out at `now`.  The lifetime takes precedence if both
+    /// expired.
+    pub fn expired(&self, session: &SessionEntry, now: Instant) -> Option<TimeoutReason> {
+        if self
+            .lifetime_deadline(session)
+            .is_some_and(|deadline| now >= deadline)
+        {
+            return Some(TimeoutReason::Lifetime);
+        }
+        if self
+            .idle_deadline(session)
+            .is_some_and(|deadline| now >= deadline)
+        {
+            return Some(TimeoutReason::Idle);
+        }
+        None
+    }
+}
+
+/// Session timeouts per service
+///
+/// Sessions get the timeouts of the first service matching their Service-Name, or the default
+/// ones if none matches.
+#[derive(Debug, Clone, Default)]
+pub struct TimeoutPolicy {
+    default: SessionTimeouts,
+    services: Vec<(ServiceNameMatcher, SessionTimeouts)>,
+}
+
+impl TimeoutPolicy {
+    pub fn new(default: SessionTimeouts) -> Self {
+        Self {
+            default,
+            services: Vec::new(),
+        }
+    }
+
+    /// Use `timeouts` for the sessions of `service`.
+    pub fn with_service(mut self, service: ServiceNameMatcher, timeouts: SessionTimeouts) -> Self {
+        self.services.push((service, timeouts));
+        self
+    }
+
+    pub fn timeouts_for(&self, service_name: &[u8]) -> &SessionTimeouts {
+        self.services
+            .iter()
+            .find(|(service, _)| service.matches(service_name))
+            .map_or(&self.default, |(_, timeouts)| timeouts)
+    }
+
+    /// Check whether `session` timed out at `now`, see `SessionTimeouts::expired`.
+    pub fn expired(&self, session: &SessionEntry, now: Instant) -> Option<TimeoutReason> {
+        self.timeouts_for(&session.service_name)
+            .expired(session, now)
+    }
+
+    /// Get the earliest deadline of `sessions`, e.g. to know when to check them again.
+    pub fn next_deadline<'s, I>(&self, sessions: I) -> Option<Instant>
+    where
+        I: IntoIterator<Item = &'s SessionEntry>,
+    {
+        sessions
+            .into_iter()
+            .filter_map(|session| self.timeouts_for(&session.service_name).deadline(session))
+            .min()
+    }
+}
+
+/// Write the PADT terminating a session which timed out, with `reason.message()` in a
+/// Generic-Error tag.
+pub fn write_timeout_padt(
+    buffer: &mut [u8],
+    local_mac: [u8; 6],
+    session: &SessionEntry,
+    reason: TimeoutReason,
+) -> Result<usize, ParseError> {
+    write_padt(
+        buffer,
+        local_mac,
+        session.client_mac,
+        session.session_id,
+        Some(reason.message()),
+    )
+}
+
+/// Pick the service to offer for the Service-Name requested in a PADI or PADR.
+///
+/// An empty request accepts any service, so the first one is offered.  `None` means the
+/// request should be answered with a Service-Name-Error.
+pub fn offered_service<'s>(
+    services: &'s [ServiceNameMatcher],
+    requested: &[u8],
+) -> Option<&'s ServiceNameMatcher> {
+    if requested.is_empty() {
+        return services.first();
+    }
+    services.iter().find(|service| service.matches(requested))
+}
+
+/// Caps on the state an Access Concentrator keeps, `None` means unlimited
+///
+/// Without caps, a flood of PADIs or PADRs (e.g. with random source MACs) lets the discovery
+/// and session state grow until the AC runs out of memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Concurrent sessions on all interfaces
+    pub max_sessions: Option<usize>,
+    pub max_sessions_per_interface: Option<usize>,
+    /// Discoveries in progress on all interfaces, i.e. PADOs sent without a PADR received yet
+    pub max_discoveries: Option<usize>,
+    pub max_discoveries_per_interface: Option<usize>,
+}
+
+/// A resource counted by `ResourceAccounting`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    Session,
+    Discovery,
+}
+
+/// The limit which made `ResourceAccounting::acquire` fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Global(Resource),
+    Interface(Resource),
+}
+
+impl LimitExceeded {
+    /// Get the message sent in the AC-System-Error tag, see `write_rejected_pads`.
+    pub fn message(&self) -> &'static [u8] {
+        match self {
+            LimitExceeded::Global(Resource::Session) => b"maximum number of sessions reached",
+            LimitExceeded::Interface(Resource::Session) => {
+                b"maximum number of sessions on this interface reached"
+            }
+            LimitExceeded::Global(Resource::Discovery)
+            | LimitExceeded::Interface(Resource::Discovery) => b"too many pending requests",
+        }
+    }
+}
+
+/// Gauges and counters of `ResourceAccounting`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceStats {
+    /// Sessions currently accounted
+    pub sessions: usize,
+    /// Discoveries currently accounted
+    pub discoveries: usize,
+    pub rejected_sessions: u64,
+    pub rejected_discoveries: u64,
+}
+
+impl ResourceStats {
+    fn count(&self, resource: Resource) -> usize {
+        match resource {
+            Resource::Session => self.sessions,
+            Resource::Discovery => self.discoveries,
+        }
+    }
+
+    fn count_mut(&mut self, resource: Resource) -> &mut usize {
+        match resource {
+            Resource::Session => &mut self.sessions,
+            Resource::Discovery => &mut self.discoveries,
+        }
+    }
+}
+
+/// Counts sessions and discoveries in progress per interface and enforces `ResourceLimits`
+///
+/// Every successful `acquire` has to be paired with a `release`, e.g. a discovery is released
+/// once the PADR arrived or the client gave up, and a session once it was terminated.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceAccounting {
+    limits: ResourceLimits,
+    stats: ResourceStats,
+    // only interfaces with something accounted, the rejections are counted in `stats` only
+    interfaces: HashMap<String, ResourceStats>,
+}
+
+impl ResourceAccounting {
+    pub fn new(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    pub fn limits(&self) -> &ResourceLimits {
+        &self.limits
+    }
+
+    /// Change the limits, e.g. after the configuration was reloaded.  Resources which are
+    /// already accounted are kept, even if they exceed the new limits.
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+
+    /// Account one `resource` on `interface`, or count a rejection if a limit is reached.
+    pub fn acquire(&mut self, resource: Resource, interface: &str) -> Result<(), LimitExceeded> {
+        let (max, max_per_interface) = match resource {
+            Resource::Session => (
+                self.limits.max_sessions,
+                self.limits.max_sessions_per_interface,
+            ),
+            Resource::Discovery => (
+                self.limits.max_discoveries,
+                self.limits.max_discoveries_per_interface,
+            ),
+        };
+        let on_interface = self.usage(interface, resource);
+
+        let exceeded = if max.is_some_and(|max| self.stats.count(resource) >= max) {
+            Some(LimitExceeded::Global(resource))
+        } else if max_per_interface.is_some_and(|max| on_interface >= max) {
+            Some(LimitExceeded::Interface(resource))
+        } else {
+            None
+        };
+        if let Some(exceeded) = exceeded {
+            match resource {
+                Resource::Session => self.stats.rejected_sessions += 1,
+                Resource::Discovery => self.stats.rejected_discoveries += 1,
+            }
+            return Err(exceeded);
+        }
+
+        *self.stats.count_mut(resource) += 1;
+        *self
+            .interfaces
+            .entry(interface.to_owned())
+            .or_default()
+            .count_mut(resource) += 1;
+        Ok(())
+    }
+
+    /// Give back a resource.  Returns `false` if none was accounted on `interface`.
+    pub fn release(&mut self, resource: Resource, interface: &str) -> bool {
+        let stats = match self.interfaces.get_mut(interface) {
+            Some(stats) if stats.count(resource) > 0 => stats,
+            _ => return false,
+        };
+        *stats.count_mut(resource) -= 1;
+        if stats.sessions == 0 && stats.discoveries == 0 {
+            self.interfaces.remove(interface);
+        }
+        *self.stats.count_mut(resource) -= 1;
+        true
+    }
+
+    /// Get the gauges and rejection counters of all interfaces.
+    pub fn stats(&self) -> &ResourceStats {
+        &self.stats
+    }
+
+    /// Get the number of resources accounted on `interface`.
+    pub fn usage(&self, interface: &str, resource: Resource) -> usize {
+        self.interfaces
+            .get(interface)
+            .map_or(0, |stats| stats.count(resource))
+    }
+
+    /// Get the gauges of every interface with something accounted.  Rejections are only
+    /// counted in `stats`, so the rejection counters of the interfaces are always 0.
+    pub fn interface_stats(&self) -> impl Iterator<Item = (&str, &ResourceStats)> {
+        self.interfaces
+            .iter()
+            .map(|(interface, stats)| (interface.as_str(), stats))
+    }
+}
+
+/// Answer a PADR which exceeds a limit with a PADS without a session (session id 0), carrying
+/// an AC-System-Error tag with `exceeded.message()`.
+///
+/// The Service-Name, Host-Uniq and Relay-Session-Id of the PADR are echoed.  PADIs exceeding a
+/// limit should be dropped instead of answered, so a flood of PADIs isn't amplified.
+pub fn write_rejected_pads(
+    buffer: &mut [u8],
+    local_mac: [u8; 6],
+    padr: &Packet,
+    exceeded: LimitExceeded,
+) -> Result<usize, Error> {
+    let request = padr.pppoe_header();
+    let mut packet = PacketBuilder::new_discovery_packet(
+        buffer,
+        local_mac,
+        padr.ethernet_header().src_address(),
+    )?;
+    packet.set_code(crate::Code::Pads);
+    let pads = packet.pppoe_header();
+    pads.add_tag(Tag::ServiceName(request.service_name().unwrap_or_default()))?;
+    if let Some(host_uniq) = request.host_uniq() {
+        pads.add_tag(Tag::HostUniq(host_uniq))?;
+    }
+    if let Some(relay_session_id) = request.relay_session_id() {
+        pads.add_tag(Tag::RelaySessionId(relay_session_id))?;
+    }
+    pads.add_tag(Tag::AcSystemError(exceeded.message()))?;
+    Ok(packet.len())
+}
+
+/// Get the index of the worker which receives the frames of a session, see
+/// `spawn_session_workers`.
+pub fn worker_for_session(session_id: NonZeroU16, workers: usize) -> usize {
+    usize::from(session_id.get()) % workers
+}
+
+#[cfg(feature = "socket")]
+fn pin_to_cpu(cpu: usize) -> io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    unsafe { libc::CPU_SET(cpu, &mut set) };
+
+    let ret = unsafe { libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Start `workers` threads, each with its own `Socket` on `interface` and pinned to its own CPU.
+///
+/// The session sockets of all workers join the fanout group `group_id`, so the frames of a
+/// session are always received by the same worker (see `worker_for_session`) and the session
+/// state never has to be shared between threads.  Discovery frames are not distributed, every
+/// worker receives all of them on its raw socket.
+///
+/// `worker` is called with the index of the worker and its socket.  The sockets are created
+/// before the threads are started, so the fanout group is complete once this returns.
+#[cfg(feature = "socket")]
+pub fn spawn_session_workers<F>(
+    interface: &str,
+    workers: usize,
+    group_id: u16,
+    worker: F,
+) -> io::Result<Vec<thread::JoinHandle<io::Result<()>>>>
+where
+    F: Fn(usize, Socket) -> io::Result<()> + Send + Sync + 'static,
+{
+    let mut sockets = Vec::with_capacity(workers);
+    for _ in 0..workers {
+        let mut socket = Socket::on_interface(interface)?;
+        socket.open_session_socket()?;
+        socket.join_session_fanout(group_id)?;
+        sockets.push(socket);
+    }
+
+    let cpus = thread::available_parallelism().map_or(1, usize::from);
+    let worker = Arc::new(worker);
+    let mut handles = Vec::with_capacity(workers);
+    for (index, socket) in sockets.into_iter().enumerate() {
+        let worker = Arc::clone(&worker);
+        let handle = thread::Builder::new()
+            .name(format!("pppoe-worker-{}", index))
+            .spawn(move || {
+                pin_to_cpu(index % cpus)?;
+                worker(index, socket)
+            })?;
+        handles.push(handle);
+    }
+    Ok(handles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn released_ids_are_quarantined() {
+        let start = Instant::now();
+        let mut allocator = SessionIdAllocator::with_quarantine(Duration::from_secs(10))
+            .with_strategy(AllocationStrategy::Sequential);
+        let first = allocator.allocate(start).unwrap();
+        assert_eq!(first.get(), 1);
+        assert!(allocator.release(start, first));
+        assert!(!allocator.release(start, first));
+
+        // not handed out again, even after wrapping around
+        allocator.next = 1;
+        assert_eq!(allocator.allocate(start).unwrap().get(), 2);
+        allocator.next = 1;
+        assert_eq!(
+            allocator
+                .allocate(start + Duration::from_secs(10))
+                .unwrap()
+                .get(),
+            1
+        );
+    }
+
+    #[test]
+    fn allocation_strategies() {
+        let now = Instant::now();
+        let mut allocator = SessionIdAllocator::without_quarantine();
+        assert_eq!(allocator.strategy(), AllocationStrategy::Random);
+        let ids: HashSet<u16> = (0..1000)
+            .map(|_| allocator.allocate(now).unwrap().get())
+            .collect();
+        assert_eq!(ids.len(), 1000);
+        // sequential allocation would have handed out 1..=1000
+        assert!(ids.iter().any(|&id| id > 1000));
+
+        let mut allocator = SessionIdAllocator::without_quarantine()
+            .with_strategy(AllocationStrategy::HashedFromMac);
+        let client = [0x02, 0, 0, 0, 0, 1];
+        let first = allocator.allocate_for(now, Some(client)).unwrap();
+        assert!(allocator.release(now, first));
+        assert_eq!(allocator.allocate_for(now, Some(client)), Some(first));
+        // the next one probes past the taken id
+        let second = allocator.allocate_for(now, Some(client)).unwrap();
+        assert_ne!(second, first);
+        assert!(allocator.allocate(now).is_some());
+    }
+
+    #[test]
+    fn offer_services() {
+        let services = [
+            ServiceNameMatcher::new("internet").ignore_trailing_nul(),
+            ServiceNameMatcher::new("iptv").ignore_ascii_case(),
+        ];
+        assert_eq!(offered_service(&services, b""), Some(&services[0]));
+        assert_eq!(
+            offered_service(&services, b"internet\0"),
+            Some(&services[0])
+        );
+        assert_eq!(offered_service(&services, b"IPTV"), Some(&services[1]));
+        assert_eq!(offered_service(&services, b"voip"), None);
+        assert_eq!(
+            offered_service(&[ServiceNameMatcher::any()], b"voip"),
+            Some(&ServiceNameMatcher::any())
+        );
+    }
+
+    #[test]
+    fn clear_sessions_on_port() {
+        // the sessions are an hour old at `now`, subtracting that from `Instant::now()` would
+        // fail on a freshly booted machine
+        let start = Instant::now();
+        let now = start + Duration::from_secs(3600);
+        let session = |id, client_mac, service_name: &[u8], idle| SessionEntry {
+            session_id: NonZeroU16::new(id).unwrap(),
+            client_mac,
+            service_name: service_name.to_vec(),
+            started: Timestamp::now().at(start),
+            last_activity: now - Duration::from_secs(idle),
+        };
+        let sessions = [
+            session(1, [0x00, 0x11, 0x22, 0, 0, 1], b"internet", 600),
+            session(2, [0x00, 0x11, 0x22, 0, 0, 2], b"iptv", 600),
+            session(3, [0x00, 0x11, 0x22, 0, 0, 3], b"internet", 5),
+            session(4, [0x02, 0x11, 0x22, 0, 0, 4], b"internet", 600),
+        ];
+        let filter = SessionFilter::new()
+            .with_mac_prefix(&[0x00, 0x11, 0x22])
+            .with_service_name(ServiceNameMatcher::new("internet"))
+            .with_idle_for(Duration::from_secs(60));
+        let local_mac = [0x02, 0, 0, 0, 0, 0xac];
+        let mut clear = ClearSessions::new(local_mac, &sessions, &filter, now)
+            .with_interval(Duration::from_millis(10))
+            .with_reason(b"admin");
+        assert_eq!(
+            clear.selected(),
+            [(sessions[0].client_mac, sessions[0].session_id)]
+        );
+
+        let mut buffer = [0u8; 64];
+        let len = clear.poll_transmit(now, &mut buffer).unwrap().unwrap();
+        let padt = crate::Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padt.pppoe_header().code(), crate::header::PADT);
+        assert_eq!(padt.pppoe_header().session_id(), 1);
+        assert_eq!(padt.ethernet_header().dst_address(), sessions[0].client_mac);
+        assert!(padt
+            .pppoe_header()
+            .tags()
+            .any(|tag| tag == Tag::GenericError(b"admin")));
+        assert!(clear.is_finished());
+        assert_eq!(clear.poll_timeout(), None);
+
+        let mut clear = ClearSessions::new(local_mac, &sessions, &SessionFilter::new(), now)
+            .with_interval(Duration::from_millis(10));
+        assert!(clear.poll_transmit(now, &mut buffer).unwrap().is_some());
+        assert_eq!(clear.poll_transmit(now, &mut buffer).unwrap(), None);
+        let next = clear.poll_timeout().unwrap();
+        assert_eq!(next, now + Duration::from_millis(10));
+        assert!(clear.poll_transmit(next, &mut buffer).unwrap().is_some());
+        assert_eq!(clear.remaining(), 2);
+    }
+
+    #[test]
+    fn session_timeouts() {
+        // sessions are up to two hours old at `now`
+        let now = Instant::now() + Duration::from_secs(7200);
+        let session = |service_name: &[u8], age, idle| SessionEntry {
+            session_id: NonZeroU16::new(7).unwrap(),
+            client_mac: [0x02, 0, 0, 0, 0, 1],
+            service_name: service_name.to_vec(),
+            started: Timestamp::now().at(now - Duration::from_secs(age)),
+            last_activity: now - Duration::from_secs(idle),
+        };
+        let policy = TimeoutPolicy::new(SessionTimeouts {
+            idle: Some(Duration::from_secs(300)),
+            lifetime: None,
+        })
+        .with_service(
+            ServiceNameMatcher::new("iptv"),
+            SessionTimeouts {
+                idle: None,
+                lifetime: Some(Duration::from_secs(3600)),
+            },
+        );
+
+        assert_eq!(policy.expired(&session(b"internet", 7200, 10), now), None);
+        assert_eq!(
+            policy.expired(&session(b"internet", 7200, 300), now),
+            Some(TimeoutReason::Idle)
+        );
+        assert_eq!(policy.expired(&session(b"iptv", 10, 3000), now), None);
+        assert_eq!(
+            policy.expired(&session(b"iptv", 3600, 0), now),
+            Some(TimeoutReason::Lifetime)
+        );
+
+        let sessions = [session(b"internet", 0, 100), session(b"iptv", 3500, 0)];
+        assert_eq!(
+            policy.next_deadline(&sessions),
+            Some(now + Duration::from_secs(100))
+        );
+        assert_eq!(SessionTimeouts::default().deadline(&sessions[0]), None);
+        let unlimited = SessionTimeouts {
+            idle: Some(Duration::MAX),
+            lifetime: Some(Duration::MAX),
+        };
+        assert_eq!(unlimited.deadline(&sessions[0]), None);
+        assert_eq!(unlimited.expired(&sessions[0], now), None);
+
+        let mut buffer = [0u8; 64];
+        let len =
+            write_timeout_padt(&mut buffer, [0x02; 6], &sessions[0], TimeoutReason::Idle).unwrap();
+        let padt = crate::Packet::with_buffer(&buffer[..len]).unwrap();
+        assert_eq!(padt.pppoe_header().session_id(), 7);
+        assert_eq!(
+            padt.pppoe_header().tags().next(),
+            Some(Tag::GenericError(b"session idle timeout"))
+        );
+    }
+
+    #[test]
+    fn render_ac_name() {
+        let info = SystemInfo::new("bras1.fra.example.net", "eth1").with_chassis_id("00:11:22");
+
+        let template = AcNameTemplate::parse("{short_hostname}-{interface} {{{chassis_id}}}");
+        assert_eq!(
+            template.unwrap().render(&info).unwrap(),
+            "bras1-eth1 {00:11:22}"
+        );
+        assert_eq!(
+            AcNameTemplate::parse("{hostname}")
+                .unwrap()
+                .with_max_len(10)
+                .render(&info),
+            Err(ParseError::AcNameTooLong {
+                length: 21,
+                max_length: 10
+            })
+        );
+        assert_eq!(
+            AcNameTemplate::parse("{chassis_id}")
+                .unwrap()
+                .render(&SystemInfo::new("ac", "eth0")),
+            Err(ParseError::MissingPlaceholderValue("chassis_id"))
+        );
+
+        assert_eq!(
+            AcNameTemplate::parse("pop-{site}"),
+            Err(ParseError::UnknownPlaceholder {
+                offset: 4,
+                length: 4
+            })
+        );
+        assert_eq!(
+            AcNameTemplate::parse("ac-{hostname"),
+            Err(ParseError::InvalidAcNameTemplate(3))
+        );
+        assert_eq!(
+            AcNameTemplate::parse("ac}"),
+            Err(ParseError::InvalidAcNameTemplate(2))
+        );
+    }
+
+    #[test]
+    fn resource_limits() {
+        let mut accounting = ResourceAccounting::new(ResourceLimits {
+            max_sessions: Some(3),
+            max_sessions_per_interface: Some(2),
+            max_discoveries: Some(1),
+            ..ResourceLimits::default()
+        });
+        assert_eq!(accounting.acquire(Resource::Session, "eth0"), Ok(()));
+        assert_eq!(accounting.acquire(Resource::Session, "eth0"), Ok(()));
+        assert_eq!(
+            accounting.acquire(Resource::Session, "eth0"),
+            Err(LimitExceeded::Interface(Resource::Session))
+        );
+        assert_eq!(accounting.acquire(Resource::Session, "eth1"), Ok(()));
+        assert_eq!(
+            accounting.acquire(Resource::Session, "eth2"),
+            Err(LimitExceeded::Global(Resource::Session))
+        );
+        assert_eq!(accounting.acquire(Resource::Discovery, "eth2"), Ok(()));
+        assert_eq!(
+            accounting.acquire(Resource::Discovery, "eth2"),
+            Err(LimitExceeded::Global(Resource::Discovery))
+        );
+        assert_eq!(
+            *accounting.stats(),
+            ResourceStats {
+                sessions: 3,
+                discoveries: 1,
+                rejected_sessions: 2,
+                rejected_discoveries: 1,
+            }
+        );
+
+        assert!(accounting.release(Resource::Discovery, "eth2"));
+        assert!(!accounting.release(Resource::Discovery, "eth2"));
+        assert!(accounting.release(Resource::Session, "eth1"));
+        assert_eq!(accounting.usage("eth0", Resource::Session), 2);
+        assert_eq!(
+            accounting.interface_stats().collect::<Vec<_>>(),
+            [(
+                "eth0",
+                &ResourceStats {
+                    sessions: 2,
+                    ..ResourceStats::default()
+                }
+            )]
+        );
+        assert_eq!(accounting.acquire(Resource::Session, "eth2"), Ok(()));
+
+        let mut buffer = [0u8; 64];
+        let mut padr =
+            PacketBuilder::new_discovery_packet(&mut buffer, [0x02, 0, 0, 0, 0, 1], [0x02; 6])
+                .unwrap();
+        padr.set_code(crate::Code::Padr);
+        padr.pppoe_header()
+            .add_tag(Tag::ServiceName(b"internet"))
+            .unwrap();
+        padr.pppoe_header().add_tag(Tag::HostUniq(b"hu")).unwrap();
+        let len = padr.len();
+        let padr = Packet::with_buffer(&buffer[..len]).unwrap();
+
+        let mut reply = [0u8; 128];
+        let exceeded = LimitExceeded::Global(Resource::Session);
+        let len = write_rejected_pads(&mut reply, [0x02; 6], &padr, exceeded).unwrap();
+        let pads = Packet::with_buffer(&reply[..len]).unwrap();
+        assert_eq!(pads.ethernet_header().dst_address(), [0x02, 0, 0, 0, 0, 1]);
+        let header = pads.pppoe_header();
+        assert_eq!(header.code(), crate::header::PADS);
+        assert_eq!(header.session_id(), 0);
+        assert_eq!(header.host_uniq(), Some(&b"hu"[..]));
+        assert_eq!(
+            header.tag_value(crate::tag::TAG_AC_SYSTEM_ERROR),
+            Some(exceeded.message())
+        );
+    }
+
+    #[test]
+    fn exhausted_allocator() {
+        let now = Instant::now();
+        let mut allocator = SessionIdAllocator::new();
+        for _ in 0..MAX_SESSION_ID {
+            assert!(allocator.allocate(now).is_some());
+        }
+        assert_eq!(allocator.len(), usize::from(MAX_SESSION_ID));
+        assert!(allocator.allocate(now).is_none());
+
+        let id = NonZeroU16::new(42).unwrap();
+        allocator.release(now, id);
+        assert!(allocator.is_quarantined(id));
+        assert!(allocator.allocate(now).is_none());
+        assert!(allocator.release_quarantined(id));
+        assert_eq!(allocator.allocate(now), Some(id));
+    }
+}
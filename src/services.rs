@@ -0,0 +1,377 @@
+//! Sessions for several services of one Access Concentrator
+//!
+//! Some wholesale setups deliver e.g. internet and IPTV as separate PPPoE services, each with
+//! its own session.  `ServiceDiscovery` runs one `Discovery` per Service-Name on a shared socket.
+//! The first service picks the Access Concentrator, the others are only requested from that AC
+//! once it is known.  If the first service fails before that, the next one picks the AC.
+//!
+//! The discoveries are told apart by their Host-Uniq: every service sends the Host-Uniq of the
+//! options followed by its index as two bytes.  Each established session still needs its own
+//! `Socket` to be connected in the kernel.
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crate::error::{Error, ProtocolError};
+use crate::logging::DISCOVERY;
+use crate::poll::Machine;
+use crate::{Discovery, DiscoveryOptions, SessionInfo};
+
+#[derive(Debug)]
+enum ServiceState {
+    /// Waiting for the Access Concentrator to be picked
+    Waiting,
+    Discovering(Box<Discovery>),
+    Established(SessionInfo),
+    Failed(ProtocolError),
+}
+
+/// The discovery stage for several services in parallel
+#[derive(Debug)]
+pub struct ServiceDiscovery {
+    local_mac: [u8; 6],
+    options: DiscoveryOptions,
+    // in the order of the Service-Names passed to `new`
+    services: Vec<(Vec<u8>, ServiceState)>,
+    ac_mac: Option<[u8; 6]>,
+}
+
+impl ServiceDiscovery {
+    /// Set up sessions for `service_names` with the `options` shared by all of them.
+    ///
+    /// `DiscoveryOptions::service_name` and `service_name_matcher` are ignored, every service
+    /// has to be offered with exactly its name.
+    pub fn new<S: AsRef<[u8]>>(
+        local_mac: [u8; 6],
+        options: DiscoveryOptions,
+        service_names: &[S],
+    ) -> Self {
+        let mut discovery = Self {
+            local_mac,
+            ac_mac: options.ac_mac,
+            options,
+            services: service_names
+                .iter()
+                .map(|service_name| (service_name.as_ref().to_vec(), ServiceState::Waiting))
+                .collect(),
+        };
+        discovery.start_waiting();
+        discovery
+    }
+
+    /// Get the session of a service, once it was established.
+    pub fn session(&self, service_name: &[u8]) -> Option<&SessionInfo> {
+        self.services
+            .iter()
+            .find(|(name, _)| name[..] == *service_name)
+            .and_then(|(_, state)| match state {
+                ServiceState::Established(session) => Some(session),
+                _ => None,
+            })
+    }
+
+    /// Get the established sessions by Service-Name.
+    pub fn sessions(&self) -> BTreeMap<&[u8], &SessionInfo> {
+        self.services
+            .iter()
+            .filter_map(|(name, state)| match state {
+                ServiceState::Established(session) => Some((&name[..], session)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the errors of the services which failed by Service-Name.
+    pub fn failures(&self) -> BTreeMap<&[u8], &ProtocolError> {
+        self.services
+            .iter()
+            .filter_map(|(name, state)| match state {
+                ServiceState::Failed(error) => Some((&name[..], error)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the MAC address of the Access Concentrator, once it was picked.
+    pub fn ac_mac(&self) -> Option<[u8; 6]> {
+        self.ac_mac
+    }
+
+    /// Check whether every service was established or failed.
+    pub fn is_finished(&self) -> bool {
+        self.services.iter().all(|(_, state)| {
+            matches!(
+                state,
+                ServiceState::Established(_) | ServiceState::Failed(_)
+            )
+        })
+    }
+
+    /// Get the earliest retransmission due of all services.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.discoveries()
+            .filter_map(|discovery| discovery.poll_timeout())
+            .min()
+    }
+
+    /// Write the next frame which has to be send for any service into `buffer`.  Call again
+    /// until it returns `None`, as several services may have to send at the same time.
+    ///
+    /// Services which time out are recorded in `failures`, only other errors are returned.
+    pub fn poll_transmit(
+        &mut self,
+        now: Instant,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, Error> {
+        for index in 0..self.services.len() {
+            let result = match &mut self.services[index].1 {
+                ServiceState::Discovering(discovery) => discovery.poll_transmit(now, buffer),
+                _ => continue,
+            };
+            if let Some(Some(len)) = self.update(index, result)? {
+                return Ok(Some(len));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pass a received frame to the discoveries of all services.
+    ///
+    /// Rejections of a service by the Access Concentrator are recorded in `failures`, only
+    /// other errors are returned.
+    pub fn handle_frame(&mut self, now: Instant, frame: &[u8]) -> Result<(), Error> {
+        for index in 0..self.services.len() {
+            let result = match &mut self.services[index].1 {
+                ServiceState::Discovering(discovery) => discovery.handle_frame(now, frame),
+                _ => continue,
+            };
+            self.update(index, result)?;
+        }
+        Ok(())
+    }
+
+    /// Run the discovery of all services on `socket` and block until all of them were
+    /// established or failed, or `deadline` passed.
+    ///
+    /// Services still pending at the deadline fail with the error of their current stage.
+    #[cfg(feature = "socket")]
+    pub fn run_blocking(&mut self, socket: &crate::Socket, deadline: Instant) -> Result<(), Error> {
+        let mut tx_buffer = vec![0u8; crate::consts::MAX_FRAME_LEN];
+        let mut rx_buffer = vec![0u8; crate::consts::MAX_FRAME_LEN];
+
+        while !self.is_finished() {
+            let now = Instant::now();
+            if now >= deadline {
+                self.give_up();
+                break;
+            }
+            while let Some(len) = self.poll_transmit(now, &mut tx_buffer[..])? {
+                socket.send(&tx_buffer[..len])?;
+            }
+
+            let wakeup = self
+                .poll_timeout()
+                .map_or(deadline, |timeout| timeout.min(deadline));
+            if crate::discovery::wait_readable(socket, wakeup.saturating_duration_since(now))? {
+                let len = socket.recv(&mut rx_buffer[..])?;
+                self.handle_frame(Instant::now(), &rx_buffer[..len])?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "socket")]
+    fn give_up(&mut self) {
+        for (_, state) in &mut self.services {
+            let error = match state {
+                ServiceState::Waiting => ProtocolError::NoOffer,
+                ServiceState::Discovering(discovery) => match discovery.give_up() {
+                    Some(error) => error,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            *state = ServiceState::Failed(error);
+        }
+    }
+
+    fn discoveries(&self) -> impl Iterator<Item = &Discovery> {
+        self.services.iter().filter_map(|(_, state)| match state {
+            ServiceState::Discovering(discovery) => Some(&**discovery),
+            _ => None,
+        })
+    }
+
+    /// Take over the outcome of a call to the discovery of a service.
+    fn update<T>(&mut self, index: usize, result: Result<T, Error>) -> Result<Option<T>, Error> {
+        let (service_name, state) = &mut self.services[index];
+        let value = match result {
+            Ok(value) => value,
+            Err(Error::Protocol(error)) => {
+                debug!(
+                    target: DISCOVERY,
+                    "service {} failed: {:?}",
+                    String::from_utf8_lossy(service_name),
+                    error
+                );
+                *state = ServiceState::Failed(error);
+                self.start_waiting();
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        };
+
+        let session = match state {
+            ServiceState::Discovering(discovery) => discovery.session().cloned(),
+            _ => None,
+        };
+        if let Some(session) = session {
+            self.ac_mac.get_or_insert(session.ac_mac);
+            *state = ServiceState::Established(session);
+            self.start_waiting();
+        }
+        Ok(Some(value))
+    }
+
+    /// Start the waiting services once the AC is known, or the next one if no service is
+    /// discovering it.
+    fn start_waiting(&mut self) {
+        let discovering = self
+            .services
+            .iter()
+            .any(|(_, state)| matches!(state, ServiceState::Discovering(_)));
+        let start_all = self.ac_mac.is_some();
+        if !start_all && discovering {
+            return;
+        }
+
+        for index in 0..self.services.len() {
+            if !matches!(self.services[index].1, ServiceState::Waiting) {
+                continue;
+            }
+            let discovery = Discovery::new(self.local_mac, self.service_options(index));
+            self.services[index].1 = ServiceState::Discovering(Box::new(discovery));
+            if !start_all {
+                break;
+            }
+        }
+    }
+
+    fn service_options(&self, index: usize) -> DiscoveryOptions {
+        let mut options = self.options.clone();
+        options.service_name = self.services[index].0.clone();
+        options.service_name_matcher = None;
+        options.ac_mac = self.ac_mac;
+        options
+            .sessions_in_use
+            .extend(self.services.iter().filter_map(|(_, state)| match state {
+                ServiceState::Established(session) => Some((session.ac_mac, session.session_id)),
+                _ => None,
+            }));
+        let mut host_uniq = options.host_uniq.take().unwrap_or_default();
+        host_uniq.extend_from_slice(&(index as u16).to_be_bytes());
+        options.host_uniq = Some(host_uniq);
+        options
+    }
+}
+
+impl Machine for ServiceDiscovery {
+    type Instant = Instant;
+    type Error = Error;
+
+    fn handle(&mut self, now: Instant, rx: &[u8]) -> Result<(), Error> {
+        self.handle_frame(now, rx)
+    }
+
+    fn poll_transmit(&mut self, now: Instant, tx: &mut [u8]) -> Result<Option<usize>, Error> {
+        ServiceDiscovery::poll_transmit(self, now, tx)
+    }
+
+    fn poll_timeout(&self) -> Option<Instant> {
+        ServiceDiscovery::poll_timeout(self)
+    }
+
+    fn is_finished(&self) -> bool {
+        ServiceDiscovery::is_finished(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{PADI, PADR};
+    use crate::{Code, Packet, PacketBuilder, Tag};
+    use byteorder::{ByteOrder, NetworkEndian as NE};
+
+    const CLIENT: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const AC: [u8; 6] = [0x02, 0, 0, 0, 0, 2];
+    const OTHER_AC: [u8; 6] = [0x02, 0, 0, 0, 0, 3];
+
+    /// Answer a PADI or PADR like an AC offering `internet` and `iptv`.
+    fn answer(request: &[u8], ac: [u8; 6], session_id: u16, buffer: &mut [u8]) -> usize {
+        let request = Packet::with_buffer(request).unwrap();
+        let header = request.pppoe_header();
+        let mut packet = PacketBuilder::new_discovery_packet(buffer, ac, CLIENT).unwrap();
+        if header.code() == PADI {
+            packet.set_code(Code::Pado);
+        } else {
+            packet.set_code(Code::Pads);
+            NE::write_u16(&mut packet.pppoe_header().get_ref_mut()[2..], session_id);
+        }
+        let tags = [
+            Tag::ServiceName(header.service_name().unwrap()),
+            Tag::AcName(b"ac"),
+            Tag::HostUniq(header.host_uniq().unwrap()),
+        ];
+        for tag in &tags {
+            packet.pppoe_header().add_tag(*tag).unwrap();
+        }
+        packet.len()
+    }
+
+    #[test]
+    fn parallel_services() {
+        let now = Instant::now();
+        let options = DiscoveryOptions {
+            host_uniq: Some(b"hu".to_vec()),
+            ..DiscoveryOptions::default()
+        };
+        let mut discovery = ServiceDiscovery::new(CLIENT, options, &["internet", "iptv"]);
+        let mut tx = [0u8; 1500];
+        let mut rx = [0u8; 1500];
+
+        // only the first service discovers the AC
+        let len = discovery.poll_transmit(now, &mut tx).unwrap().unwrap();
+        assert_eq!(discovery.poll_transmit(now, &mut tx[len..]).unwrap(), None);
+        let padi = Packet::with_buffer(&tx[..len]).unwrap();
+        assert_eq!(padi.pppoe_header().host_uniq(), Some(&b"hu\0\0"[..]));
+        let len = answer(&tx[..len], AC, 0, &mut rx);
+        discovery.handle_frame(now, &rx[..len]).unwrap();
+        let len = discovery.poll_transmit(now, &mut tx).unwrap().unwrap();
+        let len = answer(&tx[..len], AC, 1, &mut rx);
+        discovery.handle_frame(now, &rx[..len]).unwrap();
+        assert_eq!(discovery.ac_mac(), Some(AC));
+
+        // the second one only accepts the AC of the first
+        let len = discovery.poll_transmit(now, &mut tx).unwrap().unwrap();
+        let padi = tx[..len].to_vec();
+        let len = answer(&padi, OTHER_AC, 0, &mut rx);
+        discovery.handle_frame(now, &rx[..len]).unwrap();
+        assert_eq!(discovery.poll_transmit(now, &mut tx).unwrap(), None);
+        let len = answer(&padi, AC, 0, &mut rx);
+        discovery.handle_frame(now, &rx[..len]).unwrap();
+        let len = discovery.poll_transmit(now, &mut tx).unwrap().unwrap();
+        let padr = Packet::with_buffer(&tx[..len]).unwrap();
+        assert_eq!(padr.pppoe_header().code(), PADR);
+        assert_eq!(padr.ethernet_header().dst_address(), AC);
+        let len = answer(&tx[..len], AC, 2, &mut rx);
+        discovery.handle_frame(now, &rx[..len]).unwrap();
+
+        assert!(discovery.is_finished());
+        let sessions = discovery.sessions();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[&b"internet"[..]].session_id.get(), 1);
+        assert_eq!(discovery.session(b"iptv").unwrap().session_id.get(), 2);
+        assert!(discovery.failures().is_empty());
+    }
+}
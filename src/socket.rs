@@ -1,20 +1,299 @@
+use crate::error::Error;
+use crate::logging::SOCKET;
+use crate::packet::PPPOE_SESSION;
+use crate::{eth, filter, interface, uapi, Code, SessionBatch};
 use pppoe_sys::{control, pppoe};
+pub use pppoe_sys::{Call, Operation, SysError};
 
-use std::io::{self, Read, Write};
-use std::os::unix::io::{FromRawFd, RawFd};
+use std::cell::Cell;
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::path::Path;
+use std::sync::Arc;
 use std::{fs, mem, num};
 
 #[cfg(feature = "async")]
 use mio::{event::Evented, unix::EventedFd, Poll, PollOpt, Ready, Token};
 
+/// Linux capabilities required to use a `Socket`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Required for the `AF_PACKET` socket of the discovery stage
+    NetRaw,
+    /// Required to connect a PPPoE session in the kernel
+    NetAdmin,
+}
+
+impl Capability {
+    fn bit(self) -> u32 {
+        match self {
+            Capability::NetAdmin => 12,
+            Capability::NetRaw => 13,
+        }
+    }
+
+    /// Get the name of the capability as used by `setcap(8)` and `capsh(1)`
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::NetRaw => "cap_net_raw",
+            Capability::NetAdmin => "cap_net_admin",
+        }
+    }
+
+    /// Get a hint on how to grant the capability
+    pub fn hint(self) -> &'static str {
+        match self {
+            Capability::NetRaw => {
+                "run as root or grant the capability with `setcap cap_net_raw+ep <binary>`"
+            }
+            Capability::NetAdmin => {
+                "run as root or grant the capability with `setcap cap_net_admin+ep <binary>`"
+            }
+        }
+    }
+
+    /// Check whether the capability is in the effective set of the current thread
+    pub fn is_effective(self) -> io::Result<bool> {
+        #[repr(C)]
+        struct CapHeader {
+            version: u32,
+            pid: libc::c_int,
+        }
+
+        #[repr(C)]
+        #[derive(Default, Clone, Copy)]
+        struct CapData {
+            effective: u32,
+            permitted: u32,
+            inheritable: u32,
+        }
+
+        const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+        let mut header = CapHeader {
+            version: LINUX_CAPABILITY_VERSION_3,
+            pid: 0,
+        };
+        let mut data = [CapData::default(); 2];
+
+        c_call_with_os_error(|| unsafe {
+            libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()) as libc::c_int
+        })?;
+
+        let bit = self.bit();
+        Ok(data[(bit / 32) as usize].effective & (1 << (bit % 32)) != 0)
+    }
+}
+
+/// How a `PACKET_FANOUT` group distributes frames between its sockets, see `Socket::join_fanout`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FanoutMode {
+    /// By the flow hash of the frame (addresses and ports of the inner packet)
+    Hash,
+    /// Round robin
+    LoadBalance,
+    /// By the CPU the frame arrived on
+    Cpu,
+    /// Fill one socket before moving on to the next
+    Rollover,
+    Random,
+    /// By the receive queue of the NIC
+    QueueMapping,
+}
+
+impl FanoutMode {
+    fn value(self) -> libc::c_uint {
+        match self {
+            FanoutMode::Hash => uapi::PACKET_FANOUT_HASH,
+            FanoutMode::LoadBalance => uapi::PACKET_FANOUT_LB,
+            FanoutMode::Cpu => uapi::PACKET_FANOUT_CPU,
+            FanoutMode::Rollover => uapi::PACKET_FANOUT_ROLLOVER,
+            FanoutMode::Random => uapi::PACKET_FANOUT_RND,
+            FanoutMode::QueueMapping => uapi::PACKET_FANOUT_QM,
+        }
+    }
+}
+
+fn join_fanout_group(fd: RawFd, group_id: u16, mode: libc::c_uint) -> io::Result<()> {
+    let fanout = libc::c_int::from(group_id) | (mode as libc::c_int) << 16;
+    c_call_with_os_error(|| unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_PACKET,
+            uapi::PACKET_FANOUT,
+            &fanout as *const _ as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    })
+}
+
+/// A link layer membership of the raw socket, see `Socket::add_membership`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Membership {
+    /// Receive all frames on the interface
+    Promiscuous,
+    /// Receive all multicast frames on the interface
+    AllMulticast,
+    /// Receive frames sent to a multicast address
+    Multicast([u8; 6]),
+}
+
+fn set_membership(
+    fd: RawFd,
+    option: libc::c_int,
+    ifindex: u32,
+    membership: Membership,
+) -> io::Result<()> {
+    let mut mreq: uapi::packet_mreq = unsafe { mem::zeroed() };
+    mreq.mr_ifindex = ifindex as libc::c_int;
+    mreq.mr_type = match membership {
+        Membership::Promiscuous => uapi::PACKET_MR_PROMISC,
+        Membership::AllMulticast => uapi::PACKET_MR_ALLMULTI,
+        Membership::Multicast(address) => {
+            mreq.mr_alen = address.len() as libc::c_ushort;
+            mreq.mr_address[..address.len()].copy_from_slice(&address);
+            uapi::PACKET_MR_MULTICAST
+        }
+    } as libc::c_ushort;
+
+    c_call_with_os_error(|| unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_PACKET,
+            option,
+            &mreq as *const _ as *const libc::c_void,
+            mem::size_of::<uapi::packet_mreq>() as libc::socklen_t,
+        )
+    })
+}
+
+/// Checksum state of a received frame, see `RecvMeta`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumStatus {
+    /// Not reported, the frame has to be checked if it matters
+    Unknown,
+    /// Verified by the NIC or the kernel
+    Valid,
+    /// Not computed yet because of checksum offload, typical for frames send by this host
+    Pending,
+}
+
+/// Metadata of a frame received with `Socket::recv_with_meta`
+///
+/// Apart from `outgoing` the fields are only filled in after `Socket::enable_recv_meta`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvMeta {
+    /// The frame was send by this host and looped back to the socket, e.g. while capturing
+    pub outgoing: bool,
+    /// The 802.1Q tag the kernel or NIC removed from the frame (VLAN offload)
+    pub vlan: Option<eth::VlanTag>,
+    pub checksum: ChecksumStatus,
+    /// Length of the frame on the wire, before it was truncated to the buffer
+    pub original_len: Option<usize>,
+}
+
+impl RecvMeta {
+    fn from_auxdata(outgoing: bool, auxdata: Option<uapi::tpacket_auxdata>) -> Self {
+        let mut meta = RecvMeta {
+            outgoing,
+            vlan: None,
+            checksum: ChecksumStatus::Unknown,
+            original_len: None,
+        };
+        let auxdata = match auxdata {
+            Some(auxdata) => auxdata,
+            None => return meta,
+        };
+
+        let status = auxdata.tp_status;
+        // a TCI of 0 is only reported with TP_STATUS_VLAN_VALID
+        if status & uapi::TP_STATUS_VLAN_VALID != 0 || auxdata.tp_vlan_tci != 0 {
+            let tpid = if status & uapi::TP_STATUS_VLAN_TPID_VALID != 0 {
+                auxdata.tp_vlan_tpid
+            } else {
+                crate::summary::ETHERTYPE_VLAN
+            };
+            meta.vlan = Some(eth::VlanTag {
+                tpid,
+                tci: auxdata.tp_vlan_tci,
+            });
+        }
+        meta.checksum = if status & uapi::TP_STATUS_CSUM_VALID != 0 {
+            ChecksumStatus::Valid
+        } else if status & uapi::TP_STATUS_CSUMNOTREADY != 0 {
+            ChecksumStatus::Pending
+        } else {
+            ChecksumStatus::Unknown
+        };
+        meta.original_len = Some(auxdata.tp_len as usize);
+        meta
+    }
+}
+
+/// The state of the kernel resources of a `Socket`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The raw socket was closed with `Socket::close`
+    Closed,
+    /// Discovery frames can be send and received, no session is connected
+    Discovery,
+    /// A session is connected in the kernel via `Socket::connect_session`
+    Session {
+        session_id: num::NonZeroU16,
+        remote_mac: [u8; 6],
+    },
+}
+
+/// The PPPoE and raw sockets bound to an interface
+///
+/// `Socket` is `Send` and `Sync`: `send` and `recv` only take `&self` and map to independent
+/// syscalls which the kernel serializes, so a shared `Socket` can be used from multiple threads.
+/// Use `split` to hand out the sending and receiving side separately.
 #[derive(Debug)]
 pub struct Socket {
     connection: pppoe::Connection,
+    session_socket: Option<fs::File>,
+    session: Option<(num::NonZeroU16, [u8; 6])>,
+    memberships: Vec<Membership>,
+    vlan: Option<interface::VlanConfig>,
+    outgoing_filter: OutgoingFilter,
+}
+
+/// How frames send by this host are kept away from `recv`, see `Socket::ignore_outgoing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutgoingFilter {
+    Off,
+    /// `PACKET_IGNORE_OUTGOING`, Linux 4.20 and later
+    Kernel,
+    /// Dropping frames with our MAC as source after receiving them
+    SourceMac,
 }
 
+/// Set `PACKET_IGNORE_OUTGOING`, returns `false` if the kernel doesn't support it.
+fn set_ignore_outgoing(fd: RawFd, ignore: bool) -> io::Result<bool> {
+    let value = libc::c_int::from(ignore);
+    let result = c_call_with_os_error(|| unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_PACKET,
+            uapi::PACKET_IGNORE_OUTGOING,
+            &value as *const _ as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    });
+    match result {
+        Ok(()) => Ok(true),
+        Err(err) if err.raw_os_error() == Some(libc::ENOPROTOOPT) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn _assert_send_sync<T: Send + Sync>() {}
+const _: fn() = _assert_send_sync::<Socket>;
+
 fn c_call_with_os_error<F>(call: F) -> io::Result<()>
 where
-    F: Fn() -> libc::c_int,
+    F: FnOnce() -> libc::c_int,
 {
     let ret = call();
 
@@ -22,7 +301,160 @@ where
         return Err(io::Error::last_os_error());
     }
 
-    return Ok(());
+    Ok(())
+}
+
+fn send_on(fd: RawFd, buffer: &[u8], flags: libc::c_int) -> io::Result<usize> {
+    let ret = unsafe {
+        libc::send(
+            fd,
+            buffer.as_ptr() as *const libc::c_void,
+            buffer.len(),
+            flags,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    trace!(target: SOCKET, "sent {} bytes on fd {}", ret, fd);
+    Ok(ret as usize)
+}
+
+fn recv_on(fd: RawFd, buffer: &mut [u8], flags: libc::c_int) -> io::Result<usize> {
+    let ret = unsafe {
+        libc::recv(
+            fd,
+            buffer.as_mut_ptr() as *mut libc::c_void,
+            buffer.len(),
+            flags,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    trace!(target: SOCKET, "received {} bytes on fd {}", ret, fd);
+    Ok(ret as usize)
+}
+
+/// Receive a frame with `recvmsg(2)`, together with its packet type and the `PACKET_AUXDATA`
+/// control message if enabled.
+fn recv_meta_on(fd: RawFd, buffer: &mut [u8], flags: libc::c_int) -> io::Result<(usize, RecvMeta)> {
+    const AUXDATA_LEN: libc::c_uint = mem::size_of::<uapi::tpacket_auxdata>() as libc::c_uint;
+    const CONTROL_LEN: usize = unsafe { libc::CMSG_SPACE(AUXDATA_LEN) } as usize;
+    /// Room for the `PACKET_AUXDATA` control message, aligned for `cmsghdr`
+    #[repr(C, align(8))]
+    struct ControlBuffer([u8; CONTROL_LEN]);
+
+    let mut control = ControlBuffer([0; CONTROL_LEN]);
+    let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+    let mut iovec = libc::iovec {
+        iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buffer.len(),
+    };
+
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_name = &mut address as *mut _ as *mut libc::c_void;
+    message.msg_namelen = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+    message.msg_iov = &mut iovec;
+    message.msg_iovlen = 1;
+    message.msg_control = control.0.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = CONTROL_LEN as _;
+
+    let ret = unsafe { libc::recvmsg(fd, &mut message, flags) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    trace!(target: SOCKET, "received {} bytes on fd {}", ret, fd);
+
+    let mut auxdata = None;
+    let mut header = unsafe { libc::CMSG_FIRSTHDR(&message) };
+    while !header.is_null() {
+        let (level, kind) = unsafe { ((*header).cmsg_level, (*header).cmsg_type) };
+        if level == libc::SOL_PACKET && kind == uapi::PACKET_AUXDATA {
+            let data = unsafe { libc::CMSG_DATA(header) } as *const uapi::tpacket_auxdata;
+            auxdata = Some(unsafe { std::ptr::read_unaligned(data) });
+        }
+        header = unsafe { libc::CMSG_NXTHDR(&message, header) };
+    }
+
+    let outgoing = address.sll_pkttype == uapi::PACKET_OUTGOING;
+    Ok((ret as usize, RecvMeta::from_auxdata(outgoing, auxdata)))
+}
+
+/// Receive a frame of up to `max_len` bytes at the end of `buffer` and split it off.
+#[cfg(feature = "bytes")]
+fn recv_bytes_on(
+    fd: RawFd,
+    buffer: &mut bytes::BytesMut,
+    max_len: usize,
+    strip_vlan_tag: bool,
+) -> io::Result<bytes::Bytes> {
+    buffer.clear();
+    buffer.resize(max_len, 0);
+    let mut len = recv_on(fd, buffer, 0)?;
+    if strip_vlan_tag {
+        len = eth::strip_vlan_tag(buffer, len);
+    }
+    buffer.truncate(len);
+    Ok(buffer.split().freeze())
+}
+
+fn bind_packet_socket(fd: RawFd, ether_type: u16, ifindex: u32) -> io::Result<()> {
+    let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+    address.sll_family = libc::AF_PACKET as libc::c_ushort;
+    address.sll_protocol = ether_type.to_be();
+    address.sll_ifindex = ifindex as libc::c_int;
+
+    c_call_with_os_error(|| unsafe {
+        libc::bind(
+            fd,
+            &address as *const _ as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+        )
+    })
+}
+
+/// Open an `AF_PACKET` socket receiving only frames of `ether_type` on one interface.
+fn open_packet_socket(ether_type: u16, ifindex: u32) -> io::Result<fs::File> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_PACKET,
+            libc::SOCK_RAW,
+            libc::c_int::from(ether_type.to_be()),
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { fs::File::from_raw_fd(fd) };
+    bind_packet_socket(fd, ether_type, ifindex)?;
+    Ok(socket)
+}
+
+/// Read the VLAN configuration of an interface, treating errors like a non-VLAN interface.
+fn read_vlan_config(interface_name: &str) -> Option<interface::VlanConfig> {
+    match interface::vlan_config(interface_name) {
+        Ok(vlan) => {
+            if let Some(vlan) = vlan {
+                debug!(
+                    target: SOCKET,
+                    "{} is VLAN {}, reorder_hdr {}", interface_name, vlan.id, vlan.reorder_hdr
+                );
+            }
+            vlan
+        }
+        Err(err) => {
+            debug!(
+                target: SOCKET,
+                "failed to read VLAN configuration of {}: {}", interface_name, err
+            );
+            None
+        }
+    }
+}
+
+fn close_fd(fd: RawFd) -> io::Result<()> {
+    c_call_with_os_error(|| unsafe { libc::close(fd) })
 }
 
 fn set_nonblock(fd: libc::c_int) -> io::Result<()> {
@@ -32,9 +464,88 @@ fn set_nonblock(fd: libc::c_int) -> io::Result<()> {
     })
 }
 
+thread_local! {
+    /// Set once the thread failed to return to its original network namespace
+    static NETNS_POISONED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Fail if the calling thread is stuck in another network namespace, so sockets aren't created
+/// in the wrong one.
+fn ensure_netns_not_poisoned() -> io::Result<()> {
+    if NETNS_POISONED.with(Cell::get) {
+        return Err(io::Error::other(
+            "the thread failed to return to its original network namespace",
+        ));
+    }
+    Ok(())
+}
+
+/// Switches the calling thread into another network namespace and back on `restore` or drop
+///
+/// If returning fails, the thread is marked as poisoned and creating sockets on it fails.
+struct NetnsGuard {
+    original: Option<fs::File>,
+}
+
+impl NetnsGuard {
+    fn enter(netns_path: &Path) -> io::Result<Self> {
+        ensure_netns_not_poisoned()?;
+        let original = fs::File::open("/proc/thread-self/ns/net")?;
+        let target = fs::File::open(netns_path)?;
+
+        // the control socket used for the interface ioctls is bound to the namespace it was
+        // created in
+        control::close();
+        c_call_with_os_error(|| unsafe { libc::setns(target.as_raw_fd(), libc::CLONE_NEWNET) })?;
+
+        Ok(Self {
+            original: Some(original),
+        })
+    }
+
+    /// Return to the original namespace, reporting a failure instead of only logging it.
+    fn restore(mut self) -> io::Result<()> {
+        match self.original.take() {
+            Some(original) => Self::switch_back(&original),
+            None => Ok(()),
+        }
+    }
+
+    fn switch_back(original: &fs::File) -> io::Result<()> {
+        control::close();
+        c_call_with_os_error(|| unsafe { libc::setns(original.as_raw_fd(), libc::CLONE_NEWNET) })
+            .inspect_err(|_| NETNS_POISONED.with(|poisoned| poisoned.set(true)))
+    }
+}
+
+impl Drop for NetnsGuard {
+    fn drop(&mut self) {
+        // panicking here would abort the process if the guard is dropped during unwinding
+        if let Some(original) = self.original.take() {
+            if let Err(err) = Self::switch_back(&original) {
+                warn!(
+                    target: SOCKET,
+                    "failed to restore network namespace: {}", err
+                );
+            }
+        }
+    }
+}
+
 // TODO: Check std::net Sockets methods and impl them for this if applicable
 impl Socket {
+    /// Open the PPPoE and raw sockets on an interface.
+    ///
+    /// Errors of the underlying C layer carry a `pppoe_sys::SysError` (accessible via
+    /// `io::Error::get_ref`) describing the failed operation, system call, `errno` and the
+    /// interface.  With the `log` feature, `interface::binding_warnings` for the interface are
+    /// logged at warn level.
+    ///
+    /// VLAN sub-interfaces (e.g. `eth0.835`) are supported like any other interface: the kernel
+    /// adds the tag to sent frames, and received frames are returned without it, also if the
+    /// sub-interface was created with `reorder_hdr off`.
     pub fn on_interface(interface_name: &str) -> io::Result<Self> {
+        ensure_netns_not_poisoned()?;
         control::init()?;
 
         let mut connection = pppoe::Connection::new();
@@ -44,21 +555,367 @@ impl Socket {
         #[cfg(feature = "async")]
         set_nonblock(connection.raw_socket())?;
 
-        Ok(Socket { connection })
+        let vlan = read_vlan_config(interface_name);
+        debug!(target: SOCKET, "opened sockets on {}", interface_name);
+        #[cfg(feature = "log")]
+        if let Ok(warnings) = interface::binding_warnings(interface_name) {
+            for warning in warnings {
+                warn!(target: SOCKET, "{:?}: {}", warning, warning.hint());
+            }
+        }
+        Ok(Socket {
+            connection,
+            session_socket: None,
+            session: None,
+            memberships: Vec::new(),
+            vlan,
+            outgoing_filter: OutgoingFilter::Off,
+        })
+    }
+
+    /// Open the PPPoE and raw sockets on the interface with the given index.
+    pub fn on_ifindex(index: u32) -> io::Result<Self> {
+        Self::on_interface(&interface::name_from_index(index)?)
+    }
+
+    /// Open the PPPoE and raw sockets on the interface with the given hardware address.
+    ///
+    /// Fails if the address is not unique, see `interface::name_from_mac`.
+    pub fn on_mac(mac: [u8; 6]) -> io::Result<Self> {
+        Self::on_interface(&interface::name_from_mac(mac)?)
+    }
+
+    /// Open the PPPoE and raw sockets on an interface in another network namespace.
+    ///
+    /// The calling thread enters the namespace given by `netns_path` (e.g.
+    /// `/run/netns/<name>` or `/proc/<pid>/ns/net`) for the socket creation and returns to its
+    /// original namespace afterwards.  The sockets stay bound to the target namespace.  Requires
+    /// `CAP_SYS_ADMIN`.
+    ///
+    /// The control socket shared by all `Socket`s is recreated, so this must not run
+    /// concurrently with the creation of other sockets.
+    pub fn on_interface_in_netns<P: AsRef<Path>>(
+        interface_name: &str,
+        netns_path: P,
+    ) -> io::Result<Self> {
+        let guard = NetnsGuard::enter(netns_path.as_ref())?;
+        let socket = Self::on_interface(interface_name);
+        guard.restore()?;
+        socket
+    }
+
+    /// Check that the current thread has all capabilities required to create a `Socket` and
+    /// connect a session.
+    ///
+    /// Returns `Error::MissingCapability` for the first missing capability, see
+    /// `Capability::hint` on how to fix it.
+    pub fn check_permissions() -> Result<(), Error> {
+        for &capability in &[Capability::NetRaw, Capability::NetAdmin] {
+            if !capability.is_effective()? {
+                return Err(Error::MissingCapability(capability));
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a socket from sockets received from another process, see `handoff`.
+    pub(crate) fn from_parts(
+        connection: pppoe::Connection,
+        session_socket: Option<fs::File>,
+        session: Option<(num::NonZeroU16, [u8; 6])>,
+    ) -> Self {
+        let vlan = connection
+            .interface_name()
+            .and_then(|interface_name| read_vlan_config(&interface_name));
+        Socket {
+            connection,
+            session_socket,
+            session,
+            memberships: Vec::new(),
+            vlan,
+            outgoing_filter: OutgoingFilter::Off,
+        }
+    }
+
+    /// Close the file descriptors without undoing memberships and filters, which stay in effect
+    /// for other copies of the descriptors, e.g. in the process they were passed to.
+    pub(crate) fn close_fds(mut self) -> io::Result<()> {
+        self.memberships.clear();
+        self.session = None;
+
+        let session_socket = match self.session_socket.take() {
+            Some(session_socket) => close_fd(session_socket.into_raw_fd()),
+            None => Ok(()),
+        };
+        self.connection.close()?;
+        session_socket
     }
 
     fn raw_socket(&self) -> RawFd {
         self.connection.raw_socket()
     }
 
+    pub(crate) fn pppoe_socket(&self) -> RawFd {
+        self.connection.pppoe_socket()
+    }
+
+    /// Get the name of the interface the socket is bound to.
+    pub fn interface_name(&self) -> Option<String> {
+        self.connection.interface_name()
+    }
+
+    /// Get the configuration of the interface if it is a VLAN sub-interface.
+    pub fn vlan(&self) -> Option<interface::VlanConfig> {
+        self.vlan
+    }
+
+    /// Whether received frames still carry the tag of the VLAN sub-interface, see
+    /// `interface::VlanConfig::reorder_hdr`
+    fn keeps_vlan_tag(&self) -> bool {
+        self.vlan.is_some_and(|vlan| !vlan.reorder_hdr)
+    }
+
+    /// Never return frames send by this host from `recv`, `peek`, `recv_session` and
+    /// `recv_with_meta`, e.g. the own PADIs while listening to all broadcasts.
+    ///
+    /// Uses `PACKET_IGNORE_OUTGOING`, so the frames don't even reach the socket.  Kernels older
+    /// than 4.20 don't support it, then received frames with the MAC address of this socket as
+    /// source are dropped instead, which also drops frames of other hosts spoofing it.
+    pub fn ignore_outgoing(&mut self, ignore: bool) -> io::Result<()> {
+        let mut fds = vec![self.raw_socket()];
+        fds.extend(self.session_socket());
+        let mut kernel = true;
+        for &fd in &fds {
+            kernel &= set_ignore_outgoing(fd, ignore)?;
+        }
+
+        self.outgoing_filter = match (ignore, kernel) {
+            (false, _) => OutgoingFilter::Off,
+            (true, true) => OutgoingFilter::Kernel,
+            (true, false) => OutgoingFilter::SourceMac,
+        };
+        debug!(target: SOCKET, "outgoing frames filter: {:?}", self.outgoing_filter);
+        Ok(())
+    }
+
+    fn is_own_frame(&self, frame: &[u8]) -> bool {
+        self.outgoing_filter == OutgoingFilter::SourceMac
+            && frame.get(6..12) == Some(&self.mac_address()[..])
+    }
+
+    /// Receive on `fd` with `receive`, skipping own frames, and remove the VLAN tag.
+    fn recv_filtered<T, F>(
+        &self,
+        fd: RawFd,
+        buffer: &mut [u8],
+        flags: libc::c_int,
+        mut receive: F,
+    ) -> io::Result<(usize, T)>
+    where
+        F: FnMut(&mut [u8], libc::c_int) -> io::Result<(usize, T)>,
+    {
+        loop {
+            let (len, extra) = receive(buffer, flags)?;
+            if !self.is_own_frame(&buffer[..len.min(buffer.len())]) {
+                // the sockets are bound to the PPPoE ether types, so a tagged frame comes from a
+                // sub-interface which keeps the tag, even if its configuration couldn't be read
+                return Ok((eth::strip_vlan_tag(buffer, len), extra));
+            }
+            trace!(target: SOCKET, "dropped own frame on fd {}", fd);
+            if flags & libc::MSG_PEEK != 0 {
+                // a peeked frame stays queued, so it has to be consumed
+                recv_on(fd, buffer, flags & !libc::MSG_PEEK)?;
+            }
+        }
+    }
+
+    /// Open a separate socket for PPPoE session frames (`ETH_P_PPP_SES`).
+    ///
+    /// The socket created by `on_interface` only receives discovery frames.  Session frames are
+    /// received with `recv_session`, so the data path doesn't queue behind discovery traffic
+    /// and vice versa.  Does nothing if the session socket is already open.
+    pub fn open_session_socket(&mut self) -> io::Result<()> {
+        if self.session_socket.is_some() {
+            return Ok(());
+        }
+
+        let socket = open_packet_socket(PPPOE_SESSION, self.ifindex()?)?;
+        if self.outgoing_filter == OutgoingFilter::Kernel {
+            set_ignore_outgoing(socket.as_raw_fd(), true)?;
+        }
+
+        #[cfg(feature = "async")]
+        set_nonblock(socket.as_raw_fd())?;
+
+        debug!(target: SOCKET, "opened session socket");
+        self.session_socket = Some(socket);
+        Ok(())
+    }
+
+    /// Get the file descriptor of the session socket, if it was opened.
+    pub fn session_socket(&self) -> Option<RawFd> {
+        self.session_socket.as_ref().map(AsRawFd::as_raw_fd)
+    }
+
+    fn ifindex(&self) -> io::Result<u32> {
+        let interface_name = self.interface_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "socket has no interface name")
+        })?;
+        interface::index_from_name(&interface_name)
+    }
+
+    /// Receive frames of all ethertypes on the raw socket, including the frames send by this
+    /// host.  Meant for debugging, see the `capture` module.
+    ///
+    /// `reconnect` restores the normal discovery binding.
+    #[cfg(feature = "capture")]
+    pub fn capture_all(&self) -> io::Result<()> {
+        bind_packet_socket(self.raw_socket(), libc::ETH_P_ALL as u16, self.ifindex()?)
+    }
+
+    /// Receive a frame and whether it was received or send by this host.
+    #[cfg(feature = "capture")]
+    pub fn recv_captured(
+        &self,
+        buffer: &mut [u8],
+    ) -> io::Result<(usize, crate::capture::Direction)> {
+        let (len, meta) = recv_meta_on(self.raw_socket(), buffer, 0)?;
+        let direction = if meta.outgoing {
+            crate::capture::Direction::Outgoing
+        } else {
+            crate::capture::Direction::Incoming
+        };
+        Ok((len, direction))
+    }
+
+    /// Let the kernel report the VLAN tag and checksum status of received frames, see
+    /// `recv_with_meta`.
+    ///
+    /// Only affects the raw socket, and is lost on `reconnect`.
+    pub fn enable_recv_meta(&self) -> io::Result<()> {
+        let enable: libc::c_int = 1;
+        c_call_with_os_error(|| unsafe {
+            libc::setsockopt(
+                self.raw_socket(),
+                libc::SOL_PACKET,
+                uapi::PACKET_AUXDATA,
+                &enable as *const _ as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        })
+    }
+
+    /// Receive a frame like `recv_with_flags`, together with its metadata.
+    ///
+    /// Frames send by this host (`RecvMeta::outgoing`) only show up while capturing, and should
+    /// not be counted twice by a monitor.  A VLAN tag removed by the kernel is reported in
+    /// `RecvMeta::vlan` and can be put back with `eth::insert_vlan_tag`.
+    pub fn recv_with_meta(
+        &self,
+        buffer: &mut [u8],
+        flags: libc::c_int,
+    ) -> io::Result<(usize, RecvMeta)> {
+        let fd = self.raw_socket();
+        self.recv_filtered(fd, buffer, flags, |buffer, flags| {
+            recv_meta_on(fd, buffer, flags)
+        })
+    }
+
+    fn session_socket_or_err(&self) -> io::Result<RawFd> {
+        self.session_socket().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotConnected, "session socket is not open")
+        })
+    }
+
     pub fn connect_session(
         &mut self,
         session_id: num::NonZeroU16,
         remote_mac: [u8; 6],
+    ) -> io::Result<RawFd> {
+        self.connection.connect(session_id, remote_mac)?;
+        debug!(
+            target: SOCKET,
+            "connected session {:#06x} to {:02x?}",
+            session_id,
+            remote_mac
+        );
+        self.session = Some((session_id, remote_mac));
+        Ok(self.connection.pppoe_socket())
+    }
+
+    /// Connect a session on another interface than the one the sockets are bound to, e.g. a
+    /// VLAN device on top of it.
+    pub fn connect_session_on(
+        &mut self,
+        session_id: num::NonZeroU16,
+        remote_mac: [u8; 6],
+        interface_name: &str,
     ) -> io::Result<RawFd> {
         self.connection
-            .connect(session_id, remote_mac)
-            .map(|_| self.connection.pppoe_socket())
+            .connect_device(session_id, remote_mac, interface_name)?;
+        debug!(
+            target: SOCKET,
+            "connected session {:#06x} to {:02x?} on {}",
+            session_id,
+            remote_mac,
+            interface_name
+        );
+        self.session = Some((session_id, remote_mac));
+        Ok(self.connection.pppoe_socket())
+    }
+
+    /// Drop the session connected with `connect_session`.
+    ///
+    /// The PPPoE socket is replaced by a fresh one, so the file descriptor returned by
+    /// `connect_session` is closed.  A new session can be connected afterwards.
+    pub fn close_session(&mut self) -> io::Result<()> {
+        debug!(target: SOCKET, "closing session");
+        self.session = None;
+        self.connection.reset_pppoe_socket()?;
+        Ok(())
+    }
+
+    /// Close all sockets and open them again on the same interface, e.g. after the interface
+    /// went down and up again.
+    ///
+    /// A connected session is dropped, attached filters, memberships and the fanout group are
+    /// lost.  The file descriptors change, so a socket registered with `mio` has to be
+    /// registered again.
+    pub fn reconnect(&mut self) -> io::Result<()> {
+        debug!(target: SOCKET, "reopening sockets");
+        self.session = None;
+        self.memberships.clear();
+        let reopen_session_socket = self.session_socket.take().is_some();
+
+        self.connection.reinit()?;
+        self.vlan = self
+            .interface_name()
+            .and_then(|interface_name| read_vlan_config(&interface_name));
+        if self.outgoing_filter == OutgoingFilter::Kernel {
+            set_ignore_outgoing(self.raw_socket(), true)?;
+        }
+
+        #[cfg(feature = "async")]
+        set_nonblock(self.raw_socket())?;
+
+        if reopen_session_socket {
+            self.open_session_socket()?;
+        }
+        Ok(())
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        if self.raw_socket() == 0 {
+            return ConnectionState::Closed;
+        }
+
+        match self.session {
+            Some((session_id, remote_mac)) => ConnectionState::Session {
+                session_id,
+                remote_mac,
+            },
+            None => ConnectionState::Discovery,
+        }
     }
 
     pub fn mac_address(&self) -> [u8; 6] {
@@ -66,25 +923,417 @@ impl Socket {
     }
 
     pub fn set_nonblock(&self) -> io::Result<()> {
-        set_nonblock(self.raw_socket())
+        set_nonblock(self.raw_socket())?;
+        if let Some(fd) = self.session_socket() {
+            set_nonblock(fd)?;
+        }
+        Ok(())
+    }
+
+    pub fn send(&self, buffer: &[u8]) -> io::Result<usize> {
+        self.send_with_flags(buffer, 0)
+    }
+
+    /// Send a frame with `send(2)` flags, e.g. `libc::MSG_DONTWAIT`.
+    pub fn send_with_flags(&self, buffer: &[u8], flags: libc::c_int) -> io::Result<usize> {
+        send_on(self.raw_socket(), buffer, flags)
+    }
+
+    pub fn recv(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.recv_with_flags(buffer, 0)
+    }
+
+    /// Receive a frame without removing it from the receive queue.
+    ///
+    /// The next `recv` (or `peek`) returns the same frame again, so the frame can be inspected
+    /// (e.g. its code or session id) before deciding into which buffer it is read.
+    pub fn peek(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.recv_with_flags(buffer, libc::MSG_PEEK)
+    }
+
+    /// Receive a frame with `recv(2)` flags, e.g. `libc::MSG_DONTWAIT`.
+    ///
+    /// With `libc::MSG_TRUNC` the real length of the frame is returned, even if it was longer
+    /// than `buffer` and therefore got truncated.
+    pub fn recv_with_flags(&self, buffer: &mut [u8], flags: libc::c_int) -> io::Result<usize> {
+        let fd = self.raw_socket();
+        let (len, ()) = self.recv_filtered(fd, buffer, flags, |buffer, flags| {
+            Ok((recv_on(fd, buffer, flags)?, ()))
+        })?;
+        Ok(len)
+    }
+
+    /// Receive a frame of up to `max_len` bytes as `Bytes`, which can be cloned cheaply to hand
+    /// the same frame to multiple consumers.
+    ///
+    /// `buffer` works as a pool: its allocation is reused once all `Bytes` received from it
+    /// are dropped.  The parsers take the frame via `Deref`, e.g. `Packet::with_buffer(&bytes)`.
+    #[cfg(feature = "bytes")]
+    pub fn recv_bytes(
+        &self,
+        buffer: &mut bytes::BytesMut,
+        max_len: usize,
+    ) -> io::Result<bytes::Bytes> {
+        recv_bytes_on(self.raw_socket(), buffer, max_len, self.keeps_vlan_tag())
+    }
+
+    /// Like `recv_bytes`, but for the session socket, see `open_session_socket`.
+    #[cfg(feature = "bytes")]
+    pub fn recv_session_bytes(
+        &self,
+        buffer: &mut bytes::BytesMut,
+        max_len: usize,
+    ) -> io::Result<bytes::Bytes> {
+        recv_bytes_on(
+            self.session_socket_or_err()?,
+            buffer,
+            max_len,
+            self.keeps_vlan_tag(),
+        )
+    }
+
+    /// Send a session frame on the session socket, see `open_session_socket`.
+    pub fn send_session(&self, buffer: &[u8]) -> io::Result<usize> {
+        send_on(self.session_socket_or_err()?, buffer, 0)
+    }
+
+    /// Receive a session frame from the session socket, see `open_session_socket`.
+    pub fn recv_session(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.recv_session_with_flags(buffer, 0)
+    }
+
+    pub fn recv_session_with_flags(
+        &self,
+        buffer: &mut [u8],
+        flags: libc::c_int,
+    ) -> io::Result<usize> {
+        let fd = self.session_socket_or_err()?;
+        let (len, ()) = self.recv_filtered(fd, buffer, flags, |buffer, flags| {
+            Ok((recv_on(fd, buffer, flags)?, ()))
+        })?;
+        Ok(len)
+    }
+
+    /// Send multiple frames with a single `sendmmsg(2)` call.
+    ///
+    /// Returns the number of frames which were send, which can be less than `frames.len()`.
+    pub fn send_many(&self, frames: &[&[u8]]) -> io::Result<usize> {
+        let mut iovecs: Vec<libc::iovec> = frames
+            .iter()
+            .map(|frame| libc::iovec {
+                iov_base: frame.as_ptr() as *mut libc::c_void,
+                iov_len: frame.len(),
+            })
+            .collect();
+        let mut messages: Vec<libc::mmsghdr> = iovecs
+            .iter_mut()
+            .map(|iovec| {
+                let mut message: libc::mmsghdr = unsafe { mem::zeroed() };
+                message.msg_hdr.msg_iov = iovec;
+                message.msg_hdr.msg_iovlen = 1;
+                message
+            })
+            .collect();
+
+        let ret = unsafe {
+            libc::sendmmsg(
+                self.raw_socket(),
+                messages.as_mut_ptr(),
+                messages.len() as libc::c_uint,
+                0,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ret as usize)
+    }
+
+    /// Send all packets of the batch with a single syscall and remove them from the batch.
+    ///
+    /// If not all packets could be send, the remaining ones stay in the batch.
+    pub fn send_batch(&self, batch: &mut SessionBatch) -> io::Result<usize> {
+        if batch.is_empty() {
+            return Ok(0);
+        }
+
+        let frames: Vec<&[u8]> = batch.frames().collect();
+        let sent = self.send_many(&frames)?;
+        batch.consume(sent);
+        Ok(sent)
+    }
+
+    /// Attach a classic BPF filter program to the raw socket, replacing the current one.
+    pub fn attach_filter(&self, program: &[libc::sock_filter]) -> io::Result<()> {
+        if program.len() > usize::from(u16::MAX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "BPF program too long",
+            ));
+        }
+
+        let fprog = libc::sock_fprog {
+            len: program.len() as u16,
+            filter: program.as_ptr() as *mut _,
+        };
+
+        c_call_with_os_error(|| unsafe {
+            libc::setsockopt(
+                self.raw_socket(),
+                libc::SOL_SOCKET,
+                libc::SO_ATTACH_FILTER,
+                &fprog as *const _ as *const libc::c_void,
+                mem::size_of::<libc::sock_fprog>() as libc::socklen_t,
+            )
+        })
+    }
+
+    /// Join the raw socket to a `PACKET_FANOUT` group, so frames are load balanced between all
+    /// sockets (possibly of other processes) in the group.
+    ///
+    /// All sockets of the group have to be on the same interface and use the same `group_id`
+    /// and `mode`.  A socket can only be in one group and can't leave it again.
+    pub fn join_fanout(&self, group_id: u16, mode: FanoutMode) -> io::Result<()> {
+        debug!(target: SOCKET, "joining fanout group {} ({:?})", group_id, mode);
+        join_fanout_group(self.raw_socket(), group_id, mode.value())
+    }
+
+    /// Join a fanout group which distributes session frames by session id between its sockets,
+    /// see `filter::session_fanout_program`.
+    ///
+    /// All sockets of the group have to be on the same interface and use the same `group_id`.
+    /// Requires the session socket, see `open_session_socket`.
+    pub fn join_session_fanout(&self, group_id: u16) -> io::Result<()> {
+        let fd = self.session_socket_or_err()?;
+        join_fanout_group(fd, group_id, uapi::PACKET_FANOUT_CBPF)?;
+
+        let program = filter::session_fanout_program();
+        let fprog = libc::sock_fprog {
+            len: program.len() as u16,
+            filter: program.as_ptr() as *mut _,
+        };
+        c_call_with_os_error(|| unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_PACKET,
+                uapi::PACKET_FANOUT_DATA,
+                &fprog as *const _ as *const libc::c_void,
+                mem::size_of::<libc::sock_fprog>() as libc::socklen_t,
+            )
+        })
+    }
+
+    /// Only receive discovery packets with the given codes.
+    ///
+    /// Everything else (including malformed packets) is dropped in the kernel.  See
+    /// `filter::discovery_filter`.
+    pub fn attach_discovery_filter(&self, codes: &[Code]) -> io::Result<()> {
+        if self.keeps_vlan_tag() {
+            self.attach_filter(&filter::tagged_discovery_filter(codes)?)
+        } else {
+            self.attach_filter(&filter::discovery_filter(codes)?)
+        }
+    }
+
+    /// Remove the filter program of the raw socket.
+    pub fn detach_filter(&self) -> io::Result<()> {
+        let dummy: libc::c_int = 0;
+        c_call_with_os_error(|| unsafe {
+            libc::setsockopt(
+                self.raw_socket(),
+                libc::SOL_SOCKET,
+                libc::SO_DETACH_FILTER,
+                &dummy as *const _ as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        })
+    }
+
+    /// Add a membership to the raw socket, e.g. to put the interface into promiscuous mode.
+    ///
+    /// The membership is dropped again by `drop_membership`, `close` or when the socket is
+    /// dropped.  Adding a membership twice requires dropping it twice.
+    pub fn add_membership(&mut self, membership: Membership) -> io::Result<()> {
+        set_membership(
+            self.raw_socket(),
+            uapi::PACKET_ADD_MEMBERSHIP,
+            self.ifindex()?,
+            membership,
+        )?;
+        debug!(target: SOCKET, "added membership {:?}", membership);
+        self.memberships.push(membership);
+        Ok(())
+    }
+
+    /// Drop a membership added with `add_membership`.
+    pub fn drop_membership(&mut self, membership: Membership) -> io::Result<()> {
+        let index = self
+            .memberships
+            .iter()
+            .position(|&added| added == membership)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "membership was not added"))?;
+        set_membership(
+            self.raw_socket(),
+            uapi::PACKET_DROP_MEMBERSHIP,
+            self.ifindex()?,
+            membership,
+        )?;
+        debug!(target: SOCKET, "dropped membership {:?}", membership);
+        self.memberships.remove(index);
+        Ok(())
+    }
+
+    /// Get the memberships added with `add_membership`.
+    pub fn memberships(&self) -> &[Membership] {
+        &self.memberships
+    }
+
+    /// Undo everything added to the sockets and close them.
+    ///
+    /// Drops the memberships, detaches the filter, drops a connected session and closes all
+    /// sockets, which also leaves a fanout group.  Every step is attempted, the first error is
+    /// returned.  Dropping a `Socket` does the same, but can only log the errors.  Afterwards
+    /// `state` is `ConnectionState::Closed` and `reconnect` opens the sockets again.
+    pub fn close(&mut self) -> io::Result<()> {
+        if self.state() == ConnectionState::Closed {
+            return Ok(());
+        }
+        debug!(target: SOCKET, "closing sockets");
+
+        let mut result = Ok(());
+        let mut keep_first_error = |step: io::Result<()>| {
+            if result.is_ok() {
+                result = step;
+            }
+        };
+
+        let memberships = mem::take(&mut self.memberships);
+        if !memberships.is_empty() {
+            match self.ifindex() {
+                Ok(ifindex) => {
+                    for membership in memberships {
+                        keep_first_error(set_membership(
+                            self.raw_socket(),
+                            uapi::PACKET_DROP_MEMBERSHIP,
+                            ifindex,
+                            membership,
+                        ));
+                    }
+                }
+                Err(err) => keep_first_error(Err(err)),
+            }
+        }
+
+        match self.detach_filter() {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            detached => keep_first_error(detached),
+        }
+
+        self.session = None;
+        if let Some(session_socket) = self.session_socket.take() {
+            keep_first_error(close_fd(session_socket.into_raw_fd()));
+        }
+        keep_first_error(self.connection.close().map_err(io::Error::from));
+
+        result
+    }
+
+    /// Split the socket into a sending and a receiving half, e.g. for use in separate threads.
+    ///
+    /// The sockets are closed once both halves are dropped.
+    pub fn split(self) -> (SendHalf, RecvHalf) {
+        let socket = Arc::new(self);
+        (SendHalf(Arc::clone(&socket)), RecvHalf(socket))
+    }
+}
+
+/// The sending half of a `Socket`, created by `Socket::split`
+#[derive(Debug, Clone)]
+pub struct SendHalf(Arc<Socket>);
+
+impl SendHalf {
+    pub fn mac_address(&self) -> [u8; 6] {
+        self.0.mac_address()
     }
 
     pub fn send(&self, buffer: &[u8]) -> io::Result<usize> {
-        let mut fd = unsafe { fs::File::from_raw_fd(self.raw_socket()) };
-        let ret = fd.write(buffer);
-        mem::forget(fd);
-        ret
+        self.0.send(buffer)
+    }
+
+    pub fn send_with_flags(&self, buffer: &[u8], flags: libc::c_int) -> io::Result<usize> {
+        self.0.send_with_flags(buffer, flags)
+    }
+
+    pub fn send_many(&self, frames: &[&[u8]]) -> io::Result<usize> {
+        self.0.send_many(frames)
+    }
+
+    pub fn send_batch(&self, batch: &mut SessionBatch) -> io::Result<usize> {
+        self.0.send_batch(batch)
+    }
+
+    pub fn send_session(&self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.send_session(buffer)
+    }
+}
+
+impl AsRawFd for SendHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// The receiving half of a `Socket`, created by `Socket::split`
+#[derive(Debug)]
+pub struct RecvHalf(Arc<Socket>);
+
+impl RecvHalf {
+    pub fn mac_address(&self) -> [u8; 6] {
+        self.0.mac_address()
     }
 
     pub fn recv(&self, buffer: &mut [u8]) -> io::Result<usize> {
-        let mut fd = unsafe { fs::File::from_raw_fd(self.raw_socket()) };
-        let ret = fd.read(buffer);
-        mem::forget(fd);
-        ret
+        self.0.recv(buffer)
+    }
+
+    pub fn recv_with_flags(&self, buffer: &mut [u8], flags: libc::c_int) -> io::Result<usize> {
+        self.0.recv_with_flags(buffer, flags)
     }
 
-    pub fn close(&mut self) {
-        self.connection.close_raw_socket()
+    pub fn peek(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.0.peek(buffer)
+    }
+
+    pub fn recv_session(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.0.recv_session(buffer)
+    }
+
+    pub fn recv_session_with_flags(
+        &self,
+        buffer: &mut [u8],
+        flags: libc::c_int,
+    ) -> io::Result<usize> {
+        self.0.recv_session_with_flags(buffer, flags)
+    }
+}
+
+impl AsRawFd for RecvHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if let Err(err) = self.close() {
+            debug!(target: SOCKET, "failed to close sockets: {}", err);
+        }
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.raw_socket()
     }
 }
 
@@ -114,3 +1363,84 @@ impl Evented for Socket {
         EventedFd(&self.raw_socket()).deregister(poll)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_after_failed_reinit() {
+        use std::os::unix::io::IntoRawFd;
+
+        control::init().unwrap();
+        let open = || fs::File::open("/dev/null").unwrap().into_raw_fd();
+        let connection = pppoe::Connection::from_raw_parts("lo", [0; 6], open(), open()).unwrap();
+        let mut socket = Socket::from_parts(connection, None, None);
+
+        // reopening may fail, e.g. without the pppoe module, but must not lose the interface
+        for _ in 0..2 {
+            if let Err(err) = socket.reconnect() {
+                assert!(
+                    !err.to_string().contains("no interface name set"),
+                    "{}",
+                    err
+                );
+            }
+            assert_eq!(socket.interface_name().as_deref(), Some("lo"));
+        }
+    }
+
+    #[test]
+    fn poisoned_thread() {
+        std::thread::spawn(|| {
+            NETNS_POISONED.with(|poisoned| poisoned.set(true));
+            let err = Socket::on_interface("lo").unwrap_err();
+            assert!(err.to_string().contains("network namespace"), "{}", err);
+            assert!(Socket::on_interface_in_netns("lo", "/proc/self/ns/net").is_err());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn recv_meta_from_auxdata() {
+        let mut auxdata = uapi::tpacket_auxdata {
+            tp_status: uapi::TP_STATUS_VLAN_VALID | uapi::TP_STATUS_CSUMNOTREADY,
+            tp_len: 1514,
+            tp_snaplen: 60,
+            tp_mac: 0,
+            tp_net: 14,
+            tp_vlan_tci: 0x2007,
+            tp_vlan_tpid: 0,
+        };
+        let meta = RecvMeta::from_auxdata(true, Some(auxdata));
+        assert!(meta.outgoing);
+        assert_eq!(
+            meta.vlan,
+            Some(eth::VlanTag {
+                tpid: 0x8100,
+                tci: 0x2007
+            })
+        );
+        assert_eq!(meta.checksum, ChecksumStatus::Pending);
+        assert_eq!(meta.original_len, Some(1514));
+
+        auxdata.tp_status = uapi::TP_STATUS_CSUM_VALID;
+        auxdata.tp_vlan_tci = 0;
+        let meta = RecvMeta::from_auxdata(false, Some(auxdata));
+        assert_eq!(meta.vlan, None);
+        assert_eq!(meta.checksum, ChecksumStatus::Valid);
+
+        // without PACKET_AUXDATA, e.g. on a socket pair
+        let mut fds = [0; 2];
+        let ret = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_DGRAM, 0, fds.as_mut_ptr()) };
+        assert_eq!(ret, 0);
+        let (sender, receiver) =
+            unsafe { (fs::File::from_raw_fd(fds[0]), fs::File::from_raw_fd(fds[1])) };
+        send_on(sender.as_raw_fd(), b"frame", 0).unwrap();
+        let mut buffer = [0u8; 16];
+        let (len, meta) = recv_meta_on(receiver.as_raw_fd(), &mut buffer, 0).unwrap();
+        assert_eq!(&buffer[..len], b"frame");
+        assert_eq!(meta, RecvMeta::from_auxdata(false, None));
+    }
+}
@@ -0,0 +1,9 @@
+//! Counters and gauges of the stateful parts of the crate
+//!
+//! Every type is updated by its owner and read through the owner's `stats()`, e.g.
+//! `ResourceAccounting::stats` for the session and discovery gauges.  They are collected here
+//! so an exporter of metrics finds them in one place.
+
+pub use crate::guard::GuardStats;
+pub use crate::sequence::SequenceStats;
+pub use crate::server::ResourceStats;
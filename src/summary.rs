@@ -0,0 +1,204 @@
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::convert::{TryFrom, TryInto};
+
+use crate::consts::{ETHERNET_HEADER_LEN, PPPOE_HEADER_LEN, VERSION_TYPE};
+use crate::error::{Error, ParseError};
+use crate::packet::{PPPOE_DISCOVERY, PPPOE_SESSION, SESSION_DATA_CODE};
+use crate::ppp::Protocol;
+use crate::{ParserConfig, TagIterator};
+
+#[cfg(feature = "tr101")]
+use crate::Tr101Information;
+
+pub use pppoe_core::consts::ETHERTYPE_VLAN;
+
+/// An owned summary of a PPPoE frame
+///
+/// Unlike `Packet` the summary does not borrow the receive buffer, which makes it suitable for
+/// logging and storage.  Discovery and session frames are supported, as well as frames with a
+/// single 802.1Q VLAN tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketSummary {
+    pub dst_mac: [u8; 6],
+    pub src_mac: [u8; 6],
+    /// VLAN id of the 802.1Q tag, if any
+    pub vlan: Option<u16>,
+    pub ether_type: u16,
+    pub code: u8,
+    pub session_id: u16,
+    /// Length of the PPPoE payload as stated in the header
+    pub payload_length: u16,
+    /// The PPP protocol of a session frame
+    pub ppp_protocol: Option<Protocol>,
+    /// The TR-101 information of the first Broadband Forum vendor specific tag
+    #[cfg(feature = "tr101")]
+    pub tr101: Option<Tr101Information>,
+    tags: Vec<u8>,
+}
+
+impl PacketSummary {
+    /// Parse a frame and summarize it.
+    pub fn from_bytes(buffer: &[u8]) -> Result<Self, Error> {
+        Self::with_config(buffer, &ParserConfig::default())
+    }
+
+    pub(crate) fn with_config(buffer: &[u8], config: &ParserConfig) -> Result<Self, Error> {
+        if buffer.len() < ETHERNET_HEADER_LEN {
+            return Err(ParseError::BufferTooSmall(buffer.len()).into());
+        }
+
+        let dst_mac = buffer[..6].try_into().unwrap();
+        let src_mac = buffer[6..12].try_into().unwrap();
+
+        let mut ether_type = NE::read_u16(&buffer[12..]);
+        let mut offset = ETHERNET_HEADER_LEN;
+        let mut vlan = None;
+        if ether_type == ETHERTYPE_VLAN {
+            if buffer.len() < offset + 4 {
+                return Err(ParseError::BufferTooSmall(buffer.len()).into());
+            }
+            vlan = Some(NE::read_u16(&buffer[offset..]) & 0x0fff);
+            ether_type = NE::read_u16(&buffer[offset + 2..]);
+            offset += 4;
+        }
+
+        let pppoe = &buffer[offset..];
+        let (code, session_id, payload_length, ppp_protocol, tags) = match ether_type {
+            PPPOE_DISCOVERY => {
+                let header = config.parse(pppoe)?;
+                (
+                    header.code(),
+                    header.session_id(),
+                    header.declared_payload_len() as u16,
+                    None,
+                    header.payload().to_vec(),
+                )
+            }
+            PPPOE_SESSION => {
+                if pppoe.len() < PPPOE_HEADER_LEN {
+                    return Err(ParseError::BufferTooSmall(buffer.len()).into());
+                }
+                if pppoe[0] != VERSION_TYPE && !config.allow_any_version_type {
+                    return Err(ParseError::InvalidPppoeVersion(pppoe[0] >> 4).into());
+                }
+                if pppoe[1] != SESSION_DATA_CODE {
+                    return Err(ParseError::InvalidPppoeCode(pppoe[1]).into());
+                }
+                let payload_length = NE::read_u16(&pppoe[4..]);
+                let payload_end = PPPOE_HEADER_LEN + usize::from(payload_length);
+                if payload_end > pppoe.len() {
+                    return Err(ParseError::PayloadLengthOutOfBound {
+                        actual_packet_length: u16::try_from(pppoe.len()).unwrap_or(u16::MAX),
+                        payload_length,
+                    }
+                    .into());
+                }
+                (
+                    pppoe[1],
+                    NE::read_u16(&pppoe[2..]),
+                    payload_length,
+                    Protocol::of_payload(&pppoe[PPPOE_HEADER_LEN..payload_end]),
+                    Vec::new(),
+                )
+            }
+            _ => return Err(ParseError::UnexpectedEtherType(ether_type).into()),
+        };
+
+        #[cfg(feature = "tr101")]
+        let tr101 = {
+            use core::convert::TryFrom;
+            Tr101Information::try_from(TagIterator::new_unchecked(&tags)).ok()
+        };
+
+        Ok(Self {
+            dst_mac,
+            src_mac,
+            vlan,
+            ether_type,
+            code,
+            session_id,
+            payload_length,
+            ppp_protocol,
+            #[cfg(feature = "tr101")]
+            tr101,
+            tags,
+        })
+    }
+
+    /// Check whether this is a discovery frame
+    pub fn is_discovery(&self) -> bool {
+        self.ether_type == PPPOE_DISCOVERY
+    }
+
+    /// Iterate over the tags of a discovery frame.  Session frames have no tags.
+    pub fn tags(&self) -> TagIterator<'_> {
+        TagIterator::new_unchecked(&self.tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PacketBuilder, Tag};
+
+    #[test]
+    fn summary_outlives_buffer() {
+        let summary = {
+            let mut buffer = [0u8; 100];
+            let mut packet =
+                PacketBuilder::new_discovery_packet(&mut buffer[..], [1; 6], [0xff; 6]).unwrap();
+            packet
+                .pppoe_header()
+                .add_tag(Tag::ServiceName(b"internet"))
+                .unwrap();
+            let len = packet.len();
+            PacketSummary::from_bytes(&buffer[..len]).unwrap()
+        };
+
+        assert!(summary.is_discovery());
+        assert_eq!(summary.src_mac, [1; 6]);
+        assert_eq!(summary.code, crate::header::PADI);
+        assert_eq!(summary.vlan, None);
+        assert_eq!(summary.payload_length, 12);
+        let tags: Vec<_> = summary.tags().collect();
+        assert_eq!(tags, [Tag::ServiceName(b"internet")]);
+    }
+
+    #[test]
+    fn vlan_tagged_session_frame() {
+        let mut frame = vec![0u8; 26];
+        frame[12..14].copy_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+        frame[14..16].copy_from_slice(&0x2123u16.to_be_bytes());
+        frame[16..18].copy_from_slice(&PPPOE_SESSION.to_be_bytes());
+        frame[18..24].copy_from_slice(&[0x11, 0x00, 0x00, 0x2a, 0x00, 0x02]);
+        frame[24..26].copy_from_slice(&[0xc0, 0x21]);
+
+        let summary = PacketSummary::from_bytes(&frame).unwrap();
+        assert!(!summary.is_discovery());
+        assert_eq!(summary.vlan, Some(0x123));
+        assert_eq!(summary.session_id, 0x2a);
+        assert_eq!(summary.payload_length, 2);
+        assert_eq!(summary.ppp_protocol, Some(Protocol::Lcp));
+        assert_eq!(summary.tags().count(), 0);
+
+        // the payload length exceeds the frame
+        frame[23] = 3;
+        match PacketSummary::from_bytes(&frame) {
+            Err(Error::ParseError(ParseError::PayloadLengthOutOfBound {
+                actual_packet_length: 8,
+                payload_length: 3,
+            })) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_other_ether_types() {
+        let frame = [0u8; 60];
+        match PacketSummary::from_bytes(&frame) {
+            Err(Error::ParseError(ParseError::UnexpectedEtherType(0))) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
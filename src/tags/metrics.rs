@@ -0,0 +1,54 @@
+pub use pppoe_core::metrics::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseError;
+    use crate::{Header, HeaderBuilder, Tag};
+    use core::num::NonZeroU16;
+
+    #[test]
+    fn padq_with_metrics() {
+        let metrics = Metrics {
+            receive_only: true,
+            rlq: 80,
+            resources: 100,
+            latency: 12,
+            current_data_rate: 300,
+            cdr_scalar: DataRateScalar::Mbps,
+            max_data_rate: 1,
+            mdr_scalar: DataRateScalar::Gbps,
+        };
+
+        let mut buffer = [0u8; 32];
+        let mut padq =
+            HeaderBuilder::create_padq(&mut buffer, NonZeroU16::new(0x42).unwrap()).unwrap();
+        padq.add_tag(Tag::Metrics(&metrics.to_bytes())).unwrap();
+        assert_eq!(&buffer[6..12], [0x01, 0x07, 0x00, 0x0a, 0x00, 0x13]);
+
+        let padq = Header::padq_with_buffer(&buffer).unwrap();
+        let parsed = Metrics::from_tags(padq.tags()).unwrap().unwrap();
+        assert_eq!(parsed, metrics);
+        assert_eq!(parsed.current_data_rate_kbps(), 300_000);
+        assert_eq!(parsed.max_data_rate_kbps(), 1_000_000);
+
+        // reserved bits are ignored
+        let mut bytes = metrics.to_bytes();
+        bytes[0] = 0xff;
+        assert_eq!(Metrics::from_bytes(&bytes), Ok(metrics));
+        bytes[3] = 101;
+        assert_eq!(
+            Metrics::from_bytes(&bytes),
+            Err(ParseError::InvalidMetricsPercentage(101))
+        );
+        assert!(Metrics::from_bytes(&bytes[..9]).is_err());
+
+        // the strict parser rejects PADQs with a broken Metrics tag
+        let mut buffer = [0u8; 32];
+        let mut padq =
+            HeaderBuilder::create_padq(&mut buffer, NonZeroU16::new(0x42).unwrap()).unwrap();
+        padq.add_tag(Tag::Metrics(&bytes)).unwrap();
+        assert!(Header::padq_with_buffer(&buffer).is_err());
+        assert!(crate::ParserConfig::lenient().parse(&buffer).is_ok());
+    }
+}
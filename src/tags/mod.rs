@@ -1,8 +1,20 @@
 pub mod tag;
 pub use tag::{Tag, TagIterator};
 
+mod owned;
+pub use owned::{TagBuf, TagExt};
+
+mod vendor;
+pub use vendor::VendorTagBuilder;
+
+mod service;
+pub use service::ServiceNameMatcher;
+
+mod metrics;
+pub use metrics::{DataRateScalar, Metrics};
+
 #[cfg(feature = "tr101")]
 mod tr101;
 
 #[cfg(feature = "tr101")]
-pub use tr101::Tr101Information;
+pub use tr101::{AccessLoopEncapsulation, DataLink, Encaps1, Encaps2, Tr101Information};
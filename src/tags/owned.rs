@@ -0,0 +1,144 @@
+//! Tags owning their value
+//!
+//! `Tag` borrows its value, which is awkward when the value is generated on the fly (e.g. a
+//! Host-Uniq from a UUID) while the packet buffer is borrowed by a builder.  A `TagBuf` keeps
+//! the value itself and lends it out as a `Tag` for writing.
+
+use core::num::NonZeroU16;
+
+use crate::error::ParseError;
+use crate::Tag;
+
+/// A tag owning its value, see `Tag` for the variants
+///
+/// With the `serde` feature it is serialized like `Tag`, so serialized tags can be read back as
+/// `TagBuf`s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TagBuf {
+    EndOfList,
+    ServiceName(Vec<u8>),
+    AcName(Vec<u8>),
+    HostUniq(Vec<u8>),
+    AcCookie(Vec<u8>),
+    VendorSpecific(Vec<u8>),
+    RelaySessionId(Vec<u8>),
+    ServiceNameError(Vec<u8>),
+    AcSystemError(Vec<u8>),
+    GenericError(Vec<u8>),
+    PppMaxMtu(u16),
+    Credits((u16, u16)),
+    Metrics(Vec<u8>),
+    SequenceNumber(u16),
+    CreditScaleFactor(u16),
+    Unknown((NonZeroU16, Vec<u8>)),
+}
+
+impl TagBuf {
+    /// Borrow the tag, e.g. to pass it to `HeaderBuilder::add_tag`.
+    pub fn as_tag(&self) -> Tag<'_> {
+        match self {
+            TagBuf::EndOfList => Tag::EndOfList,
+            TagBuf::ServiceName(value) => Tag::ServiceName(value),
+            TagBuf::AcName(value) => Tag::AcName(value),
+            TagBuf::HostUniq(value) => Tag::HostUniq(value),
+            TagBuf::AcCookie(value) => Tag::AcCookie(value),
+            TagBuf::VendorSpecific(value) => Tag::VendorSpecific(value),
+            TagBuf::RelaySessionId(value) => Tag::RelaySessionId(value),
+            TagBuf::ServiceNameError(value) => Tag::ServiceNameError(value),
+            TagBuf::AcSystemError(value) => Tag::AcSystemError(value),
+            TagBuf::GenericError(value) => Tag::GenericError(value),
+            TagBuf::PppMaxMtu(mtu) => Tag::PppMaxMtu(*mtu),
+            TagBuf::Credits(credits) => Tag::Credits(*credits),
+            TagBuf::Metrics(value) => Tag::Metrics(value),
+            TagBuf::SequenceNumber(number) => Tag::SequenceNumber(*number),
+            TagBuf::CreditScaleFactor(factor) => Tag::CreditScaleFactor(*factor),
+            TagBuf::Unknown((tag_type, value)) => Tag::Unknown((*tag_type, value)),
+        }
+    }
+
+    pub fn get_tag_type(&self) -> u16 {
+        self.as_tag().get_tag_type()
+    }
+
+    /// Get the number of bytes `write` needs for this tag (including the tag header)
+    pub fn encoded_len(&self) -> usize {
+        self.as_tag().encoded_len()
+    }
+
+    pub fn write(&self, buffer: &mut [u8]) -> Result<usize, ParseError> {
+        self.as_tag().write(buffer)
+    }
+}
+
+impl<'a> From<Tag<'a>> for TagBuf {
+    fn from(tag: Tag<'a>) -> Self {
+        match tag {
+            Tag::EndOfList => TagBuf::EndOfList,
+            Tag::ServiceName(value) => TagBuf::ServiceName(value.to_vec()),
+            Tag::AcName(value) => TagBuf::AcName(value.to_vec()),
+            Tag::HostUniq(value) => TagBuf::HostUniq(value.to_vec()),
+            Tag::AcCookie(value) => TagBuf::AcCookie(value.to_vec()),
+            Tag::VendorSpecific(value) => TagBuf::VendorSpecific(value.to_vec()),
+            Tag::RelaySessionId(value) => TagBuf::RelaySessionId(value.to_vec()),
+            Tag::ServiceNameError(value) => TagBuf::ServiceNameError(value.to_vec()),
+            Tag::AcSystemError(value) => TagBuf::AcSystemError(value.to_vec()),
+            Tag::GenericError(value) => TagBuf::GenericError(value.to_vec()),
+            Tag::PppMaxMtu(mtu) => TagBuf::PppMaxMtu(mtu),
+            Tag::Credits(credits) => TagBuf::Credits(credits),
+            Tag::Metrics(value) => TagBuf::Metrics(value.to_vec()),
+            Tag::SequenceNumber(number) => TagBuf::SequenceNumber(number),
+            Tag::CreditScaleFactor(factor) => TagBuf::CreditScaleFactor(factor),
+            Tag::Unknown((tag_type, value)) => TagBuf::Unknown((tag_type, value.to_vec())),
+        }
+    }
+}
+
+impl<'a> From<&'a TagBuf> for Tag<'a> {
+    fn from(tag: &'a TagBuf) -> Self {
+        tag.as_tag()
+    }
+}
+
+/// Conversion of a `Tag`, which is defined in `pppoe-core`, into the owned `TagBuf`
+pub trait TagExt {
+    /// Copy the value of the tag into a `TagBuf`.
+    fn to_tag_buf(&self) -> TagBuf;
+}
+
+impl<'a> TagExt for Tag<'a> {
+    fn to_tag_buf(&self) -> TagBuf {
+        TagBuf::from(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Header, HeaderBuilder};
+
+    #[test]
+    fn build_from_generated_values() {
+        let mut buffer = [0u8; 64];
+        let mut padi = HeaderBuilder::create_padi(&mut buffer).unwrap();
+        padi.add_tag(Tag::ServiceName(b"")).unwrap();
+        // generated while the buffer is borrowed by the builder
+        let host_uniq = TagBuf::HostUniq(format!("client-{}", 7).into_bytes());
+        padi.add_tag(host_uniq.as_tag()).unwrap();
+        padi.add_tag(TagBuf::Credits((1, 2)).as_tag()).unwrap();
+        assert_eq!(host_uniq.encoded_len(), 12);
+
+        let padi = Header::padi_with_buffer(&buffer).unwrap();
+        let tags: Vec<TagBuf> = padi.tags().map(TagBuf::from).collect();
+        assert_eq!(tags[1], host_uniq);
+        assert_eq!(tags[2], TagBuf::Credits((1, 2)));
+        assert_eq!(Tag::from(&tags[0]), Tag::ServiceName(b""));
+
+        let mut written = [0u8; 16];
+        let len = tags[1].write(&mut written).unwrap();
+        assert_eq!(
+            Tag::from_buffer(&written[..len]).unwrap().0.to_tag_buf(),
+            host_uniq
+        );
+    }
+}
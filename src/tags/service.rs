@@ -0,0 +1,98 @@
+//! Matching of Service-Names
+//!
+//! Clients and Access Concentrators don't always agree on the exact bytes of a Service-Name:
+//! some pad it with a trailing NUL (C strings), others change its case.
+
+/// Decides whether a received Service-Name is acceptable
+///
+/// An empty name is a wildcard and matches every Service-Name, like an empty Service-Name in a
+/// PADI (RFC 2516).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceNameMatcher {
+    name: Vec<u8>,
+    ignore_ascii_case: bool,
+    ignore_trailing_nul: bool,
+}
+
+impl ServiceNameMatcher {
+    /// Match `name` exactly.
+    pub fn new<T: AsRef<[u8]> + ?Sized>(name: &T) -> Self {
+        Self {
+            name: name.as_ref().to_vec(),
+            ..Self::default()
+        }
+    }
+
+    /// Match every Service-Name.
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    /// Treat upper and lower case ASCII letters as equal.
+    pub fn ignore_ascii_case(mut self) -> Self {
+        self.ignore_ascii_case = true;
+        self
+    }
+
+    /// Ignore NUL bytes at the end of both names.
+    pub fn ignore_trailing_nul(mut self) -> Self {
+        self.ignore_trailing_nul = true;
+        self
+    }
+
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        self.trimmed(&self.name).is_empty()
+    }
+
+    pub fn matches(&self, service_name: &[u8]) -> bool {
+        if self.is_wildcard() {
+            return true;
+        }
+        let (expected, service_name) = (self.trimmed(&self.name), self.trimmed(service_name));
+        if self.ignore_ascii_case {
+            expected.eq_ignore_ascii_case(service_name)
+        } else {
+            expected == service_name
+        }
+    }
+
+    fn trimmed<'b>(&self, mut name: &'b [u8]) -> &'b [u8] {
+        if self.ignore_trailing_nul {
+            while let Some((0, rest)) = name.split_last() {
+                name = rest;
+            }
+        }
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_service_names() {
+        let exact = ServiceNameMatcher::new("internet");
+        assert!(exact.matches(b"internet"));
+        assert!(!exact.matches(b"Internet"));
+        assert!(!exact.matches(b"internet\0"));
+        assert!(!exact.matches(b""));
+
+        let tolerant = exact.clone().ignore_ascii_case().ignore_trailing_nul();
+        assert!(tolerant.matches(b"INTERNET\0\0"));
+        assert!(!tolerant.matches(b"internet2"));
+        assert!(!tolerant.matches(b"\0internet"));
+
+        assert!(ServiceNameMatcher::any().matches(b"iptv"));
+        assert!(ServiceNameMatcher::new("").matches(b"\0"));
+        // a lone NUL is a wildcard once it is trimmed, but matches only itself otherwise
+        assert!(ServiceNameMatcher::new(b"\0")
+            .ignore_trailing_nul()
+            .is_wildcard());
+        assert!(!ServiceNameMatcher::new(b"\0").matches(b""));
+    }
+}
@@ -1,9 +1,9 @@
 use crate::{error::ParseError, Tag, TagIterator};
 use byteorder::{ByteOrder, NetworkEndian as NE};
 use core::convert::TryFrom;
-use core::{str, u16};
+use core::{fmt, str};
 
-const BROADBAND_FORUM_VENDOR_ID: u32 = 0x0DE9;
+use crate::consts::BROADBAND_FORUM_VENDOR_ID;
 
 // TAG TLS - defined in TR101
 const AGENT_CIRCUIT_ID: u8 = 0x01;
@@ -22,50 +22,177 @@ const MAXIMUM_INTERLEAVING_DELAY_UP: u8 = 0x8B;
 const ACTUAL_INTERLEAVING_DELAY_UP: u8 = 0x8C;
 const MAXIMUM_INTERLEAVING_DELAY_DOWN: u8 = 0x8D;
 const ACTUAL_INTERLEAVING_DELAY_DOWN: u8 = 0x8E;
+const ACCESS_LOOP_ENCAPSULATION: u8 = 0x90;
+const DSL_TYPE: u8 = 0x91;
+const IWF_SESSION: u8 = 0xFE;
 
 // TODO: TAG TLVs - defined in rfc 6320 (ANCP)
 
-// vendorid + 16 fields * 4 bytes each + circuit_id + remote_id + 2 byte overhead for each field
-const BUFFER_MIN_SIZE: usize = 104;
+// vendor id + Access-Loop-Encapsulation
+const FIXED_LEN: usize = 4 + 5;
+const NUMERIC_TLV_LEN: usize = 6;
+
+macro_rules! encapsulation_enum {
+    ($(#[$meta:meta])* $name:ident { $($(#[$variant_meta:meta])* $variant:ident = $value:expr,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// A value not defined in TR-101
+            Other(u8),
+        }
+
+        impl From<u8> for $name {
+            fn from(value: u8) -> Self {
+                match value {
+                    $($value => $name::$variant,)*
+                    other => $name::Other(other),
+                }
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> u8 {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Other(other) => other,
+                }
+            }
+        }
+    };
+}
 
-// TODO: some fancy functions for this
-#[derive(Default)]
+encapsulation_enum! {
+    /// The data link of the access loop
+    DataLink {
+        AtmAal5 = 0x00,
+        Ethernet = 0x01,
+    }
+}
+
+encapsulation_enum! {
+    /// The Ethernet encapsulation of the access loop
+    Encaps1 {
+        NotAvailable = 0x00,
+        UntaggedEthernet = 0x01,
+        SingleTaggedEthernet = 0x02,
+    }
+}
+
+encapsulation_enum! {
+    /// The ATM encapsulation of the access loop
+    Encaps2 {
+        NotAvailable = 0x00,
+        PppoaLlc = 0x01,
+        PppoaNull = 0x02,
+        IpoaLlc = 0x03,
+        IpoaNull = 0x04,
+        EthernetOverAal5LlcWithFcs = 0x05,
+        EthernetOverAal5LlcWithoutFcs = 0x06,
+        EthernetOverAal5NullWithFcs = 0x07,
+        EthernetOverAal5NullWithoutFcs = 0x08,
+    }
+}
+
+/// The Access-Loop-Encapsulation sub-option (0x90) of TR-101
+///
+/// The raw values are kept, so values not defined in TR-101 are written back unchanged.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AccessLoopEncapsulation {
     data_link: u8,
     encaps1: u8,
     encaps2: u8,
 }
 
-macro_rules! write_tlv {
-    ($type:expr, $name:expr, $buffer:ident) => {{
-        $buffer[0] = $type;
-        $buffer[1] = 4;
-        NE::write_u32(&mut $buffer[2..], $name);
-        $buffer = &mut $buffer[6..];
-    }};
+impl AccessLoopEncapsulation {
+    pub fn new(data_link: DataLink, encaps1: Encaps1, encaps2: Encaps2) -> Self {
+        Self {
+            data_link: data_link.into(),
+            encaps1: encaps1.into(),
+            encaps2: encaps2.into(),
+        }
+    }
+
+    /// Parse the value of the sub-option, which is exactly 3 bytes long.
+    pub fn from_bytes(value: &[u8]) -> Result<Self, ParseError> {
+        match *value {
+            [data_link, encaps1, encaps2] => Ok(Self {
+                data_link,
+                encaps1,
+                encaps2,
+            }),
+            _ => Err(ParseError::InvalidTr101TagLength {
+                tag_type: ACCESS_LOOP_ENCAPSULATION,
+                expected_min_length: 5,
+                expected_max_length: 5,
+                actual_length: value.len() as u16 + 2,
+            }),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [self.data_link, self.encaps1, self.encaps2]
+    }
+
+    pub fn data_link(&self) -> DataLink {
+        self.data_link.into()
+    }
+
+    pub fn set_data_link(&mut self, data_link: DataLink) {
+        self.data_link = data_link.into();
+    }
+
+    pub fn encaps1(&self) -> Encaps1 {
+        self.encaps1.into()
+    }
+
+    pub fn set_encaps1(&mut self, encaps1: Encaps1) {
+        self.encaps1 = encaps1.into();
+    }
+
+    pub fn encaps2(&self) -> Encaps2 {
+        self.encaps2.into()
+    }
+
+    pub fn set_encaps2(&mut self, encaps2: Encaps2) {
+        self.encaps2 = encaps2.into();
+    }
 }
 
+/// The line information of TR-101 carried in a Vendor-Specific tag
+///
+/// Data rates (in kbit/s) and interleaving delays (in ms) are only written if they are set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tr101Information {
+    #[cfg_attr(feature = "serde", serde(with = "id_string"))]
     circuit_id: (u8, [u8; 64]),
+    #[cfg_attr(feature = "serde", serde(with = "id_string"))]
     remote_id: (u8, [u8; 64]),
-    pub act_data_rate_up: u32,
-    pub act_data_rate_down: u32,
-    pub min_data_rate_up: u32,
-    pub min_data_rate_down: u32,
-    pub att_data_rate_up: u32,
-    pub att_data_rate_down: u32,
-    pub max_data_rate_up: u32,
-    pub max_data_rate_down: u32,
-    pub min_data_rate_up_lp: u32,
-    pub min_data_rate_down_lp: u32,
-    pub max_interl_delay_up: u32,
-    pub act_interl_delay_up: u32,
-    pub max_interl_delay_down: u32,
-    pub act_interl_delay_down: u32,
-    pub dsl_type: u32,
+    pub act_data_rate_up: Option<u32>,
+    pub act_data_rate_down: Option<u32>,
+    pub min_data_rate_up: Option<u32>,
+    pub min_data_rate_down: Option<u32>,
+    pub att_data_rate_up: Option<u32>,
+    pub att_data_rate_down: Option<u32>,
+    pub max_data_rate_up: Option<u32>,
+    pub max_data_rate_down: Option<u32>,
+    pub min_data_rate_up_lp: Option<u32>,
+    pub min_data_rate_down_lp: Option<u32>,
+    pub max_interl_delay_up: Option<u32>,
+    pub act_interl_delay_up: Option<u32>,
+    pub max_interl_delay_down: Option<u32>,
+    pub act_interl_delay_down: Option<u32>,
+    pub dsl_type: Option<u32>,
+    /// The session is a PPPoA session converted to PPPoE by the access node (IWF-Session)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub iwf_session: bool,
     pub access_loop_encapsulation: AccessLoopEncapsulation,
 }
 
+// never empty, `write` always writes at least the vendor id
+#[allow(clippy::len_without_is_empty)]
 impl Tr101Information {
     pub fn with_circuit_id(circuit_id: &str) -> Result<Self, ParseError> {
         Self::with_both_ids(circuit_id, "")
@@ -84,12 +211,12 @@ impl Tr101Information {
 
     pub fn remote_id(&self) -> &str {
         let len = usize::from(self.remote_id.0);
-        unsafe { str::from_utf8_unchecked(&self.remote_id.1[len..]) }
+        unsafe { str::from_utf8_unchecked(&self.remote_id.1[..len]) }
     }
 
     pub fn circuit_id(&self) -> &str {
         let len = usize::from(self.circuit_id.0);
-        unsafe { str::from_utf8_unchecked(&self.circuit_id.1[len..]) }
+        unsafe { str::from_utf8_unchecked(&self.circuit_id.1[..len]) }
     }
 
     pub fn set_remote_id(&mut self, remote_id: &str) -> Result<(), ParseError> {
@@ -122,8 +249,42 @@ impl Tr101Information {
         Ok(())
     }
 
+    /// Get the number of bytes `write` needs, including the vendor id.
     pub fn len(&self) -> usize {
-        BUFFER_MIN_SIZE + usize::from(self.circuit_id.0) + usize::from(self.remote_id.0)
+        let id_len = |len: u8| match len {
+            0 => 0,
+            len => 2 + usize::from(len),
+        };
+        let numeric_count = self
+            .numeric_tlvs()
+            .iter()
+            .filter(|(_, value)| value.is_some())
+            .count();
+        FIXED_LEN
+            + id_len(self.circuit_id.0)
+            + id_len(self.remote_id.0)
+            + numeric_count * NUMERIC_TLV_LEN
+            + if self.iwf_session { 2 } else { 0 }
+    }
+
+    fn numeric_tlvs(&self) -> [(u8, Option<u32>); 15] {
+        [
+            (ACTUAL_DATA_RATE_UP, self.act_data_rate_up),
+            (ACTUAL_DATA_RATE_DOWN, self.act_data_rate_down),
+            (MINIMUM_DATA_RATE_UP, self.min_data_rate_up),
+            (MINIMUM_DATA_RATE_DOWN, self.min_data_rate_down),
+            (ATTAINABLE_DATA_RATE_UP, self.att_data_rate_up),
+            (ATTAINABLE_DATA_RATE_DOWN, self.att_data_rate_down),
+            (MAXIMUM_DATA_RATE_UP, self.max_data_rate_up),
+            (MAXIMUM_DATA_RATE_DOWN, self.max_data_rate_down),
+            (MINIMUM_DATA_RATE_UP_LOW_POWER, self.min_data_rate_up_lp),
+            (MINIMUM_DATA_RATE_DOWN_LOW_POWER, self.min_data_rate_down_lp),
+            (MAXIMUM_INTERLEAVING_DELAY_UP, self.max_interl_delay_up),
+            (ACTUAL_INTERLEAVING_DELAY_UP, self.act_interl_delay_up),
+            (MAXIMUM_INTERLEAVING_DELAY_DOWN, self.max_interl_delay_down),
+            (ACTUAL_INTERLEAVING_DELAY_DOWN, self.act_interl_delay_down),
+            (DSL_TYPE, self.dsl_type),
+        ]
     }
 
     #[allow(unused_assignments)]
@@ -136,7 +297,7 @@ impl Tr101Information {
             return Err(ParseError::BufferTooSmall(required_size));
         }
 
-        NE::write_u32(&mut buffer, BROADBAND_FORUM_VENDOR_ID);
+        NE::write_u32(buffer, BROADBAND_FORUM_VENDOR_ID);
         buffer = &mut buffer[4..];
 
         if cid_len != 0 {
@@ -153,54 +314,84 @@ impl Tr101Information {
             buffer = &mut buffer[2 + rid_len..];
         }
 
-        buffer[0] = 0x90;
+        buffer[0] = ACCESS_LOOP_ENCAPSULATION;
         buffer[1] = 3;
-        buffer[2] = self.access_loop_encapsulation.data_link;
-        buffer[3] = self.access_loop_encapsulation.encaps1;
-        buffer[4] = self.access_loop_encapsulation.encaps2;
+        buffer[2..5].copy_from_slice(&self.access_loop_encapsulation.to_bytes());
         buffer = &mut buffer[5..];
 
-        write_tlv!(ACTUAL_DATA_RATE_UP, self.act_data_rate_up, buffer);
-        write_tlv!(ACTUAL_DATA_RATE_DOWN, self.act_data_rate_down, buffer);
-        write_tlv!(MINIMUM_DATA_RATE_UP, self.min_data_rate_up, buffer);
-        write_tlv!(MINIMUM_DATA_RATE_DOWN, self.min_data_rate_down, buffer);
-        write_tlv!(ATTAINABLE_DATA_RATE_UP, self.att_data_rate_up, buffer);
-        write_tlv!(ATTAINABLE_DATA_RATE_DOWN, self.att_data_rate_down, buffer);
-        write_tlv!(MAXIMUM_DATA_RATE_UP, self.max_data_rate_up, buffer);
-        write_tlv!(MAXIMUM_DATA_RATE_DOWN, self.max_data_rate_down, buffer);
-        write_tlv!(
-            MINIMUM_DATA_RATE_UP_LOW_POWER,
-            self.min_data_rate_up_lp,
-            buffer
-        );
-        write_tlv!(
-            MINIMUM_DATA_RATE_DOWN_LOW_POWER,
-            self.min_data_rate_down_lp,
-            buffer
-        );
-        write_tlv!(
-            MAXIMUM_INTERLEAVING_DELAY_UP,
-            self.max_interl_delay_up,
-            buffer
-        );
-        write_tlv!(
-            ACTUAL_INTERLEAVING_DELAY_UP,
-            self.act_interl_delay_up,
-            buffer
-        );
-        write_tlv!(
-            MAXIMUM_INTERLEAVING_DELAY_DOWN,
-            self.max_interl_delay_down,
-            buffer
-        );
-        write_tlv!(
-            ACTUAL_INTERLEAVING_DELAY_DOWN,
-            self.act_interl_delay_down,
-            buffer
-        );
+        for (tlv_type, value) in self.numeric_tlvs().iter() {
+            if let Some(value) = value {
+                buffer[0] = *tlv_type;
+                buffer[1] = 4;
+                NE::write_u32(&mut buffer[2..], *value);
+                buffer = &mut buffer[NUMERIC_TLV_LEN..];
+            }
+        }
+
+        if self.iwf_session {
+            buffer[0] = IWF_SESSION;
+            buffer[1] = 0;
+            buffer = &mut buffer[2..];
+        }
 
         Ok(required_size)
     }
+
+    /// Write a one-line summary of the ids and the actual data rates, e.g.
+    /// `circuit-id "atm 1/1/1:7" remote-id "cpe-42" rate up 1024 down 8192 kbit/s`.  Empty ids
+    /// and data rates which are not set are left out.
+    ///
+    /// Doesn't allocate, see `Tag::write_summary`.
+    pub fn write_summary(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        let mut separator = "";
+        if !self.circuit_id().is_empty() {
+            write!(w, "circuit-id {:?}", self.circuit_id())?;
+            separator = " ";
+        }
+        if !self.remote_id().is_empty() {
+            write!(w, "{}remote-id {:?}", separator, self.remote_id())?;
+            separator = " ";
+        }
+        if self.act_data_rate_up.is_some() || self.act_data_rate_down.is_some() {
+            write!(w, "{}rate", separator)?;
+            if let Some(up) = self.act_data_rate_up {
+                write!(w, " up {}", up)?;
+            }
+            if let Some(down) = self.act_data_rate_down {
+                write!(w, " down {}", down)?;
+            }
+            w.write_str(" kbit/s")?;
+            separator = " ";
+        }
+        if separator.is_empty() {
+            w.write_str("no line information")?;
+        }
+        Ok(())
+    }
+}
+
+/// (De)serialize the circuit and remote ids as strings
+#[cfg(feature = "serde")]
+mod id_string {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(id: &(u8, [u8; 64]), serializer: S) -> Result<S::Ok, S::Error> {
+        let id =
+            core::str::from_utf8(&id.1[..usize::from(id.0)]).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(id)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<(u8, [u8; 64]), D::Error> {
+        let id = String::deserialize(deserializer)?;
+        if id.len() > 63 {
+            return Err(de::Error::invalid_length(id.len(), &"at most 63 bytes"));
+        }
+        let mut buffer = [0; 64];
+        buffer[..id.len()].copy_from_slice(id.as_bytes());
+        Ok((id.len() as u8, buffer))
+    }
 }
 
 impl Default for Tr101Information {
@@ -208,22 +399,23 @@ impl Default for Tr101Information {
         Self {
             circuit_id: (0, [0; 64]),
             remote_id: (0, [0; 64]),
-            act_data_rate_up: 0,
-            act_data_rate_down: 0,
-            min_data_rate_up: 0,
-            min_data_rate_down: 0,
-            att_data_rate_up: 0,
-            att_data_rate_down: 0,
-            max_data_rate_up: 0,
-            max_data_rate_down: 0,
-            min_data_rate_up_lp: 0,
-            min_data_rate_down_lp: 0,
-            max_interl_delay_up: 0,
-            act_interl_delay_up: 0,
-            max_interl_delay_down: 0,
-            act_interl_delay_down: 0,
+            act_data_rate_up: None,
+            act_data_rate_down: None,
+            min_data_rate_up: None,
+            min_data_rate_down: None,
+            att_data_rate_up: None,
+            att_data_rate_down: None,
+            max_data_rate_up: None,
+            max_data_rate_down: None,
+            min_data_rate_up_lp: None,
+            min_data_rate_down_lp: None,
+            max_interl_delay_up: None,
+            act_interl_delay_up: None,
+            max_interl_delay_down: None,
+            act_interl_delay_down: None,
             access_loop_encapsulation: Default::default(),
-            dsl_type: 0,
+            dsl_type: None,
+            iwf_session: false,
         }
     }
 }
@@ -233,11 +425,8 @@ impl<'a> TryFrom<TagIterator<'a>> for Tr101Information {
 
     fn try_from(tag_iterator: TagIterator<'a>) -> Result<Tr101Information, Self::Error> {
         for tag in tag_iterator {
-            match Self::try_from(tag) {
-                Ok(info) => {
-                    return Ok(info);
-                }
-                _ => (),
+            if let Ok(info) = Self::try_from(tag) {
+                return Ok(info);
             }
         }
         Err(())
@@ -258,7 +447,9 @@ impl<'a> TryFrom<Tag<'a>> for Tr101Information {
                 return Err(ParseError::InvalidTr101VendorId(vendor_id));
             }
             let mut info = Tr101Information::default();
-            let tr_iter = Tr101TagIterator { buffer };
+            let tr_iter = Tr101TagIterator {
+                buffer: &buffer[4..],
+            };
 
             for tr_tag in tr_iter {
                 let tr_tag = tr_tag?;
@@ -272,50 +463,51 @@ impl<'a> TryFrom<Tag<'a>> for Tr101Information {
                         info.remote_id.1[..rid.len()].copy_from_slice(rid);
                     }
                     Tr101Tag::ActDataRateUp(rate) => {
-                        info.act_data_rate_up = rate;
+                        info.act_data_rate_up = Some(rate);
                     }
                     Tr101Tag::ActDataRateDown(rate) => {
-                        info.act_data_rate_down = rate;
+                        info.act_data_rate_down = Some(rate);
                     }
                     Tr101Tag::MinDataRateUp(rate) => {
-                        info.min_data_rate_up = rate;
+                        info.min_data_rate_up = Some(rate);
                     }
                     Tr101Tag::MinDataRateDown(rate) => {
-                        info.min_data_rate_down = rate;
+                        info.min_data_rate_down = Some(rate);
                     }
                     Tr101Tag::AttDataRateUp(rate) => {
-                        info.att_data_rate_up = rate;
+                        info.att_data_rate_up = Some(rate);
                     }
                     Tr101Tag::AttDataRateDown(rate) => {
-                        info.att_data_rate_down = rate;
+                        info.att_data_rate_down = Some(rate);
                     }
                     Tr101Tag::MaxDataRateUp(rate) => {
-                        info.max_data_rate_up = rate;
+                        info.max_data_rate_up = Some(rate);
                     }
                     Tr101Tag::MaxDataRateDown(rate) => {
-                        info.max_data_rate_down = rate;
+                        info.max_data_rate_down = Some(rate);
                     }
                     Tr101Tag::MinDataRateUpLp(rate) => {
-                        info.min_data_rate_up_lp = rate;
+                        info.min_data_rate_up_lp = Some(rate);
                     }
                     Tr101Tag::MinDataRateDownLp(rate) => {
-                        info.min_data_rate_down_lp = rate;
+                        info.min_data_rate_down_lp = Some(rate);
                     }
-                    Tr101Tag::MaxInterlDelayUp(rate) => {
-                        info.max_interl_delay_up = rate;
+                    Tr101Tag::MaxInterlDelayUp(delay) => {
+                        info.max_interl_delay_up = Some(delay);
                     }
-                    Tr101Tag::ActInterlDelayUp(rate) => {
-                        info.act_interl_delay_down = rate;
+                    Tr101Tag::ActInterlDelayUp(delay) => {
+                        info.act_interl_delay_up = Some(delay);
                     }
-                    Tr101Tag::MaxInterlDelayDown(rate) => {
-                        info.max_interl_delay_up = rate;
+                    Tr101Tag::MaxInterlDelayDown(delay) => {
+                        info.max_interl_delay_down = Some(delay);
                     }
-                    Tr101Tag::ActInterlDelayDown(rate) => {
-                        info.act_interl_delay_down = rate;
+                    Tr101Tag::ActInterlDelayDown(delay) => {
+                        info.act_interl_delay_down = Some(delay);
                     }
                     Tr101Tag::DslType(dsl_type) => {
-                        info.dsl_type = dsl_type;
+                        info.dsl_type = Some(dsl_type);
                     }
+                    Tr101Tag::IwfSession => info.iwf_session = true,
                     Tr101Tag::AccessLoopEncapsulation(ale) => info.access_loop_encapsulation = ale,
                     Tr101Tag::Unknown(_) => (),
                 }
@@ -327,6 +519,7 @@ impl<'a> TryFrom<Tag<'a>> for Tr101Information {
     }
 }
 
+#[allow(dead_code)]
 pub enum Tr101Tag<'a> {
     CircuitId(&'a [u8]),
     RemoteId(&'a [u8]),
@@ -345,6 +538,7 @@ pub enum Tr101Tag<'a> {
     MaxInterlDelayDown(u32),
     ActInterlDelayDown(u32),
     DslType(u32),
+    IwfSession,
     AccessLoopEncapsulation(AccessLoopEncapsulation),
     Unknown((u8, &'a [u8])),
 }
@@ -372,7 +566,7 @@ impl<'a> Iterator for Tr101TagIterator<'a> {
     type Item = Result<Tr101Tag<'a>, ParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.buffer.len() == 0 {
+        if self.buffer.is_empty() {
             return None;
         }
 
@@ -460,40 +654,58 @@ impl<'a> Iterator for Tr101TagIterator<'a> {
             ),
             MINIMUM_DATA_RATE_UP_LOW_POWER => read_tag!(
                 MINIMUM_DATA_RATE_UP_LOW_POWER,
-                Tr101Tag::MinDataRateUp,
+                Tr101Tag::MinDataRateUpLp,
                 self.buffer,
                 tag_length
             ),
             MINIMUM_DATA_RATE_DOWN_LOW_POWER => read_tag!(
                 MINIMUM_DATA_RATE_DOWN_LOW_POWER,
-                Tr101Tag::MinDataRateDown,
+                Tr101Tag::MinDataRateDownLp,
                 self.buffer,
                 tag_length
             ),
             MAXIMUM_INTERLEAVING_DELAY_UP => read_tag!(
                 MAXIMUM_INTERLEAVING_DELAY_UP,
-                Tr101Tag::MaxDataRateUp,
+                Tr101Tag::MaxInterlDelayUp,
                 self.buffer,
                 tag_length
             ),
             ACTUAL_INTERLEAVING_DELAY_UP => read_tag!(
                 ACTUAL_INTERLEAVING_DELAY_UP,
-                Tr101Tag::ActDataRateDown,
+                Tr101Tag::ActInterlDelayUp,
                 self.buffer,
                 tag_length
             ),
             MAXIMUM_INTERLEAVING_DELAY_DOWN => read_tag!(
                 MAXIMUM_INTERLEAVING_DELAY_DOWN,
-                Tr101Tag::MaxDataRateUp,
+                Tr101Tag::MaxInterlDelayDown,
                 self.buffer,
                 tag_length
             ),
             ACTUAL_INTERLEAVING_DELAY_DOWN => read_tag!(
                 ACTUAL_INTERLEAVING_DELAY_DOWN,
-                Tr101Tag::ActDataRateDown,
+                Tr101Tag::ActInterlDelayDown,
                 self.buffer,
                 tag_length
             ),
+            DSL_TYPE => read_tag!(DSL_TYPE, Tr101Tag::DslType, self.buffer, tag_length),
+            IWF_SESSION => {
+                if tag_length != 2 {
+                    return Some(Err(ParseError::InvalidTr101TagLength {
+                        tag_type: IWF_SESSION,
+                        expected_min_length: 2,
+                        expected_max_length: 2,
+                        actual_length: tag_length as u16,
+                    }));
+                }
+                Tr101Tag::IwfSession
+            }
+            ACCESS_LOOP_ENCAPSULATION => {
+                match AccessLoopEncapsulation::from_bytes(&self.buffer[2..tag_length]) {
+                    Ok(ale) => Tr101Tag::AccessLoopEncapsulation(ale),
+                    Err(err) => return Some(Err(err)),
+                }
+            }
             unknown => Tr101Tag::Unknown((unknown, &self.buffer[2..tag_length])),
         };
 
@@ -502,3 +714,142 @@ impl<'a> Iterator for Tr101TagIterator<'a> {
         Some(Ok(tag))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids() {
+        let info = Tr101Information::with_both_ids("atm 1/1/1:7", "cpe-42").unwrap();
+        assert_eq!(info.circuit_id(), "atm 1/1/1:7");
+        assert_eq!(info.remote_id(), "cpe-42");
+        assert_eq!(Tr101Information::default().circuit_id(), "");
+    }
+
+    #[test]
+    fn write_summary() {
+        let mut summary = String::new();
+        Tr101Information::default()
+            .write_summary(&mut summary)
+            .unwrap();
+        assert_eq!(summary, "no line information");
+
+        let mut info = Tr101Information::with_both_ids("atm 1/1/1:7", "cpe-42").unwrap();
+        info.act_data_rate_up = Some(1024);
+        info.act_data_rate_down = Some(8192);
+        summary.clear();
+        info.write_summary(&mut summary).unwrap();
+        assert_eq!(
+            summary,
+            "circuit-id \"atm 1/1/1:7\" remote-id \"cpe-42\" rate up 1024 down 8192 kbit/s"
+        );
+
+        info.act_data_rate_up = None;
+        summary.clear();
+        info.write_summary(&mut summary).unwrap();
+        assert!(summary.ends_with("\"cpe-42\" rate down 8192 kbit/s"));
+    }
+
+    #[test]
+    fn write_only_set_fields() {
+        let mut info = Tr101Information::with_circuit_id("atm 1/1/1:7").unwrap();
+        assert_eq!(info.len(), 4 + 13 + 5);
+        info.act_data_rate_down = Some(8192);
+        info.max_interl_delay_up = Some(16);
+        assert_eq!(info.len(), 4 + 13 + 5 + 2 * 6);
+        info.dsl_type = Some(5);
+        info.iwf_session = true;
+        assert_eq!(info.len(), 4 + 13 + 5 + 3 * 6 + 2);
+
+        let mut buffer = [0u8; 64];
+        let len = info.write(&mut buffer).unwrap();
+        assert_eq!(len, info.len());
+        assert!(info.write(&mut buffer[..len - 1]).is_err());
+        let parsed = Tr101Information::try_from(Tag::VendorSpecific(&buffer[..len])).unwrap();
+        assert_eq!(parsed, info);
+        assert_eq!(parsed.act_data_rate_up, None);
+    }
+
+    #[test]
+    fn parse_vendor_tag() {
+        #[rustfmt::skip]
+        let value = [
+            0x00, 0x00, 0x0d, 0xe9,
+            0x01, 0x03, b'c', b'i', b'd',
+            0x89, 0x04, 0x00, 0x00, 0x01, 0x00,
+            0x8a, 0x04, 0x00, 0x00, 0x02, 0x00,
+            0x8b, 0x04, 0x00, 0x00, 0x00, 0x10,
+            0x8c, 0x04, 0x00, 0x00, 0x00, 0x08,
+            0x8d, 0x04, 0x00, 0x00, 0x00, 0x20,
+            0x8e, 0x04, 0x00, 0x00, 0x00, 0x04,
+        ];
+        let info = Tr101Information::try_from(Tag::VendorSpecific(&value)).unwrap();
+        assert_eq!(info.circuit_id(), "cid");
+        assert_eq!(info.min_data_rate_up_lp, Some(256));
+        assert_eq!(info.min_data_rate_down_lp, Some(512));
+        assert_eq!(info.max_interl_delay_up, Some(16));
+        assert_eq!(info.act_interl_delay_up, Some(8));
+        assert_eq!(info.max_interl_delay_down, Some(32));
+        assert_eq!(info.act_interl_delay_down, Some(4));
+        assert_eq!(info.min_data_rate_up, None);
+        assert_eq!(info.act_data_rate_down, None);
+    }
+
+    #[test]
+    fn access_loop_encapsulation() {
+        let mut info = Tr101Information::with_circuit_id("atm 1/1/1:7").unwrap();
+        info.access_loop_encapsulation = AccessLoopEncapsulation::new(
+            DataLink::AtmAal5,
+            Encaps1::NotAvailable,
+            Encaps2::PppoaLlc,
+        );
+        info.access_loop_encapsulation
+            .set_encaps2(Encaps2::EthernetOverAal5LlcWithoutFcs);
+
+        let mut buffer = [0u8; 128];
+        let len = info.write(&mut buffer).unwrap();
+        let ale = Tr101TagIterator {
+            buffer: &buffer[4..len],
+        }
+        .find_map(|tag| match tag.unwrap() {
+            Tr101Tag::AccessLoopEncapsulation(ale) => Some(ale),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(ale.data_link(), DataLink::AtmAal5);
+        assert_eq!(ale.encaps1(), Encaps1::NotAvailable);
+        assert_eq!(ale.encaps2(), Encaps2::EthernetOverAal5LlcWithoutFcs);
+
+        // undefined values are kept
+        let ale = AccessLoopEncapsulation::from_bytes(&[0x01, 0x07, 0x00]).unwrap();
+        assert_eq!(ale.encaps1(), Encaps1::Other(7));
+        assert_eq!(ale.to_bytes(), [0x01, 0x07, 0x00]);
+
+        let mut iterator = Tr101TagIterator {
+            buffer: &[0x90, 0x02, 0x01, 0x01],
+        };
+        assert!(matches!(
+            iterator.next(),
+            Some(Err(ParseError::InvalidTr101TagLength { .. }))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let mut info = Tr101Information::with_both_ids("atm 1/1/1:7", "cpe-42").unwrap();
+        info.act_data_rate_down = Some(8192);
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["circuit_id"], "atm 1/1/1:7");
+        assert_eq!(json["act_data_rate_down"], 8192);
+        assert_eq!(
+            serde_json::from_value::<Tr101Information>(json).unwrap(),
+            info
+        );
+
+        let mut json = serde_json::to_value(&info).unwrap();
+        json["remote_id"] = "x".repeat(64).into();
+        assert!(serde_json::from_value::<Tr101Information>(json).is_err());
+    }
+}
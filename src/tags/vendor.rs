@@ -0,0 +1,187 @@
+//! Building the value of Vendor-Specific tags
+//!
+//! RFC 2516 only defines the first four bytes of a Vendor-Specific tag, the vendor id.  Most
+//! vendors (including the Broadband Forum for TR-101) follow it with sub-TLVs of a one byte type
+//! and a one byte length, which is what `VendorTagBuilder` writes.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use core::convert::TryFrom;
+
+use crate::error::ParseError;
+
+/// Writes the vendor id and the sub-TLVs of a Vendor-Specific tag
+///
+/// The builder only writes the value of the tag, so it fits `add_vendor_tag_with_callback`:
+///
+/// ```
+/// # use pppoe::{HeaderBuilder, VendorTagBuilder};
+/// let mut buffer = [0u8; 64];
+/// let mut padi = HeaderBuilder::create_padi(&mut buffer).unwrap();
+/// padi.add_vendor_tag_with_callback(|value| {
+///     Ok(VendorTagBuilder::new(value, 0x0000_0de9)?
+///         .sub_tlv(0x01, b"atm 1/1/1:7")?
+///         .sub_tlv(0x81, &1024u32.to_be_bytes())?
+///         .finish())
+/// })
+/// .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct VendorTagBuilder<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+// never empty, the vendor id is written right away
+#[allow(clippy::len_without_is_empty)]
+impl<'a> VendorTagBuilder<'a> {
+    /// Start the value in `buffer` with `vendor_id`, whose first byte has to be 0 (RFC 2516).
+    pub fn new(buffer: &'a mut [u8], vendor_id: u32) -> Result<Self, ParseError> {
+        if vendor_id >> 24 != 0 {
+            return Err(ParseError::InvalidVendorId(vendor_id));
+        }
+        let mut builder = Self { buffer, len: 0 };
+        builder.reserve(4)?;
+        NE::write_u32(builder.buffer, vendor_id);
+        builder.len = 4;
+        Ok(builder)
+    }
+
+    fn reserve(&self, requested: usize) -> Result<(), ParseError> {
+        let available = self.buffer.len() - self.len;
+        if requested > available {
+            return Err(ParseError::BufferTooSmallForTag {
+                available: u16::try_from(available).unwrap_or(u16::MAX),
+                requested,
+            });
+        }
+        Ok(())
+    }
+
+    /// Append a sub-TLV with `value`, at most 255 bytes.
+    pub fn sub_tlv(self, sub_type: u8, value: &[u8]) -> Result<Self, ParseError> {
+        if value.len() > usize::from(u8::MAX) {
+            return Err(ParseError::VendorSubTlvTooLong {
+                sub_type,
+                length: value.len(),
+            });
+        }
+        self.sub_tlv_with(sub_type, |buffer| {
+            if value.len() > buffer.len() {
+                return Err(ParseError::BufferTooSmallForTag {
+                    available: u16::try_from(buffer.len()).unwrap_or(u16::MAX),
+                    requested: value.len(),
+                });
+            }
+            buffer[..value.len()].copy_from_slice(value);
+            Ok(value.len())
+        })
+    }
+
+    /// Append a sub-TLV whose value is written by `write`, which returns the length of the
+    /// value.  The length field is filled in afterwards.
+    ///
+    /// `write` gets at most 255 bytes, or less if the buffer ends earlier.
+    pub fn sub_tlv_with<F>(mut self, sub_type: u8, write: F) -> Result<Self, ParseError>
+    where
+        F: FnOnce(&mut [u8]) -> Result<usize, ParseError>,
+    {
+        self.reserve(2)?;
+        let value_start = self.len + 2;
+        let value_end = self.buffer.len().min(value_start + usize::from(u8::MAX));
+        let length = write(&mut self.buffer[value_start..value_end])?;
+        let length = u8::try_from(length)
+            .ok()
+            .filter(|_| value_start + length <= value_end)
+            .ok_or(ParseError::VendorSubTlvTooLong { sub_type, length })?;
+
+        self.buffer[self.len] = sub_type;
+        self.buffer[self.len + 1] = length;
+        self.len = value_start + usize::from(length);
+        Ok(self)
+    }
+
+    /// Get the length of the value written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Finish the value and return its length, e.g. to return it from the callback of
+    /// `add_vendor_tag_with_callback`.
+    pub fn finish(self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Header, HeaderBuilder, Tag};
+
+    #[test]
+    fn build_sub_tlvs() {
+        let mut buffer = [0u8; 64];
+        let mut padi = HeaderBuilder::create_padi(&mut buffer).unwrap();
+        padi.add_tag(Tag::ServiceName(b"")).unwrap();
+        padi.add_vendor_tag_with_callback(|value| {
+            Ok(VendorTagBuilder::new(value, 0x0000_0de9)?
+                .sub_tlv(0x01, b"cid")?
+                .sub_tlv_with(0x81, |value| {
+                    NE::write_u32(value, 1024);
+                    Ok(4)
+                })?
+                .finish())
+        })
+        .unwrap();
+
+        let padi = Header::with_buffer(&buffer).unwrap();
+        assert_eq!(
+            padi.tags().nth(1),
+            Some(Tag::VendorSpecific(&[
+                0x00, 0x00, 0x0d, 0xe9, 0x01, 3, b'c', b'i', b'd', 0x81, 4, 0, 0, 4, 0
+            ]))
+        );
+
+        let mut value = [0u8; 300];
+        assert_eq!(
+            VendorTagBuilder::new(&mut value, 0x0100_0000).unwrap_err(),
+            ParseError::InvalidVendorId(0x0100_0000)
+        );
+        assert_eq!(
+            VendorTagBuilder::new(&mut value, 1)
+                .unwrap()
+                .sub_tlv(0x02, &[0; 256])
+                .unwrap_err(),
+            ParseError::VendorSubTlvTooLong {
+                sub_type: 0x02,
+                length: 256
+            }
+        );
+        assert_eq!(
+            VendorTagBuilder::new(&mut value[..100], 1)
+                .unwrap()
+                .sub_tlv(0x02, &[0; 200])
+                .unwrap_err(),
+            ParseError::BufferTooSmallForTag {
+                available: 94,
+                requested: 200
+            }
+        );
+        assert_eq!(
+            VendorTagBuilder::new(&mut value, 1)
+                .unwrap()
+                .sub_tlv_with(0x02, |_| Ok(256))
+                .unwrap_err(),
+            ParseError::VendorSubTlvTooLong {
+                sub_type: 0x02,
+                length: 256
+            }
+        );
+        // the sub-TLV header doesn't fit anymore
+        let mut value = [0u8; 5];
+        assert!(VendorTagBuilder::new(&mut value, 1)
+            .unwrap()
+            .sub_tlv(0x01, b"")
+            .is_err());
+    }
+}
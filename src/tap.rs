@@ -0,0 +1,243 @@
+//! Mirroring of session frames
+//!
+//! Taps get a read-only view of the frames on the session datapath, e.g. to mirror a session
+//! for lawful interception or debugging.  They are called after a frame was received or
+//! forwarded and can't change or delay it beyond their own run time, so a tap which hands the
+//! frame to another thread should do so without blocking, like `ChannelTap`.  With no tap
+//! registered, `Taps::mirror` returns after checking an empty `Vec`.
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::num::NonZeroU16;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+
+use crate::consts::ETHERNET_HEADER_LEN;
+use crate::error::Error;
+use crate::PacketSummary;
+
+/// Where in the datapath a frame was seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapPoint {
+    Received,
+    /// After the Ethernet addresses were rewritten and the frame was send on
+    Forwarded,
+}
+
+/// A frame handed to a tap
+#[derive(Debug, Clone, Copy)]
+pub struct TappedFrame<'a> {
+    pub point: TapPoint,
+    pub frame: &'a [u8],
+}
+
+impl<'a> TappedFrame<'a> {
+    /// Get the session id of a PPPoE frame, `None` for frames too short or without a session.
+    pub fn session_id(&self) -> Option<NonZeroU16> {
+        self.frame
+            .get(ETHERNET_HEADER_LEN + 2..ETHERNET_HEADER_LEN + 4)
+            .and_then(|session_id| NonZeroU16::new(NE::read_u16(session_id)))
+    }
+
+    /// Summarize the frame instead of copying it.
+    pub fn summary(&self) -> Result<PacketSummary, Error> {
+        PacketSummary::from_bytes(self.frame)
+    }
+}
+
+/// A receiver of mirrored frames
+pub trait Tap {
+    fn mirror(&mut self, frame: &TappedFrame);
+}
+
+impl<F> Tap for F
+where
+    F: FnMut(&TappedFrame),
+{
+    fn mirror(&mut self, frame: &TappedFrame) {
+        self(frame)
+    }
+}
+
+/// A tap sending copies of the frames to a bounded channel
+///
+/// Frames are dropped (and counted) when the channel is full, so a slow consumer doesn't slow
+/// down the datapath.  Clones share the channel and the counter, so a clone can be kept to read
+/// `dropped` after the tap was registered.
+#[derive(Debug, Clone)]
+pub struct ChannelTap {
+    sender: mpsc::SyncSender<(TapPoint, Vec<u8>)>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl ChannelTap {
+    /// Create a tap and the receiving end of its channel with room for `capacity` frames.
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<(TapPoint, Vec<u8>)>) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let tap = ChannelTap {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        (tap, receiver)
+    }
+
+    /// Number of frames dropped because the channel was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Tap for ChannelTap {
+    fn mirror(&mut self, frame: &TappedFrame) {
+        let copy = (frame.point, frame.frame.to_vec());
+        if self.sender.try_send(copy).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Identifies a registered tap, to remove it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TapId(u64);
+
+struct Registration<'t> {
+    id: TapId,
+    session_id: Option<NonZeroU16>,
+    tap: Box<dyn Tap + 't>,
+}
+
+/// The taps registered on a datapath
+#[derive(Default)]
+pub struct Taps<'t> {
+    registrations: Vec<Registration<'t>>,
+    next_id: u64,
+}
+
+impl<'t> Taps<'t> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mirror the frames of all sessions to `tap`.
+    pub fn register<T: Tap + 't>(&mut self, tap: T) -> TapId {
+        self.add(None, Box::new(tap))
+    }
+
+    /// Mirror only the frames of one session to `tap`.
+    pub fn register_for_session<T: Tap + 't>(&mut self, session_id: NonZeroU16, tap: T) -> TapId {
+        self.add(Some(session_id), Box::new(tap))
+    }
+
+    fn add(&mut self, session_id: Option<NonZeroU16>, tap: Box<dyn Tap + 't>) -> TapId {
+        let id = TapId(self.next_id);
+        self.next_id += 1;
+        self.registrations.push(Registration {
+            id,
+            session_id,
+            tap,
+        });
+        id
+    }
+
+    /// Remove a tap.  Returns `false` if it was not registered.
+    pub fn unregister(&mut self, id: TapId) -> bool {
+        let len = self.registrations.len();
+        self.registrations
+            .retain(|registration| registration.id != id);
+        self.registrations.len() != len
+    }
+
+    pub fn len(&self) -> usize {
+        self.registrations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registrations.is_empty()
+    }
+
+    /// Hand a frame to all taps registered for it.
+    #[inline]
+    pub fn mirror(&mut self, point: TapPoint, frame: &[u8]) {
+        if self.registrations.is_empty() {
+            return;
+        }
+        self.mirror_to_all(&TappedFrame { point, frame });
+    }
+
+    fn mirror_to_all(&mut self, frame: &TappedFrame) {
+        let session_id = frame.session_id();
+        for registration in &mut self.registrations {
+            if registration.session_id.is_none() || registration.session_id == session_id {
+                registration.tap.mirror(frame);
+            }
+        }
+    }
+
+    /// Forward a received frame like `Packet::forward_into` and mirror it once it was send.
+    #[cfg(feature = "socket")]
+    pub fn forward_into(
+        &mut self,
+        frame: &mut [u8],
+        socket: &crate::Socket,
+        new_dst_mac: [u8; 6],
+        new_src_mac: [u8; 6],
+    ) -> Result<usize, Error> {
+        let len =
+            crate::packet::prepare_forwarding(frame, frame.len(), new_dst_mac, new_src_mac, None)?;
+        let sent = socket.send(&frame[..len])?;
+        self.mirror(TapPoint::Forwarded, &frame[..len]);
+        Ok(sent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PacketBuilder;
+
+    fn session_frame(buffer: &mut [u8], session_id: u16) -> &[u8] {
+        let mut builder = PacketBuilder::new_session_packet(
+            buffer,
+            [0x02, 0, 0, 0, 0, 1],
+            [0x02, 0, 0, 0, 0, 2],
+            NonZeroU16::new(session_id).unwrap(),
+        )
+        .unwrap();
+        builder.payload_mut()[..2].copy_from_slice(&[0x00, 0x21]);
+        builder.finish(2).unwrap()
+    }
+
+    #[test]
+    fn mirror_to_registered_taps() {
+        let mut summaries = Vec::new();
+        let mut taps = Taps::new();
+        // nothing registered, nothing to do
+        taps.mirror(TapPoint::Received, &[0u8; 4]);
+
+        let (channel, receiver) = ChannelTap::new(1);
+        let all = taps.register(channel.clone());
+        let session = taps
+            .register_for_session(NonZeroU16::new(7).unwrap(), |frame: &TappedFrame| {
+                summaries.push(frame.summary().unwrap().session_id)
+            });
+
+        let mut buffer = [0u8; 64];
+        taps.mirror(TapPoint::Received, session_frame(&mut buffer, 7));
+        taps.mirror(TapPoint::Forwarded, session_frame(&mut buffer, 8));
+        assert_eq!(taps.len(), 2);
+
+        let (point, frame) = receiver.try_recv().unwrap();
+        assert_eq!(point, TapPoint::Received);
+        assert_eq!(frame, session_frame(&mut buffer, 7));
+        // the second frame didn't fit into the channel
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(channel.dropped(), 1);
+
+        assert!(taps.unregister(all));
+        assert!(!taps.unregister(all));
+        assert!(taps.unregister(session));
+        assert!(taps.is_empty());
+        drop(taps);
+        assert_eq!(summaries, [7]);
+    }
+}
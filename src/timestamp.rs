@@ -0,0 +1,89 @@
+//! Points in time in both monotonic and wall-clock form
+//!
+//! Timers have to use `Instant`, which doesn't jump with the system clock, but accounting and
+//! logs need the wall-clock time.  `Timestamp` carries both.  The state machines only get an
+//! `Instant` passed in, so they convert it with `Timestamp::at`, relative to a reference point
+//! taken when they were created.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A point in time as `Instant` and as `SystemTime`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub monotonic: Instant,
+    pub wall_clock: SystemTime,
+}
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self {
+            monotonic: Instant::now(),
+            wall_clock: SystemTime::now(),
+        }
+    }
+
+    /// Get the timestamp of `instant`, using `self` as reference point.
+    ///
+    /// Changes of the system clock after `self` was taken are not reflected.
+    pub fn at(&self, instant: Instant) -> Self {
+        let wall_clock = if instant >= self.monotonic {
+            self.wall_clock + (instant - self.monotonic)
+        } else {
+            self.wall_clock - (self.monotonic - instant)
+        };
+        Self {
+            monotonic: instant,
+            wall_clock,
+        }
+    }
+
+    /// Time since the Unix epoch, e.g. for RADIUS accounting.  Zero if the system clock is set
+    /// before 1970.
+    pub fn unix_time(&self) -> Duration {
+        self.wall_clock
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+
+    /// Time elapsed since `earlier`, measured with the monotonic clock.  Zero if `earlier` is
+    /// later than `self`.
+    pub fn duration_since(&self, earlier: &Timestamp) -> Duration {
+        self.monotonic.saturating_duration_since(earlier.monotonic)
+    }
+}
+
+impl From<Timestamp> for Instant {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.monotonic
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.wall_clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_instants() {
+        let reference = Timestamp {
+            monotonic: Instant::now(),
+            wall_clock: UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+        };
+        let later = reference.at(reference.monotonic + Duration::from_millis(1500));
+        assert_eq!(later.unix_time(), Duration::from_millis(1_600_000_001_500));
+        assert_eq!(
+            later.duration_since(&reference),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(reference.duration_since(&later), Duration::from_secs(0));
+
+        let earlier = later.at(reference.monotonic);
+        assert_eq!(earlier, reference);
+        assert_eq!(Instant::from(earlier), reference.monotonic);
+    }
+}
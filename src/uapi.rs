@@ -0,0 +1,157 @@
+//! Constants and structs of the Linux UAPI headers which `libc` doesn't provide on all targets
+//!
+//! `libc` only defines the `AF_PACKET` and classic BPF items for glibc and musl, Bionic (Android)
+//! gets them from here.  The values are part of the stable kernel ABI.
+
+#[cfg(not(target_os = "android"))]
+pub(crate) use libc::{
+    packet_mreq, BPF_A, BPF_ABS, BPF_B, BPF_H, BPF_JEQ, BPF_JMP, BPF_K, BPF_LD, BPF_RET,
+    PACKET_ADD_MEMBERSHIP, PACKET_DROP_MEMBERSHIP, PACKET_FANOUT, PACKET_FANOUT_CBPF,
+    PACKET_FANOUT_CPU, PACKET_FANOUT_DATA, PACKET_FANOUT_HASH, PACKET_FANOUT_LB, PACKET_FANOUT_QM,
+    PACKET_FANOUT_RND, PACKET_FANOUT_ROLLOVER, PACKET_MR_ALLMULTI, PACKET_MR_MULTICAST,
+    PACKET_MR_PROMISC, PACKET_OUTGOING,
+};
+
+// linux/if_packet.h, only in recent `libc` versions on every target
+pub(crate) const PACKET_AUXDATA: libc::c_int = 8;
+pub(crate) const PACKET_IGNORE_OUTGOING: libc::c_int = 23;
+pub(crate) const TP_STATUS_CSUMNOTREADY: u32 = 1 << 3;
+pub(crate) const TP_STATUS_VLAN_VALID: u32 = 1 << 4;
+pub(crate) const TP_STATUS_VLAN_TPID_VALID: u32 = 1 << 6;
+pub(crate) const TP_STATUS_CSUM_VALID: u32 = 1 << 7;
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy)]
+pub(crate) struct tpacket_auxdata {
+    pub tp_status: u32,
+    pub tp_len: u32,
+    pub tp_snaplen: u32,
+    pub tp_mac: u16,
+    pub tp_net: u16,
+    pub tp_vlan_tci: u16,
+    pub tp_vlan_tpid: u16,
+}
+
+#[cfg(target_os = "android")]
+pub(crate) use self::android::*;
+
+#[cfg(target_os = "android")]
+#[allow(non_camel_case_types)]
+mod android {
+    use libc::{c_int, c_uchar, c_uint, c_ushort};
+
+    // linux/filter.h
+    pub(crate) const BPF_LD: u32 = 0x00;
+    pub(crate) const BPF_JMP: u32 = 0x05;
+    pub(crate) const BPF_RET: u32 = 0x06;
+    pub(crate) const BPF_H: u32 = 0x08;
+    pub(crate) const BPF_B: u32 = 0x10;
+    pub(crate) const BPF_ABS: u32 = 0x20;
+    pub(crate) const BPF_JEQ: u32 = 0x10;
+    pub(crate) const BPF_K: u32 = 0x00;
+    pub(crate) const BPF_A: u32 = 0x10;
+
+    // linux/if_packet.h
+    pub(crate) const PACKET_OUTGOING: c_uchar = 4;
+    pub(crate) const PACKET_ADD_MEMBERSHIP: c_int = 1;
+    pub(crate) const PACKET_DROP_MEMBERSHIP: c_int = 2;
+    pub(crate) const PACKET_FANOUT: c_int = 18;
+    pub(crate) const PACKET_FANOUT_DATA: c_int = 22;
+    pub(crate) const PACKET_FANOUT_HASH: c_uint = 0;
+    pub(crate) const PACKET_FANOUT_LB: c_uint = 1;
+    pub(crate) const PACKET_FANOUT_CPU: c_uint = 2;
+    pub(crate) const PACKET_FANOUT_ROLLOVER: c_uint = 3;
+    pub(crate) const PACKET_FANOUT_RND: c_uint = 4;
+    pub(crate) const PACKET_FANOUT_QM: c_uint = 5;
+    pub(crate) const PACKET_FANOUT_CBPF: c_uint = 6;
+    pub(crate) const PACKET_MR_MULTICAST: c_int = 0;
+    pub(crate) const PACKET_MR_PROMISC: c_int = 1;
+    pub(crate) const PACKET_MR_ALLMULTI: c_int = 2;
+
+    #[repr(C)]
+    pub(crate) struct packet_mreq {
+        pub mr_ifindex: c_int,
+        pub mr_type: c_ushort,
+        pub mr_alen: c_ushort,
+        pub mr_address: [c_uchar; 8],
+    }
+}
+
+#[cfg(feature = "xdp")]
+pub(crate) use self::bpf::*;
+
+// linux/bpf.h, the attributes only contain the fields in use, the kernel zeroes the rest
+#[cfg(feature = "xdp")]
+mod bpf {
+    pub(crate) const BPF_MAP_CREATE: libc::c_long = 0;
+    pub(crate) const BPF_MAP_UPDATE_ELEM: libc::c_long = 2;
+    pub(crate) const BPF_MAP_DELETE_ELEM: libc::c_long = 3;
+    pub(crate) const BPF_PROG_LOAD: libc::c_long = 5;
+    pub(crate) const BPF_LINK_CREATE: libc::c_long = 28;
+
+    pub(crate) const BPF_MAP_TYPE_HASH: u32 = 1;
+    pub(crate) const BPF_MAP_TYPE_ARRAY: u32 = 2;
+    pub(crate) const BPF_PROG_TYPE_XDP: u32 = 6;
+    pub(crate) const BPF_XDP: u32 = 37;
+    pub(crate) const BPF_PSEUDO_MAP_FD: u8 = 1;
+
+    pub(crate) const XDP_DROP: i32 = 1;
+    pub(crate) const XDP_PASS: i32 = 2;
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub(crate) struct MapCreateAttr {
+        pub map_type: u32,
+        pub key_size: u32,
+        pub value_size: u32,
+        pub max_entries: u32,
+        pub map_flags: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub(crate) struct MapElemAttr {
+        pub map_fd: u32,
+        pub key: u64,
+        pub value: u64,
+        pub flags: u64,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub(crate) struct ProgLoadAttr {
+        pub prog_type: u32,
+        pub insn_cnt: u32,
+        pub insns: u64,
+        pub license: u64,
+        pub log_level: u32,
+        pub log_size: u32,
+        pub log_buf: u64,
+        pub kern_version: u32,
+        pub prog_flags: u32,
+        pub prog_name: [u8; 16],
+        pub prog_ifindex: u32,
+        pub expected_attach_type: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub(crate) struct LinkCreateAttr {
+        pub prog_fd: u32,
+        pub target_ifindex: u32,
+        pub attach_type: u32,
+        pub flags: u32,
+    }
+
+    /// An eBPF instruction, `regs` holds the destination register in the low and the source
+    /// register in the high nibble (on little endian targets)
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub(crate) struct bpf_insn {
+        pub code: u8,
+        pub regs: u8,
+        pub off: i16,
+        pub imm: i32,
+    }
+}
@@ -0,0 +1,368 @@
+//! XDP program dropping the PADIs of exhausted `DiscoveryGuard` limits
+//!
+//! The socket filters of `filter` still copy every PADI into the kernel's network stack.  During
+//! a flood this is most of the work, so `XdpFilter` attaches an eBPF program to the driver of
+//! the interface, which drops PADIs before an `sk_buff` is allocated for them.
+//!
+//! eBPF programs have state, but a token bucket needs a clock and atomic updates shared by all
+//! CPUs.  Instead the `DiscoveryGuard` keeps doing the accounting and `XdpFilter::sync` copies
+//! the exhausted limits into two maps of the program:
+//!
+//! * an array with a single flag, set while the global limit is exhausted
+//! * a hash of the client MAC addresses whose limit is exhausted
+//!
+//! PADIs dropped by the program never reach the guard, so `sync` has to be called regularly
+//! (e.g. on every poll timeout) to lift the limits again once their buckets are refilled.
+//!
+//! Requires Linux 5.9 (BPF links for XDP) and `CAP_BPF` plus `CAP_NET_ADMIN`.  The program is
+//! detached when the `XdpFilter` is dropped.
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::time::Instant;
+
+use crate::guard::{DiscoveryGuard, DEFAULT_MAX_CLIENTS};
+use crate::header::PADI;
+use crate::interface;
+use crate::packet::PPPOE_DISCOVERY;
+use crate::uapi::{self, bpf_insn};
+
+// the license only matters for GPL-only helpers, the program doesn't use any
+const LICENSE: &[u8] = b"Apache-2.0 OR MIT\0";
+const PROGRAM_NAME: &[u8] = b"pppoe_padi";
+
+// keys of the client map are the MAC address padded to 8 bytes
+const CLIENT_KEY_LEN: usize = 8;
+
+const BPF_LDX_W: u8 = 0x61;
+const BPF_LDX_H: u8 = 0x69;
+const BPF_LDX_B: u8 = 0x71;
+const BPF_ST_W: u8 = 0x62;
+const BPF_ST_DW: u8 = 0x7a;
+const BPF_STX_W: u8 = 0x63;
+const BPF_STX_H: u8 = 0x6b;
+const BPF_MOV_REG: u8 = 0xbf;
+const BPF_MOV_IMM: u8 = 0xb7;
+const BPF_ADD_IMM: u8 = 0x07;
+const BPF_LD_IMM64: u8 = 0x18;
+const BPF_JGT_REG: u8 = 0x2d;
+const BPF_JEQ_IMM: u8 = 0x15;
+const BPF_JNE_IMM: u8 = 0x55;
+const BPF_CALL: u8 = 0x85;
+const BPF_EXIT: u8 = 0x95;
+
+// bpf_map_lookup_elem
+const HELPER_MAP_LOOKUP: i32 = 1;
+
+// registers
+const R0: u8 = 0;
+const R1: u8 = 1;
+const R2: u8 = 2;
+const R6: u8 = 6;
+const R7: u8 = 7;
+const R8: u8 = 8;
+const R10: u8 = 10;
+
+fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> bpf_insn {
+    #[cfg(target_endian = "little")]
+    let regs = dst | src << 4;
+    #[cfg(target_endian = "big")]
+    let regs = dst << 4 | src;
+    bpf_insn {
+        code,
+        regs,
+        off,
+        imm,
+    }
+}
+
+/// Load a map reference, which takes two instruction slots
+fn load_map(dst: u8, map_fd: i32) -> [bpf_insn; 2] {
+    [
+        insn(BPF_LD_IMM64, dst, uapi::BPF_PSEUDO_MAP_FD, 0, map_fd),
+        insn(0, 0, 0, 0, 0),
+    ]
+}
+
+/// Create the XDP program, with the file descriptors of the global flag and the client map.
+fn padi_program(global_fd: i32, clients_fd: i32) -> Vec<bpf_insn> {
+    let [ether_type_high, ether_type_low] = PPPOE_DISCOVERY.to_be_bytes();
+    let mut program = vec![
+        insn(BPF_MOV_REG, R6, R1, 0, 0),
+        // r7 = data, r8 = data_end of the xdp_md context
+        insn(BPF_LDX_W, R7, R6, 0, 0),
+        insn(BPF_LDX_W, R8, R6, 4, 0),
+        insn(BPF_MOV_REG, R1, R7, 0, 0),
+        insn(BPF_ADD_IMM, R1, 0, 0, 16),
+        insn(BPF_JGT_REG, R1, R8, 26, 0),
+        // ethertype and code, byte by byte to be independent of the byte order
+        insn(BPF_LDX_B, R1, R7, 12, 0),
+        insn(BPF_JNE_IMM, R1, 0, 24, i32::from(ether_type_high)),
+        insn(BPF_LDX_B, R1, R7, 13, 0),
+        insn(BPF_JNE_IMM, R1, 0, 22, i32::from(ether_type_low)),
+        insn(BPF_LDX_B, R1, R7, 15, 0),
+        insn(BPF_JNE_IMM, R1, 0, 20, i32::from(PADI)),
+        // global flag at index 0
+        insn(BPF_ST_W, R10, 0, -4, 0),
+    ];
+    program.extend_from_slice(&load_map(R1, global_fd));
+    program.extend_from_slice(&[
+        insn(BPF_MOV_REG, R2, R10, 0, 0),
+        insn(BPF_ADD_IMM, R2, 0, 0, -4),
+        insn(BPF_CALL, 0, 0, 0, HELPER_MAP_LOOKUP),
+        insn(BPF_JEQ_IMM, R0, 0, 2, 0),
+        insn(BPF_LDX_W, R1, R0, 0, 0),
+        insn(BPF_JNE_IMM, R1, 0, 13, 0),
+        // source MAC address, padded with zeroes
+        insn(BPF_ST_DW, R10, 0, -16, 0),
+        insn(BPF_LDX_W, R1, R7, 6, 0),
+        insn(BPF_STX_W, R10, R1, -16, 0),
+        insn(BPF_LDX_H, R1, R7, 10, 0),
+        insn(BPF_STX_H, R10, R1, -12, 0),
+    ]);
+    program.extend_from_slice(&load_map(R1, clients_fd));
+    program.extend_from_slice(&[
+        insn(BPF_MOV_REG, R2, R10, 0, 0),
+        insn(BPF_ADD_IMM, R2, 0, 0, -16),
+        insn(BPF_CALL, 0, 0, 0, HELPER_MAP_LOOKUP),
+        insn(BPF_JNE_IMM, R0, 0, 2, 0),
+        // pass
+        insn(BPF_MOV_IMM, R0, 0, 0, uapi::XDP_PASS),
+        insn(BPF_EXIT, 0, 0, 0, 0),
+        // drop
+        insn(BPF_MOV_IMM, R0, 0, 0, uapi::XDP_DROP),
+        insn(BPF_EXIT, 0, 0, 0, 0),
+    ]);
+    program
+}
+
+fn bpf<T>(cmd: libc::c_long, attr: &mut T) -> io::Result<libc::c_long> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *mut T as *mut libc::c_void,
+            mem::size_of::<T>() as libc::c_uint,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ret)
+}
+
+/// Run a command which returns a new file descriptor, e.g. of a map or program.
+fn bpf_fd<T>(cmd: libc::c_long, attr: &mut T) -> io::Result<fs::File> {
+    let fd = bpf(cmd, attr)?;
+    Ok(unsafe { fs::File::from_raw_fd(fd as libc::c_int) })
+}
+
+fn create_map(
+    map_type: u32,
+    key_size: usize,
+    value_size: usize,
+    max_entries: usize,
+) -> io::Result<fs::File> {
+    let max_entries = u32::try_from(max_entries)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many map entries"))?;
+    let mut attr = uapi::MapCreateAttr {
+        map_type,
+        key_size: key_size as u32,
+        value_size: value_size as u32,
+        max_entries,
+        ..Default::default()
+    };
+    bpf_fd(uapi::BPF_MAP_CREATE, &mut attr)
+}
+
+fn update_elem(map: &fs::File, key: &[u8], value: &[u8]) -> io::Result<()> {
+    let mut attr = uapi::MapElemAttr {
+        map_fd: map.as_raw_fd() as u32,
+        key: key.as_ptr() as u64,
+        value: value.as_ptr() as u64,
+        // BPF_ANY
+        flags: 0,
+    };
+    bpf(uapi::BPF_MAP_UPDATE_ELEM, &mut attr).map(drop)
+}
+
+fn delete_elem(map: &fs::File, key: &[u8]) -> io::Result<()> {
+    let mut attr = uapi::MapElemAttr {
+        map_fd: map.as_raw_fd() as u32,
+        key: key.as_ptr() as u64,
+        ..Default::default()
+    };
+    match bpf(uapi::BPF_MAP_DELETE_ELEM, &mut attr) {
+        Ok(_) => Ok(()),
+        Err(err) if err.raw_os_error() == Some(libc::ENOENT) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+fn client_key(source: &[u8; 6]) -> [u8; CLIENT_KEY_LEN] {
+    let mut key = [0u8; CLIENT_KEY_LEN];
+    key[..6].copy_from_slice(source);
+    key
+}
+
+/// An XDP program attached to an interface, dropping the PADIs a `DiscoveryGuard` would drop
+pub struct XdpFilter {
+    // dropping the link detaches the program
+    _link: fs::File,
+    _program: fs::File,
+    global: fs::File,
+    clients: fs::File,
+    max_clients: usize,
+    global_exhausted: bool,
+    exhausted_clients: HashSet<[u8; 6]>,
+}
+
+impl XdpFilter {
+    /// Attach the program to the interface with the given name, tracking up to
+    /// `DEFAULT_MAX_CLIENTS` exhausted clients.
+    pub fn attach(interface: &str) -> io::Result<Self> {
+        Self::attach_with_max_clients(interface, DEFAULT_MAX_CLIENTS)
+    }
+
+    /// Like `attach`, with room for `max_clients` exhausted clients in the map.  Further
+    /// clients are only limited by the guard in userspace.
+    pub fn attach_with_max_clients(interface: &str, max_clients: usize) -> io::Result<Self> {
+        let ifindex = interface::index_from_name(interface)?;
+        let global = create_map(
+            uapi::BPF_MAP_TYPE_ARRAY,
+            mem::size_of::<u32>(),
+            mem::size_of::<u32>(),
+            1,
+        )?;
+        let clients = create_map(
+            uapi::BPF_MAP_TYPE_HASH,
+            CLIENT_KEY_LEN,
+            mem::size_of::<u8>(),
+            max_clients.max(1),
+        )?;
+
+        let program = padi_program(global.as_raw_fd(), clients.as_raw_fd());
+        let mut prog_name = [0u8; 16];
+        prog_name[..PROGRAM_NAME.len()].copy_from_slice(PROGRAM_NAME);
+        let mut attr = uapi::ProgLoadAttr {
+            prog_type: uapi::BPF_PROG_TYPE_XDP,
+            insn_cnt: program.len() as u32,
+            insns: program.as_ptr() as u64,
+            license: LICENSE.as_ptr() as u64,
+            prog_name,
+            expected_attach_type: uapi::BPF_XDP,
+            ..Default::default()
+        };
+        let program = bpf_fd(uapi::BPF_PROG_LOAD, &mut attr)?;
+
+        let mut attr = uapi::LinkCreateAttr {
+            prog_fd: program.as_raw_fd() as u32,
+            target_ifindex: ifindex,
+            attach_type: uapi::BPF_XDP,
+            flags: 0,
+        };
+        let link = bpf_fd(uapi::BPF_LINK_CREATE, &mut attr)?;
+
+        Ok(Self {
+            _link: link,
+            _program: program,
+            global,
+            clients,
+            max_clients,
+            global_exhausted: false,
+            exhausted_clients: HashSet::new(),
+        })
+    }
+
+    /// Copy the exhausted limits of the guard into the maps of the program.
+    pub fn sync(&mut self, guard: &mut DiscoveryGuard, now: Instant) -> io::Result<()> {
+        let global_exhausted = guard.is_exhausted(now);
+        if global_exhausted != self.global_exhausted {
+            let value = u32::from(global_exhausted);
+            update_elem(&self.global, &0u32.to_ne_bytes(), &value.to_ne_bytes())?;
+            self.global_exhausted = global_exhausted;
+        }
+
+        let exhausted: HashSet<_> = guard.exhausted_clients(now).into_iter().collect();
+        let lifted: Vec<_> = self
+            .exhausted_clients
+            .difference(&exhausted)
+            .copied()
+            .collect();
+        for source in lifted {
+            delete_elem(&self.clients, &client_key(&source))?;
+            self.exhausted_clients.remove(&source);
+        }
+        for source in exhausted {
+            if self.exhausted_clients.len() >= self.max_clients {
+                break;
+            }
+            if self.exhausted_clients.insert(source) {
+                update_elem(&self.clients, &client_key(&source), &[1])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of clients whose PADIs are dropped by the program
+    pub fn exhausted_clients(&self) -> usize {
+        self.exhausted_clients.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guard::RateLimit;
+
+    #[test]
+    fn jumps_stay_in_program() {
+        let program = padi_program(3, 4);
+        let mut exits = 0;
+        for (pc, insn) in program.iter().enumerate() {
+            match insn.code {
+                BPF_JGT_REG | BPF_JEQ_IMM | BPF_JNE_IMM => {
+                    let target = pc as isize + 1 + insn.off as isize;
+                    assert!(target > pc as isize && (target as usize) < program.len());
+                    // every conditional jump ends in one of the returns
+                    if insn.off > 2 {
+                        assert_eq!(program[target as usize].code, BPF_MOV_IMM);
+                    }
+                }
+                BPF_EXIT => exits += 1,
+                _ => (),
+            }
+        }
+        assert_eq!(exits, 2);
+        assert_eq!(program.last().unwrap().code, BPF_EXIT);
+    }
+
+    #[test]
+    fn attach_and_sync() {
+        let now = Instant::now();
+        let mut guard = DiscoveryGuard::new().with_client_limit(RateLimit::new(1, 1));
+        let mut filter = XdpFilter::attach_with_max_clients("lo", 1).unwrap();
+
+        let mut frame = [0u8; 20];
+        frame[6..12].copy_from_slice(&[0x02, 0, 0, 0, 0, 1]);
+        frame[12..14].copy_from_slice(&[0x88, 0x63]);
+        frame[14] = 0x11;
+        frame[15] = PADI;
+        for source in 1..=2 {
+            frame[11] = source;
+            guard.check(now, &frame);
+            guard.check(now, &frame);
+        }
+        filter.sync(&mut guard, now).unwrap();
+        assert_eq!(filter.exhausted_clients(), 1);
+
+        filter
+            .sync(&mut guard, now + std::time::Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(filter.exhausted_clients(), 0);
+    }
+}
@@ -0,0 +1,158 @@
+//! A minimal Access Concentrator answering discovery and LCP Echo-Requests on a `Socket`
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::io;
+use std::num::NonZeroU16;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use pppoe::consts::MAX_FRAME_LEN;
+use pppoe::cookie::CookieJar;
+use pppoe::error::Error;
+use pppoe::header::{PADI, PADR};
+use pppoe::packet::PPPOE_DISCOVERY;
+use pppoe::ppp::{lcp, ControlPacket, Protocol, PROTOCOL_LCP};
+use pppoe::{eth, HeaderBuilder, Packet, PacketBuilder, SessionIdAllocator, Socket, Tag};
+
+pub const AC_NAME: &[u8] = b"netns-ac";
+const MAGIC_NUMBER: u32 = 0x4143_4143;
+
+pub struct AccessConcentrator {
+    socket: Socket,
+    allocator: SessionIdAllocator,
+    cookies: CookieJar,
+    buffer: Vec<u8>,
+}
+
+impl AccessConcentrator {
+    pub fn new(mut socket: Socket) -> io::Result<Self> {
+        socket.open_session_socket()?;
+        socket.set_nonblock()?;
+        Ok(Self {
+            socket,
+            allocator: SessionIdAllocator::without_quarantine(),
+            cookies: CookieJar::new([0x42; 16]),
+            buffer: vec![0u8; MAX_FRAME_LEN],
+        })
+    }
+
+    /// Answer discovery frames until a client sent an LCP Echo-Request in its session, and
+    /// return the id of that session.
+    pub fn serve(mut self, deadline: Instant) -> Result<NonZeroU16, Error> {
+        let mut rx_buffer = vec![0u8; MAX_FRAME_LEN];
+        while Instant::now() < deadline {
+            let mut idle = true;
+            if let Some(len) = would_block_to_none(self.socket.recv(&mut rx_buffer))? {
+                self.handle_discovery(&rx_buffer[..len])?;
+                idle = false;
+            }
+            if let Some(len) = would_block_to_none(self.socket.recv_session(&mut rx_buffer))? {
+                if let Some(session_id) = self.handle_session(&rx_buffer[..len])? {
+                    return Ok(session_id);
+                }
+                idle = false;
+            }
+            if idle {
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::TimedOut, "no LCP Echo-Request received").into())
+    }
+
+    /// Answer a PADI with a PADO and a PADR with a PADS.
+    fn handle_discovery(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let request = Packet::with_buffer(frame)?;
+        let client_mac = request.ethernet_header().src_address();
+        let header = request.pppoe_header();
+
+        let session_id = match header.code() {
+            PADI => None,
+            PADR => {
+                let cookie = header.tags().find_map(|tag| match tag {
+                    Tag::AcCookie(cookie) => Some(cookie),
+                    _ => None,
+                });
+                let valid = cookie.is_some_and(|cookie| {
+                    self.cookies
+                        .verify(SystemTime::now(), cookie, client_mac, b"")
+                        .is_ok()
+                });
+                if !valid {
+                    return Ok(());
+                }
+                self.allocator
+                    .allocate_for(Instant::now(), Some(client_mac))
+            }
+            _ => return Ok(()),
+        };
+        let cookie = self.cookies.issue(SystemTime::now(), client_mac, b"");
+
+        let (eth_buf, pppoe_buf) = self.buffer.split_at_mut(14);
+        let mut ethernet = eth::HeaderBuilder::with_buffer(eth_buf)?;
+        ethernet.set_src_address(self.socket.mac_address());
+        ethernet.set_dst_address(client_mac);
+        ethernet.set_ether_type(PPPOE_DISCOVERY);
+
+        let mut reply = match session_id {
+            Some(session_id) => HeaderBuilder::create_pads(pppoe_buf, session_id)?,
+            None => {
+                let mut pado = HeaderBuilder::create_pado(pppoe_buf)?;
+                pado.add_tag(Tag::AcName(AC_NAME))?;
+                pado.add_tag(Tag::AcCookie(&cookie))?;
+                pado
+            }
+        };
+        reply.add_tag(Tag::ServiceName(b""))?;
+        for tag in header.tags() {
+            if let Tag::HostUniq(_) = tag {
+                reply.add_tag(tag)?;
+            }
+        }
+        let len = 14 + reply.len();
+        self.socket.send(&self.buffer[..len])?;
+        Ok(())
+    }
+
+    /// Answer an LCP Echo-Request, returning the session id it was received in.
+    fn handle_session(&mut self, frame: &[u8]) -> Result<Option<NonZeroU16>, Error> {
+        // Ethernet and PPPoE header, PPP protocol
+        if frame.len() < 22 {
+            return Ok(None);
+        }
+        let client_mac = eth::Header::with_buffer(frame)?.src_address();
+        let session_id = match NonZeroU16::new(NE::read_u16(&frame[16..])) {
+            Some(session_id) if self.allocator.is_allocated(session_id) => session_id,
+            _ => return Ok(None),
+        };
+        let payload = &frame[20..];
+        if Protocol::of_payload(payload) != Some(Protocol::Lcp) {
+            return Ok(None);
+        }
+        let request = ControlPacket::with_buffer(&payload[2..])?;
+        if request.code() != lcp::ECHO_REQUEST {
+            return Ok(None);
+        }
+
+        let mut reply = PacketBuilder::new_session_packet(
+            &mut self.buffer,
+            self.socket.mac_address(),
+            client_mac,
+            session_id,
+        )?;
+        NE::write_u16(reply.payload_mut(), PROTOCOL_LCP);
+        let len = lcp::write_echo_reply(&request, MAGIC_NUMBER, &mut reply.payload_mut()[2..])?;
+        let reply = reply.finish(2 + len)?;
+        self.socket.send_session(reply)?;
+        Ok(Some(session_id))
+    }
+}
+
+/// Map `WouldBlock` of a non-blocking socket to `None`.
+pub fn would_block_to_none(result: io::Result<usize>) -> io::Result<Option<usize>> {
+    match result {
+        Ok(len) => Ok(Some(len)),
+        Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,101 @@
+//! End-to-end tests in network namespaces
+//!
+//! Each test creates a namespace for a client and one for an Access Concentrator, connected by a
+//! veth pair, and runs the discovery stage followed by an LCP echo in the session.  The tests
+//! need root and are skipped unless `PPPOE_NETNS_TESTS` is set:
+//!
+//! ```text
+//! sudo PPPOE_NETNS_TESTS=1 cargo test --features socket --test integration
+//! ```
+
+mod access_concentrator;
+mod netns;
+
+use byteorder::{ByteOrder, NetworkEndian as NE};
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pppoe::consts::MAX_FRAME_LEN;
+use pppoe::ppp::{lcp, ControlPacket, Protocol, PROTOCOL_LCP};
+use pppoe::{Discovery, DiscoveryOptions, PacketBuilder, Socket};
+
+use access_concentrator::{would_block_to_none, AccessConcentrator, AC_NAME};
+use netns::Topology;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Run discovery and an LCP echo between the client and the AC on `interface_name`.
+fn discovery_and_lcp_echo(topology: &Topology, interface_name: &str) {
+    let ac_socket = Socket::on_interface_in_netns(interface_name, topology.ac.path()).unwrap();
+    let mut client = Socket::on_interface_in_netns(interface_name, topology.client.path()).unwrap();
+    client.open_session_socket().unwrap();
+    client.set_nonblock().unwrap();
+
+    let deadline = Instant::now() + TIMEOUT;
+    let ac = AccessConcentrator::new(ac_socket).unwrap();
+    let ac = thread::spawn(move || ac.serve(deadline));
+
+    let options = DiscoveryOptions {
+        host_uniq: Some(b"netns".to_vec()),
+        ..DiscoveryOptions::default()
+    };
+    let mut discovery = Discovery::new(client.mac_address(), options);
+    let session = discovery.run_blocking(&client, deadline).unwrap();
+    assert_eq!(session.ac_name, AC_NAME);
+
+    let mut buffer = [0u8; MAX_FRAME_LEN];
+    let mut request = PacketBuilder::new_session_packet(
+        &mut buffer,
+        client.mac_address(),
+        session.ac_mac,
+        session.session_id,
+    )
+    .unwrap();
+    NE::write_u16(request.payload_mut(), PROTOCOL_LCP);
+    let len = lcp::write_echo_request(&mut request.payload_mut()[2..], 7, 0x1234_5678, 64).unwrap();
+    client
+        .send_session(request.finish(2 + len).unwrap())
+        .unwrap();
+
+    assert_eq!(ac.join().unwrap().unwrap(), session.session_id);
+
+    let mut buffer = [0u8; MAX_FRAME_LEN];
+    loop {
+        assert!(Instant::now() < deadline, "no LCP Echo-Reply received");
+        let len = match would_block_to_none(client.recv_session(&mut buffer)).unwrap() {
+            Some(len) => len,
+            None => {
+                thread::sleep(Duration::from_millis(5));
+                continue;
+            }
+        };
+        let frame = &buffer[..len];
+        assert_eq!(NE::read_u16(&frame[16..]), session.session_id.get());
+        assert_eq!(Protocol::of_payload(&frame[20..]), Some(Protocol::Lcp));
+        let reply = ControlPacket::with_buffer(&frame[22..]).unwrap();
+        assert_eq!(reply.code(), lcp::ECHO_REPLY);
+        assert_eq!(reply.identifier(), 7);
+        assert_eq!(reply.len(), 64);
+        break;
+    }
+}
+
+#[test]
+fn session_over_veth() {
+    if !netns::enabled() {
+        return;
+    }
+    let topology = Topology::new().unwrap();
+    discovery_and_lcp_echo(&topology, netns::LINK);
+}
+
+#[test]
+fn session_over_vlan_sub_interface() {
+    if !netns::enabled() {
+        return;
+    }
+    let topology = Topology::new().unwrap();
+    let vlan = topology.add_vlan(835).unwrap();
+    discovery_and_lcp_echo(&topology, &vlan);
+}
@@ -0,0 +1,137 @@
+//! Network namespaces connected by veth pairs, set up with `ip(8)`
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Mutex, MutexGuard};
+use std::{env, io};
+
+/// Environment variable which has to be set to run the tests
+pub const ENABLE_VAR: &str = "PPPOE_NETNS_TESTS";
+
+// the sockets are created in the namespaces one after the other, see
+// `Socket::on_interface_in_netns`
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Check whether the tests are enabled and running as root, and explain why not otherwise.
+pub fn enabled() -> bool {
+    if env::var_os(ENABLE_VAR).is_none() {
+        eprintln!("skipped, set {} to run the netns tests", ENABLE_VAR);
+        return false;
+    }
+    if unsafe { libc::geteuid() } != 0 {
+        eprintln!("skipped, the netns tests have to run as root");
+        return false;
+    }
+    true
+}
+
+fn ip(args: &[&str]) -> io::Result<()> {
+    let output = Command::new("ip").args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "ip {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// A named network namespace, deleted on drop
+#[derive(Debug)]
+pub struct Netns {
+    name: String,
+}
+
+impl Netns {
+    pub fn new(role: &str) -> io::Result<Self> {
+        let name = format!("pppoe-{}-{}", std::process::id(), role);
+        ip(&["netns", "add", &name])?;
+        let netns = Netns { name };
+        netns.ip(&["link", "set", "lo", "up"])?;
+        Ok(netns)
+    }
+
+    /// Path to pass to `Socket::on_interface_in_netns`
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from("/run/netns").join(&self.name)
+    }
+
+    /// Run `ip` inside the namespace.
+    pub fn ip(&self, args: &[&str]) -> io::Result<()> {
+        let mut netns_args = vec!["-n", &self.name];
+        netns_args.extend_from_slice(args);
+        ip(&netns_args)
+    }
+}
+
+impl Drop for Netns {
+    fn drop(&mut self) {
+        let _ = ip(&["netns", "delete", &self.name]);
+    }
+}
+
+/// A client and an Access Concentrator namespace, connected by the veth pair `veth0`
+pub struct Topology {
+    pub client: Netns,
+    pub ac: Netns,
+    _lock: MutexGuard<'static, ()>,
+}
+
+pub const LINK: &str = "veth0";
+
+impl Topology {
+    pub fn new() -> io::Result<Self> {
+        let lock = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let client = Netns::new("client")?;
+        let ac = Netns::new("ac")?;
+        ip(&[
+            "link",
+            "add",
+            LINK,
+            "netns",
+            &client.name,
+            "type",
+            "veth",
+            "peer",
+            "name",
+            LINK,
+            "netns",
+            &ac.name,
+        ])?;
+        client.ip(&["link", "set", LINK, "up"])?;
+        ac.ip(&["link", "set", LINK, "up"])?;
+
+        Ok(Topology {
+            client,
+            ac,
+            _lock: lock,
+        })
+    }
+
+    /// Add the VLAN sub-interface `veth0.<id>` on both ends and return its name.
+    ///
+    /// On the client it is created with `reorder_hdr off`, so received frames keep their tag.
+    pub fn add_vlan(&self, id: u16) -> io::Result<String> {
+        let name = format!("{}.{}", LINK, id);
+        let id = id.to_string();
+        for (netns, reorder_hdr) in &[(&self.client, "off"), (&self.ac, "on")] {
+            netns.ip(&[
+                "link",
+                "add",
+                "link",
+                LINK,
+                "name",
+                &name,
+                "type",
+                "vlan",
+                "id",
+                &id,
+                "reorder_hdr",
+                reorder_hdr,
+            ])?;
+            netns.ip(&["link", "set", &name, "up"])?;
+        }
+        Ok(name)
+    }
+}